@@ -3,7 +3,8 @@ use std::{error,
           io,
           num,
           path::PathBuf,
-          result};
+          result,
+          time::Duration};
 
 use hyper;
 use serde_json;
@@ -18,17 +19,24 @@ pub type Result<T> = result::Result<T, Error>;
 pub enum Error {
     APIError(hyper::status::StatusCode, String),
     BadResponseBody(io::Error),
+    BuilderUnreachable(String),
+    CrossOriginPromotionForbidden(String /* ident */, String /* origin */),
     DownloadWrite(PathBuf, io::Error),
     HabitatCore(hab_core::Error),
     HabitatHttpClient(hab_http::Error),
     HyperError(hyper::error::Error),
     IO(io::Error),
+    InvalidToken,
     Json(serde_json::Error),
     KeyReadError(PathBuf, io::Error),
     NoFilePart,
+    NotSupported(String),
+    PackageNotFound(String),
+    PackageNotInChannel(String /* ident */, String /* channel */),
     PackageReadError(PathBuf, io::Error),
     ParseIntError(num::ParseIntError),
     IdentNotFullyQualified,
+    RateLimited(Option<Duration>, String),
     UploadFailed(String),
     UrlParseError(url::ParseError),
     WriteSyncFailed,
@@ -40,6 +48,12 @@ impl fmt::Display for Error {
             Error::APIError(ref c, ref m) if !m.is_empty() => format!("[{}] {}", c, m),
             Error::APIError(ref c, _) => format!("[{}]", c),
             Error::BadResponseBody(ref e) => format!("Failed to read response body, {}", e),
+            Error::BuilderUnreachable(ref e) => format!("Builder could not be reached, {}", e),
+            Error::CrossOriginPromotionForbidden(ref ident, ref origin) => {
+                format!("Builder refused to promote {} into the {} origin's channels; the auth \
+                         token must belong to a member of {}",
+                        ident, origin, origin)
+            }
             Error::DownloadWrite(ref p, ref e) => {
                 format!("Failed to write contents of builder response, {}, {}",
                         p.display(),
@@ -49,6 +63,11 @@ impl fmt::Display for Error {
             Error::HabitatHttpClient(ref e) => format!("{}", e),
             Error::HyperError(ref err) => format!("{}", err),
             Error::IO(ref e) => format!("{}", e),
+            Error::InvalidToken => {
+                "Builder rejected the auth token; it may have expired. Check the token given with \
+                 --auth or HAB_AUTH_TOKEN"
+                                          .to_string()
+            }
             Error::Json(ref e) => format!("{}", e),
             Error::KeyReadError(ref p, ref e) => {
                 format!("Failed to read origin key, {}, {}", p.display(), e)
@@ -56,6 +75,13 @@ impl fmt::Display for Error {
             Error::NoFilePart => "An invalid path was passed - we needed a filename, and this \
                                   path does not have one"
                                                          .to_string(),
+            Error::NotSupported(ref feature) => {
+                format!("This Builder does not support {}", feature)
+            }
+            Error::PackageNotFound(ref ident) => format!("Package not found in Builder: {}", ident),
+            Error::PackageNotInChannel(ref ident, ref channel) => {
+                format!("Package {} is not in the '{}' channel", ident, channel)
+            }
             Error::PackageReadError(ref p, ref e) => {
                 format!("Failed to read package artifact, {}, {}", p.display(), e)
             }
@@ -65,6 +91,12 @@ impl fmt::Display for Error {
                  identifier (ex: core/busybox-static/1.42.2/20170513215502)"
                                                                             .to_string()
             }
+            Error::RateLimited(Some(wait), ref m) => {
+                format!("[429 Too Many Requests] {} (retry after {}s)",
+                        m,
+                        wait.as_secs())
+            }
+            Error::RateLimited(None, ref m) => format!("[429 Too Many Requests] {}", m),
             Error::UploadFailed(ref s) => format!("Upload failed: {}", s),
             Error::UrlParseError(ref e) => format!("{}", e),
             Error::WriteSyncFailed => {
@@ -80,22 +112,31 @@ impl error::Error for Error {
         match *self {
             Error::APIError(..) => "Received a non-2XX response code from API",
             Error::BadResponseBody(_) => "Failed to read response body",
+            Error::BuilderUnreachable(_) => "Builder could not be reached",
+            Error::CrossOriginPromotionForbidden(..) => {
+                "Builder refused to promote a package into another origin's channels"
+            }
             Error::DownloadWrite(..) => "Failed to write response contents to file",
             Error::HabitatCore(ref err) => err.description(),
             Error::HabitatHttpClient(ref err) => err.description(),
             Error::HyperError(ref err) => err.description(),
             Error::IO(ref err) => err.description(),
+            Error::InvalidToken => "Builder rejected the auth token",
             Error::Json(ref err) => err.description(),
             Error::KeyReadError(..) => "Failed to read origin key from disk",
             Error::NoFilePart => {
                 "An invalid path was passed - we needed a filename, and this path does not have one"
             }
+            Error::NotSupported(_) => "Feature not supported by Builder",
+            Error::PackageNotFound(_) => "Package not found in Builder",
+            Error::PackageNotInChannel(..) => "Package is not in the channel",
             Error::PackageReadError(..) => "Failed to read package artifact from disk",
             Error::ParseIntError(ref err) => err.description(),
             Error::IdentNotFullyQualified => {
                 "Cannot perform the specified operation. Specify a fully qualifed package \
                  identifier (ex: core/busybox-static/1.42.2/20170513215502)"
             }
+            Error::RateLimited(..) => "Builder is limiting the rate of requests",
             Error::UploadFailed(_) => "Upload failed",
             Error::UrlParseError(ref err) => err.description(),
             Error::WriteSyncFailed => {
@@ -14,10 +14,17 @@ extern crate serde_derive;
 extern crate serde_json;
 
 pub mod error;
-pub use crate::error::{Error,
-                       Result};
-
-use std::{fmt,
+pub mod retry;
+#[cfg(any(test, feature = "testing"))]
+pub use crate::retry::RecordingSleeper;
+pub use crate::{error::{Error,
+                        Result},
+                retry::{RetryPolicy,
+                        Sleeper,
+                        ThreadSleeper}};
+
+use std::{env,
+          fmt,
           fs::{self,
                File},
           io::{self,
@@ -25,7 +32,10 @@ use std::{fmt,
                Write},
           path::{Path,
                  PathBuf},
-          string::ToString};
+          str::FromStr,
+          string::ToString,
+          sync::Mutex,
+          time::Duration};
 
 use crate::{hab_core::{crypto::keys::box_key_pair::WrappedSealedBox,
                        fs::AtomicWriter,
@@ -37,7 +47,8 @@ use crate::{hab_core::{crypto::keys::box_key_pair::WrappedSealedBox,
             hab_http::{util::decoded_response,
                        ApiClient}};
 use broadcast::BroadcastWriter;
-use chrono::DateTime;
+use chrono::{DateTime,
+             FixedOffset};
 use hyper::{client::{Body,
                      IntoUrl,
                      RequestBuilder,
@@ -45,7 +56,11 @@ use hyper::{client::{Body,
             header::{Accept,
                      Authorization,
                      Bearer,
-                     ContentType},
+                     ContentType,
+                     Header,
+                     HeaderFormat,
+                     Headers},
+            method::Method,
             status::StatusCode,
             Url};
 use tee::TeeReader;
@@ -54,9 +69,16 @@ use url::percent_encoding::{percent_encode,
 
 header! { (XFileName, "X-Filename") => [String] }
 header! { (ETag, "ETag") => [String] }
+// Builders which support it apply a retried request carrying the same key only once. Builders
+// which don't simply ignore it.
+header! { (IdempotencyKey, "Idempotency-Key") => [String] }
 
 const DEFAULT_API_PATH: &str = "/v1";
 
+/// When this is `1`, each request to Builder and the status of its response are logged at the
+/// debug level, with any `Authorization` header redacted.
+pub const API_DEBUG_ENVVAR: &str = "HAB_API_DEBUG";
+
 #[derive(Clone, Deserialize)]
 #[serde(rename = "error")]
 pub struct NetError {
@@ -187,14 +209,14 @@ mod json_u64 {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct OriginPrivateSigningKey {
     #[serde(with = "json_u64")]
-    pub id: u64,
+    pub id:        u64,
     #[serde(with = "json_u64")]
     pub origin_id: u64,
-    pub name: String,
-    pub revision: String,
-    pub body: Vec<u8>,
+    pub name:      String,
+    pub revision:  String,
+    pub body:      Vec<u8>,
     #[serde(with = "json_u64")]
-    pub owner_id: u64,
+    pub owner_id:  u64,
 }
 
 mod json {
@@ -207,6 +229,10 @@ mod json {
         pub tdeps:    Vec<PackageIdent>,
         pub exposes:  Vec<u32>,
         pub config:   String,
+        #[serde(default)]
+        pub channels: Vec<String>,
+        #[serde(default)]
+        pub target:   Option<String>,
     }
 
     #[derive(Clone, Deserialize)]
@@ -235,17 +261,256 @@ pub struct PackageResults<T> {
     pub data:        Vec<T>,
 }
 
+/// The details of a package in Builder which commands commonly need.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackageMetadata {
+    /// The fully qualified identifier of the package.
+    pub ident:    PackageIdent,
+    /// The channels the package is a member of.
+    pub channels: Vec<ChannelIdent>,
+    pub target:   PackageTarget,
+}
+
+impl PackageMetadata {
+    /// Decodes Builder's package JSON. When the response doesn't name a target, `target` (the one
+    /// which was asked for) is assumed.
+    fn from_json(body: &str, target: PackageTarget) -> Result<Self> {
+        let package: json::Package = serde_json::from_str(body)?;
+        let target = match package.target {
+            Some(ref t) => PackageTarget::from_str(t)?,
+            None => target,
+        };
+        Ok(PackageMetadata { ident: package.ident.into(),
+                             channels: package.channels
+                                              .into_iter()
+                                              .map(ChannelIdent::from)
+                                              .collect(),
+                             target })
+    }
+
+    pub fn in_channel(&self, channel: &ChannelIdent) -> bool { self.channels.contains(channel) }
+}
+
+/// The settings an origin has on Builder. Settings which a Builder doesn't have are left empty.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct OriginSettings {
+    /// The channels, in the order they are promoted through, which a package must be in before
+    /// it is promoted to stable.
+    #[serde(default)]
+    pub required_channel_order: Vec<String>,
+}
+
 #[derive(Clone, Deserialize)]
 pub struct OriginChannelIdent {
     pub name: String,
 }
 
+/// A single response from Builder's channel listing. Older Builders return every channel as a
+/// bare array, while newer ones wrap a range of them in a paginated envelope.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChannelPage {
+    Paginated(PackageResults<OriginChannelIdent>),
+    Complete(Vec<OriginChannelIdent>),
+}
+
+/// Decodes one page of a channel listing, returning its channels along with the start of the
+/// next range when more channels remain to be fetched.
+fn channel_page(body: &str) -> Result<(Vec<ChannelIdent>, Option<isize>)> {
+    let page: ChannelPage = serde_json::from_str(body)?;
+    let (channels, next) = match page {
+        ChannelPage::Complete(channels) => (channels, None),
+        ChannelPage::Paginated(results) => {
+            let next = next_range(&results);
+            (results.data, next)
+        }
+    };
+    Ok((channels.into_iter()
+                .map(|c| ChannelIdent::from(c.name))
+                .collect(),
+        next))
+}
+
+/// Returns the start of the range following `results`, if there is more to fetch.
+fn next_range<T>(results: &PackageResults<T>) -> Option<isize> {
+    if !results.data.is_empty() && results.range_end + 1 < results.total_count {
+        Some(results.range_end + 1)
+    } else {
+        None
+    }
+}
+
+/// Whether a channel history event added a package to the channel or removed it.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelOperation {
+    Promote,
+    Demote,
+}
+
+impl fmt::Display for ChannelOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match *self {
+            ChannelOperation::Promote => "promote",
+            ChannelOperation::Demote => "demote",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// A package being promoted into or demoted out of a channel, as recorded by Builder.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ChannelEvent {
+    pub operation:  ChannelOperation,
+    pub ident:      String,
+    pub created_at: String,
+    #[serde(default)]
+    pub requester:  Option<String>,
+}
+
+impl ChannelEvent {
+    /// The time the event was recorded, if Builder reported it as an RFC 3339 timestamp.
+    pub fn timestamp(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.created_at).ok()
+    }
+}
+
+/// Decodes one page of a channel's history, returning its events along with the start of the
+/// next range when more events remain to be fetched.
+fn history_page(body: &str) -> Result<(Vec<ChannelEvent>, Option<isize>)> {
+    let results: PackageResults<ChannelEvent> = serde_json::from_str(body)?;
+    let next = next_range(&results);
+    Ok((results.data, next))
+}
+
+/// The outcome of a successful package promotion.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PromoteResult {
+    /// The package was added to the channel.
+    Promoted,
+    /// The package was already a member of the channel; nothing changed.
+    AlreadyInChannel,
+}
+
+impl PromoteResult {
+    /// Decodes a successful promote response from Builder.
+    ///
+    /// A `201 Created` always indicates a new channel membership. A `200 OK` carrying a body of
+    /// `{"promoted": false}` indicates the package was already in the channel; any other `200 OK`
+    /// is treated as a promotion, which is what older Builders return in every case.
+    pub fn from_response(status: StatusCode, body: &str) -> Self {
+        if status == StatusCode::Created {
+            return PromoteResult::Promoted;
+        }
+        let value: serde_json::Value = match serde_json::from_str(body) {
+            Ok(value) => value,
+            Err(_) => return PromoteResult::Promoted,
+        };
+        match value.get("promoted").and_then(serde_json::Value::as_bool) {
+            Some(false) => PromoteResult::AlreadyInChannel,
+            _ => PromoteResult::Promoted,
+        }
+    }
+}
+
+/// The outcome of a successful request to create a channel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelCreated {
+    /// The channel was created.
+    Created,
+    /// The channel already existed; nothing changed.
+    AlreadyExisted,
+}
+
+impl ChannelCreated {
+    /// Interprets the status of a create channel response, returning `None` when it is an error.
+    pub fn from_status(status: StatusCode) -> Option<Self> {
+        match status {
+            StatusCode::Created => Some(ChannelCreated::Created),
+            StatusCode::Conflict => Some(ChannelCreated::AlreadyExisted),
+            _ => None,
+        }
+    }
+}
+
 pub trait DisplayProgress: Write {
     fn size(&mut self, size: u64);
     fn finish(&mut self);
 }
 
-pub struct Client(ApiClient);
+/// The settings a `Client` is made with, so that one client (and its pool of connections) can be
+/// built once and shared by every request of an operation.
+///
+/// Socket timeouts and the proxy are not settings here: the underlying HTTP client fixes the
+/// former and reads the latter from the `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment
+/// variables.
+#[derive(Debug)]
+pub struct ClientBuilder {
+    endpoint:     Url,
+    product:      String,
+    version:      String,
+    fs_root_path: Option<PathBuf>,
+    retry_policy: RetryPolicy,
+}
+
+impl ClientBuilder {
+    /// Starts a builder for a client of the Builder API at `endpoint`. An endpoint without a path
+    /// gets the default API path.
+    ///
+    /// # Failures
+    ///
+    /// * `endpoint` is not a valid URL
+    pub fn new<U>(endpoint: U, product: &str, version: &str) -> Result<Self>
+        where U: IntoUrl
+    {
+        let mut endpoint = endpoint.into_url().map_err(Error::UrlParseError)?;
+        if !endpoint.cannot_be_a_base() && endpoint.path() == "/" {
+            endpoint.set_path(DEFAULT_API_PATH);
+        }
+        Ok(ClientBuilder { endpoint,
+                           product: product.to_string(),
+                           version: version.to_string(),
+                           fs_root_path: None,
+                           retry_policy: RetryPolicy::none() })
+    }
+
+    /// Sets the filesystem root under which the SSL certificates are looked for.
+    pub fn fs_root_path<P>(mut self, fs_root_path: P) -> Self
+        where P: Into<PathBuf>
+    {
+        self.fs_root_path = Some(fs_root_path.into());
+        self
+    }
+
+    /// Sets how the client retries the requests bulk operations make, checking and changing the
+    /// channels of packages, when they fail transiently. By default they aren't retried.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let inner = ApiClient::new(self.endpoint,
+                                   &self.product,
+                                   &self.version,
+                                   self.fs_root_path.as_ref().map(PathBuf::as_path))
+                    .map_err(Error::HabitatHttpClient)?;
+        Ok(Client { inner,
+                    retry_policy: self.retry_policy,
+                    rate_limit_remaining: Mutex::new(None),
+                    api_debug: env::var(API_DEBUG_ENVVAR).map(|v| v == "1")
+                                                         .unwrap_or(false) })
+    }
+}
+
+pub struct Client {
+    inner:                ApiClient,
+    retry_policy:         RetryPolicy,
+    /// The `X-RateLimit-Remaining` Builder last sent.
+    rate_limit_remaining: Mutex<Option<u64>>,
+    /// Whether requests are logged, as `HAB_API_DEBUG` said when the client was built.
+    api_debug:            bool,
+}
 
 impl Client {
     pub fn new<U>(endpoint: U,
@@ -255,14 +520,115 @@ impl Client {
                   -> Result<Self>
         where U: IntoUrl
     {
-        let mut endpoint = endpoint.into_url().map_err(Error::UrlParseError)?;
-        if !endpoint.cannot_be_a_base() && endpoint.path() == "/" {
-            endpoint.set_path(DEFAULT_API_PATH);
+        let builder = ClientBuilder::new(endpoint, product, version)?;
+        match fs_root_path {
+            Some(path) => builder.fs_root_path(path),
+            None => builder,
+        }.build()
+    }
+
+    /// How the requests bulk operations make are retried when they fail transiently.
+    pub fn retry_policy(&self) -> &RetryPolicy { &self.retry_policy }
+
+    /// How many more requests Builder's `X-RateLimit-Remaining` header said could be made, as of
+    /// the last promotion, demotion or channel creation, so that a bulk operation can slow down
+    /// before it is rate limited. `None` until Builder has sent the header.
+    pub fn rate_limit_remaining(&self) -> Option<u64> {
+        *self.rate_limit_remaining
+             .lock()
+             .expect("Rate limit lock is poisoned")
+    }
+
+    fn get(&self, path: &str) -> ApiRequest<'_> { self.get_with_custom_url(path, |_| ()) }
+
+    fn get_with_custom_url<T>(&self, path: &str, customize: T) -> ApiRequest<'_>
+        where T: FnMut(&mut Url)
+    {
+        self.request(Method::Get, customize, |c| {
+                self.inner.get_with_custom_url(path, c)
+            })
+    }
+
+    fn post(&self, path: &str) -> ApiRequest<'_> { self.post_with_custom_url(path, |_| ()) }
+
+    fn post_with_custom_url<T>(&self, path: &str, customize: T) -> ApiRequest<'_>
+        where T: FnMut(&mut Url)
+    {
+        self.request(Method::Post, customize, |c| {
+                self.inner.post_with_custom_url(path, c)
+            })
+    }
+
+    fn put(&self, path: &str) -> ApiRequest<'_> { self.put_with_custom_url(path, |_| ()) }
+
+    fn put_with_custom_url<T>(&self, path: &str, customize: T) -> ApiRequest<'_>
+        where T: FnMut(&mut Url)
+    {
+        self.request(Method::Put, customize, |c| {
+                self.inner.put_with_custom_url(path, c)
+            })
+    }
+
+    fn delete(&self, path: &str) -> ApiRequest<'_> {
+        self.request(Method::Delete,
+                     |_| (),
+                     |c| self.inner.delete_with_custom_url(path, c))
+    }
+
+    /// Starts a request with `make`, handing it `customize` along with a note of the URL the
+    /// underlying HTTP client builds, so that the request is logged by the URL it is sent to.
+    fn request<'a, T, M>(&'a self, method: Method, mut customize: T, make: M) -> ApiRequest<'a>
+        where T: FnMut(&mut Url),
+              M: FnOnce(&mut dyn FnMut(&mut Url)) -> RequestBuilder<'a>
+    {
+        let mut url = None;
+        let mut note_url = |u: &mut Url| {
+            customize(u);
+            url = Some(u.clone());
+        };
+        let inner = make(&mut note_url);
+        ApiRequest { method,
+                     url: url.expect("The HTTP client always builds a request's URL"),
+                     headers: Headers::new(),
+                     debug: self.api_debug,
+                     inner }
+    }
+
+    /// Makes a request with `f`, retrying it as the client's retry policy says.
+    fn retrying<T, F>(&self, f: F) -> Result<T>
+        where F: FnMut() -> Result<T>
+    {
+        self.retry_policy.run(f)
+    }
+
+    fn note_rate_limit(&self, headers: &Headers) {
+        if let Some(remaining) = header_u64(headers, "X-RateLimit-Remaining") {
+            *self.rate_limit_remaining
+                 .lock()
+                 .expect("Rate limit lock is poisoned") = Some(remaining);
+        }
+    }
+
+    /// Checks that Builder can be reached and, given a token, that it accepts the token, so that
+    /// a bulk operation can fail before it has touched any packages rather than part way through.
+    /// With a token this asks who the token belongs to; without one, only for Builder's status.
+    ///
+    /// # Failures
+    ///
+    /// * Remote Builder is not available, as `Error::BuilderUnreachable`
+    /// * The token is invalid or has expired, as `Error::InvalidToken`
+    pub fn ping(&self, token: Option<&str>) -> Result<()> {
+        let path = if token.is_some() { "profile" } else { "status" };
+        debug!("Pinging Builder, path: {:?}", path);
+
+        let res = self.maybe_add_authz(self.get(path), token)
+                      .send()
+                      .map_err(builder_unreachable)?;
+
+        match ping_status(res.status) {
+            Some(result) => result,
+            None => Err(err_from_response(res)),
         }
-        Ok(Client(
-            ApiClient::new(endpoint, product, version, fs_root_path)
-                .map_err(Error::HabitatHttpClient)?,
-        ))
     }
 
     /// Retrieves the status of every group job in an origin
@@ -283,7 +649,7 @@ impl Client {
                .append_pair("limit", &limit.to_string());
         };
 
-        let res = self.0.get_with_custom_url(&path, custom).send()?;
+        let res = self.get_with_custom_url(&path, custom).send()?;
 
         if res.status != StatusCode::Ok {
             return Err(err_from_response(res));
@@ -308,7 +674,7 @@ impl Client {
                .append_pair("include_projects", &include_projects.to_string());
         };
 
-        let res = self.0.get_with_custom_url(&path, custom).send()?;
+        let res = self.get_with_custom_url(&path, custom).send()?;
 
         if res.status != StatusCode::Ok {
             return Err(err_from_response(res));
@@ -338,7 +704,7 @@ impl Client {
                .append_pair("target", &target.to_string());
         };
 
-        match self.add_authz(self.0.post_with_custom_url(&path, custom), token)
+        match self.add_authz(self.post_with_custom_url(&path, custom), token)
                   .send()
         {
             Ok(response) => {
@@ -363,8 +729,7 @@ impl Client {
 
         let url = format!("rdeps/{}", ident);
 
-        let mut res = self.0
-                          .get_with_custom_url(&url, |u| {
+        let mut res = self.get_with_custom_url(&url, |u| {
                               u.set_query(Some(&format!("target={}", &target.to_string())))
                           })
                           .send()
@@ -400,7 +765,7 @@ impl Client {
                           group_id,
                           if promote { "promote" } else { "demote" },
                           channel);
-        let res = self.add_authz(self.0.post(&url), token)
+        let res = self.add_authz(self.post(&url), token)
                       .body(&sbody)
                       .header(Accept::json())
                       .header(ContentType::json())
@@ -424,7 +789,7 @@ impl Client {
     /// * Remote API Server is not available
     pub fn job_group_cancel(&self, group_id: u64, token: &str) -> Result<()> {
         let url = format!("jobs/group/{}/cancel", group_id);
-        let res = self.add_authz(self.0.post(&url), token)
+        let res = self.add_authz(self.post(&url), token)
                       .send()
                       .map_err(Error::HyperError)?;
 
@@ -452,8 +817,7 @@ impl Client {
         where P: AsRef<Path>,
               D: DisplayProgress + Sized
     {
-        self.download(self.0
-                          .get(&format!("depot/origins/{}/encryption_key", origin)),
+        self.download(self.get(&format!("depot/origins/{}/encryption_key", origin)),
                       dst_path.as_ref(),
                       Some(token),
                       progress)
@@ -477,7 +841,7 @@ impl Client {
         });
 
         let sbody = serde_json::to_string(&body)?;
-        let res = self.add_authz(self.0.post(&path), token)
+        let res = self.add_authz(self.post(&path), token)
                       .body(&sbody)
                       .header(Accept::json())
                       .header(ContentType::json())
@@ -498,7 +862,7 @@ impl Client {
     pub fn delete_origin_secret(&self, origin: &str, token: &str, key: &str) -> Result<()> {
         let path = format!("depot/origins/{}/secret/{}", origin, key);
 
-        let res = self.add_authz(self.0.delete(&path), token).send()?;
+        let res = self.add_authz(self.delete(&path), token).send()?;
 
         // We expect NoContent, because the origin must be empty to delete
         if res.status == StatusCode::NoContent {
@@ -518,7 +882,7 @@ impl Client {
     pub fn delete_origin(&self, origin: &str, token: &str) -> Result<()> {
         let path = format!("depot/origins/{}", origin);
 
-        let res = self.add_authz(self.0.delete(&path), token).send()?;
+        let res = self.add_authz(self.delete(&path), token).send()?;
 
         if res.status != StatusCode::NoContent {
             return Err(err_from_response(res));
@@ -535,7 +899,7 @@ impl Client {
     pub fn list_origin_secrets(&self, origin: &str, token: &str) -> Result<Vec<String>> {
         let path = format!("depot/origins/{}/secret", origin);
 
-        let mut res = self.add_authz(self.0.get(&path), token).send()?;
+        let mut res = self.add_authz(self.get(&path), token).send()?;
 
         if res.status != StatusCode::Ok {
             return Err(err_from_response(res));
@@ -568,8 +932,7 @@ impl Client {
         where P: AsRef<Path>,
               D: DisplayProgress + Sized
     {
-        self.download(self.0
-                          .get(&format!("depot/origins/{}/keys/{}", origin, revision)),
+        self.download(self.get(&format!("depot/origins/{}/keys/{}", origin, revision)),
                       dst_path.as_ref(),
                       None,
                       progress)
@@ -591,15 +954,14 @@ impl Client {
         where P: AsRef<Path>,
               D: DisplayProgress + Sized
     {
-        self.download(self.0
-                          .get(&format!("depot/origins/{}/secret_keys/latest", origin)),
+        self.download(self.get(&format!("depot/origins/{}/secret_keys/latest", origin)),
                       dst_path.as_ref(),
                       Some(token),
                       progress)
     }
 
     pub fn show_origin_keys(&self, origin: &str) -> Result<Vec<OriginKeyIdent>> {
-        let mut res = self.0.get(&origin_keys_path(origin)).send()?;
+        let mut res = self.get(&origin_keys_path(origin)).send()?;
         debug!("Response: {:?}", res);
 
         if res.status != StatusCode::Ok {
@@ -631,19 +993,53 @@ impl Client {
         let path = package_channels_path(ident);
         debug!("Retrieving channels for {}", ident);
 
-        let mut res = self.maybe_add_authz(self.0.get(&path), token).send()?;
+        let channels = || -> Result<Vec<String>> {
+            let mut res = self.maybe_add_authz(self.get(&path), token).send()?;
 
-        if res.status != StatusCode::Ok {
-            return Err(err_from_response(res));
-        }
+            if res.status != StatusCode::Ok {
+                return Err(err_from_response(res));
+            }
 
-        let mut encoded = String::new();
-        res.read_to_string(&mut encoded)
-           .map_err(Error::BadResponseBody)?;
-        debug!("Response body: {:?}", encoded);
-        let channels: Vec<String> = serde_json::from_str::<Vec<String>>(&encoded)?.into_iter()
-                                                                                  .collect();
-        Ok(channels)
+            let mut encoded = String::new();
+            res.read_to_string(&mut encoded)
+               .map_err(Error::BadResponseBody)?;
+            debug!("Response body: {:?}", encoded);
+            let channels: Vec<String> = serde_json::from_str::<Vec<String>>(&encoded)?.into_iter()
+                                                                                      .collect();
+            Ok(channels)
+        };
+        self.retrying(channels)
+    }
+
+    /// Retrieves an origin's settings, such as the channels its packages must be promoted
+    /// through.
+    ///
+    /// # Failures
+    ///
+    /// * Remote Builder is not available
+    /// * Remote Builder does not keep origin settings
+    pub fn origin_settings(&self, origin: &str, token: Option<&str>) -> Result<OriginSettings> {
+        let path = format!("depot/origins/{}/settings", origin);
+        debug!("Retrieving settings for the {} origin", origin);
+
+        let settings = || -> Result<OriginSettings> {
+            let mut res = self.maybe_add_authz(self.get(&path), token).send()?;
+
+            match res.status {
+                StatusCode::Ok => (),
+                StatusCode::NotFound | StatusCode::NotImplemented => {
+                    return Err(Error::NotSupported("origin settings".to_string()));
+                }
+                _ => return Err(err_from_response(res)),
+            }
+
+            let mut encoded = String::new();
+            res.read_to_string(&mut encoded)
+               .map_err(Error::BadResponseBody)?;
+            debug!("Response body: {:?}", encoded);
+            Ok(serde_json::from_str(&encoded)?)
+        };
+        self.retrying(settings)
     }
 
     /// Upload a public origin key to a remote Builder.
@@ -675,11 +1071,11 @@ impl Client {
         let result = if let Some(mut progress) = progress {
             progress.size(file_size);
             let mut reader = TeeReader::new(file, progress);
-            self.add_authz(self.0.post(&path), token)
+            self.add_authz(self.post(&path), token)
                 .body(Body::SizedBody(&mut reader, file_size))
                 .send()
         } else {
-            self.add_authz(self.0.post(&path), token)
+            self.add_authz(self.post(&path), token)
                 .body(Body::SizedBody(&mut file, file_size))
                 .send()
         };
@@ -740,11 +1136,11 @@ impl Client {
         let result = if let Some(mut progress) = progress {
             progress.size(file_size);
             let mut reader = TeeReader::new(file, progress);
-            self.add_authz(self.0.post(&path), token)
+            self.add_authz(self.post(&path), token)
                 .body(Body::SizedBody(&mut reader, file_size))
                 .send()
         } else {
-            self.add_authz(self.0.post(&path), token)
+            self.add_authz(self.post(&path), token)
                 .body(Body::SizedBody(&mut file, file_size))
                 .send()
         };
@@ -787,9 +1183,9 @@ impl Client {
             return Err(Error::IdentNotFullyQualified);
         }
 
-        let req_builder = self.0.get_with_custom_url(&package_download(ident), |u| {
-                                    u.set_query(Some(&format!("target={}", target)))
-                                });
+        let req_builder = self.get_with_custom_url(&package_download(ident), |u| {
+                                  u.set_query(Some(&format!("target={}", target)))
+                              });
 
         match self.download(req_builder, dst_path.as_ref(), token, progress) {
             Ok(file) => Ok(PackageArchive::new(file)),
@@ -797,44 +1193,45 @@ impl Client {
         }
     }
 
-    /// Returns a package struct for the latest package.
+    /// Returns the metadata of the latest package, including its fully qualified identifier, the
+    /// channels it is in, and its target.
     ///
     /// An optional version can be specified which will scope the release returned to the latest
     /// release of that package.
     ///
     /// # Failures
     ///
-    /// * Package cannot be found
+    /// * Package cannot be found (`Error::PackageNotFound`)
     /// * Remote Builder is not available
     pub fn show_package(&self,
                         package: &PackageIdent,
                         target: PackageTarget,
                         channel: &ChannelIdent,
                         token: Option<&str>)
-                        -> Result<PackageIdent> {
+                        -> Result<PackageMetadata> {
         let mut url = channel_package_path(channel, package);
 
         if !package.fully_qualified() {
             url.push_str("/latest");
         }
 
-        let mut res = self.maybe_add_authz(self.0
-                                               .get_with_custom_url(&url, |u| {
+        let mut res = self.maybe_add_authz(self.get_with_custom_url(&url, |u| {
                                                    u.set_query(Some(&format!("target={}", target)))
                                                }),
                                            token)
                           .send()?;
 
-        if res.status != StatusCode::Ok {
-            return Err(err_from_response(res));
+        match res.status {
+            StatusCode::Ok => (),
+            StatusCode::NotFound => return Err(Error::PackageNotFound(package.to_string())),
+            _ => return Err(err_from_response(res)),
         }
 
         let mut encoded = String::new();
         res.read_to_string(&mut encoded)
            .map_err(Error::BadResponseBody)?;
         debug!("Body: {:?}", encoded);
-        let package: json::Package = serde_json::from_str::<json::Package>(&encoded)?;
-        Ok(package.ident.into())
+        PackageMetadata::from_json(&encoded, target)
     }
 
     /// Upload a package to a remote Builder.
@@ -878,7 +1275,7 @@ impl Client {
             Box::new(file)
         };
 
-        let result = self.add_authz(self.0.post_with_custom_url(&path, custom), token)
+        let result = self.add_authz(self.post_with_custom_url(&path, custom), token)
                          .body(Body::SizedBody(&mut reader, file_size))
                          .send();
 
@@ -906,7 +1303,7 @@ impl Client {
         };
         debug!("Reading from {}", &pa.path.display());
 
-        let result = self.add_authz(self.0.post_with_custom_url(&path, custom), token)
+        let result = self.add_authz(self.post_with_custom_url(&path, custom), token)
                          .body(Body::SizedBody(&mut file, file_size))
                          .send();
         match result {
@@ -917,7 +1314,7 @@ impl Client {
         }
     }
 
-    /// Promote a package to a given channel
+    /// Promote a package to a given channel, reporting whether the channel membership changed.
     ///
     /// # Failures
     ///
@@ -931,35 +1328,95 @@ impl Client {
                            ident: &PackageIdent,
                            channel: &ChannelIdent,
                            token: &str)
-                           -> Result<()> {
+                           -> Result<PromoteResult> {
+        let key = promote_idempotency_key(ident, channel);
+        self.promote_package_with_key(ident, channel, token, &key)
+    }
+
+    /// `promote_package`, sending `idempotency_key` so that a retry after a timeout isn't applied
+    /// twice. Every attempt at the same promotion should send the same key.
+    pub fn promote_package_with_key(&self,
+                                    ident: &PackageIdent,
+                                    channel: &ChannelIdent,
+                                    token: &str,
+                                    idempotency_key: &str)
+                                    -> Result<PromoteResult> {
+        self.promote_package_to(ident, ident.origin(), channel, token, idempotency_key)
+    }
+
+    /// Like `promote_package`, but into a channel of `origin` rather than of the package's own
+    /// origin, as when mirroring `acme/redis/...` into `mycorp`'s channels. Builder only allows
+    /// this when the token is a member of `origin`, and answers `403 Forbidden` otherwise.
+    ///
+    /// # Failures
+    ///
+    /// * Remote Builder is not available
+    /// * The token doesn't allow promoting into `origin`'s channels
+    pub fn promote_package_into(&self,
+                                ident: &PackageIdent,
+                                origin: &str,
+                                channel: &ChannelIdent,
+                                token: &str)
+                                -> Result<PromoteResult> {
+        let key = promote_into_idempotency_key(ident, origin, channel);
+        self.promote_package_to(ident, origin, channel, token, &key)
+    }
+
+    fn promote_package_to(&self,
+                          ident: &PackageIdent,
+                          origin: &str,
+                          channel: &ChannelIdent,
+                          token: &str,
+                          idempotency_key: &str)
+                          -> Result<PromoteResult> {
         if !ident.fully_qualified() {
             return Err(Error::IdentNotFullyQualified);
         }
         let path = channel_package_promote(channel, ident);
-        debug!("Promoting package {}", ident);
-
-        let res = self.add_authz(self.0.put(&path), token).send()?;
-
-        if res.status != StatusCode::Ok {
-            return Err(err_from_response(res));
+        let query = promote_origin_query(ident, origin);
+        debug!("Promoting package {} into the {} origin", ident, origin);
+
+        let promote = || -> Result<PromoteResult> {
+            let rb = self.put_with_custom_url(&path, |u| {
+                             u.set_query(query.as_ref().map(String::as_str))
+                         });
+            let rb = rb.header(IdempotencyKey(idempotency_key.to_string()));
+            let mut res = self.add_authz(rb, token).send()?;
+            self.note_rate_limit(&res.headers);
+
+            match res.status {
+                StatusCode::Ok | StatusCode::Created => {
+                    let mut encoded = String::new();
+                    res.read_to_string(&mut encoded)
+                       .map_err(Error::BadResponseBody)?;
+                    debug!("Response body: {:?}", encoded);
+                    Ok(PromoteResult::from_response(res.status, &encoded))
+                }
+                status => {
+                    match promote_forbidden(status, ident, origin) {
+                        Some(e) => Err(e),
+                        None => Err(err_from_response(res)),
+                    }
+                }
+            }
         };
-
-        Ok(())
+        self.retrying(promote)
     }
 
-    /// Demote a package from a given channel
+    /// Demote a package for the given target from a given channel
     ///
     /// # Failures
     ///
     /// * Remote Builder is not available
+    /// * The package is not in the channel
     ///
     /// # Panics
     ///
-    /// * If package does not exist in Builder
     /// * Authorization token was not set on client
     pub fn demote_package(&self,
                           ident: &PackageIdent,
                           channel: &ChannelIdent,
+                          target: PackageTarget,
                           token: &str)
                           -> Result<()> {
         if !ident.fully_qualified() {
@@ -968,31 +1425,44 @@ impl Client {
         let path = channel_package_demote(channel, ident);
         debug!("Demoting package {}", ident);
 
-        let res = self.add_authz(self.0.put(&path), token).send()?;
+        let demote = || -> Result<()> {
+            let rb = self.put_with_custom_url(&path, |u| {
+                             u.set_query(Some(&format!("target={}", target)))
+                         });
+            let res = self.add_authz(rb, token).send()?;
+            self.note_rate_limit(&res.headers);
 
-        if res.status != StatusCode::Ok {
-            return Err(err_from_response(res));
+            match demote_status(res.status, ident, channel) {
+                Some(result) => result,
+                None => Err(err_from_response(res)),
+            }
         };
-
-        Ok(())
+        self.retrying(demote)
     }
 
-    /// Create a custom channel
+    /// Create a custom channel, reporting whether it was created or already existed.
     ///
     /// # Failures
     ///
     /// * Remote Builder is not available
-    pub fn create_channel(&self, origin: &str, channel: &ChannelIdent, token: &str) -> Result<()> {
+    pub fn create_channel(&self,
+                          origin: &str,
+                          channel: &ChannelIdent,
+                          token: &str)
+                          -> Result<ChannelCreated> {
         let path = format!("depot/channels/{}/{}", origin, channel);
         debug!("Creating channel, path: {:?}", path);
 
-        let res = self.add_authz(self.0.post(&path), token).send()?;
+        let create = || -> Result<ChannelCreated> {
+            let res = self.add_authz(self.post(&path), token).send()?;
+            self.note_rate_limit(&res.headers);
 
-        if res.status != StatusCode::Created {
-            return Err(err_from_response(res));
+            match ChannelCreated::from_status(res.status) {
+                Some(created) => Ok(created),
+                None => Err(err_from_response(res)),
+            }
         };
-
-        Ok(())
+        self.retrying(create)
     }
 
     /// Delete a custom channel
@@ -1004,7 +1474,7 @@ impl Client {
         let path = format!("depot/channels/{}/{}", origin, channel);
         debug!("Deleting channel, path: {:?}", path);
 
-        let res = self.add_authz(self.0.delete(&path), token).send()?;
+        let res = self.add_authz(self.delete(&path), token).send()?;
 
         if res.status != StatusCode::Ok {
             return Err(err_from_response(res));
@@ -1013,34 +1483,75 @@ impl Client {
         Ok(())
     }
 
-    /// Return a list of channels for a given origin
+    /// Return a list of channels for a given origin, following Builder's pagination until every
+    /// channel has been retrieved.
     ///
     /// # Failures
     /// * Remote Builder is not available
-    /// * Authorization token was not set on client
-    pub fn list_channels(&self,
-                         origin: &str,
-                         include_sandbox_channels: bool)
-                         -> Result<Vec<String>> {
+    pub fn list_channels(&self, origin: &str, token: Option<&str>) -> Result<Vec<ChannelIdent>> {
         let path = format!("depot/channels/{}", origin);
-        let mut res = if include_sandbox_channels {
-            self.0
-                .get_with_custom_url(&path, |url| url.set_query(Some("sandbox=true")))
-                .send()?
-        } else {
-            self.0.get(&path).send()?
-        };
+        let mut channels = Vec::new();
+        let mut range = 0;
+
+        loop {
+            let query = format!("range={}", range);
+            let rb = self.get_with_custom_url(&path, |url| url.set_query(Some(&query)));
+            let mut res = self.maybe_add_authz(rb, token).send()?;
+
+            match res.status {
+                StatusCode::Ok | StatusCode::PartialContent => {
+                    let mut encoded = String::new();
+                    res.read_to_string(&mut encoded)
+                       .map_err(Error::BadResponseBody)?;
+                    let (page, next) = channel_page(&encoded)?;
+                    channels.extend(page);
+                    match next {
+                        Some(next) => range = next,
+                        None => return Ok(channels),
+                    }
+                }
+                _ => return Err(err_from_response(res)),
+            }
+        }
+    }
 
-        match res.status {
-            StatusCode::Ok | StatusCode::PartialContent => {
-                let mut encoded = String::new();
-                res.read_to_string(&mut encoded)
-                   .map_err(Error::BadResponseBody)?;
-                let results: Vec<OriginChannelIdent> = serde_json::from_str(&encoded)?;
-                let channels = results.into_iter().map(|o| o.name).collect();
-                Ok(channels)
+    /// Return every promote and demote recorded for a channel, in the order Builder returns them.
+    ///
+    /// # Failures
+    ///
+    /// * Remote Builder is not available
+    /// * Remote Builder does not record channel history
+    pub fn channel_history(&self,
+                           origin: &str,
+                           channel: &ChannelIdent,
+                           token: Option<&str>)
+                           -> Result<Vec<ChannelEvent>> {
+        let path = channel_history_path(origin, channel);
+        let mut events = Vec::new();
+        let mut range = 0;
+
+        loop {
+            let query = format!("range={}", range);
+            let rb = self.get_with_custom_url(&path, |url| url.set_query(Some(&query)));
+            let mut res = self.maybe_add_authz(rb, token).send()?;
+
+            match res.status {
+                StatusCode::Ok | StatusCode::PartialContent => {
+                    let mut encoded = String::new();
+                    res.read_to_string(&mut encoded)
+                       .map_err(Error::BadResponseBody)?;
+                    let (page, next) = history_page(&encoded)?;
+                    events.extend(page);
+                    match next {
+                        Some(next) => range = next,
+                        None => return Ok(events),
+                    }
+                }
+                StatusCode::NotFound | StatusCode::NotImplemented => {
+                    return Err(Error::NotSupported("channel history".to_string()));
+                }
+                _ => return Err(err_from_response(res)),
             }
-            _ => Err(err_from_response(res)),
         }
     }
 
@@ -1053,7 +1564,7 @@ impl Client {
                           search_term: &str,
                           token: Option<&str>)
                           -> Result<(Vec<PackageIdent>, bool)> {
-        let mut res = self.maybe_add_authz(self.0.get(&package_search(search_term)), token)
+        let mut res = self.maybe_add_authz(self.get(&package_search(search_term)), token)
                           .send()?;
         match res.status {
             StatusCode::Ok | StatusCode::PartialContent => {
@@ -1068,10 +1579,7 @@ impl Client {
         }
     }
 
-    fn maybe_add_authz<'a>(&'a self,
-                           rb: RequestBuilder<'a>,
-                           token: Option<&str>)
-                           -> RequestBuilder<'_> {
+    fn maybe_add_authz<'a>(&'a self, rb: ApiRequest<'a>, token: Option<&str>) -> ApiRequest<'_> {
         if token.is_some() {
             rb.header(Authorization(Bearer { token: token.unwrap().to_string(), }))
         } else {
@@ -1079,12 +1587,12 @@ impl Client {
         }
     }
 
-    fn add_authz<'a>(&'a self, rb: RequestBuilder<'a>, token: &str) -> RequestBuilder<'_> {
+    fn add_authz<'a>(&'a self, rb: ApiRequest<'a>, token: &str) -> ApiRequest<'_> {
         rb.header(Authorization(Bearer { token: token.to_string(), }))
     }
 
     fn download<'a, D>(&'a self,
-                       rb: RequestBuilder<'a>,
+                       rb: ApiRequest<'a>,
                        dst_path: &Path,
                        token: Option<&str>,
                        progress: Option<D>)
@@ -1129,7 +1637,7 @@ impl Client {
     // infer the type for a None for a Display + Sized trait, and makes this task
     // much more difficult than it should be. Fix later.
     fn x_download(&self, path: &str, dst_path: &Path, token: &str) -> Result<PathBuf> {
-        let mut res = self.add_authz(self.0.get(path), token).send()?;
+        let mut res = self.add_authz(self.get(path), token).send()?;
         debug!("Response: {:?}", res);
 
         if res.status != hyper::status::StatusCode::Ok {
@@ -1148,6 +1656,91 @@ impl Client {
     }
 }
 
+/// A request to Builder which remembers its method, URL and headers, so that it can be logged
+/// when it is sent in `HAB_API_DEBUG` mode.
+struct ApiRequest<'a> {
+    method:  Method,
+    url:     Url,
+    headers: Headers,
+    debug:   bool,
+    inner:   RequestBuilder<'a>,
+}
+
+impl<'a> ApiRequest<'a> {
+    fn header<H>(mut self, header: H) -> Self
+        where H: Header + HeaderFormat + Clone
+    {
+        self.headers.set(header.clone());
+        self.inner = self.inner.header(header);
+        self
+    }
+
+    fn body<B>(mut self, body: B) -> Self
+        where B: Into<Body<'a>>
+    {
+        self.inner = self.inner.body(body);
+        self
+    }
+
+    fn send(self) -> hyper::Result<Response> {
+        let ApiRequest { method,
+                         url,
+                         headers,
+                         debug,
+                         inner, } = self;
+        if !debug {
+            return inner.send();
+        }
+        send_logged(&method,
+                    &url,
+                    &headers,
+                    || inner.send(),
+                    |line| debug!("{}", line))
+    }
+}
+
+/// Sends a request with `send`, handing `log` the request's debug line, and then the response's
+/// or the failure's.
+fn send_logged<F, L>(method: &Method,
+                     url: &Url,
+                     headers: &Headers,
+                     send: F,
+                     mut log: L)
+                     -> hyper::Result<Response>
+    where F: FnOnce() -> hyper::Result<Response>,
+          L: FnMut(String)
+{
+    log(request_debug_line(method, url, headers));
+    let result = send();
+    match result {
+        Ok(ref res) => log(response_debug_line(method, &res.url, res.status)),
+        Err(ref e) => log(format!("API request failed: {} {}: {}", method, url, e)),
+    }
+    result
+}
+
+/// The debug line for a request, which never includes the value of an `Authorization` header.
+fn request_debug_line(method: &Method, url: &Url, headers: &Headers) -> String {
+    let headers = headers.iter()
+                         .map(|header| {
+                             if header.name().eq_ignore_ascii_case("Authorization") {
+                                 format!("{}: <redacted>", header.name())
+                             } else {
+                                 format!("{}: {}", header.name(), header.value_string())
+                             }
+                         })
+                         .collect::<Vec<_>>();
+    if headers.is_empty() {
+        format!("API request: {} {}", method, url)
+    } else {
+        format!("API request: {} {} [{}]", method, url, headers.join(", "))
+    }
+}
+
+fn response_debug_line(method: &Method, url: &Url, status: StatusCode) -> String {
+    format!("API response: {} for {} {}", status, method, url)
+}
+
 fn err_from_response(mut response: hyper::client::Response) -> Error {
     if response.status == StatusCode::Unauthorized {
         return Error::APIError(response.status,
@@ -1157,22 +1750,57 @@ fn err_from_response(mut response: hyper::client::Response) -> Error {
     }
 
     let mut buff = String::new();
-    match response.read_to_string(&mut buff) {
+    let message = match response.read_to_string(&mut buff) {
         Ok(_) => {
             match serde_json::from_str::<NetError>(&buff) {
-                Ok(err) => Error::APIError(response.status, err.to_string()),
-                Err(_) => Error::APIError(response.status, buff),
+                Ok(err) => err.to_string(),
+                Err(_) => buff,
             }
         }
-        Err(_) => {
-            buff.truncate(0);
-            Error::APIError(response.status, buff)
-        }
+        Err(_) => String::new(),
+    };
+    if response.status == StatusCode::TooManyRequests {
+        return Error::RateLimited(retry_after(&response.headers), message);
+    }
+    Error::APIError(response.status, message)
+}
+
+/// The idempotency key for promoting `ident` to `channel`. It depends on nothing else, so every
+/// attempt at the promotion sends the same key.
+pub fn promote_idempotency_key(ident: &PackageIdent, channel: &ChannelIdent) -> String {
+    format!("promote:{}:{}", ident, channel)
+}
+
+/// The idempotency key for promoting `ident` to a channel of another origin.
+fn promote_into_idempotency_key(ident: &PackageIdent,
+                                origin: &str,
+                                channel: &ChannelIdent)
+                                -> String {
+    if origin == ident.origin() {
+        promote_idempotency_key(ident, channel)
+    } else {
+        format!("promote:{}:{}/{}", ident, origin, channel)
     }
 }
 
+/// The wait a `Retry-After` header asks for, when it is given in seconds rather than as a date.
+fn retry_after(headers: &Headers) -> Option<Duration> {
+    header_u64(headers, "Retry-After").map(Duration::from_secs)
+}
+
+fn header_u64(headers: &Headers, name: &str) -> Option<u64> {
+    headers.get_raw(name)
+           .and_then(|values| values.first())
+           .and_then(|value| std::str::from_utf8(value).ok())
+           .and_then(|value| value.trim().parse().ok())
+}
+
 fn origin_keys_path(origin: &str) -> String { format!("depot/origins/{}/keys", origin) }
 
+fn channel_history_path(origin: &str, channel: &ChannelIdent) -> String {
+    format!("depot/channels/{}/{}/history", origin, channel)
+}
+
 fn origin_secret_keys_latest(origin: &str) -> String {
     format!("depot/origins/{}/secret_keys/latest", origin)
 }
@@ -1212,6 +1840,27 @@ fn package_channels_path(package: &PackageIdent) -> String {
             package.release().unwrap())
 }
 
+/// The query which names the origin whose channel a package is promoted into, when that isn't
+/// the package's own origin. The package is always found by its own origin, in the path.
+fn promote_origin_query(ident: &PackageIdent, origin: &str) -> Option<String> {
+    if origin == ident.origin() {
+        None
+    } else {
+        Some(format!("origin={}", origin))
+    }
+}
+
+/// Promoting into a channel of `origin` is refused with `403 Forbidden` when the package is of
+/// another origin and the token isn't allowed to, which is told apart from other refusals.
+fn promote_forbidden(status: StatusCode, ident: &PackageIdent, origin: &str) -> Option<Error> {
+    match status {
+        StatusCode::Forbidden if origin != ident.origin() => {
+            Some(Error::CrossOriginPromotionForbidden(ident.to_string(), origin.to_string()))
+        }
+        _ => None,
+    }
+}
+
 fn channel_package_promote(channel: &ChannelIdent, package: &PackageIdent) -> String {
     format!("depot/channels/{}/{}/pkgs/{}/{}/{}/promote",
             package.origin(),
@@ -1221,6 +1870,36 @@ fn channel_package_promote(channel: &ChannelIdent, package: &PackageIdent) -> St
             package.release().unwrap())
 }
 
+/// The error for a request which could not be sent to Builder at all.
+fn builder_unreachable(err: hyper::error::Error) -> Error {
+    Error::BuilderUnreachable(err.to_string())
+}
+
+/// Interprets the status of a ping response, returning `None` when it is an error which Builder
+/// describes in the response body.
+fn ping_status(status: StatusCode) -> Option<Result<()>> {
+    match status {
+        StatusCode::Ok => Some(Ok(())),
+        StatusCode::Unauthorized => Some(Err(Error::InvalidToken)),
+        _ => None,
+    }
+}
+
+/// Interprets the status of a demote response, returning `None` when it is an error which
+/// Builder describes in the response body.
+fn demote_status(status: StatusCode,
+                 ident: &PackageIdent,
+                 channel: &ChannelIdent)
+                 -> Option<Result<()>> {
+    match status {
+        StatusCode::Ok => Some(Ok(())),
+        StatusCode::NotFound => {
+            Some(Err(Error::PackageNotInChannel(ident.to_string(), channel.to_string())))
+        }
+        _ => None,
+    }
+}
+
 fn channel_package_demote(channel: &ChannelIdent, package: &PackageIdent) -> String {
     format!("depot/channels/{}/{}/pkgs/{}/{}/{}/demote",
             package.origin(),
@@ -1233,7 +1912,415 @@ fn channel_package_demote(channel: &ChannelIdent, package: &PackageIdent) -> Str
 #[cfg(test)]
 mod tests {
     use super::*;
+    use hyper::net::NetworkStream;
     use serde_json;
+    use std::{io::{BufRead,
+                   BufReader},
+              net::{SocketAddr,
+                    TcpListener},
+              sync::mpsc,
+              thread,
+              time::Duration};
+
+    fn headers(name: &'static str, value: &str) -> Headers {
+        let mut headers = Headers::new();
+        headers.set_raw(name, vec![value.as_bytes().to_vec()]);
+        headers
+    }
+
+    #[test]
+    fn api_debug_lines_never_include_the_token() {
+        let token = "s3cr3t-t0k3n";
+        let mut headers = Headers::new();
+        headers.set(Authorization(Bearer { token: token.to_string(), }));
+        headers.set(ContentType::json());
+        let url = Url::parse("https://bldr.example.com/v1/depot/channels/core/foo").unwrap();
+
+        let request = request_debug_line(&Method::Post, &url, &headers);
+        let response = response_debug_line(&Method::Post, &url, StatusCode::Created);
+        for line in &[&request, &response] {
+            assert!(!line.contains(token), "the token was logged: {}", line);
+        }
+        assert!(request.starts_with("API request: POST \
+                                     https://bldr.example.com/v1/depot/channels/core/foo ["));
+        assert!(request.contains("Authorization: <redacted>"));
+        assert!(request.contains("Content-Type: application/json"));
+        assert_eq!(response,
+                   "API response: 201 Created for POST \
+                    https://bldr.example.com/v1/depot/channels/core/foo");
+    }
+
+    /// A connection which answers with `response`, whatever is written to it.
+    struct CannedStream(io::Cursor<Vec<u8>>);
+
+    impl Read for CannedStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.0.read(buf) }
+    }
+
+    impl Write for CannedStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> { Ok(buf.len()) }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    impl NetworkStream for CannedStream {
+        fn peer_addr(&mut self) -> io::Result<SocketAddr> { Ok(([127, 0, 0, 1], 80).into()) }
+
+        fn set_read_timeout(&self, _: Option<Duration>) -> io::Result<()> { Ok(()) }
+
+        fn set_write_timeout(&self, _: Option<Duration>) -> io::Result<()> { Ok(()) }
+    }
+
+    fn canned_response(url: &Url, response: &str) -> hyper::Result<Response> {
+        let stream = CannedStream(io::Cursor::new(response.as_bytes().to_vec()));
+        Response::new(url.clone(), Box::new(stream))
+    }
+
+    fn token_headers(token: &str) -> Headers {
+        let mut headers = Headers::new();
+        headers.set(Authorization(Bearer { token: token.to_string(), }));
+        headers
+    }
+
+    #[test]
+    fn send_logs_the_request_and_the_response_by_url_without_the_token() {
+        let token = "s3cr3t-t0k3n";
+        let url = Url::parse("https://bldr.example.com/v1/depot/channels/core/foo").unwrap();
+        let mut lines = Vec::new();
+
+        let res = send_logged(&Method::Post,
+                              &url,
+                              &token_headers(token),
+                              || canned_response(&url, "HTTP/1.1 201 Created\r\n\r\n"),
+                              |line| lines.push(line)).unwrap();
+
+        assert_eq!(res.status, StatusCode::Created);
+        assert_eq!(lines,
+                   vec![format!("API request: POST {} [Authorization: <redacted>]", url),
+                        format!("API response: 201 Created for POST {}", url)]);
+    }
+
+    #[test]
+    fn send_logs_a_failed_request_by_url_without_the_token() {
+        let token = "s3cr3t-t0k3n";
+        let url = Url::parse("https://bldr.example.com/v1/depot/channels/core/foo").unwrap();
+        let mut lines = Vec::new();
+
+        let result =
+            send_logged(&Method::Put,
+                        &url,
+                        &token_headers(token),
+                        || {
+                            Err(hyper::Error::Io(io::Error::new(io::ErrorKind::ConnectionRefused,
+                                                                "refused")))
+                        },
+                        |line| lines.push(line));
+
+        assert!(result.is_err());
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| !line.contains(token)),
+                "{:?}",
+                lines);
+        assert!(lines[1].starts_with(&format!("API request failed: PUT {}: ", url)),
+                "{:?}",
+                lines);
+    }
+
+    #[test]
+    fn promote_idempotency_key_depends_on_ident_and_channel() {
+        let ident = PackageIdent::from_str("core/redis/4.0.14/20190319155852").unwrap();
+        let key = promote_idempotency_key(&ident, &ChannelIdent::from("bar"));
+
+        assert_eq!(key,
+                   promote_idempotency_key(&ident, &ChannelIdent::from("bar")));
+        assert_ne!(key,
+                   promote_idempotency_key(&ident, &ChannelIdent::from("baz")));
+    }
+
+    #[test]
+    fn promote_into_names_the_override_origin_apart_from_the_package() {
+        let ident = PackageIdent::from_str("acme/redis/4.0.14/20190319155852").unwrap();
+        let channel = ChannelIdent::from("stable");
+
+        assert_eq!(channel_package_promote(&channel, &ident),
+                   "depot/channels/acme/stable/pkgs/redis/4.0.14/20190319155852/promote");
+        assert_eq!(promote_origin_query(&ident, "mycorp"),
+                   Some("origin=mycorp".to_string()));
+        assert_eq!(promote_origin_query(&ident, "acme"), None);
+        assert_eq!(promote_into_idempotency_key(&ident, "acme", &channel),
+                   promote_idempotency_key(&ident, &channel));
+        assert_ne!(promote_into_idempotency_key(&ident, "mycorp", &channel),
+                   promote_idempotency_key(&ident, &channel));
+    }
+
+    #[test]
+    fn promote_forbidden_only_when_crossing_origins() {
+        let ident = PackageIdent::from_str("acme/redis/4.0.14/20190319155852").unwrap();
+
+        match promote_forbidden(StatusCode::Forbidden, &ident, "mycorp") {
+            Some(Error::CrossOriginPromotionForbidden(ref i, ref o)) => {
+                assert_eq!(i, "acme/redis/4.0.14/20190319155852");
+                assert_eq!(o, "mycorp");
+            }
+            other => panic!("Expected CrossOriginPromotionForbidden, got {:?}", other),
+        }
+        assert!(promote_forbidden(StatusCode::Forbidden, &ident, "acme").is_none());
+        assert!(promote_forbidden(StatusCode::NotFound, &ident, "mycorp").is_none());
+    }
+
+    #[test]
+    fn origin_settings_decode_the_required_channel_order() {
+        let json = r#"{"default_package_visibility":"public",
+                       "required_channel_order":["qa","staging"]}"#;
+        let settings: OriginSettings = serde_json::from_str(json).unwrap();
+        assert_eq!(settings.required_channel_order,
+                   vec!["qa".to_string(), "staging".to_string()]);
+
+        let settings: OriginSettings =
+            serde_json::from_str(r#"{"default_package_visibility":"public"}"#).unwrap();
+        assert_eq!(settings, OriginSettings::default());
+    }
+
+    #[test]
+    fn retry_after_is_read_in_seconds() {
+        assert_eq!(retry_after(&headers("Retry-After", "5")),
+                   Some(Duration::from_secs(5)));
+        assert_eq!(retry_after(&headers("Retry-After", "Wed, 21 Oct 2015 07:28:00 GMT")),
+                   None);
+        assert_eq!(retry_after(&Headers::new()), None);
+    }
+
+    #[test]
+    fn rate_limit_remaining_is_read_as_a_number() {
+        assert_eq!(header_u64(&headers("X-RateLimit-Remaining", " 42 "),
+                              "X-RateLimit-Remaining"),
+                   Some(42));
+        assert_eq!(header_u64(&headers("X-RateLimit-Remaining", "lots"),
+                              "X-RateLimit-Remaining"),
+                   None);
+    }
+
+    #[test]
+    fn client_builder_defaults_the_api_path() {
+        let builder = ClientBuilder::new("https://bldr.example.com", "hab", "0.0.0").unwrap();
+        assert_eq!(builder.endpoint.as_str(), "https://bldr.example.com/v1");
+        assert_eq!(builder.fs_root_path, None);
+        assert_eq!(builder.retry_policy, RetryPolicy::none());
+
+        let builder = ClientBuilder::new("https://bldr.example.com/v2", "hab", "0.0.0").unwrap();
+        assert_eq!(builder.endpoint.as_str(), "https://bldr.example.com/v2");
+    }
+
+    #[test]
+    fn client_builder_applies_settings() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(10));
+        let builder = ClientBuilder::new("https://bldr.example.com", "hab", "0.0.0").unwrap();
+        let builder = builder.fs_root_path("/hab/root").retry_policy(policy);
+        assert_eq!(builder.product, "hab");
+        assert_eq!(builder.version, "0.0.0");
+        assert_eq!(builder.fs_root_path, Some(PathBuf::from("/hab/root")));
+        assert_eq!(builder.retry_policy, policy);
+    }
+
+    /// A response with `status` and `body`, after which the connection is closed.
+    fn response(status: &str, body: &str) -> String {
+        format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body)
+    }
+
+    /// Answers the requests made to a local port with `responses`, one each and in order,
+    /// returning Builder's URL there and a receiver of each request line, which is sent before
+    /// the request is answered.
+    fn serve(responses: Vec<String>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for (stream, response) in listener.incoming().zip(responses) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut body_len = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if line.to_lowercase().starts_with("content-length:") {
+                        body_len = line["content-length:".len()..].trim().parse().unwrap();
+                    }
+                }
+                io::copy(&mut reader.by_ref().take(body_len), &mut io::sink()).unwrap();
+                tx.send(request_line.trim_end().to_string()).unwrap();
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    /// A builder of a client of the Builder at `url`, keeping its SSL certificates apart from the
+    /// real ones.
+    fn test_builder(url: &str) -> ClientBuilder {
+        let builder = ClientBuilder::new(url, "hab", "0.0.0").unwrap();
+        builder.fs_root_path(env::temp_dir().join("hab-api-client"))
+    }
+
+    #[test]
+    fn client_retries_as_its_builder_says() {
+        let (url, requests) = serve(vec![response("503 Service Unavailable", ""); 3]);
+        let client = test_builder(&url).retry_policy(RetryPolicy::new(3, Duration::from_millis(0)))
+                                       .build()
+                                       .unwrap();
+
+        match client.origin_settings("core", None) {
+            Err(Error::APIError(StatusCode::ServiceUnavailable, _)) => (),
+            other => panic!("Expected a 503 APIError, got {:?}", other),
+        }
+        let requests = requests.try_iter().collect::<Vec<_>>();
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter()
+                        .all(|line| line == "GET /v1/depot/origins/core/settings HTTP/1.1"),
+                "{:?}",
+                requests);
+    }
+
+    #[test]
+    fn client_does_not_retry_by_default() {
+        let (url, requests) = serve(vec![response("503 Service Unavailable", ""); 2]);
+        let client = test_builder(&url).build().unwrap();
+
+        assert!(client.origin_settings("core", None).is_err());
+        assert_eq!(requests.try_iter().count(), 1);
+    }
+
+    #[test]
+    fn client_builder_rejects_invalid_urls() {
+        match ClientBuilder::new("not a url", "hab", "0.0.0") {
+            Err(Error::UrlParseError(_)) => (),
+            other => panic!("Expected UrlParseError, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn package_metadata_from_json() {
+        let body = r#"{
+            "ident": {"origin": "core", "name": "redis", "version": "4.0.14",
+                      "release": "20190319155852"},
+            "checksum": "abc123",
+            "manifest": "",
+            "deps": [],
+            "tdeps": [],
+            "exposes": [6379],
+            "config": "",
+            "channels": ["unstable", "stable", "bar"],
+            "target": "x86_64-windows"
+        }"#;
+        let target = PackageTarget::from_str("x86_64-linux").unwrap();
+        let metadata = PackageMetadata::from_json(body, target).unwrap();
+
+        assert_eq!(metadata.ident,
+                   PackageIdent::from_str("core/redis/4.0.14/20190319155852").unwrap());
+        assert_eq!(metadata.channels,
+                   vec![ChannelIdent::unstable(),
+                        ChannelIdent::stable(),
+                        ChannelIdent::from("bar")]);
+        assert!(metadata.in_channel(&ChannelIdent::stable()));
+        assert!(!metadata.in_channel(&ChannelIdent::from("baz")));
+        assert_eq!(metadata.target,
+                   PackageTarget::from_str("x86_64-windows").unwrap());
+    }
+
+    #[test]
+    fn package_metadata_defaults_target_and_channels() {
+        let body = r#"{
+            "ident": {"origin": "core", "name": "redis", "version": "4.0.14",
+                      "release": "20190319155852"},
+            "checksum": "", "manifest": "", "deps": [], "tdeps": [], "exposes": [], "config": ""
+        }"#;
+        let target = PackageTarget::from_str("x86_64-linux").unwrap();
+        let metadata = PackageMetadata::from_json(body, target).unwrap();
+
+        assert!(metadata.channels.is_empty());
+        assert_eq!(metadata.target, target);
+    }
+
+    #[test]
+    fn channel_page_decodes_multiple_pages() {
+        let first = r#"{"range_start": 0, "range_end": 1, "total_count": 3,
+                         "data": [{"name": "unstable"}, {"name": "bar"}]}"#;
+        let second = r#"{"range_start": 2, "range_end": 2, "total_count": 3,
+                          "data": [{"name": "stable"}]}"#;
+
+        let (mut channels, next) = channel_page(first).unwrap();
+        assert_eq!(next, Some(2));
+        let (page, next) = channel_page(second).unwrap();
+        assert_eq!(next, None);
+        channels.extend(page);
+
+        assert_eq!(channels,
+                   vec![ChannelIdent::unstable(),
+                        ChannelIdent::from("bar"),
+                        ChannelIdent::stable()]);
+    }
+
+    #[test]
+    fn channel_page_decodes_unpaginated_array() {
+        let body = r#"[{"name": "stable"}, {"name": "unstable"}]"#;
+        let (channels, next) = channel_page(body).unwrap();
+        assert_eq!(next, None);
+        assert_eq!(channels,
+                   vec![ChannelIdent::stable(), ChannelIdent::unstable()]);
+    }
+
+    #[test]
+    fn history_page_decodes_events() {
+        let body = r#"{"range_start": 0, "range_end": 1, "total_count": 2, "data": [
+            {"operation": "promote", "ident": "core/redis/4.0.14/20190319155852",
+             "created_at": "2019-03-20T10:00:00Z", "requester": "fnichol"},
+            {"operation": "demote", "ident": "core/redis/4.0.10/20180608091936",
+             "created_at": "2019-03-21T10:00:00+01:00"}
+        ]}"#;
+        let (events, next) = history_page(body).unwrap();
+
+        assert_eq!(next, None);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].operation, ChannelOperation::Promote);
+        assert_eq!(events[0].ident, "core/redis/4.0.14/20190319155852");
+        assert_eq!(events[0].requester, Some("fnichol".to_string()));
+        assert_eq!(events[1].operation, ChannelOperation::Demote);
+        assert_eq!(events[1].requester, None);
+        assert!(events[1].timestamp().unwrap() > events[0].timestamp().unwrap());
+    }
+
+    #[test]
+    fn demote_status_maps_not_found_to_not_in_channel() {
+        let ident = PackageIdent::from_str("core/redis/4.0.14/20190319155852").unwrap();
+        let channel = ChannelIdent::from("bar");
+
+        assert!(demote_status(StatusCode::Ok, &ident, &channel).unwrap()
+                                                               .is_ok());
+        match demote_status(StatusCode::NotFound, &ident, &channel) {
+            Some(Err(Error::PackageNotInChannel(ref i, ref c))) => {
+                assert_eq!(i, "core/redis/4.0.14/20190319155852");
+                assert_eq!(c, "bar");
+            }
+            other => panic!("Expected PackageNotInChannel, got {:?}", other),
+        }
+        assert!(demote_status(StatusCode::Forbidden, &ident, &channel).is_none());
+    }
+
+    #[test]
+    fn channel_page_stops_on_empty_page() {
+        let body = r#"{"range_start": 50, "range_end": 49, "total_count": 100, "data": []}"#;
+        let (channels, next) = channel_page(body).unwrap();
+        assert!(channels.is_empty());
+        assert_eq!(next, None);
+    }
 
     #[test]
     fn json_round_trip_u64_fields() {
@@ -1264,4 +2351,213 @@ mod tests {
         assert_eq!(pre.origin_id, post.origin_id);
         assert_eq!(pre.owner_id, post.owner_id);
     }
+
+    #[test]
+    fn ping_ok_is_ok() {
+        match ping_status(StatusCode::Ok) {
+            Some(Ok(())) => (),
+            other => panic!("Expected Some(Ok(())), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ping_unauthorized_is_an_invalid_token() {
+        match ping_status(StatusCode::Unauthorized) {
+            Some(Err(Error::InvalidToken)) => (),
+            other => panic!("Expected InvalidToken, got {:?}", other),
+        }
+        assert!(ping_status(StatusCode::InternalServerError).is_none());
+    }
+
+    #[test]
+    fn ping_connection_error_is_builder_unreachable() {
+        let err = hyper::error::Error::Io(io::Error::new(io::ErrorKind::ConnectionRefused,
+                                                         "connection refused"));
+        match builder_unreachable(err) {
+            Error::BuilderUnreachable(ref msg) => assert!(msg.contains("connection refused")),
+            other => panic!("Expected BuilderUnreachable, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn channel_created_maps_created_and_conflict() {
+        assert_eq!(ChannelCreated::from_status(StatusCode::Created),
+                   Some(ChannelCreated::Created));
+        assert_eq!(ChannelCreated::from_status(StatusCode::Conflict),
+                   Some(ChannelCreated::AlreadyExisted));
+    }
+
+    #[test]
+    fn channel_created_leaves_other_statuses_as_errors() {
+        assert_eq!(ChannelCreated::from_status(StatusCode::Ok), None);
+        assert_eq!(ChannelCreated::from_status(StatusCode::Forbidden), None);
+        assert_eq!(ChannelCreated::from_status(StatusCode::InternalServerError),
+                   None);
+    }
+
+    #[test]
+    fn promote_result_created_is_promoted() {
+        assert_eq!(PromoteResult::from_response(StatusCode::Created, ""),
+                   PromoteResult::Promoted);
+        assert_eq!(PromoteResult::from_response(StatusCode::Created, r#"{"promoted":false}"#),
+                   PromoteResult::Promoted);
+    }
+
+    #[test]
+    fn promote_result_ok_without_body_is_promoted() {
+        assert_eq!(PromoteResult::from_response(StatusCode::Ok, ""),
+                   PromoteResult::Promoted);
+        assert_eq!(PromoteResult::from_response(StatusCode::Ok, r#"{"promoted":true}"#),
+                   PromoteResult::Promoted);
+    }
+
+    #[test]
+    fn promote_result_ok_not_promoted_is_already_in_channel() {
+        assert_eq!(PromoteResult::from_response(StatusCode::Ok, r#"{"promoted":false}"#),
+                   PromoteResult::AlreadyInChannel);
+    }
+
+    fn redis() -> PackageIdent {
+        PackageIdent::from_str("core/redis/4.0.14/20190319155852").unwrap()
+    }
+
+    #[test]
+    fn promote_package_reports_whether_builder_promoted_the_package() {
+        let (url, requests) = serve(vec![response("201 Created", ""),
+                                         response("200 OK", r#"{"promoted":false}"#)]);
+        let client = test_builder(&url).build().unwrap();
+        let channel = ChannelIdent::from("bar");
+
+        assert_eq!(client.promote_package(&redis(), &channel, "token").unwrap(),
+                   PromoteResult::Promoted);
+        assert_eq!(client.promote_package(&redis(), &channel, "token").unwrap(),
+                   PromoteResult::AlreadyInChannel);
+        let promote =
+            "PUT /v1/depot/channels/core/bar/pkgs/redis/4.0.14/20190319155852/promote HTTP/1.1";
+        assert_eq!(requests.try_iter().collect::<Vec<_>>(), vec![promote; 2]);
+    }
+
+    #[test]
+    fn list_channels_follows_builder_through_every_page() {
+        let first = r#"{"range_start": 0, "range_end": 1, "total_count": 3,
+                         "data": [{"name": "unstable"}, {"name": "bar"}]}"#;
+        let second = r#"{"range_start": 2, "range_end": 2, "total_count": 3,
+                          "data": [{"name": "stable"}]}"#;
+        let (url, requests) = serve(vec![response("206 Partial Content", first),
+                                         response("200 OK", second)]);
+        let client = test_builder(&url).build().unwrap();
+
+        assert_eq!(client.list_channels("core", None).unwrap(),
+                   vec![ChannelIdent::unstable(),
+                        ChannelIdent::from("bar"),
+                        ChannelIdent::stable()]);
+        assert_eq!(requests.try_iter().collect::<Vec<_>>(),
+                   vec!["GET /v1/depot/channels/core?range=0 HTTP/1.1",
+                        "GET /v1/depot/channels/core?range=2 HTTP/1.1"]);
+    }
+
+    #[test]
+    fn show_package_decodes_builder_metadata_and_reports_missing_packages() {
+        let body = r#"{
+            "ident": {"origin": "core", "name": "redis", "version": "4.0.14",
+                      "release": "20190319155852"},
+            "checksum": "abc123", "manifest": "", "deps": [], "tdeps": [], "exposes": [6379],
+            "config": "", "channels": ["unstable", "stable", "bar"], "target": "x86_64-linux"
+        }"#;
+        let (url, requests) = serve(vec![response("200 OK", body), response("404 Not Found", "")]);
+        let client = test_builder(&url).build().unwrap();
+        let target = PackageTarget::from_str("x86_64-linux").unwrap();
+
+        let metadata = client.show_package(&redis(), target, &ChannelIdent::stable(), None)
+                             .unwrap();
+        assert_eq!(metadata.ident, redis());
+        assert_eq!(metadata.channels,
+                   vec![ChannelIdent::unstable(),
+                        ChannelIdent::stable(),
+                        ChannelIdent::from("bar")]);
+        match client.show_package(&redis(), target, &ChannelIdent::from("baz"), None) {
+            Err(Error::PackageNotFound(ref ident)) => {
+                assert_eq!(ident, "core/redis/4.0.14/20190319155852")
+            }
+            other => panic!("Expected PackageNotFound, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(requests.try_iter().collect::<Vec<_>>(),
+                   vec!["GET /v1/depot/channels/core/stable/pkgs/redis/4.0.14/20190319155852?\
+                         target=x86_64-linux HTTP/1.1",
+                        "GET /v1/depot/channels/core/baz/pkgs/redis/4.0.14/20190319155852?\
+                         target=x86_64-linux HTTP/1.1"]);
+    }
+
+    #[test]
+    fn demote_package_reports_packages_builder_has_not_got_in_the_channel() {
+        let (url, requests) = serve(vec![response("200 OK", ""), response("404 Not Found", "")]);
+        let client = test_builder(&url).build().unwrap();
+        let channel = ChannelIdent::from("bar");
+        let target = PackageTarget::from_str("x86_64-linux").unwrap();
+
+        assert!(client.demote_package(&redis(), &channel, target, "token")
+                      .is_ok());
+        match client.demote_package(&redis(), &channel, target, "token") {
+            Err(Error::PackageNotInChannel(ref ident, ref channel)) => {
+                assert_eq!(ident, "core/redis/4.0.14/20190319155852");
+                assert_eq!(channel, "bar");
+            }
+            other => panic!("Expected PackageNotInChannel, got {:?}", other),
+        }
+        let demote = "PUT /v1/depot/channels/core/bar/pkgs/redis/4.0.14/20190319155852/demote?\
+                      target=x86_64-linux HTTP/1.1";
+        assert_eq!(requests.try_iter().collect::<Vec<_>>(), vec![demote; 2]);
+    }
+
+    #[test]
+    fn create_channel_reports_whether_builder_created_the_channel() {
+        let (url, requests) = serve(vec![response("201 Created", ""),
+                                         response("409 Conflict", ""),
+                                         response("403 Forbidden", "")]);
+        let client = test_builder(&url).build().unwrap();
+        let channel = ChannelIdent::from("bar");
+
+        assert_eq!(client.create_channel("core", &channel, "token").unwrap(),
+                   ChannelCreated::Created);
+        assert_eq!(client.create_channel("core", &channel, "token").unwrap(),
+                   ChannelCreated::AlreadyExisted);
+        match client.create_channel("core", &channel, "token") {
+            Err(Error::APIError(StatusCode::Forbidden, _)) => (),
+            other => panic!("Expected a 403 APIError, got {:?}", other),
+        }
+        assert_eq!(requests.try_iter().collect::<Vec<_>>(),
+                   vec!["POST /v1/depot/channels/core/bar HTTP/1.1"; 3]);
+    }
+
+    #[test]
+    fn ping_checks_the_token_only_when_given_one() {
+        let (url, requests) = serve(vec![response("200 OK", "{}"),
+                                         response("200 OK", "{}"),
+                                         response("401 Unauthorized", "")]);
+        let client = test_builder(&url).build().unwrap();
+
+        assert!(client.ping(None).is_ok());
+        assert!(client.ping(Some("token")).is_ok());
+        match client.ping(Some("expired")) {
+            Err(Error::InvalidToken) => (),
+            other => panic!("Expected InvalidToken, got {:?}", other),
+        }
+        assert_eq!(requests.try_iter().collect::<Vec<_>>(),
+                   vec!["GET /v1/status HTTP/1.1",
+                        "GET /v1/profile HTTP/1.1",
+                        "GET /v1/profile HTTP/1.1"]);
+    }
+
+    #[test]
+    fn ping_of_nothing_listening_is_builder_unreachable() {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap()
+                                                   .local_addr()
+                                                   .unwrap();
+        let client = test_builder(&format!("http://{}", addr)).build().unwrap();
+
+        match client.ping(None) {
+            Err(Error::BuilderUnreachable(_)) => (),
+            other => panic!("Expected BuilderUnreachable, got {:?}", other),
+        }
+    }
 }
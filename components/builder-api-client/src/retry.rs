@@ -0,0 +1,264 @@
+//! Retrying of Builder requests which fail for reasons that are likely to be temporary.
+
+#[cfg(any(test, feature = "testing"))]
+use std::cell::RefCell;
+use std::{thread,
+          time::Duration};
+
+use hyper::status::StatusCode;
+
+use crate::error::{Error,
+                   Result};
+
+/// Something which can wait between attempts.
+pub trait Sleeper {
+    fn sleep(&self, duration: Duration);
+}
+
+/// Waits by sleeping the current thread.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadSleeper;
+
+impl Sleeper for ThreadSleeper {
+    fn sleep(&self, duration: Duration) { thread::sleep(duration) }
+}
+
+/// Doesn't wait at all, but remembers each wait it was asked for, so tests of retrying can
+/// check the waits without taking as long as them.
+#[cfg(any(test, feature = "testing"))]
+#[derive(Debug, Default)]
+pub struct RecordingSleeper {
+    slept: RefCell<Vec<Duration>>,
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl RecordingSleeper {
+    pub fn new() -> Self { RecordingSleeper::default() }
+
+    /// The waits asked for so far, in order.
+    pub fn slept(&self) -> Vec<Duration> { self.slept.borrow().clone() }
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl Sleeper for RecordingSleeper {
+    fn sleep(&self, duration: Duration) { self.slept.borrow_mut().push(duration) }
+}
+
+/// The longest a policy waits between attempts unless told otherwise, however long Builder asks.
+pub const DEFAULT_MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// How many times a request which fails transiently is attempted, and how long to wait between
+/// attempts. The first wait is `wait`, and each wait after it is `backoff` times the one before.
+/// No wait is longer than `max_wait`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub attempts: u64,
+    pub wait:     Duration,
+    pub backoff:  u32,
+    pub max_wait: Duration,
+}
+
+impl RetryPolicy {
+    /// A policy which waits the same `wait` between each attempt.
+    pub fn new(attempts: u64, wait: Duration) -> Self {
+        RetryPolicy { attempts,
+                      wait,
+                      backoff: 1,
+                      max_wait: DEFAULT_MAX_WAIT }
+    }
+
+    /// A policy which makes a single attempt and never retries.
+    pub fn none() -> Self { RetryPolicy::new(1, Duration::from_millis(0)) }
+
+    /// This policy, with each wait `backoff` times as long as the one before it.
+    pub fn backoff(mut self, backoff: u32) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// This policy, waiting at most `max_wait` between attempts, even when Builder asks for
+    /// longer.
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Calls `f` until it succeeds, fails with an error which isn't transient, or runs out of
+    /// attempts, returning the result of the last call. When Builder is rate limiting and says
+    /// how long to wait, that wait, up to `max_wait`, is used in place of the policy's own.
+    pub fn run<T, F>(&self, f: F) -> Result<T>
+        where F: FnMut() -> Result<T>
+    {
+        self.run_with(&ThreadSleeper, f)
+    }
+
+    /// `run`, waiting between attempts with `sleeper`.
+    pub fn run_with<T, F, S>(&self, sleeper: &S, mut f: F) -> Result<T>
+        where F: FnMut() -> Result<T>,
+              S: Sleeper + ?Sized
+    {
+        let mut attempt = 1;
+        loop {
+            match f() {
+                Err(ref e) if attempt < self.attempts && is_transient(e) => {
+                    debug!("Attempt {} of {} failed, retrying: {}",
+                           attempt, self.attempts, e);
+                    sleeper.sleep(self.wait_after(attempt, e));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// How long to wait before retrying a request whose `attempt`th attempt failed with `err`.
+    fn wait_after(&self, attempt: u64, err: &Error) -> Duration {
+        let wait = match *err {
+            Error::RateLimited(Some(wait), _) => wait,
+            _ => self.wait_before(attempt + 1),
+        };
+        wait.min(self.max_wait)
+    }
+
+    /// The policy's own wait before the `attempt`th attempt, which is the longest possible wait
+    /// should it overflow.
+    fn wait_before(&self, attempt: u64) -> Duration {
+        let max = Duration::new(u64::max_value(), 0);
+        (2..attempt).fold(self.wait, |wait, _| {
+                        wait.checked_mul(self.backoff).unwrap_or(max)
+                    })
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self { RetryPolicy::new(5, Duration::from_millis(3000)) }
+}
+
+/// Whether an error is likely to be temporary: the request couldn't be made or its response
+/// couldn't be read, Builder answered with a server error other than `501 Not Implemented`, or
+/// Builder is rate limiting.
+pub fn is_transient(err: &Error) -> bool {
+    match *err {
+        Error::APIError(status, _) => {
+            status.is_server_error() && status != StatusCode::NotImplemented
+        }
+        Error::HyperError(_) | Error::IO(_) | Error::RateLimited(..) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_error(status: StatusCode) -> Error { Error::APIError(status, String::new()) }
+
+    #[test]
+    fn server_errors_are_transient() {
+        assert!(is_transient(&api_error(StatusCode::ServiceUnavailable)));
+        assert!(is_transient(&api_error(StatusCode::BadGateway)));
+        assert!(!is_transient(&api_error(StatusCode::NotImplemented)));
+        assert!(!is_transient(&api_error(StatusCode::Conflict)));
+        assert!(!is_transient(&Error::PackageNotFound("core/redis".to_string())));
+    }
+
+    #[test]
+    fn run_retries_until_a_non_transient_result() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(0));
+        let mut responses = vec![api_error(StatusCode::ServiceUnavailable),
+                                 api_error(StatusCode::Conflict)].into_iter();
+        let mut calls = 0;
+
+        let result: Result<()> = policy.run(|| {
+                                           calls += 1;
+                                           Err(responses.next().unwrap())
+                                       });
+
+        assert_eq!(calls, 2);
+        match result {
+            Err(Error::APIError(StatusCode::Conflict, _)) => (),
+            other => panic!("Expected the 409, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rate_limiting_is_transient() {
+        assert!(is_transient(&Error::RateLimited(None, String::new())));
+        assert!(is_transient(&Error::RateLimited(Some(Duration::from_secs(5)), String::new())));
+    }
+
+    #[test]
+    fn run_waits_as_long_as_retry_after_asks() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(3000));
+        let mut responses = vec![Err(Error::RateLimited(Some(Duration::from_secs(5)),
+                                                        String::new())),
+                                 Err(Error::RateLimited(None, String::new())),
+                                 Ok(())].into_iter();
+        let sleeper = RecordingSleeper::new();
+
+        let result = policy.run_with(&sleeper, || responses.next().unwrap());
+
+        assert!(result.is_ok());
+        assert_eq!(sleeper.slept(),
+                   vec![Duration::from_secs(5), Duration::from_millis(3000)]);
+    }
+
+    #[test]
+    fn run_waits_no_longer_than_the_max_wait() {
+        let max_wait = Duration::from_secs(2);
+        let policy = RetryPolicy::new(3, Duration::from_millis(100)).backoff(100)
+                                                                    .max_wait(max_wait);
+        let mut responses = vec![Err(Error::RateLimited(Some(Duration::from_secs(86_400)),
+                                                        String::new())),
+                                 Err(api_error(StatusCode::ServiceUnavailable)),
+                                 Ok(())].into_iter();
+        let sleeper = RecordingSleeper::new();
+
+        let result = policy.run_with(&sleeper, || responses.next().unwrap());
+
+        assert!(result.is_ok());
+        assert_eq!(sleeper.slept(), vec![max_wait, max_wait]);
+    }
+
+    #[test]
+    fn run_backs_off_exponentially() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(100)).backoff(2);
+        let sleeper = RecordingSleeper::new();
+
+        let result: Result<()> =
+            policy.run_with(&sleeper, || Err(api_error(StatusCode::ServiceUnavailable)));
+
+        assert!(result.is_err());
+        assert_eq!(sleeper.slept(),
+                   vec![Duration::from_millis(100),
+                        Duration::from_millis(200),
+                        Duration::from_millis(400),
+                        Duration::from_millis(800)]);
+    }
+
+    #[test]
+    fn run_without_backoff_waits_the_same_each_time() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(100));
+        let sleeper = RecordingSleeper::new();
+
+        let _: Result<()> =
+            policy.run_with(&sleeper, || Err(api_error(StatusCode::ServiceUnavailable)));
+
+        assert_eq!(sleeper.slept(),
+                   vec![Duration::from_millis(100), Duration::from_millis(100)]);
+    }
+
+    #[test]
+    fn run_gives_up_after_the_last_attempt() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(0));
+        let mut calls = 0;
+
+        let result: Result<()> = policy.run(|| {
+                                           calls += 1;
+                                           Err(api_error(StatusCode::ServiceUnavailable))
+                                       });
+
+        assert_eq!(calls, 3);
+        assert!(result.is_err());
+    }
+}
@@ -33,8 +33,7 @@ use std::{borrow::Cow,
           str::FromStr};
 
 use crate::{api_client::{self,
-                         Client,
-                         Error::APIError},
+                         Client},
             hcore::{self,
                     crypto::{artifact,
                              keys::parse_name_with_rev,
@@ -540,7 +539,7 @@ impl<'a> InstallTask<'a> {
                               &ident, self.channel))?;
             let latest_remote = match self.fetch_latest_pkg_ident_for(&ident, target, token) {
                 Ok(latest_ident) => Some(latest_ident),
-                Err(Error::APIClient(APIError(StatusCode::NotFound, _))) => None,
+                Err(Error::APIClient(api_client::Error::PackageNotFound(_))) => None,
                 Err(e) => {
                     debug!("error fetching ident: {:?}", e);
                     return Err(e);
@@ -844,7 +843,7 @@ impl<'a> InstallTask<'a> {
                                              -> Result<FullyQualifiedPackageIdent<'_>> {
         let origin_package = self.api_client
                                  .show_package(ident, target, channel, token)?;
-        FullyQualifiedPackageIdent::from(origin_package)
+        FullyQualifiedPackageIdent::from(origin_package.ident)
     }
 
     /// Retrieve the identified package from the depot, ensuring that
@@ -862,7 +861,7 @@ impl<'a> InstallTask<'a> {
                                             target,
                                             token,
                                             self.artifact_cache_path,
-                                            ui.progress())
+                                            ui.progress_for(&ident.to_string()))
         {
             Ok(_) => Ok(()),
             Err(api_client::Error::APIError(StatusCode::NotImplemented, _)) => {
@@ -1020,7 +1019,7 @@ impl<'a> InstallTask<'a> {
                                    -> Result<Vec<(String, String)>> {
         let mut res = Vec::new();
 
-        let channels = match self.api_client.list_channels(ident.origin(), false) {
+        let channels = match self.api_client.list_channels(ident.origin(), token) {
             Ok(channels) => channels,
             Err(e) => {
                 debug!("Failed to get channel list: {:?}", e);
@@ -1028,7 +1027,7 @@ impl<'a> InstallTask<'a> {
             }
         };
 
-        for channel in channels.into_iter().map(ChannelIdent::from) {
+        for channel in channels {
             if let Ok(pkg) =
                 self.fetch_latest_pkg_ident_in_channel_for(ident, target, &channel, token)
             {
@@ -22,11 +22,19 @@ pub enum Error {
     /// Occurs when there is no valid toml of json in the environment variable
     BadEnvConfig(String),
     BadGlyphStyle(String),
+    BadOutputFormat(String),
+    BadProgressStyle(String),
     CantUploadGossipToml,
     ChannelNotFound,
+    /// A destructive action, by its impact, which there was no one to confirm.
+    ConfirmationRequired(String),
     CryptoKeyError(String),
     DownloadFailed(String),
     EditorEnv(env::VarError),
+    /// The `EDITOR` command, which could not be found to run.
+    EditorNotFound(String),
+    /// The editor saved text which isn't UTF-8, which was kept in the file named.
+    EditInvalidUtf8(PathBuf),
     EditStatus,
     FileNameError,
     /// Occurs when a file that should exist does not or could not be read.
@@ -35,17 +43,27 @@ pub enum Error {
     HabitatCore(hcore::Error),
     InstallHookFailed(PackageIdent),
     InvalidInstallHookMode(String),
+    /// A path given in answer to a prompt, or the prompt's default, which failed its check.
+    InvalidPath(PathBuf, String),
+    /// A number given in answer to a prompt, or the prompt's default, which failed its check.
+    InvalidNumber(String, String),
     /// Occurs when making lower level IO calls.
     IO(io::Error),
     /// Errors when joining paths :)
     JoinPathsError(env::JoinPathsError),
     NetParseError(net::AddrParseError),
+    /// A prompt for a number had no default, and no one to answer it.
+    NumberRequired(String),
     OfflineArtifactNotFound(PackageIdent),
     OfflineOriginKeyNotFound(String),
     OfflinePackageNotFound(PackageIdent),
     PackageNotFound(String),
+    /// A prompt for a path had no default, and no one to answer it.
+    PathRequired(String),
     /// Occurs upon errors related to file or directory permissions.
     PermissionFailed(String),
+    /// A prompt was answered `quit`.
+    PromptCancelled,
     /// When an error occurs serializing rendering context
     RenderContextSerialization(serde_json::Error),
     RootRequired,
@@ -68,6 +86,8 @@ pub enum Error {
     /// When an error occurs parsing toml
     TomlParser(toml::de::Error),
     TomlSerializeError(toml::ser::Error),
+    /// When input ends before a prompt without a default is answered
+    UnexpectedEof,
     WireDecode(String),
 }
 
@@ -83,13 +103,25 @@ impl fmt::Display for Error {
                 format!("Unable to find valid TOML or JSON in {} ENVVAR", varname)
             }
             Error::BadGlyphStyle(ref style) => format!("Unknown symbol style '{}'", style),
+            Error::BadOutputFormat(ref format) => format!("Unknown output format '{}'", format),
+            Error::BadProgressStyle(ref style) => format!("Unknown progress bar style '{}'", style),
             Error::CantUploadGossipToml => {
                 "Can't upload gossip.toml, it's a reserved file name".to_string()
             }
             Error::ChannelNotFound => "Channel not found".to_string(),
+            Error::ConfirmationRequired(ref impact) => {
+                format!("Not going ahead without confirmation, and there is no one to prompt for \
+                         it: {}. Pass --yes or set HAB_ASSUME_YES=1 to go ahead.",
+                        impact)
+            }
             Error::CryptoKeyError(ref s) => format!("Missing or invalid key: {}", s),
             Error::DownloadFailed(ref msg) => msg.to_string(),
             Error::EditorEnv(ref e) => format!("Missing EDITOR environment variable: {}", e),
+            Error::EditorNotFound(ref editor) => format!("editor '{}' not found", editor),
+            Error::EditInvalidUtf8(ref path) => {
+                format!("The edited text is not valid UTF-8; it was kept in {}",
+                        path.display())
+            }
             Error::EditStatus => "Failed edit text command".to_string(),
             Error::FileNameError => "Failed to extract a filename".to_string(),
             Error::FileNotFound(ref e) => format!("File not found at: {}", e),
@@ -104,9 +136,19 @@ impl fmt::Display for Error {
             Error::InvalidInstallHookMode(ref e) => {
                 format!("Invalid InstallHookMode conversion from {}", e)
             }
+            Error::InvalidPath(ref path, ref reason) => {
+                format!("Invalid path {}: {}", path.display(), reason)
+            }
+            Error::InvalidNumber(ref value, ref reason) => {
+                format!("Invalid number {}: {}", value, reason)
+            }
             Error::IO(ref err) => format!("{}", err),
             Error::JoinPathsError(ref err) => format!("{}", err),
             Error::NetParseError(ref err) => format!("{}", err),
+            Error::NumberRequired(ref question) => {
+                format!("No number was given for '{}', and there is no one to prompt for one",
+                        question)
+            }
             Error::OfflineArtifactNotFound(ref ident) => {
                 format!("Cached artifact not found in offline mode: {}", ident)
             }
@@ -120,7 +162,12 @@ impl fmt::Display for Error {
                         ident)
             }
             Error::PackageNotFound(ref e) => format!("Package not found. {}", e),
+            Error::PathRequired(ref question) => {
+                format!("No path was given for '{}', and there is no one to prompt for one",
+                        question)
+            }
             Error::PermissionFailed(ref e) => e.to_string(),
+            Error::PromptCancelled => "Cancelled at the prompt".to_string(),
             Error::RenderContextSerialization(ref e) => {
                 format!("Unable to serialize rendering context, {}", e)
             }
@@ -138,6 +185,9 @@ impl fmt::Display for Error {
             Error::TomlMergeError(ref e) => format!("Failed to merge TOML: {}", e),
             Error::TomlParser(ref err) => format!("Failed to parse TOML: {}", err),
             Error::TomlSerializeError(ref e) => format!("Can't serialize TOML: {}", e),
+            Error::UnexpectedEof => {
+                "Input ended before the question was answered, and it has no default".to_string()
+            }
             Error::WireDecode(ref m) => format!("Failed to decode wire message: {}", m),
         };
         write!(f, "{}", msg)
@@ -151,11 +201,18 @@ impl error::Error for Error {
             Error::ArtifactIdentMismatch((..)) => "Artifact ident does not match expected ident",
             Error::BadEnvConfig(_) => "Unknown syntax in Env Configuration",
             Error::BadGlyphStyle(_) => "Unknown symbol style",
+            Error::BadOutputFormat(_) => "Unknown output format",
+            Error::BadProgressStyle(_) => "Unknown progress bar style",
             Error::CantUploadGossipToml => "Can't upload gossip.toml, it's a reserved filename",
             Error::ChannelNotFound => "Channel not found",
+            Error::ConfirmationRequired(_) => {
+                "Not going ahead without confirmation, and there is no one to prompt for it"
+            }
             Error::CryptoKeyError(_) => "Missing or invalid key",
             Error::DownloadFailed(_) => "Failed to download from remote",
             Error::EditorEnv(_) => "Missing EDITOR environment variable",
+            Error::EditorNotFound(_) => "The EDITOR command was not found",
+            Error::EditInvalidUtf8(_) => "The edited text is not valid UTF-8",
             Error::EditStatus => "Failed edit text command",
             Error::FileNameError => "Failed to extract a filename from a path",
             Error::FileNotFound(_) => "File not found",
@@ -165,16 +222,23 @@ impl error::Error for Error {
             Error::HabitatCore(ref err) => err.description(),
             Error::InstallHookFailed(_) => "Install hook exited unsuccessfully",
             Error::InvalidInstallHookMode(_) => "Invalid InstallHookMode",
+            Error::InvalidPath(..) => "Invalid path",
+            Error::InvalidNumber(..) => "Invalid number",
             Error::IO(ref err) => err.description(),
             Error::JoinPathsError(ref err) => err.description(),
             Error::NetParseError(_) => "Can't parse IP:port",
+            Error::NumberRequired(_) => {
+                "No number was given, and there is no one to prompt for one"
+            }
             Error::OfflineArtifactNotFound(_) => "Cached artifact not found in offline mode",
             Error::OfflineOriginKeyNotFound(_) => "Cached origin key not found in offline mode",
             Error::OfflinePackageNotFound(_) => {
                 "No installed package or cached artifact could be found locally in offline mode"
             }
             Error::PackageNotFound(_) => "Package not found",
+            Error::PathRequired(_) => "No path was given, and there is no one to prompt for one",
             Error::PermissionFailed(_) => "File system permissions error",
+            Error::PromptCancelled => "A prompt was answered with quit",
             Error::RenderContextSerialization(_) => "Unable to serialize rendering context",
             Error::RootRequired => {
                 "Root or administrator permissions required to complete operation"
@@ -187,6 +251,7 @@ impl error::Error for Error {
             Error::TomlMergeError(_) => "Failed to merge TOML!",
             Error::TomlParser(_) => "Failed to parse TOML!",
             Error::TomlSerializeError(_) => "Can't serialize TOML",
+            Error::UnexpectedEof => "Input ended before the question was answered",
             Error::WireDecode(_) => "Failed to decode wire message",
         }
     }
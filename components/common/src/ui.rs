@@ -1,4 +1,5 @@
-use std::{env,
+use std::{collections::HashMap,
+          env,
           fmt,
           fs::{self,
                File},
@@ -8,18 +9,30 @@ use std::{env,
                Read,
                Stdout,
                Write},
+          mem,
+          ops::RangeInclusive,
+          path::{Path,
+                 PathBuf},
           process::{self,
                     Command},
-          str::FromStr};
+          str::FromStr,
+          sync::{Arc,
+                 Mutex},
+          time::{Duration,
+                 Instant,
+                 SystemTime}};
 use uuid::Uuid;
 
 use crate::api_client::DisplayProgress;
 use pbr;
 use termcolor::{self,
+                Ansi,
                 ColorChoice,
                 ColorSpec,
                 StandardStream,
                 WriteColor};
+use unicode_width::{UnicodeWidthChar,
+                    UnicodeWidthStr};
 
 use self::tty::StdStream;
 use crate::error::{Error,
@@ -31,7 +44,39 @@ pub const NOCOLORING_ENVVAR: &str = "HAB_NOCOLORING";
 
 pub const GLYPH_STYLE_ENVVAR: &str = "HAB_GLYPH_STYLE";
 
-#[derive(Clone, Copy)]
+pub const ASSUME_YES_ENVVAR: &str = "HAB_ASSUME_YES";
+
+pub const COLUMNS_ENVVAR: &str = "COLUMNS";
+
+pub const ACCESSIBLE_ENVVAR: &str = "HAB_ACCESSIBLE";
+
+pub const AUDIT_FILE_ENVVAR: &str = "HAB_AUDIT_FILE";
+
+pub const OUTPUT_FORMAT_ENVVAR: &str = "HAB_OUTPUT_FORMAT";
+
+pub const MAX_WIDTH_ENVVAR: &str = "HAB_MAX_WIDTH";
+
+pub const PROGRESS_PIPE_ENVVAR: &str = "HAB_PROGRESS_PIPE";
+
+pub const PROGRESS_STYLE_ENVVAR: &str = "HAB_PROGRESS_STYLE";
+
+/// The width `para` wraps text to.
+const PARA_WIDTH: usize = 75;
+/// Spaces messages are indented by for each `begin` still open, when nesting is tracked.
+const NESTING_INDENT: usize = 2;
+
+/// The line which ends input to `prompt_ask_multiline` when no other terminator is given.
+pub const DEFAULT_MULTILINE_TERMINATOR: &str = ".";
+
+/// What `read_key` returns for Enter, whether the terminal sends a carriage return or a newline.
+pub const KEY_ENTER: char = '\n';
+/// What `read_key` returns for Esc.
+pub const KEY_ESC: char = '\u{1b}';
+
+/// With the `serde` feature, serialized by its lowercase name, such as `"warn"`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Color {
     Plain,
     Info,
@@ -41,6 +86,20 @@ pub enum Color {
     End,
 }
 
+impl Color {
+    /// How severe a message in this color is, from `Plain` (least) to `Critical` (most). `End`
+    /// marks the successful end of work, so ranks with `Info`.
+    fn severity(self) -> u8 {
+        match self {
+            Color::Plain => 0,
+            Color::Info | Color::End => 1,
+            Color::Important => 2,
+            Color::Warn => 3,
+            Color::Critical => 4,
+        }
+    }
+}
+
 impl From<Color> for termcolor::Color {
     fn from(color: Color) -> Self {
         match color {
@@ -54,7 +113,10 @@ impl From<Color> for termcolor::Color {
     }
 }
 
-#[derive(Clone, Copy)]
+/// With the `serde` feature, serialized by the name `FromStr` accepts, such as `"ascii"`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum GlyphStyle {
     Full,
     Limited,
@@ -93,7 +155,158 @@ impl fmt::Display for GlyphStyle {
     }
 }
 
-#[derive(Clone, Copy)]
+/// Whether command results are rendered for people or for machines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputMode {
+    Human,
+    Json,
+    Yaml,
+}
+
+impl Default for OutputMode {
+    fn default() -> OutputMode { OutputMode::Human }
+}
+
+impl FromStr for OutputMode {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_ref() {
+            "human" => Ok(OutputMode::Human),
+            "json" => Ok(OutputMode::Json),
+            "yaml" => Ok(OutputMode::Yaml),
+            _ => Err(Error::BadOutputFormat(value.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match *self {
+            OutputMode::Human => "human",
+            OutputMode::Json => "json",
+            OutputMode::Yaml => "yaml",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+/// The range of colors a stream can display, from none at all to 24-bit color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    None,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Determines the color depth of a stream from whether it supports color at all and the
+    /// values of the `COLORTERM` and `TERM` environment variables.
+    fn detect(supports_color: bool, colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if !supports_color {
+            return ColorDepth::None;
+        }
+        match colorterm.map(str::to_lowercase) {
+            Some(ref c) if c == "truecolor" || c == "24bit" => return ColorDepth::TrueColor,
+            _ => {}
+        }
+        match term.map(str::to_lowercase) {
+            Some(ref t) if t == "dumb" => ColorDepth::None,
+            Some(ref t) if t.ends_with("-direct") || t.ends_with("-truecolor") => {
+                ColorDepth::TrueColor
+            }
+            Some(ref t) if t.contains("256color") => ColorDepth::Ansi256,
+            _ => ColorDepth::Ansi16,
+        }
+    }
+}
+
+/// How a `ConsoleProgressBar` draws its bar, for terminals which don't show the classic bar well.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BarStyle {
+    /// `[===>---]`
+    Classic,
+    /// `[████░░░]`
+    Blocks,
+    /// `[####---]`
+    Ascii,
+}
+
+impl BarStyle {
+    /// The bar's start, filled, current, remaining and end characters, as `pbr` takes them.
+    fn format(self) -> &'static str {
+        match self {
+            BarStyle::Classic => "[=>-]",
+            BarStyle::Blocks => "[██░]",
+            BarStyle::Ascii => "[##-]",
+        }
+    }
+}
+
+impl Default for BarStyle {
+    fn default() -> BarStyle { BarStyle::Classic }
+}
+
+impl FromStr for BarStyle {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_ref() {
+            "classic" => Ok(BarStyle::Classic),
+            "blocks" => Ok(BarStyle::Blocks),
+            "ascii" => Ok(BarStyle::Ascii),
+            _ => Err(Error::BadProgressStyle(value.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for BarStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match *self {
+            BarStyle::Classic => "classic",
+            BarStyle::Blocks => "blocks",
+            BarStyle::Ascii => "ascii",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+/// How `fatal` frames its message on a terminal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorBannerStyle {
+    /// A line of `ErrorX` symbols above and below the message, and another before each of its
+    /// lines.
+    Classic,
+    /// A rule across the terminal above and below the message, and a single `ErrorX` symbol
+    /// before each of its lines.
+    Rule,
+}
+
+impl Default for ErrorBannerStyle {
+    fn default() -> ErrorBannerStyle { ErrorBannerStyle::Classic }
+}
+
+/// The bar style named by `HAB_PROGRESS_STYLE`, or the default style when it is unset or invalid.
+fn bar_style_from_env() -> BarStyle {
+    if let Ok(s) = env::var(PROGRESS_STYLE_ENVVAR) {
+        match BarStyle::from_str(&s) {
+            Ok(style) => style,
+            Err(e) => {
+                eprintln!("{}\nSetting BarStyle to {}", e, BarStyle::default());
+                BarStyle::default()
+            }
+        }
+    } else {
+        BarStyle::default()
+    }
+}
+
+/// With the `serde` feature, serialized by its lowercase, underscore separated name, such as
+/// `"check_mark"`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Glyph {
     UpArrow,
     FingerPoint,
@@ -113,22 +326,48 @@ pub enum Glyph {
 }
 
 impl Glyph {
-    pub fn to_str(&self) -> &str {
-        let style = if let Ok(s) = env::var(GLYPH_STYLE_ENVVAR) {
-            match GlyphStyle::from_str(&s) {
-                Ok(style) => style,
-                Err(e) => {
-                    eprintln!("{}\nSetting GlyphStyle to {}", e, GlyphStyle::default());
-                    GlyphStyle::default()
-                }
-            }
-        } else {
-            GlyphStyle::default()
-        };
+    const ALL: [Glyph; 15] = [Glyph::UpArrow,
+                              Glyph::FingerPoint,
+                              Glyph::CheckMark,
+                              Glyph::BoxedCheckMark,
+                              Glyph::Omega,
+                              Glyph::BoxedX,
+                              Glyph::RightArrow,
+                              Glyph::Cloud,
+                              Glyph::DownArrow,
+                              Glyph::Elipses,
+                              Glyph::Because,
+                              Glyph::RightShift,
+                              Glyph::Star,
+                              Glyph::SlashedZero,
+                              Glyph::ErrorX];
+
+    pub fn to_str(&self) -> &str { self.to_style_str(glyph_style_from_env()) }
+
+    /// Like `to_str`, but padded as `to_padded_style_str` pads.
+    pub fn to_padded_str(self) -> String { self.to_padded_style_str(glyph_style_from_env()) }
+
+    /// The number of columns the glyph takes up in the given style.
+    pub fn width(self, style: GlyphStyle) -> usize { self.to_style_str(style).width() }
+
+    /// Renders the glyph in the given style, right-aligned to the width of the style's widest
+    /// glyph, so that whatever follows lines up no matter which glyph comes first.
+    pub fn to_padded_style_str(self, style: GlyphStyle) -> String {
+        let width = Glyph::ALL.iter()
+                              .map(|glyph| glyph.width(style))
+                              .max()
+                              .unwrap_or(0);
+        // `format!` would pad by characters rather than columns.
+        format!("{}{}",
+                " ".repeat(width - self.width(style)),
+                self.to_style_str(style))
+    }
 
+    /// Renders the glyph in the given style, regardless of `HAB_GLYPH_STYLE`.
+    pub fn to_style_str(self, style: GlyphStyle) -> &'static str {
         match style {
             GlyphStyle::Ascii => {
-                match *self {
+                match self {
                     Glyph::UpArrow => "/^\\",
                     Glyph::FingerPoint => "-->",
                     Glyph::CheckMark => "[x]",
@@ -147,7 +386,7 @@ impl Glyph {
                 }
             }
             GlyphStyle::Limited => {
-                match *self {
+                match self {
                     Glyph::UpArrow => "↑",
                     Glyph::FingerPoint => "→",
                     Glyph::CheckMark => "√",
@@ -166,7 +405,7 @@ impl Glyph {
                 }
             }
             GlyphStyle::Full => {
-                match *self {
+                match self {
                     Glyph::UpArrow => "↑",
                     Glyph::FingerPoint => "☛",
                     Glyph::CheckMark => "✓",
@@ -188,6 +427,21 @@ impl Glyph {
     }
 }
 
+/// The glyph style named by `HAB_GLYPH_STYLE`, or the default style when it is unset or invalid.
+fn glyph_style_from_env() -> GlyphStyle {
+    if let Ok(s) = env::var(GLYPH_STYLE_ENVVAR) {
+        match GlyphStyle::from_str(&s) {
+            Ok(style) => style,
+            Err(e) => {
+                eprintln!("{}\nSetting GlyphStyle to {}", e, GlyphStyle::default());
+                GlyphStyle::default()
+            }
+        }
+    } else {
+        GlyphStyle::default()
+    }
+}
+
 pub enum Status {
     Applying,
     Added,
@@ -275,7 +529,254 @@ pub trait UIReader {
     /// Returns true if message reads should expect the source as a tty.
     fn is_a_tty(&self) -> bool;
     fn prompt_ask(&mut self, question: &str, default: Option<&str>) -> Result<String>;
+    /// Like `prompt_ask`, but the `[default: ...]` hint only shows the last few characters of
+    /// `default`, so that a secret such as an auth token isn't echoed to the screen or to logs.
+    /// An empty response still returns the whole default.
+    fn prompt_ask_secret_default(&mut self,
+                                 question: &str,
+                                 default: Option<&str>)
+                                 -> Result<String>;
+    /// Asks a yes or no question. Answering `quit` exits the process; new code should use
+    /// `prompt_yes_no_cancellable` instead, which leaves that to the caller.
     fn prompt_yes_no(&mut self, question: &str, default: Option<bool>) -> Result<bool>;
+    /// Like `prompt_yes_no`, but answering `quit` returns `YesNoCancel::Cancel` rather than
+    /// exiting the process.
+    fn prompt_yes_no_cancellable(&mut self,
+                                 question: &str,
+                                 default: Option<bool>)
+                                 -> Result<YesNoCancel>;
+    /// Like `prompt_yes_no`, but also offering `all` and `none`, which answer yes or no both now
+    /// and for every later call with the same `tag`, so that a question asked of each of many
+    /// items (e.g. whether to overwrite each file) need only be answered once. Answering `quit`
+    /// fails with `Error::PromptCancelled` rather than exiting the process.
+    fn prompt_yes_no_all(&mut self,
+                         tag: &str,
+                         question: &str,
+                         default: Option<bool>)
+                         -> Result<bool>;
+    /// Asks for a path, expanding a leading `~` to the home directory, and asks again, saying
+    /// why, until the path is of the `kind` wanted and, when `must_exist` is set, exists. When
+    /// there is no one to prompt, the default is checked instead, and is an error when it fails
+    /// the check or there isn't one.
+    fn prompt_ask_path(&mut self,
+                       question: &str,
+                       default: Option<&str>,
+                       must_exist: bool,
+                       kind: PathKind)
+                       -> Result<PathBuf>;
+    /// Asks for a whole number, asking again, saying why, until the answer is one and, when
+    /// `range` is given, falls within it. An empty response takes the default. When there is no
+    /// one to prompt, the default is checked against `range` instead, and is an error when it
+    /// falls outside it or there isn't one.
+    fn prompt_int(&mut self,
+                  question: &str,
+                  default: Option<i64>,
+                  range: Option<RangeInclusive<i64>>)
+                  -> Result<i64>;
+    /// Asks the user to type `phrase` exactly to confirm an action. Returns true if the typed
+    /// response matches.
+    fn prompt_confirm_phrase(&mut self, question: &str, phrase: &str) -> Result<bool>;
+    /// The gate for a destructive action: warns of its `impact` in red, then goes ahead when
+    /// assuming yes, or asks for `phrase` to be typed to confirm it. When there is no one to
+    /// prompt, this is an `Error::ConfirmationRequired` rather than a refusal, so that a script
+    /// which forgot `--yes` fails loudly.
+    fn confirm_destructive(&mut self, impact: &str, phrase: &str) -> Result<bool>;
+    /// Reads lines of input until a line equal to `terminator` (or `.` if it is empty), returning
+    /// the lines joined with newlines. When the input isn't a terminal, every line up to the end
+    /// of the input is read.
+    fn prompt_ask_multiline(&mut self, question: &str, terminator: &str) -> Result<String>;
+    /// Reads a single key, without waiting for Enter, for menus and "press any key" pauses. On a
+    /// terminal, it is put in raw mode for the read and restored afterwards, even when the read
+    /// fails. Enter is returned as `KEY_ENTER` and Esc as `KEY_ESC`. When the input isn't a
+    /// terminal, the next character is read from it, and the end of the input is an error.
+    fn read_key(&mut self) -> Result<char>;
+
+    /// Returns true when it makes sense to prompt: input, output, and error are all terminals and
+    /// `HAB_NONINTERACTIVE` isn't set.
+    fn is_interactive(&self) -> bool { self.is_a_tty() && !noninteractive_env() }
+}
+
+/// The `[yes/no/all/none/quit]` hint `prompt_yes_no_all` is shown with, the default capitalized.
+fn yes_no_all_hint(words: &YesNoWords, default: Option<bool>) -> String {
+    let [yes, no, quit] = words.hint;
+    let (yes, no) = match default {
+        Some(true) => (capitalize(yes), no.to_string()),
+        Some(false) => (yes.to_string(), capitalize(no)),
+        None => (yes.to_string(), no.to_string()),
+    };
+    format!("[{}/{}/all/none/{}]", yes, no, quit)
+}
+
+/// An answer to `prompt_yes_no_cancellable`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum YesNoCancel {
+    Yes,
+    No,
+    /// The user answered `quit`.
+    Cancel,
+}
+
+/// Reads one character, as `read_key` does once any raw mode is set up.
+fn read_key_from<R>(input: &mut R) -> io::Result<char>
+    where R: Read + ?Sized
+{
+    let mut buf = [0u8; 4];
+    input.read_exact(&mut buf[..1]).map_err(|e| {
+                                        if e.kind() == io::ErrorKind::UnexpectedEof {
+                                            io::Error::new(e.kind(), "No key to read, input ended")
+                                        } else {
+                                            e
+                                        }
+                                    })?;
+    let len = match buf[0] {
+        b if b >= 0xF0 => 4,
+        b if b >= 0xE0 => 3,
+        b if b >= 0xC0 => 2,
+        _ => 1,
+    };
+    input.read_exact(&mut buf[1..len])?;
+    let key = std::str::from_utf8(&buf[..len]).ok()
+                                              .and_then(|s| s.chars().next())
+                                              .ok_or_else(|| {
+                                                  io::Error::new(io::ErrorKind::InvalidData,
+                                                                 "Key is not valid UTF-8")
+                                              })?;
+    match key {
+        '\r' | '\n' => Ok(KEY_ENTER),
+        key => Ok(key),
+    }
+}
+
+/// The kind of path `prompt_ask_path` accepts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathKind {
+    File,
+    Dir,
+    Any,
+}
+
+impl PathKind {
+    /// Why `path` won't do, if it won't. A path which doesn't exist is only refused when
+    /// `must_exist` is set, as there is nothing yet to be of the wrong kind.
+    fn check(self, path: &Path, must_exist: bool) -> std::result::Result<(), String> {
+        if !path.exists() {
+            return if must_exist {
+                Err("it does not exist".to_string())
+            } else {
+                Ok(())
+            };
+        }
+        match self {
+            PathKind::File if !path.is_file() => Err("it is not a file".to_string()),
+            PathKind::Dir if !path.is_dir() => Err("it is not a directory".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Why `value` won't do as an answer to `prompt_int`, if it won't.
+fn check_int_range(value: i64,
+                   range: &Option<RangeInclusive<i64>>)
+                   -> std::result::Result<(), String> {
+    match *range {
+        Some(ref range) if value < *range.start() || value > *range.end() => {
+            Err(format!("it is not between {} and {}",
+                        range.start(),
+                        range.end()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Expands a leading `~` in `path` to `home`. A `~user` prefix is left as it is.
+pub fn expand_tilde(path: &str, home: Option<&Path>) -> PathBuf {
+    match home {
+        Some(home) if path == "~" => home.to_path_buf(),
+        Some(home) if path.starts_with("~/") || path.starts_with("~\\") => home.join(&path[2..]),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))
+                       .map(PathBuf::from)
+}
+
+/// How many trailing characters of a secret `mask_secret` leaves visible.
+const SECRET_VISIBLE_CHARS: usize = 4;
+
+/// Masks all but the last 4 characters of `secret` as `****`, e.g. `****c0de`. A secret of 4
+/// characters or fewer is masked entirely.
+pub fn mask_secret(secret: &str) -> String {
+    let len = secret.chars().count();
+    if len <= SECRET_VISIBLE_CHARS {
+        return "****".to_string();
+    }
+    let visible: String = secret.chars().skip(len - SECRET_VISIBLE_CHARS).collect();
+    format!("****{}", visible)
+}
+
+fn noninteractive_env() -> bool {
+    env::var(NONINTERACTIVE_ENVVAR).map(|val| val == "1" || val == "true")
+                                   .unwrap_or(false)
+}
+
+/// The answers `prompt_yes_no` accepts in a language besides the English ones, which are always
+/// accepted, and the words its `[yes/no/quit]` hint is shown with.
+struct YesNoWords {
+    language: &'static str,
+    yes:      &'static [&'static str],
+    no:       &'static [&'static str],
+    hint:     [&'static str; 3],
+}
+
+const ENGLISH_YES_NO: YesNoWords = YesNoWords { language: "en",
+                                                yes:      &[],
+                                                no:       &[],
+                                                hint:     ["yes", "no", "quit"], };
+
+const LOCALIZED_YES_NO: &[YesNoWords] = &[YesNoWords { language: "de",
+                                                       yes:      &["j", "ja"],
+                                                       no:       &["nein"],
+                                                       hint:     ["ja", "nein", "beenden"], },
+                                          YesNoWords { language: "es",
+                                                       yes:      &["s", "sí", "si"],
+                                                       no:       &[],
+                                                       hint:     ["sí", "no", "salir"], },
+                                          YesNoWords { language: "fr",
+                                                       yes:      &["o", "oui"],
+                                                       no:       &["non"],
+                                                       hint:     ["oui", "non", "quitter"], }];
+
+/// The yes/no words for the language of the message locale, taken from `LC_ALL`, `LC_MESSAGES`
+/// or `LANG` in that order, falling back to English.
+fn yes_no_words() -> &'static YesNoWords {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+                                                  .filter_map(|var| env::var(var).ok())
+                                                  .find(|val| !val.is_empty());
+    match locale {
+        Some(locale) => yes_no_words_for(&locale),
+        None => &ENGLISH_YES_NO,
+    }
+}
+
+/// The yes/no words for a locale such as `fr_FR.UTF-8`, falling back to English.
+fn yes_no_words_for(locale: &str) -> &'static YesNoWords {
+    let language = locale.split(|c| c == '_' || c == '.' || c == '@')
+                         .next()
+                         .unwrap_or("")
+                         .to_lowercase();
+    LOCALIZED_YES_NO.iter()
+                    .find(|words| words.language == language)
+                    .unwrap_or(&ENGLISH_YES_NO)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
 }
 
 /// Functions applied to an IO stream for sending information to a UI.
@@ -295,24 +796,113 @@ pub trait UIWriter {
     /// Returns a progress bar widget implementation for writing operation's progress to.
     fn progress(&self) -> Option<Self::ProgressBar>;
 
+    /// Like `progress`, but the bar is labeled with what is being transferred, such as a package
+    /// identifier or file name, so that one of several transfers in a row can be told apart from
+    /// the others. By default, the label is left out.
+    fn progress_for(&self, _label: &str) -> Option<Self::ProgressBar> { self.progress() }
+
+    /// Width that `info` messages are wrapped to, if wrapping is enabled.
+    fn info_wrap_width(&self) -> Option<usize> { None }
+
+    /// Messages will carry a textual severity tag and high-contrast symbols if true, so their
+    /// meaning does not depend on color alone.
+    fn accessible(&self) -> bool { false }
+
+    /// Whether command results should be rendered for people or for machines.
+    fn output_mode(&self) -> OutputMode { OutputMode::Human }
+
+    /// When `Some`, `status` renders the status word right-aligned in a column of this width.
+    fn status_column_width(&self) -> Option<usize> { None }
+
+    /// When `Some`, wrapped output (`para`, `rule`, and wrapped `info`) never exceeds this many
+    /// columns, even when the terminal is wider.
+    fn max_width(&self) -> Option<usize> { None }
+
+    /// When `true`, `fatal` writes only its prefixed message lines, without the lines of framing
+    /// symbols above and below them.
+    fn compact_errors(&self) -> bool { false }
+
+    /// How `fatal` frames its message when it is not compact.
+    fn error_banner_style(&self) -> ErrorBannerStyle { ErrorBannerStyle::Classic }
+
+    /// When `true`, secondary guidance such as `tip`s is left out.
+    fn quiet(&self) -> bool { false }
+
+    /// The symbol which starts a `begin` message.
+    fn begin_symbol(&self) -> Glyph { Glyph::RightShift }
+
+    /// When `Some`, `status` skips statuses whose color is less severe than this one.
+    fn status_min_severity(&self) -> Option<Color> { None }
+
+    /// The symbol which starts an `end` message.
+    fn end_symbol(&self) -> Glyph { Glyph::Star }
+
+    /// The phase of work in progress, which is attached to events in JSON output mode.
+    fn phase(&self) -> Option<&str> { None }
+
+    /// The current time, for any output which includes it.
+    fn now(&self) -> SystemTime { SystemTime::now() }
+
+    /// Returns the sequence number for the next event in JSON output mode. Each call returns a
+    /// number greater than the last.
+    fn next_event_seq(&mut self) -> u64 { 0 }
+
+    /// A side channel, such as a pipe to a program which embeds hab, which `begin`, `end` and
+    /// `status` events are written to as JSON lines, whatever the output mode, in place of being
+    /// written to the output stream.
+    fn event_stream(&mut self) -> Option<&mut dyn Write> { None }
+
+    /// How many `begin` messages are open without their matching `end`, when nesting is tracked.
+    /// Text messages are indented by `NESTING_INDENT` spaces for each open `begin`.
+    fn nesting_depth(&self) -> usize { 0 }
+
+    /// Called by `begin` once its message is written, to open a level of nesting.
+    fn open_nesting(&mut self) {}
+
+    /// Called by `end` before its message is written, to close the innermost level of nesting.
+    /// An `end` without a `begin` leaves the depth at zero.
+    fn close_nesting(&mut self) {}
+
+    /// Adds a change the command made, such as to a Builder channel, to the record of this
+    /// session's changes. What a change records is up to the command.
+    fn record_change<T>(&mut self, _change: &T)
+        where T: serde::Serialize
+    {
+    }
+
     /// Write a message formatted with `begin`.
     fn begin<T>(&mut self, message: T) -> io::Result<()>
         where T: fmt::Display
     {
-        let symbol = Glyph::RightShift.to_str();
+        if self.output_mode() == OutputMode::Json || self.event_stream().is_some() {
+            write_json_event(self, "begin", None, &message.to_string())?;
+            self.open_nesting();
+            return Ok(());
+        }
+        let glyph = self.begin_symbol();
+        let symbol = glyph.to_str();
+        let indent = nesting_indent(self.nesting_depth());
         println(self.out(),
-                format!("{} {}", symbol, message).as_bytes(),
+                format!("{}{} {}", indent, symbol, message).as_bytes(),
                 ColorSpec::new().set_fg(Some(Color::Warn.into()))
-                                .set_bold(true))
+                                .set_bold(true))?;
+        self.open_nesting();
+        Ok(())
     }
 
     /// Write a message formatted with `end`.
     fn end<T>(&mut self, message: T) -> io::Result<()>
         where T: fmt::Display
     {
-        let symbol = Glyph::Star.to_str();
+        self.close_nesting();
+        if self.output_mode() == OutputMode::Json || self.event_stream().is_some() {
+            return write_json_event(self, "end", None, &message.to_string());
+        }
+        let glyph = self.end_symbol();
+        let symbol = glyph.to_str();
+        let indent = nesting_indent(self.nesting_depth());
         println(self.out(),
-                format!("{} {}", symbol, message).as_bytes(),
+                format!("{}{} {}", indent, symbol, message).as_bytes(),
                 ColorSpec::new().set_fg(Some(Color::End.into()))
                                 .set_bold(true))
     }
@@ -321,19 +911,118 @@ pub trait UIWriter {
     fn status<T>(&mut self, status: Status, message: T) -> io::Result<()>
         where T: fmt::Display
     {
-        let (symbol, status_str, color) = status.parts();
-        print(self.out(),
-              format!("{} {}", symbol.to_str(), status_str).as_bytes(),
-              ColorSpec::new().set_fg(Some(color.into())).set_bold(true))?;
-        self.out().write_all(format!(" {}\n", message).as_bytes())?;
-        self.out().flush()
+        let (_, _, color) = status.parts();
+        if let Some(min) = self.status_min_severity() {
+            if color.severity() < min.severity() {
+                return Ok(());
+            }
+        }
+        self.status_with(status, color, message)
+    }
+
+    /// Write a message formatted with `status`, using `severity` in place of the status' own
+    /// color. The symbol and status text still come from `status`.
+    fn status_with<T>(&mut self, status: Status, severity: Color, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        write_status(self, StatusStream::Out, status, severity, message)
+    }
+
+    /// Reports work in progress as a `start` status (e.g. `Promoting`), runs `f`, and then
+    /// reports how it went: as a `done` status (e.g. `Promoted`) when `f` succeeds, or as
+    /// `status_fail` does when it fails. On a terminal, the in-progress line is left open and the
+    /// outcome drawn over it, so `f` should write nothing itself. The result of `f` is handed
+    /// back.
+    fn status_progress<M, F, T, E>(&mut self,
+                                   start: Status,
+                                   done: Status,
+                                   message: M,
+                                   f: F)
+                                   -> io::Result<std::result::Result<T, E>>
+        where M: fmt::Display,
+              F: FnOnce() -> std::result::Result<T, E>
+    {
+        let message = message.to_string();
+        let (_, _, start_color) = start.parts();
+        let start_shown = match self.status_min_severity() {
+            Some(min) => start_color.severity() >= min.severity(),
+            None => true,
+        };
+        let in_place = start_shown
+                       && self.is_out_a_terminal()
+                       && self.output_mode() == OutputMode::Human
+                       && self.event_stream().is_none()
+                       && !self.accessible();
+        let line = format!("{} {}", status_prefix(self, &start, start_color), message);
+        if in_place {
+            rewrite_line(self.out(), &line, terminal_width())?;
+            self.out().flush()?;
+        } else {
+            self.status(start, &message)?;
+        }
+        let result = f();
+        if in_place {
+            let width = terminal_width().unwrap_or_else(|| line.width());
+            rewrite_line(self.out(), "", Some(width))?;
+            self.out().write_all(b"\r")?;
+        }
+        match result {
+            Ok(_) => self.status(done, &message)?,
+            Err(_) => self.status_fail(&message)?,
+        }
+        Ok(result)
+    }
+
+    /// Reports that an action succeeded, as a `Done` status with a check mark in the
+    /// informational color.
+    fn status_done<T>(&mut self, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        self.status_with(Status::Custom(Glyph::CheckMark, "Done".to_string()),
+                         Color::Info,
+                         message)
+    }
+
+    /// Reports that an action failed, as a `Failed` status with an X in the critical color,
+    /// written to the error stream.
+    fn status_fail<T>(&mut self, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        write_status(self,
+                     StatusStream::Err,
+                     Status::Custom(Glyph::ErrorX, "Failed".to_string()),
+                     Color::Critical,
+                     message)
     }
 
     /// Write a message formatted with `info`.
     fn info<T>(&mut self, text: T) -> io::Result<()>
         where T: fmt::Display
     {
-        self.out().write_all(format!("{}\n", text).as_bytes())?;
+        let indent = nesting_indent(self.nesting_depth());
+        match self.info_wrap_width() {
+            Some(width) => {
+                let width = width.saturating_sub(indent.len()).max(1);
+                for line in text.to_string().lines() {
+                    if line.width() > width {
+                        for wrapped in wrap_text(line, width) {
+                            self.out()
+                                .write_all(format!("{}{}\n", indent, wrapped).as_bytes())?;
+                        }
+                    } else {
+                        self.out()
+                            .write_all(format!("{}{}\n", indent, line).as_bytes())?;
+                    }
+                }
+            }
+            None if indent.is_empty() => self.out().write_all(format!("{}\n", text).as_bytes())?,
+            None => {
+                for line in text.to_string().lines() {
+                    self.out()
+                        .write_all(format!("{}{}\n", indent, line).as_bytes())?;
+                }
+            }
+        }
         self.out().flush()
     }
 
@@ -341,30 +1030,98 @@ pub trait UIWriter {
     fn warn<T>(&mut self, message: T) -> io::Result<()>
         where T: fmt::Display
     {
-        println(self.err(),
-                format!("{} {}", Glyph::SlashedZero.to_str(), message).as_bytes(),
-                ColorSpec::new().set_fg(Some(Color::Warn.into()))
-                                .set_bold(true))
+        write_warn(self, message)
+    }
+
+    /// Write a tip: secondary guidance, such as a shortcut the user could take, which is less
+    /// pressing than a warning. Tips go to standard output, and are left out when `quiet` is set.
+    fn tip<T>(&mut self, text: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        if self.quiet() {
+            return Ok(());
+        }
+        if self.output_mode() == OutputMode::Json || self.event_stream().is_some() {
+            return write_json_event(self, "tip", None, &text.to_string());
+        }
+        let indent = nesting_indent(self.nesting_depth());
+        println(self.out(),
+                format!("{}{} {}", indent, Glyph::FingerPoint.to_str(), text).as_bytes(),
+                ColorSpec::new().set_fg(Some(Color::Important.into())))
+    }
+
+    /// Write related warnings together: `title` as a warning, followed by each of `warnings` as
+    /// an indented bullet. In JSON output mode, the group is a single
+    /// `{"level":"warn_group","title":...,"items":[...]}` object, so that it can be shown
+    /// collapsed.
+    fn warn_group(&mut self, title: &str, warnings: &[String]) -> io::Result<()> {
+        if self.output_mode() == OutputMode::Json || self.event_stream().is_some() {
+            let seq = self.next_event_seq();
+            let json = serde_json::to_string(&JsonWarnGroup { seq,
+                                                               level: "warn_group",
+                                                               title,
+                                                               items: warnings })
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            return write_json_line(self, &json);
+        }
+        self.warn(title)?;
+        let indent = nesting_indent(self.nesting_depth() + 1);
+        for warning in warnings {
+            println(self.err(),
+                    format!("{}- {}", indent, warning).as_bytes(),
+                    ColorSpec::new().set_fg(Some(Color::Warn.into())))?;
+        }
+        Ok(())
     }
 
     /// Write a message formatted with `fatal`.
     fn fatal<T>(&mut self, message: T) -> io::Result<()>
         where T: fmt::Display
     {
-        println(self.err(),
-                Glyph::ErrorX.to_str().as_bytes(),
-                ColorSpec::new().set_fg(Some(Color::Critical.into()))
-                                .set_bold(true))?;
-        for line in message.to_string().lines() {
-            println(self.err(),
-                    format!("{} {}", Glyph::ErrorX.to_str(), line).as_bytes(),
-                    ColorSpec::new().set_fg(Some(Color::Critical.into()))
-                                    .set_bold(true))?;
+        write_fatal(self, message)
+    }
+
+    /// Hands back `result`, first writing its error, if it has one, as a `fatal` message saying
+    /// what was being done when it failed: `while <context>: <error>`.
+    fn with_context<T, E>(&mut self,
+                          context: &str,
+                          result: std::result::Result<T, E>)
+                          -> std::result::Result<T, E>
+        where E: fmt::Display
+    {
+        if let Err(ref e) = result {
+            // The error being handed back matters more than a failure to report it.
+            self.fatal(format!("while {}: {}", context, e)).ok();
         }
-        println(self.err(),
-                Glyph::ErrorX.to_str().as_bytes(),
-                ColorSpec::new().set_fg(Some(Color::Critical.into()))
-                                .set_bold(true))
+        result
+    }
+
+    /// Write `value`, the structured result of a command, to standard output. In JSON output mode
+    /// it is a single line of JSON, without the wrapper events are given, so that it can be read
+    /// by other programs; otherwise it is pretty-printed JSON.
+    fn json_value<T>(&mut self, value: &T) -> io::Result<()>
+        where T: serde::Serialize
+    {
+        let json = match self.output_mode() {
+            OutputMode::Json => serde_json::to_string(value),
+            _ => serde_json::to_string_pretty(value),
+        };
+        let json = json.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        self.out().write_all(format!("{}\n", json).as_bytes())?;
+        self.out().flush()
+    }
+
+    /// Like `json_value`, but outside of JSON output mode `human` is written in its place, for a
+    /// result which reads better as text.
+    fn json_value_or<T, D>(&mut self, value: &T, human: D) -> io::Result<()>
+        where T: serde::Serialize,
+              D: fmt::Display
+    {
+        if self.output_mode() == OutputMode::Json {
+            return self.json_value(value);
+        }
+        self.out().write_all(format!("{}\n", human).as_bytes())?;
+        self.out().flush()
     }
 
     /// Write a message formatted with `title`.
@@ -391,35 +1148,267 @@ pub trait UIWriter {
     }
 
     /// Write a message formatted with `para`.
-    fn para(&mut self, text: &str) -> io::Result<()> { print_wrapped(self.out(), text, 75, 2) }
+    fn para(&mut self, text: &str) -> io::Result<()> {
+        let width = capped_width(PARA_WIDTH, self.max_width());
+        print_wrapped(self.out(), text, width, 2)
+    }
+
+    /// Like `para`, but indented by `indent` columns, with the first line starting with
+    /// `first_line_prefix` (e.g. `1. `) and the lines after it lined up under its text.
+    fn para_with(&mut self, text: &str, indent: usize, first_line_prefix: &str) -> io::Result<()> {
+        let width = capped_width(PARA_WIDTH, self.max_width());
+        print_wrapped_with_prefix(self.out(), text, width, indent, first_line_prefix)
+    }
+
+    /// Write `items` as a numbered list, each as a `para_with` paragraph set off by its number,
+    /// with the numbers right aligned so that the items' text lines up however many there are.
+    fn ordered_list<I, T>(&mut self, items: I) -> io::Result<()>
+        where I: IntoIterator<Item = T>,
+              T: fmt::Display
+    {
+        let items: Vec<String> = items.into_iter().map(|item| item.to_string()).collect();
+        let digits = items.len().to_string().len();
+        for (n, item) in items.iter().enumerate() {
+            let number = format!("{:>width$}. ", n + 1, width = digits);
+            self.para_with(item, 2, &number)?;
+        }
+        Ok(())
+    }
+
+    /// Write a horizontal rule across the terminal.
+    fn rule(&mut self) -> io::Result<()> {
+        let width = capped_width(terminal_width().unwrap_or(PARA_WIDTH), self.max_width());
+        self.out()
+            .write_all(format!("{}\n", "-".repeat(width)).as_bytes())?;
+        self.out().flush()
+    }
+
+    /// Write a horizontal rule across the terminal with `label` set into the middle of it, as in
+    /// `──── Dependencies ────`, in the plain color, for dividing long output into sections. A
+    /// label too long for the line is truncated.
+    fn divider_with_label(&mut self, label: &str) -> io::Result<()> {
+        let width = capped_width(terminal_width().unwrap_or(PARA_WIDTH), self.max_width());
+        println(self.out(),
+                labelled_rule(label, width, glyph_style_from_env()).as_bytes(),
+                ColorSpec::new().set_fg(Some(Color::Plain.into())))
+    }
 
     /// Write a line break message`.
     fn br(&mut self) -> io::Result<()> {
         self.out().write_all(b"\n")?;
         self.out().flush()
     }
-}
 
-/// Console (shell) backed UI.
-#[derive(Debug)]
-pub struct UI {
-    shell: Shell,
-}
+    /// Write `bytes` to the output stream exactly as given, with no symbol, color or newline, and
+    /// whatever the output mode. Any color still set is reset first.
+    fn raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out().reset()?;
+        self.out().write_all(bytes)?;
+        self.out().flush()
+    }
 
-impl UI {
-    /// Creates a new `UI` from a `Shell`.
-    pub fn new(shell: Shell) -> Self { UI { shell } }
+    /// Resets any color left set on the output and error streams and flushes them, so that
+    /// buffered writers have written everything before the process exits.
+    fn finalize(&mut self) -> io::Result<()> {
+        self.out().reset()?;
+        self.out().flush()?;
+        self.err().reset()?;
+        self.err().flush()
+    }
 
-    /// Creates a new default `UI` with a coloring strategy and tty hinting.
-    pub fn default_with(coloring: ColorChoice, isatty: Option<bool>) -> Self {
-        Self::new(Shell::default_with(coloring, isatty))
+    /// Wraps an iterator so that each item it yields advances a percentage display labeled with
+    /// `label`. The display is only drawn when the output stream is a terminal.
+    fn progress_iter<I>(&mut self, label: &str, iter: I) -> ProgressIter<I>
+        where I: ExactSizeIterator
+    {
+        ProgressIter::new(label, iter, self.is_out_a_terminal())
     }
 
-    /// Creates a new default `UI` with a coloring strategy and tty hinting.
-    pub fn default_with_env() -> Self {
-        let isatty = if env::var(NONINTERACTIVE_ENVVAR)
-            // Keep string boolean for backwards-compatibility
-            .map(|val| val == "1" || val == "true")
+    /// Starts a display of `total` named steps, each written as `[N/total] label`. On a terminal
+    /// each step replaces the last on the same line; otherwise every step gets a line of its own.
+    fn progress_steps(&mut self, total: usize) -> StepProgress {
+        StepProgress::new(total, self.is_out_a_terminal())
+    }
+
+    /// Returns a progress display for a transfer of `total` bytes: a byte bar when the total is
+    /// known and a spinner when it isn't, such as a download without a `Content-Length`. Like
+    /// `progress`, nothing is returned unless the output stream is a terminal.
+    fn progress_with_total(&self, total: Option<u64>) -> Option<Box<dyn DisplayProgress>> {
+        if !self.is_out_a_terminal() {
+            return None;
+        }
+        match total {
+            Some(total) => Some(Box::new(ConsoleProgressBar::new_sized(total))),
+            None => {
+                let stdout = OutputStream::from_stdout(ColorChoice::Never, Some(true));
+                Some(Box::new(ConsoleSpinner::new(Box::new(stdout.ending_on_broken_pipe()))))
+            }
+        }
+    }
+}
+
+/// How long after the last of a run of identical `warn` or `fatal` messages a further identical
+/// message is still coalesced into the run.
+const WARN_COALESCE_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Severity {
+    Warn,
+    Fatal,
+}
+
+/// A `warn` or `fatal` message which has been written once and then repeated `count` more times
+/// without being written.
+#[derive(Debug)]
+struct Repeated {
+    severity: Severity,
+    message:  String,
+    count:    usize,
+    last:     Instant,
+}
+
+/// The standard output and standard error written by a `UI` made with
+/// `UI::from_env_for_tests`.
+#[cfg(any(test, feature = "testing"))]
+#[derive(Clone, Default)]
+pub struct CapturedOutput {
+    stdout: Arc<Mutex<Vec<u8>>>,
+    stderr: Arc<Mutex<Vec<u8>>>,
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl CapturedOutput {
+    pub fn stdout(&self) -> String { captured_string(&self.stdout) }
+
+    pub fn stderr(&self) -> String { captured_string(&self.stderr) }
+}
+
+fn captured_string(buf: &Mutex<Vec<u8>>) -> String {
+    String::from_utf8_lossy(&buf.lock().expect("Captured output lock is poisoned")).into_owned()
+}
+
+struct CaptureWriter(Arc<Mutex<Vec<u8>>>);
+
+impl Write for CaptureWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .expect("Captured output lock is poisoned")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+/// Puts a `UI`'s own output streams back when dropped, ending a `UI::capture`.
+struct StreamsGuard<'a> {
+    ui:  &'a mut UI,
+    out: Option<OutputStream>,
+    err: Option<OutputStream>,
+}
+
+impl<'a> Drop for StreamsGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(out) = self.out.take() {
+            self.ui.shell.out = out;
+        }
+        if let Some(err) = self.err.take() {
+            self.ui.shell.err = err;
+        }
+    }
+}
+
+/// What the audit file is written as: every change recorded during the session, in the order
+/// they were made.
+#[derive(Serialize)]
+struct AuditFile<'a> {
+    mutations: &'a [serde_json::Value],
+}
+
+/// Where a `UI` reads the current time from.
+struct Clock(Box<dyn Fn() -> SystemTime + Send>);
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "Clock") }
+}
+
+/// Where a `UI` writes its events to when they have a side channel of their own.
+struct EventStream(Box<dyn Write + Send>);
+
+impl fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "EventStream") }
+}
+
+/// Console (shell) backed UI.
+#[derive(Debug)]
+pub struct UI {
+    shell:        Shell,
+    assume_yes:   bool,
+    wrap_info:    bool,
+    accessible:   bool,
+    output_mode:  OutputMode,
+    status_width: Option<usize>,
+    max_width:    Option<usize>,
+    phase:        Option<String>,
+    event_seq:    u64,
+    coalesce:     bool,
+    repeated:     Option<Repeated>,
+    compact_errs: bool,
+    banner_style: ErrorBannerStyle,
+    quiet:        bool,
+    begin_glyph:  Glyph,
+    end_glyph:    Glyph,
+    min_severity: Option<Color>,
+    clock:        Clock,
+    events:       Option<EventStream>,
+    nesting:      bool,
+    depth:        usize,
+    changes:      Vec<serde_json::Value>,
+    audit_file:   Option<PathBuf>,
+    /// The answers given as `all` or `none` to `prompt_yes_no_all`, by tag.
+    remembered:   HashMap<String, bool>,
+}
+
+impl UI {
+    /// Creates a new `UI` from a `Shell`.
+    pub fn new(shell: Shell) -> Self {
+        let compact_errs = !shell.err.is_a_terminal();
+        UI { shell,
+             assume_yes: false,
+             wrap_info: false,
+             accessible: false,
+             output_mode: OutputMode::default(),
+             status_width: None,
+             max_width: None,
+             phase: None,
+             event_seq: 0,
+             coalesce: false,
+             repeated: None,
+             compact_errs,
+             banner_style: ErrorBannerStyle::default(),
+             quiet: false,
+             begin_glyph: Glyph::RightShift,
+             end_glyph: Glyph::Star,
+             min_severity: None,
+             clock: Clock(Box::new(SystemTime::now)),
+             events: None,
+             nesting: false,
+             depth: 0,
+             changes: Vec::new(),
+             audit_file: None,
+             remembered: HashMap::new() }
+    }
+
+    /// Creates a new default `UI` with a coloring strategy and tty hinting.
+    pub fn default_with(coloring: ColorChoice, isatty: Option<bool>) -> Self {
+        Self::new(Shell::default_with(coloring, isatty))
+    }
+
+    /// Creates a new default `UI` with a coloring strategy and tty hinting.
+    pub fn default_with_env() -> Self {
+        let isatty = if env::var(NONINTERACTIVE_ENVVAR)
+            // Keep string boolean for backwards-compatibility
+            .map(|val| val == "1" || val == "true")
             .unwrap_or(false)
         {
             Some(false)
@@ -434,11 +1423,37 @@ impl UI {
             ColorChoice::Auto
         };
 
-        let ui = UI::default_with(coloring, isatty);
+        let mut ui = UI::default_with(coloring, isatty);
+        ui.apply_env();
         debug!("{:?}", &ui);
         ui
     }
 
+    /// Applies the settings of `HAB_ASSUME_YES`, `HAB_ACCESSIBLE`, `HAB_MAX_WIDTH`,
+    /// `HAB_AUDIT_FILE` and `HAB_OUTPUT_FORMAT`, for a `UI` whose coloring and tty hinting have
+    /// already been chosen.
+    pub fn apply_env(&mut self) {
+        self.set_assume_yes(env::var(ASSUME_YES_ENVVAR).map(|val| val == "1" || val == "true")
+                                                       .unwrap_or(false));
+        self.set_accessible(env::var(ACCESSIBLE_ENVVAR).map(|val| val == "1" || val == "true")
+                                                       .unwrap_or(false));
+        if let Ok(s) = env::var(MAX_WIDTH_ENVVAR) {
+            match s.parse::<usize>() {
+                Ok(width) if width > 0 => self.set_max_width(Some(width)),
+                _ => eprintln!("Ignoring invalid {} value '{}'", MAX_WIDTH_ENVVAR, s),
+            }
+        }
+        if let Some(path) = env::var_os(AUDIT_FILE_ENVVAR) {
+            self.set_audit_file(Some(PathBuf::from(path)));
+        }
+        if let Ok(s) = env::var(OUTPUT_FORMAT_ENVVAR) {
+            match OutputMode::from_str(&s) {
+                Ok(mode) => self.set_output_mode(mode),
+                Err(e) => eprintln!("{}\nSetting output format to {}", e, OutputMode::default()),
+            }
+        }
+    }
+
     /// Creates a new `UI` from generic `Read` and `Write` streams.
     ///
     /// The standard input stream needs to implement `Read` and both the standard output and
@@ -461,6 +1476,27 @@ impl UI {
                                                isatty)))
     }
 
+    /// Creates a new `UI` from arbitrary input and output objects, where standard output and
+    /// standard error each have their own coloring and terminal settings.
+    ///
+    /// See `Shell::from_parts` for details.
+    pub fn from_parts(stdin: Box<dyn Read + Send>,
+                      stdout: Box<dyn Write + Send>,
+                      stdout_coloring: ColorChoice,
+                      stdout_isatty: bool,
+                      stderr: Box<dyn Write + Send>,
+                      stderr_coloring: ColorChoice,
+                      stderr_isatty: bool)
+                      -> Self {
+        Self::new(Shell::from_parts(stdin,
+                                    stdout,
+                                    stdout_coloring,
+                                    stdout_isatty,
+                                    stderr,
+                                    stderr_coloring,
+                                    stderr_isatty))
+    }
+
     /// Creates a new `UI` which an empty standard input and sinks (i.e. a `/dev/null`-like stream)
     /// for standard output and standard error.
     pub fn with_sinks() -> Self {
@@ -470,86 +1506,190 @@ impl UI {
                            ColorChoice::Never,
                            false)
     }
-}
 
-impl Default for UI {
-    fn default() -> Self { UI::default_with(ColorChoice::Auto, None) }
-}
+    /// Creates a `UI` whose output doesn't depend on the machine or environment it runs on, for
+    /// tests: nothing is read from the environment, standard input is empty, output is plain
+    /// text written to buffers which the returned `CapturedOutput` reads, and no stream is a
+    /// terminal.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn from_env_for_tests() -> (Self, CapturedOutput) {
+        let captured = CapturedOutput::default();
+        let (stdout, stderr) = (captured.stdout.clone(), captured.stderr.clone());
+        let ui = Self::with_streams(Box::new(io::empty()),
+                                    move || Box::new(CaptureWriter(stdout.clone())),
+                                    move || Box::new(CaptureWriter(stderr.clone())),
+                                    ColorChoice::Never,
+                                    false);
+        (ui, captured)
+    }
 
-impl UIWriter for UI {
-    type ProgressBar = ConsoleProgressBar;
+    /// When set, confirmation prompts answer affirmatively without reading any input, and
+    /// `prompt_ask` returns its default (if one is given) without prompting.
+    pub fn set_assume_yes(&mut self, assume_yes: bool) { self.assume_yes = assume_yes; }
 
-    fn out(&mut self) -> &mut dyn WriteColor { &mut self.shell.out }
+    pub fn assume_yes(&self) -> bool { self.assume_yes }
 
-    fn err(&mut self) -> &mut dyn WriteColor { &mut self.shell.err }
+    /// Returns true once standard output has been closed by its reader going away, after which
+    /// nothing more written to it is seen, so that a command can stop early.
+    pub fn is_output_closed(&self) -> bool { self.shell.out.is_closed() }
 
-    fn is_out_a_terminal(&self) -> bool { self.shell.out.is_a_terminal() }
+    /// When set, `info` messages longer than the terminal width are wrapped to fit it.
+    pub fn set_wrap_info(&mut self, wrap_info: bool) { self.wrap_info = wrap_info; }
 
-    fn is_err_a_terminal(&self) -> bool { self.shell.err.is_a_terminal() }
+    /// When set, `status`, `warn`, and `fatal` include a textual severity tag alongside color.
+    pub fn set_accessible(&mut self, accessible: bool) { self.accessible = accessible; }
 
-    fn progress(&self) -> Option<Self::ProgressBar> {
-        if self.is_out_a_terminal() {
-            Some(Self::ProgressBar::default())
-        } else {
-            None
-        }
+    pub fn set_output_mode(&mut self, output_mode: OutputMode) { self.output_mode = output_mode; }
+
+    /// When set, `status` pads or truncates the status word to this width and right-aligns it,
+    /// so that messages in a long stream of statuses line up.
+    pub fn set_status_column_width(&mut self, width: Option<usize>) { self.status_width = width; }
+
+    /// When set, caps the width of wrapped output, which otherwise follows the terminal width (or
+    /// 75 columns for `para`).
+    pub fn set_max_width(&mut self, width: Option<usize>) { self.max_width = width; }
+
+    /// Labels the events which follow, in JSON output mode, with the phase of work they belong
+    /// to (e.g. `Downloading`), so consumers can group them.
+    pub fn set_phase(&mut self, phase: &str) { self.phase = Some(phase.to_string()); }
+
+    pub fn clear_phase(&mut self) { self.phase = None; }
+
+    /// When set, `status` only writes statuses whose color is at least this severe, ordered
+    /// `Plain`, `Info`, `Important`, `Warn`, `Critical`. `warn` and `fatal` are always written.
+    pub fn set_status_min_severity(&mut self, severity: Option<Color>) {
+        self.min_severity = severity;
     }
-}
 
-impl UIReader for UI {
-    fn is_a_tty(&self) -> bool {
-        self.shell.input.isatty && self.shell.out.isatty && self.shell.err.isatty
+    /// When set, secondary guidance such as `tip`s is left out.
+    pub fn set_quiet(&mut self, quiet: bool) { self.quiet = quiet; }
+
+    /// Replaces the symbol which starts `begin` messages, for products with their own house style.
+    pub fn set_begin_symbol(&mut self, symbol: Glyph) { self.begin_glyph = symbol; }
+
+    /// Replaces the symbol which starts `end` messages.
+    pub fn set_end_symbol(&mut self, symbol: Glyph) { self.end_glyph = symbol; }
+
+    /// Replaces the clock the current time is read from, which is `SystemTime::now` by default,
+    /// so that tests can fix the time that output shows.
+    pub fn set_clock(&mut self, clock: Box<dyn Fn() -> SystemTime + Send>) {
+        self.clock = Clock(clock);
     }
 
-    fn prompt_yes_no(&mut self, question: &str, default: Option<bool>) -> Result<bool> {
-        let stream = &mut self.shell.out;
-        let (prefix, default_text, suffix) = match default {
-            Some(true) => ("[", "Yes", "/no/quit]"),
-            Some(false) => ("[yes/", "No", "/quit]"),
-            None => ("[yes/no/quit]", "", ""),
+    /// Sends `begin`, `end` and `status` events to `stream`, as JSON lines, rather than to
+    /// standard output, so that a program running hab can follow its progress on a descriptor
+    /// of its own while standard output carries the command's results. `None` sends them back.
+    pub fn set_event_stream(&mut self, stream: Option<Box<dyn Write + Send>>) {
+        self.events = stream.map(EventStream);
+    }
+
+    /// Indents `begin`, `end`, `status` and `info` messages by how many `begin`s are open, so the
+    /// steps of sub-operations sit under the operation they belong to. Off by default, because
+    /// many commands write a `begin` which is never matched by an `end`.
+    pub fn set_nesting(&mut self, nesting: bool) {
+        self.nesting = nesting;
+        self.depth = 0;
+    }
+
+    /// The changes recorded so far in this session, as they are written to the audit file.
+    pub fn changes(&self) -> &[serde_json::Value] { &self.changes }
+
+    /// Writes the session's changes to `path`, as JSON, on `finalize`. `default_with_env`
+    /// sets this from `HAB_AUDIT_FILE`.
+    pub fn set_audit_file(&mut self, path: Option<PathBuf>) { self.audit_file = path; }
+
+    /// When set, `fatal` omits the lines of framing symbols around its message, which are only
+    /// noise in logs. This is on by default when standard error is not a terminal.
+    pub fn set_compact_errors(&mut self, compact: bool) { self.compact_errs = compact; }
+
+    /// Sets how `fatal` frames its message. With `ErrorBannerStyle::Rule`, the lines above and
+    /// below the message are rules across the terminal rather than runs of symbols.
+    pub fn set_error_banner_style(&mut self, style: ErrorBannerStyle) { self.banner_style = style; }
+
+    /// When set, identical consecutive `warn` or `fatal` messages are written only once. When a
+    /// different message is written, or on `finalize`, the last of the run is written again with
+    /// a `(repeated N times)` summary.
+    pub fn set_warn_coalesce(&mut self, coalesce: bool) { self.coalesce = coalesce; }
+
+    /// Runs `f` with standard output and standard error both written, as plain text, to a buffer
+    /// rather than to the streams, and hands back the result of `f` with the text it wrote. The
+    /// streams are put back afterwards, even if `f` panics.
+    pub fn capture<T, F>(&mut self, f: F) -> (T, String)
+        where F: FnOnce(&mut UI) -> T
+    {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let stream = |buf: &Arc<Mutex<Vec<u8>>>| {
+            OutputStream::new(WriteStream::Write(Box::new(CaptureWriter(buf.clone()))),
+                              ColorChoice::Never,
+                              false)
         };
-        loop {
-            print(stream,
-                  question.as_bytes(),
-                  ColorSpec::new().set_fg(Some(Color::Important.into())))?;
-            print(stream,
-                  format!(" {}", prefix).as_bytes(),
-                  ColorSpec::new().set_fg(Some(Color::Plain.into())))?;
-            print(stream,
-                  default_text.as_bytes(),
-                  ColorSpec::new().set_fg(Some(Color::Plain.into()))
-                                  .set_bold(true))?;
-            print(stream,
-                  format!("{} ", suffix).as_bytes(),
-                  ColorSpec::new().set_fg(Some(Color::Plain.into())))?;
-            let mut response = String::new();
+        let result = {
+            let out = mem::replace(&mut self.shell.out, stream(&captured));
+            let err = mem::replace(&mut self.shell.err, stream(&captured));
+            let guard = StreamsGuard { ui:  self,
+                                       out: Some(out),
+                                       err: Some(err), };
+            f(guard.ui)
+        };
+        (result, captured_string(&captured))
+    }
+
+    /// Writes a message, or only counts it if it repeats the current run of messages.
+    fn write_coalesced(&mut self, severity: Severity, message: String) -> io::Result<()> {
+        if let Some(ref mut repeated) = self.repeated {
+            if repeated.severity == severity
+               && repeated.message == message
+               && repeated.last.elapsed() < WARN_COALESCE_WINDOW
             {
-                let reference = self.shell.input.by_ref();
-                BufReader::new(reference).read_line(&mut response)?;
+                repeated.count += 1;
+                repeated.last = Instant::now();
+                return Ok(());
             }
-            match response.trim().chars().next().unwrap_or('\n') {
-                'y' | 'Y' => return Ok(true),
-                'n' | 'N' => return Ok(false),
-                'q' | 'Q' => process::exit(0),
-                '\n' => {
-                    match default {
-                        Some(default) => return Ok(default),
-                        None => continue,
-                    }
+        }
+        self.flush_repeated()?;
+        match severity {
+            Severity::Warn => write_warn(self, &message)?,
+            Severity::Fatal => write_fatal(self, &message)?,
+        }
+        self.repeated = Some(Repeated { severity,
+                                        message,
+                                        count: 0,
+                                        last: Instant::now() });
+        Ok(())
+    }
+
+    /// Writes the summary of the current run of repeated messages, if there were any repeats.
+    fn flush_repeated(&mut self) -> io::Result<()> {
+        match self.repeated.take() {
+            Some(ref repeated) if repeated.count > 0 => {
+                let summary = format!("{} (repeated {} times)", repeated.message, repeated.count);
+                match repeated.severity {
+                    Severity::Warn => write_warn(self, summary),
+                    Severity::Fatal => write_fatal(self, summary),
                 }
-                _ => continue,
             }
+            _ => Ok(()),
         }
     }
 
-    fn prompt_ask(&mut self, question: &str, default: Option<&str>) -> Result<String> {
+    /// Asks `question`, with `shown` standing in for `default` in the `[default: ...]` hint.
+    fn prompt_ask_showing(&mut self,
+                          question: &str,
+                          default: Option<&str>,
+                          shown: Option<&str>)
+                          -> Result<String> {
+        if self.assume_yes {
+            if let Some(d) = default {
+                return Ok(d.to_string());
+            }
+        }
         let stream = &mut self.shell.out;
         loop {
             print(stream,
                   question.as_bytes(),
                   ColorSpec::new().set_fg(Some(Color::Important.into())))?;
             stream.write_all(b": ")?;
-            if let Some(d) = default {
+            if let Some(d) = shown {
                 print(stream,
                       b"[default: ",
                       ColorSpec::new().set_fg(Some(Color::Plain.into())))?;
@@ -564,351 +1704,4560 @@ impl UIReader for UI {
             stream.write_all(b" ")?;
             stream.flush()?;
             let mut response = String::new();
-            {
-                let reference = self.shell.input.by_ref();
-                BufReader::new(reference).read_line(&mut response)?;
-            }
+            let read = self.shell.input.read_line(&mut response)?;
             if response.trim().is_empty() {
                 match default {
                     Some(d) => return Ok(d.to_string()),
+                    // Asking again would only read the end of input again.
+                    None if read == 0 => return Err(Error::UnexpectedEof),
                     None => continue,
                 }
             }
             return Ok(response.trim().to_string());
         }
     }
+}
 
-    fn edit<T>(&mut self, contents: &[T]) -> Result<String>
-        where T: fmt::Display
-    {
-        let editor = env::var("EDITOR").map_err(Error::EditorEnv)?;
-
-        let mut tmp_file_path = env::temp_dir();
-        tmp_file_path.push(format!("_hab_{}.tmp", Uuid::new_v4()));
+impl Default for UI {
+    fn default() -> Self { UI::default_with(ColorChoice::Auto, None) }
+}
 
-        let mut tmp_file = File::create(&tmp_file_path)?;
+impl UIWriter for UI {
+    type ProgressBar = ConsoleProgressBar;
 
-        if !contents.is_empty() {
-            for line in contents {
-                write!(tmp_file, "{}", line)?;
-            }
-            tmp_file.sync_all()?;
-        }
+    fn out(&mut self) -> &mut dyn WriteColor { &mut self.shell.out }
 
-        let mut cmd = Command::new(editor);
-        cmd.arg(tmp_file_path.display().to_string());
-        let status = cmd.spawn()?.wait()?;
-        if !status.success() {
-            debug!("Failed edit with status: {:?}", status);
-            return Err(Error::EditStatus);
-        }
+    fn err(&mut self) -> &mut dyn WriteColor { &mut self.shell.err }
 
-        let mut out = String::new();
-        tmp_file = File::open(&tmp_file_path)?;
-        tmp_file.read_to_string(&mut out)?;
+    fn is_out_a_terminal(&self) -> bool { self.shell.out.is_a_terminal() }
 
-        fs::remove_file(tmp_file_path)?;
+    fn is_err_a_terminal(&self) -> bool { self.shell.err.is_a_terminal() }
 
-        Ok(out)
+    fn progress(&self) -> Option<Self::ProgressBar> {
+        if self.is_out_a_terminal() {
+            Some(Self::ProgressBar::default())
+        } else {
+            None
+        }
     }
-}
-
-#[derive(Debug)]
-pub struct Shell {
-    input: InputStream,
-    out:   OutputStream,
-    err:   OutputStream,
-}
 
-impl Shell {
-    pub fn new(input: InputStream, out: OutputStream, err: OutputStream) -> Self {
-        Shell { input, out, err }
+    fn progress_for(&self, label: &str) -> Option<Self::ProgressBar> {
+        self.progress().map(|mut bar| {
+                           bar.set_label(label);
+                           bar
+                       })
     }
 
-    pub fn default_with(coloring: ColorChoice, isatty: Option<bool>) -> Self {
-        let stdin = InputStream::from_stdin(isatty);
-        let stdout = OutputStream::from_stdout(coloring, isatty);
-        let stderr = OutputStream::from_stderr(coloring, isatty);
-        Shell::new(stdin, stdout, stderr)
+    fn info_wrap_width(&self) -> Option<usize> {
+        if !self.wrap_info {
+            return None;
+        }
+        match (terminal_width(), self.max_width) {
+            (Some(width), Some(max)) => Some(width.min(max)),
+            (terminal, max) => terminal.or(max),
+        }
     }
 
-    pub fn input(&mut self) -> &mut InputStream { &mut self.input }
+    fn accessible(&self) -> bool { self.accessible }
 
-    pub fn out(&mut self) -> &mut OutputStream { &mut self.out }
+    fn output_mode(&self) -> OutputMode { self.output_mode }
 
-    pub fn err(&mut self) -> &mut OutputStream { &mut self.err }
-}
+    fn status_column_width(&self) -> Option<usize> { self.status_width }
 
-impl Default for Shell {
-    fn default() -> Self { Shell::default_with(ColorChoice::Auto, None) }
-}
+    fn max_width(&self) -> Option<usize> { self.max_width }
 
-pub struct InputStream {
-    inner:  Box<dyn Read + Send>,
-    isatty: bool,
-}
+    fn compact_errors(&self) -> bool { self.compact_errs }
 
-impl InputStream {
-    pub fn new(inner: Box<dyn Read + Send>, isatty: bool) -> Self { InputStream { inner, isatty } }
+    fn error_banner_style(&self) -> ErrorBannerStyle { self.banner_style }
 
-    pub fn from_stdin(isatty: Option<bool>) -> Self {
-        Self::new(Box::new(io::stdin()), match isatty {
-            Some(val) => val,
-            None => tty::isatty(StdStream::Stdin),
-        })
-    }
+    fn quiet(&self) -> bool { self.quiet }
 
-    pub fn is_a_terminal(&self) -> bool { self.isatty }
-}
+    fn begin_symbol(&self) -> Glyph { self.begin_glyph }
 
-impl Read for InputStream {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.inner.read(buf) }
-}
+    fn status_min_severity(&self) -> Option<Color> { self.min_severity }
 
-impl fmt::Debug for InputStream {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "InputStream {{ isatty: {} }}", self.isatty)
-    }
-}
+    fn end_symbol(&self) -> Glyph { self.end_glyph }
 
-pub struct OutputStream {
-    inner:    WriteStream,
-    coloring: ColorChoice,
-    isatty:   bool,
-}
+    fn phase(&self) -> Option<&str> { self.phase.as_ref().map(String::as_str) }
 
-impl OutputStream {
-    pub fn new(inner: WriteStream, coloring: ColorChoice, isatty: bool) -> Self {
-        OutputStream { inner,
-                       coloring,
-                       isatty }
+    fn now(&self) -> SystemTime { (self.clock.0)() }
+
+    fn next_event_seq(&mut self) -> u64 {
+        self.event_seq += 1;
+        self.event_seq
     }
 
-    pub fn from_stdout(coloring: ColorChoice, isatty: Option<bool>) -> Self {
-        Self::new(WriteStream::from_stdout(coloring), coloring, match isatty {
-            Some(val) => val,
-            None => tty::isatty(StdStream::Stdout),
-        })
+    fn event_stream(&mut self) -> Option<&mut dyn Write> {
+        match self.events {
+            Some(ref mut events) => Some(&mut *events.0),
+            None => None,
+        }
     }
 
-    pub fn from_stderr(coloring: ColorChoice, isatty: Option<bool>) -> Self {
-        Self::new(WriteStream::from_stderr(coloring), coloring, match isatty {
-            Some(val) => val,
-            None => tty::isatty(StdStream::Stderr),
-        })
+    fn nesting_depth(&self) -> usize { self.depth }
+
+    fn open_nesting(&mut self) {
+        if self.nesting {
+            self.depth += 1;
+        }
     }
 
-    pub fn is_a_terminal(&self) -> bool { self.isatty }
-}
+    fn close_nesting(&mut self) { self.depth = self.depth.saturating_sub(1); }
 
-impl WriteColor for OutputStream {
-    fn supports_color(&self) -> bool {
-        match self.inner {
-            WriteStream::Stream(ref stream) => stream.supports_color(),
-            _ => false,
+    fn record_change<T>(&mut self, change: &T)
+        where T: serde::Serialize
+    {
+        match serde_json::to_value(change) {
+            Ok(change) => self.changes.push(change),
+            Err(e) => debug!("Unable to record a change in the audit: {}", e),
         }
     }
 
-    fn reset(&mut self) -> io::Result<()> {
-        match self.inner {
-            WriteStream::Stream(ref mut stream) => stream.reset(),
-            _ => Ok(()),
+    fn warn<T>(&mut self, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        if self.coalesce {
+            self.write_coalesced(Severity::Warn, message.to_string())
+        } else {
+            write_warn(self, message)
         }
     }
 
-    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
-        match self.inner {
-            WriteStream::Stream(ref mut stream) => stream.set_color(spec),
-            _ => Ok(()),
+    fn fatal<T>(&mut self, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        if self.coalesce {
+            self.write_coalesced(Severity::Fatal, message.to_string())
+        } else {
+            write_fatal(self, message)
         }
     }
-}
 
-impl Write for OutputStream {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        match self.inner {
-            WriteStream::Stream(ref mut stream) => stream.write(buf),
-            WriteStream::Write(ref mut w) => w.write(buf),
+    /// Also writes the session's changes to the audit file, when there is one, even if there
+    /// were none, and removes any FIFO a progress pipe created.
+    fn finalize(&mut self) -> io::Result<()> {
+        remove_created_fifos();
+        self.flush_repeated()?;
+        if let Some(ref path) = self.audit_file {
+            let audit = AuditFile { mutations: &self.changes, };
+            let json = match serde_json::to_string_pretty(&audit) {
+                Ok(json) => json,
+                Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+            };
+            fs::write(path, format!("{}\n", json))?;
         }
+        self.out().reset()?;
+        self.out().flush()?;
+        self.err().reset()?;
+        self.err().flush()
     }
+}
 
-    fn flush(&mut self) -> io::Result<()> {
-        match self.inner {
-            WriteStream::Stream(ref mut stream) => stream.flush(),
-            WriteStream::Write(ref mut w) => w.flush(),
-        }
+impl UIReader for UI {
+    fn is_a_tty(&self) -> bool {
+        self.shell.input.isatty && self.shell.out.isatty && self.shell.err.isatty
     }
-}
 
-impl fmt::Debug for OutputStream {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f,
-               "OutputStream {{ coloring: {:?}, isatty: {} }}",
-               self.coloring, self.isatty,)
+    /// Also false when `assume_yes` is set, since prompts are answered without asking.
+    fn is_interactive(&self) -> bool {
+        self.is_a_tty() && !noninteractive_env() && !self.assume_yes
     }
-}
 
-pub enum WriteStream {
-    /// A plain write object without color support
-    Write(Box<dyn Write + Send>),
-    /// Color-enabled stdio, with information on whether color should be used
-    Stream(StandardStream),
-}
+    fn prompt_yes_no(&mut self, question: &str, default: Option<bool>) -> Result<bool> {
+        match self.prompt_yes_no_cancellable(question, default)? {
+            YesNoCancel::Yes => Ok(true),
+            YesNoCancel::No => Ok(false),
+            YesNoCancel::Cancel => process::exit(0),
+        }
+    }
 
-impl WriteStream {
-    // Implementation heavily inspired and based on the Cargo `shell.rs` implementation. Source:
-    // https://github.com/rust-lang/cargo/blob/5c6aa46e6f28661270979696e7b4c2f0dff8628f/
-    // src/cargo/core/shell.rs
+    fn prompt_yes_no_cancellable(&mut self,
+                                 question: &str,
+                                 default: Option<bool>)
+                                 -> Result<YesNoCancel> {
+        if self.assume_yes {
+            return Ok(YesNoCancel::Yes);
+        }
+        let stream = &mut self.shell.out;
+        let words = yes_no_words();
+        let [yes, no, quit] = words.hint;
+        let (prefix, default_text, suffix) = match default {
+            Some(true) => ("[".to_string(), capitalize(yes), format!("/{}/{}]", no, quit)),
+            Some(false) => (format!("[{}/", yes), capitalize(no), format!("/{}]", quit)),
+            None => (format!("[{}/{}/{}]", yes, no, quit), String::new(), String::new()),
+        };
+        loop {
+            print(stream,
+                  question.as_bytes(),
+                  ColorSpec::new().set_fg(Some(Color::Important.into())))?;
+            print(stream,
+                  format!(" {}", prefix).as_bytes(),
+                  ColorSpec::new().set_fg(Some(Color::Plain.into())))?;
+            print(stream,
+                  default_text.as_bytes(),
+                  ColorSpec::new().set_fg(Some(Color::Plain.into()))
+                                  .set_bold(true))?;
+            print(stream,
+                  format!("{} ", suffix).as_bytes(),
+                  ColorSpec::new().set_fg(Some(Color::Plain.into())))?;
+            let mut response = String::new();
+            let read = self.shell.input.read_line(&mut response)?;
+            if read == 0 {
+                // Input has ended, so asking again would only loop.
+                return match default {
+                    Some(true) => Ok(YesNoCancel::Yes),
+                    Some(false) => Ok(YesNoCancel::No),
+                    None => Err(Error::UnexpectedEof),
+                };
+            }
+            let response = response.trim().to_lowercase();
+            if words.yes.contains(&response.as_str()) {
+                return Ok(YesNoCancel::Yes);
+            }
+            if words.no.contains(&response.as_str()) {
+                return Ok(YesNoCancel::No);
+            }
+            match response.chars().next().unwrap_or('\n') {
+                'y' | 'Y' => return Ok(YesNoCancel::Yes),
+                'n' | 'N' => return Ok(YesNoCancel::No),
+                'q' | 'Q' => return Ok(YesNoCancel::Cancel),
+                '\n' => {
+                    match default {
+                        Some(true) => return Ok(YesNoCancel::Yes),
+                        Some(false) => return Ok(YesNoCancel::No),
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            }
+        }
+    }
 
-    pub fn from_stdout(coloring: ColorChoice) -> Self {
-        WriteStream::Stream(StandardStream::stdout(coloring))
+    fn prompt_yes_no_all(&mut self,
+                         tag: &str,
+                         question: &str,
+                         default: Option<bool>)
+                         -> Result<bool> {
+        if let Some(&answer) = self.remembered.get(tag) {
+            return Ok(answer);
+        }
+        if self.assume_yes {
+            return Ok(true);
+        }
+        let words = yes_no_words();
+        let hint = yes_no_all_hint(&words, default);
+        loop {
+            {
+                let stream = &mut self.shell.out;
+                print(stream,
+                      question.as_bytes(),
+                      ColorSpec::new().set_fg(Some(Color::Important.into())))?;
+                print(stream,
+                      format!(" {} ", hint).as_bytes(),
+                      ColorSpec::new().set_fg(Some(Color::Plain.into())))?;
+                stream.flush()?;
+            }
+            let mut response = String::new();
+            let read = self.shell.input.read_line(&mut response)?;
+            if read == 0 {
+                return default.ok_or(Error::UnexpectedEof);
+            }
+            let response = response.trim().to_lowercase();
+            let answer = match response.as_str() {
+                "all" | "a" => {
+                    self.remembered.insert(tag.to_string(), true);
+                    Some(true)
+                }
+                "none" => {
+                    self.remembered.insert(tag.to_string(), false);
+                    Some(false)
+                }
+                r if words.yes.contains(&r) => Some(true),
+                r if words.no.contains(&r) => Some(false),
+                r => {
+                    match r.chars().next() {
+                        Some('y') => Some(true),
+                        Some('n') => Some(false),
+                        Some('q') => return Err(Error::PromptCancelled),
+                        Some(_) => None,
+                        None => default,
+                    }
+                }
+            };
+            if let Some(answer) = answer {
+                return Ok(answer);
+            }
+        }
     }
 
-    pub fn from_stderr(coloring: ColorChoice) -> Self {
-        WriteStream::Stream(StandardStream::stderr(coloring))
+    fn prompt_ask_path(&mut self,
+                       question: &str,
+                       default: Option<&str>,
+                       must_exist: bool,
+                       kind: PathKind)
+                       -> Result<PathBuf> {
+        let home = home_dir();
+        if !self.is_interactive() {
+            let default = default.ok_or_else(|| Error::PathRequired(question.to_string()))?;
+            let path = expand_tilde(default, home.as_ref().map(PathBuf::as_path));
+            return match kind.check(&path, must_exist) {
+                Ok(()) => Ok(path),
+                Err(reason) => Err(Error::InvalidPath(path, reason)),
+            };
+        }
+        loop {
+            let answer = self.prompt_ask(question, default)?;
+            let path = expand_tilde(&answer, home.as_ref().map(PathBuf::as_path));
+            match kind.check(&path, must_exist) {
+                Ok(()) => return Ok(path),
+                Err(reason) => {
+                    self.warn(format!("Can't use {}: {}. Please try again.",
+                                      path.display(),
+                                      reason))?
+                }
+            }
+        }
     }
 
-    /// Create a shell from a plain writable object, with no color, and max verbosity.
-    pub fn from_write<T: FnMut() -> Box<dyn Write + Send>>(mut writable_fn: T) -> Self {
-        WriteStream::Write(writable_fn())
+    fn prompt_int(&mut self,
+                  question: &str,
+                  default: Option<i64>,
+                  range: Option<RangeInclusive<i64>>)
+                  -> Result<i64> {
+        if !self.is_interactive() {
+            let default = default.ok_or_else(|| Error::NumberRequired(question.to_string()))?;
+            return match check_int_range(default, &range) {
+                Ok(()) => Ok(default),
+                Err(reason) => Err(Error::InvalidNumber(default.to_string(), reason)),
+            };
+        }
+        let default_str = default.map(|d| d.to_string());
+        loop {
+            let answer = self.prompt_ask(question, default_str.as_ref().map(String::as_str))?;
+            let reason = match answer.trim().parse::<i64>() {
+                Ok(value) => {
+                    match check_int_range(value, &range) {
+                        Ok(()) => return Ok(value),
+                        Err(reason) => reason,
+                    }
+                }
+                Err(_) => "it is not a whole number".to_string(),
+            };
+            self.warn(format!("Can't use '{}': {}. Please try again.",
+                              answer.trim(),
+                              reason))?
+        }
     }
-}
 
-mod tty {
-    #[derive(Clone, Copy)]
-    pub enum StdStream {
-        Stdin,
-        Stdout,
-        Stderr,
+    fn prompt_ask(&mut self, question: &str, default: Option<&str>) -> Result<String> {
+        self.prompt_ask_showing(question, default, default)
     }
 
-    #[cfg(unix)]
-    pub fn isatty(output: StdStream) -> bool {
-        use libc;
+    fn prompt_ask_secret_default(&mut self,
+                                 question: &str,
+                                 default: Option<&str>)
+                                 -> Result<String> {
+        let shown = default.map(mask_secret);
+        self.prompt_ask_showing(question, default, shown.as_ref().map(String::as_str))
+    }
 
-        let fd = match output {
-            StdStream::Stdin => libc::STDIN_FILENO,
-            StdStream::Stdout => libc::STDOUT_FILENO,
-            StdStream::Stderr => libc::STDERR_FILENO,
-        };
+    fn prompt_confirm_phrase(&mut self, question: &str, phrase: &str) -> Result<bool> {
+        if self.assume_yes {
+            return Ok(true);
+        }
+        let stream = &mut self.shell.out;
+        print(stream,
+              question.as_bytes(),
+              ColorSpec::new().set_fg(Some(Color::Important.into())))?;
+        print(stream,
+              b" Type ",
+              ColorSpec::new().set_fg(Some(Color::Plain.into())))?;
+        print(stream,
+              phrase.as_bytes(),
+              ColorSpec::new().set_fg(Some(Color::Plain.into()))
+                              .set_bold(true))?;
+        print(stream,
+              b" to continue: ",
+              ColorSpec::new().set_fg(Some(Color::Plain.into())))?;
+        let mut response = String::new();
+        self.shell.input.read_line(&mut response)?;
+        Ok(response.trim() == phrase)
+    }
 
-        unsafe { libc::isatty(fd) != 0 }
+    fn confirm_destructive(&mut self, impact: &str, phrase: &str) -> Result<bool> {
+        write_warn_in(self, impact, Color::Critical)?;
+        if self.assume_yes {
+            return Ok(true);
+        }
+        if !self.is_interactive() {
+            return Err(Error::ConfirmationRequired(impact.to_string()));
+        }
+        self.prompt_confirm_phrase("Are you sure?", phrase)
     }
-    #[cfg(windows)]
-    pub fn isatty(output: StdStream) -> bool {
-        use winapi::um::{consoleapi,
-                         processenv,
-                         winbase};
 
-        let handle = match output {
-            StdStream::Stdin => winbase::STD_INPUT_HANDLE,
-            StdStream::Stdout => winbase::STD_OUTPUT_HANDLE,
-            StdStream::Stderr => winbase::STD_ERROR_HANDLE,
+    fn read_key(&mut self) -> Result<char> {
+        // Should the terminal refuse raw mode, the key is still read, just once Enter is pressed.
+        let _raw = if self.shell.input.isatty && !noninteractive_env() {
+            tty::RawMode::stdin().ok()
+        } else {
+            None
         };
+        Ok(read_key_from(&mut self.shell.input)?)
+    }
 
-        unsafe {
-            let handle = processenv::GetStdHandle(handle);
-            let mut out = 0;
-            consoleapi::GetConsoleMode(handle, &mut out) != 0
+    fn prompt_ask_multiline(&mut self, question: &str, terminator: &str) -> Result<String> {
+        let terminator = if terminator.is_empty() {
+            DEFAULT_MULTILINE_TERMINATOR
+        } else {
+            terminator
+        };
+        let interactive = self.is_a_tty();
+        let stream = &mut self.shell.out;
+        print(stream,
+              question.as_bytes(),
+              ColorSpec::new().set_fg(Some(Color::Important.into())))?;
+        if interactive {
+            print(stream,
+                  format!(" (end with a line containing only '{}')", terminator).as_bytes(),
+                  ColorSpec::new().set_fg(Some(Color::Plain.into())))?;
+        }
+        stream.write_all(b"\n")?;
+        stream.flush()?;
+        let mut lines = Vec::new();
+        for line in self.shell.input.by_ref().lines() {
+            let line = line?;
+            if interactive && line.trim_end() == terminator {
+                break;
+            }
+            lines.push(line);
         }
+        Ok(lines.join("\n"))
     }
-}
 
-/// A moving progress bar to track progress of a sized event, similar to wget, curl, npm, etc.
-///
-/// This is designed to satisfy a generic behavior which sets the size of the task (usually a
-/// number of bytes representing the total download/upload/transfer size) and will be a generic
-/// writer (i.e. implementing the `Write` trait) as a means to increase progress towards
-/// completion.
-pub struct ConsoleProgressBar {
-    bar:     pbr::ProgressBar<Stdout>,
-    total:   u64,
-    current: u64,
-}
+    fn edit<T>(&mut self, contents: &[T]) -> Result<String>
+        where T: fmt::Display
+    {
+        let editor = env::var("EDITOR").map_err(Error::EditorEnv)?;
 
-impl Default for ConsoleProgressBar {
-    fn default() -> Self {
-        ConsoleProgressBar { bar:     pbr::ProgressBar::new(0),
-                             total:   0,
-                             current: 0, }
-    }
-}
+        let mut tmp_file_path = env::temp_dir();
+        tmp_file_path.push(format!("_hab_{}.tmp", Uuid::new_v4()));
 
-impl DisplayProgress for ConsoleProgressBar {
-    fn size(&mut self, size: u64) {
-        self.bar = pbr::ProgressBar::new(size);
-        self.bar.set_units(pbr::Units::Bytes);
-        self.bar.show_tick = true;
-        self.bar.message("    ");
-        self.total = size;
-    }
+        let mut tmp_file = File::create(&tmp_file_path)?;
 
-    fn finish(&mut self) {
-        println!();
-        io::stdout().flush().expect("flush() fail");
-    }
-}
+        if !contents.is_empty() {
+            for line in contents {
+                write!(tmp_file, "{}", line)?;
+            }
+            tmp_file.sync_all()?;
+        }
 
-impl Write for ConsoleProgressBar {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        match self.bar.write(buf) {
-            Ok(n) => {
-                self.current += n as u64;
-                if self.current == self.total {
-                    self.finish();
+        let mut cmd = Command::new(&editor);
+        cmd.arg(tmp_file_path.display().to_string());
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_file_path);
+                if e.kind() == io::ErrorKind::NotFound {
+                    return Err(Error::EditorNotFound(editor));
                 }
-                Ok(n)
+                return Err(Error::from(e));
             }
-            Err(e) => Err(e),
+        };
+        let status = child.wait()?;
+        if !status.success() {
+            debug!("Failed edit with status: {:?}", status);
+            return Err(Error::EditStatus);
         }
+
+        let out = match String::from_utf8(fs::read(&tmp_file_path)?) {
+            Ok(out) => out,
+            // The file is left for the user to recover their edit from.
+            Err(_) => return Err(Error::EditInvalidUtf8(tmp_file_path)),
+        };
+
+        fs::remove_file(tmp_file_path)?;
+
+        Ok(out)
     }
+}
 
-    fn flush(&mut self) -> io::Result<()> { self.bar.flush() }
+/// A single call made against a `RecordingUI`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UiEvent {
+    Begin(String),
+    End(String),
+    /// The status text (e.g. `Promoted`) and the message which followed it.
+    Status(String, String),
+    Info(String),
+    Tip(String),
+    Warn(String),
+    /// The title of a group of warnings, and the warnings in it.
+    WarnGroup(String, Vec<String>),
+    Fatal(String),
+    JsonValue(serde_json::Value),
+    Title(String),
+    Heading(String),
+    Para(String),
+    /// The text, the indent, and the first line's prefix.
+    ParaWith(String, usize, String),
+    OrderedList(Vec<String>),
+    Rule,
+    Divider(String),
+    Br,
+    Raw(Vec<u8>),
+    Edit(Vec<String>),
+    PromptAsk(String, Option<String>),
+    /// The question and the default as it was shown, masked.
+    PromptAskSecretDefault(String, Option<String>),
+    PromptYesNo(String, Option<bool>),
+    PromptYesNoCancellable(String, Option<bool>),
+    /// The tag, the question, and the default.
+    PromptYesNoAll(String, String, Option<bool>),
+    PromptAskPath(String, Option<String>),
+    PromptInt(String, Option<i64>),
+    PromptConfirmPhrase(String, String),
+    /// The impact and the phrase.
+    ConfirmDestructive(String, String),
+    PromptAskMultiline(String, String),
+    ReadKey,
 }
 
-pub fn print_wrapped<U>(stream: &mut dyn WriteColor,
-                        text: U,
-                        wrap_width: usize,
-                        left_indent: usize)
-                        -> io::Result<()>
-    where U: AsRef<str>
-{
-    for line in text.as_ref().split("\n\n") {
-        let mut buffer = String::new();
-        let mut width = 0;
-        for word in line.split_whitespace() {
-            let wl = word.chars().count();
-            if (width + wl + 1) > (wrap_width - left_indent) {
-                stream.write_all(
-                    format!("{:<width$}{}\n", " ", buffer, width = left_indent).as_bytes(),
-                )?;
-                buffer.clear();
-                width = 0;
-            }
-            width = width + wl + 1;
-            buffer.push_str(word);
-            buffer.push(' ');
-        }
-        if !buffer.is_empty() {
-            stream.write_all(
-                format!("{:<width$}{}\n", " ", buffer, width = left_indent).as_bytes(),
-            )?;
-        }
-        stream.write_all(b"\n")?;
-    }
-    stream.flush()
+/// A `UI` which records every call made against it as a `UiEvent`, while still writing its output
+/// through the wrapped `UI`.
+///
+/// This allows tests to assert the exact sequence of messages a command emits, independent of
+/// colors and other formatting.
+#[derive(Debug)]
+pub struct RecordingUI {
+    inner:  UI,
+    events: Vec<UiEvent>,
 }
 
-pub fn print(writer: &mut WriteColor, buf: &[u8], color_spec: &ColorSpec) -> io::Result<()> {
-    writer.reset()?;
-    writer.set_color(color_spec)?;
-    writer.write_all(buf)?;
-    writer.flush()?;
-    writer.reset()
+impl RecordingUI {
+    pub fn new(inner: UI) -> Self {
+        RecordingUI { inner,
+                      events: Vec::new() }
+    }
+
+    /// The calls made so far, in the order they were made.
+    pub fn events(&self) -> &[UiEvent] { &self.events }
+
+    pub fn into_inner(self) -> UI { self.inner }
 }
 
-pub fn println(writer: &mut WriteColor, buf: &[u8], color_spec: &ColorSpec) -> io::Result<()> {
-    print(writer, buf, color_spec)?;
-    writer.write_all(b"\n")?;
-    writer.flush()
+impl UIWriter for RecordingUI {
+    type ProgressBar = ConsoleProgressBar;
+
+    fn out(&mut self) -> &mut dyn WriteColor { self.inner.out() }
+
+    fn err(&mut self) -> &mut dyn WriteColor { self.inner.err() }
+
+    fn is_out_a_terminal(&self) -> bool { self.inner.is_out_a_terminal() }
+
+    fn is_err_a_terminal(&self) -> bool { self.inner.is_err_a_terminal() }
+
+    fn progress(&self) -> Option<Self::ProgressBar> { self.inner.progress() }
+
+    fn progress_for(&self, label: &str) -> Option<Self::ProgressBar> {
+        self.inner.progress_for(label)
+    }
+
+    fn info_wrap_width(&self) -> Option<usize> { self.inner.info_wrap_width() }
+
+    fn accessible(&self) -> bool { self.inner.accessible() }
+
+    fn output_mode(&self) -> OutputMode { self.inner.output_mode() }
+
+    fn status_column_width(&self) -> Option<usize> { self.inner.status_column_width() }
+
+    fn max_width(&self) -> Option<usize> { self.inner.max_width() }
+
+    fn compact_errors(&self) -> bool { self.inner.compact_errors() }
+
+    fn error_banner_style(&self) -> ErrorBannerStyle { self.inner.error_banner_style() }
+
+    fn quiet(&self) -> bool { self.inner.quiet() }
+
+    fn begin_symbol(&self) -> Glyph { self.inner.begin_symbol() }
+
+    fn status_min_severity(&self) -> Option<Color> { self.inner.status_min_severity() }
+
+    fn end_symbol(&self) -> Glyph { self.inner.end_symbol() }
+
+    fn phase(&self) -> Option<&str> { self.inner.phase() }
+
+    fn now(&self) -> SystemTime { self.inner.now() }
+
+    fn next_event_seq(&mut self) -> u64 { self.inner.next_event_seq() }
+
+    fn event_stream(&mut self) -> Option<&mut dyn Write> { self.inner.event_stream() }
+
+    fn nesting_depth(&self) -> usize { self.inner.nesting_depth() }
+
+    fn open_nesting(&mut self) { self.inner.open_nesting() }
+
+    fn close_nesting(&mut self) { self.inner.close_nesting() }
+
+    fn record_change<T>(&mut self, change: &T)
+        where T: serde::Serialize
+    {
+        self.inner.record_change(change)
+    }
+
+    fn begin<T>(&mut self, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        self.events.push(UiEvent::Begin(message.to_string()));
+        self.inner.begin(message)
+    }
+
+    fn end<T>(&mut self, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        self.events.push(UiEvent::End(message.to_string()));
+        self.inner.end(message)
+    }
+
+    fn status<T>(&mut self, status: Status, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        let (_, status_str, _) = status.parts();
+        self.events
+            .push(UiEvent::Status(status_str, message.to_string()));
+        self.inner.status(status, message)
+    }
+
+    fn status_with<T>(&mut self, status: Status, severity: Color, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        let (_, status_str, _) = status.parts();
+        self.events
+            .push(UiEvent::Status(status_str, message.to_string()));
+        self.inner.status_with(status, severity, message)
+    }
+
+    fn status_fail<T>(&mut self, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        self.events
+            .push(UiEvent::Status("Failed".to_string(), message.to_string()));
+        self.inner.status_fail(message)
+    }
+
+    fn info<T>(&mut self, text: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        self.events.push(UiEvent::Info(text.to_string()));
+        self.inner.info(text)
+    }
+
+    fn warn<T>(&mut self, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        self.events.push(UiEvent::Warn(message.to_string()));
+        self.inner.warn(message)
+    }
+
+    fn tip<T>(&mut self, text: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        self.events.push(UiEvent::Tip(text.to_string()));
+        self.inner.tip(text)
+    }
+
+    fn warn_group(&mut self, title: &str, warnings: &[String]) -> io::Result<()> {
+        self.events
+            .push(UiEvent::WarnGroup(title.to_string(), warnings.to_vec()));
+        self.inner.warn_group(title, warnings)
+    }
+
+    fn fatal<T>(&mut self, message: T) -> io::Result<()>
+        where T: fmt::Display
+    {
+        self.events.push(UiEvent::Fatal(message.to_string()));
+        self.inner.fatal(message)
+    }
+
+    fn json_value<T>(&mut self, value: &T) -> io::Result<()>
+        where T: serde::Serialize
+    {
+        self.events
+            .push(UiEvent::JsonValue(serde_json::to_value(value).unwrap_or_default()));
+        self.inner.json_value(value)
+    }
+
+    fn json_value_or<T, D>(&mut self, value: &T, human: D) -> io::Result<()>
+        where T: serde::Serialize,
+              D: fmt::Display
+    {
+        self.events
+            .push(UiEvent::JsonValue(serde_json::to_value(value).unwrap_or_default()));
+        self.inner.json_value_or(value, human)
+    }
+
+    fn title<T>(&mut self, text: T) -> io::Result<()>
+        where T: AsRef<str>
+    {
+        self.events.push(UiEvent::Title(text.as_ref().to_string()));
+        self.inner.title(text)
+    }
+
+    fn heading<T>(&mut self, text: T) -> io::Result<()>
+        where T: AsRef<str>
+    {
+        self.events
+            .push(UiEvent::Heading(text.as_ref().to_string()));
+        self.inner.heading(text)
+    }
+
+    fn para(&mut self, text: &str) -> io::Result<()> {
+        self.events.push(UiEvent::Para(text.to_string()));
+        self.inner.para(text)
+    }
+
+    fn para_with(&mut self, text: &str, indent: usize, first_line_prefix: &str) -> io::Result<()> {
+        self.events
+            .push(UiEvent::ParaWith(text.to_string(), indent, first_line_prefix.to_string()));
+        self.inner.para_with(text, indent, first_line_prefix)
+    }
+
+    fn ordered_list<I, T>(&mut self, items: I) -> io::Result<()>
+        where I: IntoIterator<Item = T>,
+              T: fmt::Display
+    {
+        let items: Vec<String> = items.into_iter().map(|item| item.to_string()).collect();
+        self.events.push(UiEvent::OrderedList(items.clone()));
+        self.inner.ordered_list(items)
+    }
+
+    fn rule(&mut self) -> io::Result<()> {
+        self.events.push(UiEvent::Rule);
+        self.inner.rule()
+    }
+
+    fn divider_with_label(&mut self, label: &str) -> io::Result<()> {
+        self.events.push(UiEvent::Divider(label.to_string()));
+        self.inner.divider_with_label(label)
+    }
+
+    fn br(&mut self) -> io::Result<()> {
+        self.events.push(UiEvent::Br);
+        self.inner.br()
+    }
+
+    fn raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.events.push(UiEvent::Raw(bytes.to_vec()));
+        self.inner.raw(bytes)
+    }
+
+    fn finalize(&mut self) -> io::Result<()> { self.inner.finalize() }
+}
+
+impl UIReader for RecordingUI {
+    fn is_a_tty(&self) -> bool { self.inner.is_a_tty() }
+
+    fn is_interactive(&self) -> bool { self.inner.is_interactive() }
+
+    fn edit<T>(&mut self, contents: &[T]) -> Result<String>
+        where T: fmt::Display
+    {
+        self.events
+            .push(UiEvent::Edit(contents.iter().map(ToString::to_string).collect()));
+        self.inner.edit(contents)
+    }
+
+    fn prompt_ask(&mut self, question: &str, default: Option<&str>) -> Result<String> {
+        self.events
+            .push(UiEvent::PromptAsk(question.to_string(), default.map(str::to_string)));
+        self.inner.prompt_ask(question, default)
+    }
+
+    fn prompt_ask_secret_default(&mut self,
+                                 question: &str,
+                                 default: Option<&str>)
+                                 -> Result<String> {
+        self.events
+            .push(UiEvent::PromptAskSecretDefault(question.to_string(), default.map(mask_secret)));
+        self.inner.prompt_ask_secret_default(question, default)
+    }
+
+    fn prompt_yes_no(&mut self, question: &str, default: Option<bool>) -> Result<bool> {
+        self.events
+            .push(UiEvent::PromptYesNo(question.to_string(), default));
+        self.inner.prompt_yes_no(question, default)
+    }
+
+    fn prompt_yes_no_cancellable(&mut self,
+                                 question: &str,
+                                 default: Option<bool>)
+                                 -> Result<YesNoCancel> {
+        self.events
+            .push(UiEvent::PromptYesNoCancellable(question.to_string(), default));
+        self.inner.prompt_yes_no_cancellable(question, default)
+    }
+
+    fn prompt_yes_no_all(&mut self,
+                         tag: &str,
+                         question: &str,
+                         default: Option<bool>)
+                         -> Result<bool> {
+        self.events
+            .push(UiEvent::PromptYesNoAll(tag.to_string(), question.to_string(), default));
+        self.inner.prompt_yes_no_all(tag, question, default)
+    }
+
+    fn prompt_ask_path(&mut self,
+                       question: &str,
+                       default: Option<&str>,
+                       must_exist: bool,
+                       kind: PathKind)
+                       -> Result<PathBuf> {
+        self.events
+            .push(UiEvent::PromptAskPath(question.to_string(), default.map(str::to_string)));
+        self.inner
+            .prompt_ask_path(question, default, must_exist, kind)
+    }
+
+    fn prompt_int(&mut self,
+                  question: &str,
+                  default: Option<i64>,
+                  range: Option<RangeInclusive<i64>>)
+                  -> Result<i64> {
+        self.events
+            .push(UiEvent::PromptInt(question.to_string(), default));
+        self.inner.prompt_int(question, default, range)
+    }
+
+    fn prompt_confirm_phrase(&mut self, question: &str, phrase: &str) -> Result<bool> {
+        self.events
+            .push(UiEvent::PromptConfirmPhrase(question.to_string(), phrase.to_string()));
+        self.inner.prompt_confirm_phrase(question, phrase)
+    }
+
+    fn confirm_destructive(&mut self, impact: &str, phrase: &str) -> Result<bool> {
+        self.events
+            .push(UiEvent::ConfirmDestructive(impact.to_string(), phrase.to_string()));
+        self.inner.confirm_destructive(impact, phrase)
+    }
+
+    fn prompt_ask_multiline(&mut self, question: &str, terminator: &str) -> Result<String> {
+        self.events
+            .push(UiEvent::PromptAskMultiline(question.to_string(), terminator.to_string()));
+        self.inner.prompt_ask_multiline(question, terminator)
+    }
+
+    fn read_key(&mut self) -> Result<char> {
+        self.events.push(UiEvent::ReadKey);
+        self.inner.read_key()
+    }
+}
+
+#[derive(Debug)]
+pub struct Shell {
+    input: InputStream,
+    out:   OutputStream,
+    err:   OutputStream,
+}
+
+impl Shell {
+    /// Standard output ends on a broken pipe, so that output piped to something like `head`,
+    /// which stops reading early, doesn't fail the command.
+    pub fn new(input: InputStream, out: OutputStream, err: OutputStream) -> Self {
+        Shell { input,
+                out: out.ending_on_broken_pipe(),
+                err }
+    }
+
+    pub fn default_with(coloring: ColorChoice, isatty: Option<bool>) -> Self {
+        let stdin = InputStream::from_stdin(isatty);
+        let stdout = OutputStream::from_stdout(coloring, isatty);
+        let stderr = OutputStream::from_stderr(coloring, isatty);
+        Shell::new(stdin, stdout, stderr)
+    }
+
+    /// Creates a shell whose standard output and standard error are configured independently,
+    /// as happens with redirection like `hab ... > file`, where standard output is a plain file
+    /// but standard error is still a colored terminal.
+    ///
+    /// Standard input is considered a terminal whenever standard output is, since that's where
+    /// prompts are written.
+    pub fn from_parts(stdin: Box<dyn Read + Send>,
+                      stdout: Box<dyn Write + Send>,
+                      stdout_coloring: ColorChoice,
+                      stdout_isatty: bool,
+                      stderr: Box<dyn Write + Send>,
+                      stderr_coloring: ColorChoice,
+                      stderr_isatty: bool)
+                      -> Self {
+        Shell::new(InputStream::new(stdin, stdout_isatty),
+                   OutputStream::new(WriteStream::from_write_colored(stdout,
+                                                                     stdout_coloring,
+                                                                     stdout_isatty),
+                                     stdout_coloring,
+                                     stdout_isatty),
+                   OutputStream::new(WriteStream::from_write_colored(stderr,
+                                                                     stderr_coloring,
+                                                                     stderr_isatty),
+                                     stderr_coloring,
+                                     stderr_isatty))
+    }
+
+    pub fn input(&mut self) -> &mut InputStream { &mut self.input }
+
+    pub fn out(&mut self) -> &mut OutputStream { &mut self.out }
+
+    pub fn err(&mut self) -> &mut OutputStream { &mut self.err }
+}
+
+impl Default for Shell {
+    fn default() -> Self { Shell::default_with(ColorChoice::Auto, None) }
+}
+
+/// The input prompts read their answers from. It is buffered once, for all of them, so that
+/// input read ahead while answering one prompt is kept for the next.
+pub struct InputStream {
+    inner:  BufReader<Box<dyn Read + Send>>,
+    isatty: bool,
+}
+
+impl InputStream {
+    pub fn new(inner: Box<dyn Read + Send>, isatty: bool) -> Self {
+        InputStream { inner: BufReader::new(inner),
+                      isatty }
+    }
+
+    pub fn from_stdin(isatty: Option<bool>) -> Self {
+        Self::new(Box::new(io::stdin()), match isatty {
+            Some(val) => val,
+            None => tty::isatty(StdStream::Stdin),
+        })
+    }
+
+    pub fn is_a_terminal(&self) -> bool { self.isatty }
+}
+
+impl Read for InputStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.inner.read(buf) }
+}
+
+impl BufRead for InputStream {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> { self.inner.fill_buf() }
+
+    fn consume(&mut self, amt: usize) { self.inner.consume(amt) }
+}
+
+impl fmt::Debug for InputStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "InputStream {{ isatty: {} }}", self.isatty)
+    }
+}
+
+/// An output stream which only changes color when text is written in a color other than the
+/// last, so that runs of text in one color cost a single escape sequence rather than a reset and
+/// a color for every fragment.
+pub struct OutputStream {
+    inner:    WriteStream,
+    coloring: ColorChoice,
+    isatty:   bool,
+    /// The color text should next be written in, with `None` being no color at all.
+    wanted:   Option<ColorSpec>,
+    /// The color the underlying stream was last set to.
+    applied:  Option<ColorSpec>,
+    /// Whether a broken pipe, as when `hab ... | head` exits early, closes the stream rather
+    /// than failing the write.
+    closable: bool,
+    /// Set once the stream has been closed by a broken pipe, after which writes are dropped.
+    closed:   bool,
+}
+
+impl OutputStream {
+    pub fn new(inner: WriteStream, coloring: ColorChoice, isatty: bool) -> Self {
+        OutputStream { inner,
+                       coloring,
+                       isatty,
+                       wanted: None,
+                       applied: None,
+                       closable: false,
+                       closed: false }
+    }
+
+    /// Treats a broken pipe as the reader having gone away: the stream is closed, and it and
+    /// every later write quietly succeed without writing anything.
+    pub fn ending_on_broken_pipe(mut self) -> Self {
+        self.closable = true;
+        self
+    }
+
+    /// Returns true once a broken pipe has closed the stream.
+    pub fn is_closed(&self) -> bool { self.closed }
+
+    pub fn from_stdout(coloring: ColorChoice, isatty: Option<bool>) -> Self {
+        Self::new(WriteStream::from_stdout(coloring), coloring, match isatty {
+            Some(val) => val,
+            None => tty::isatty(StdStream::Stdout),
+        })
+    }
+
+    pub fn from_stderr(coloring: ColorChoice, isatty: Option<bool>) -> Self {
+        Self::new(WriteStream::from_stderr(coloring), coloring, match isatty {
+            Some(val) => val,
+            None => tty::isatty(StdStream::Stderr),
+        })
+    }
+
+    pub fn is_a_terminal(&self) -> bool { self.isatty }
+
+    /// Returns the range of colors this stream can display, so callers can choose between
+    /// 16-color, 256-color, and 24-bit color output.
+    pub fn color_depth(&self) -> ColorDepth {
+        ColorDepth::detect(self.supports_color(),
+                           env::var("COLORTERM").ok().as_ref().map(String::as_str),
+                           env::var("TERM").ok().as_ref().map(String::as_str))
+    }
+}
+
+impl WriteColor for OutputStream {
+    fn supports_color(&self) -> bool {
+        match self.inner {
+            WriteStream::Stream(ref stream) => stream.supports_color(),
+            WriteStream::Ansi(ref stream) => stream.supports_color(),
+            _ => false,
+        }
+    }
+
+    /// The reset is only made once more text is written, or the stream flushed.
+    fn reset(&mut self) -> io::Result<()> {
+        self.wanted = None;
+        Ok(())
+    }
+
+    /// The color is only set when it differs from the color last set.
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.wanted = Some(spec.clone());
+        self.apply_color()
+    }
+}
+
+impl OutputStream {
+    /// Brings the underlying stream's color up to date with the color asked for.
+    fn apply_color(&mut self) -> io::Result<()> {
+        if self.wanted == self.applied {
+            return Ok(());
+        }
+        match (&mut self.inner, &self.wanted) {
+            (WriteStream::Stream(stream), Some(spec)) => stream.set_color(spec)?,
+            (WriteStream::Stream(stream), None) => stream.reset()?,
+            (WriteStream::Ansi(stream), Some(spec)) => stream.set_color(spec)?,
+            (WriteStream::Ansi(stream), None) => stream.reset()?,
+            (WriteStream::Write(_), _) => (),
+        }
+        self.applied = self.wanted.clone();
+        Ok(())
+    }
+
+    fn write_through(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !buf.is_empty() {
+            self.apply_color()?;
+        }
+        match self.inner {
+            WriteStream::Stream(ref mut stream) => stream.write(buf),
+            WriteStream::Ansi(ref mut stream) => stream.write(buf),
+            WriteStream::Write(ref mut w) => w.write(buf),
+        }
+    }
+
+    fn flush_through(&mut self) -> io::Result<()> {
+        self.apply_color()?;
+        match self.inner {
+            WriteStream::Stream(ref mut stream) => stream.flush(),
+            WriteStream::Ansi(ref mut stream) => stream.flush(),
+            WriteStream::Write(ref mut w) => w.flush(),
+        }
+    }
+
+    /// Closes the stream, and answers `closed` in place of the error, when `result` is a broken
+    /// pipe and the stream is closable.
+    fn close_on_broken_pipe<T>(&mut self, result: io::Result<T>, closed: T) -> io::Result<T> {
+        match result {
+            Err(ref e) if self.closable && e.kind() == io::ErrorKind::BrokenPipe => {
+                self.closed = true;
+                Ok(closed)
+            }
+            result => result,
+        }
+    }
+}
+
+impl Write for OutputStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.closed {
+            return Ok(buf.len());
+        }
+        let result = self.write_through(buf);
+        self.close_on_broken_pipe(result, buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        let result = self.flush_through();
+        self.close_on_broken_pipe(result, ())
+    }
+}
+
+impl Drop for OutputStream {
+    /// Makes any reset still waiting to be made, so that a terminal isn't left colored.
+    fn drop(&mut self) { let _ = self.flush(); }
+}
+
+impl fmt::Debug for OutputStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f,
+               "OutputStream {{ coloring: {:?}, isatty: {} }}",
+               self.coloring, self.isatty,)
+    }
+}
+
+pub enum WriteStream {
+    /// A plain write object without color support
+    Write(Box<dyn Write + Send>),
+    /// Color-enabled stdio, with information on whether color should be used
+    Stream(StandardStream),
+    /// A plain write object which is sent ANSI color escapes
+    Ansi(Ansi<Box<dyn Write + Send>>),
+}
+
+impl WriteStream {
+    // Implementation heavily inspired and based on the Cargo `shell.rs` implementation. Source:
+    // https://github.com/rust-lang/cargo/blob/5c6aa46e6f28661270979696e7b4c2f0dff8628f/
+    // src/cargo/core/shell.rs
+
+    pub fn from_stdout(coloring: ColorChoice) -> Self {
+        WriteStream::Stream(StandardStream::stdout(coloring))
+    }
+
+    pub fn from_stderr(coloring: ColorChoice) -> Self {
+        WriteStream::Stream(StandardStream::stderr(coloring))
+    }
+
+    /// Create a shell from a plain writable object, with no color, and max verbosity.
+    pub fn from_write<T: FnMut() -> Box<dyn Write + Send>>(mut writable_fn: T) -> Self {
+        WriteStream::Write(writable_fn())
+    }
+
+    /// Create a shell from a plain writable object, emitting ANSI colors when `coloring` asks for
+    /// them. As an arbitrary writer can't be inspected, `ColorChoice::Auto` colors only when the
+    /// writer is flagged as a terminal.
+    pub fn from_write_colored(writable: Box<dyn Write + Send>,
+                              coloring: ColorChoice,
+                              isatty: bool)
+                              -> Self {
+        match coloring {
+            ColorChoice::Always | ColorChoice::AlwaysAnsi => WriteStream::Ansi(Ansi::new(writable)),
+            ColorChoice::Auto if isatty => WriteStream::Ansi(Ansi::new(writable)),
+            _ => WriteStream::Write(writable),
+        }
+    }
+}
+
+mod tty {
+    use std::io;
+
+    #[derive(Clone, Copy)]
+    pub enum StdStream {
+        Stdin,
+        Stdout,
+        Stderr,
+    }
+
+    #[cfg(unix)]
+    pub fn isatty(output: StdStream) -> bool {
+        use libc;
+
+        let fd = match output {
+            StdStream::Stdin => libc::STDIN_FILENO,
+            StdStream::Stdout => libc::STDOUT_FILENO,
+            StdStream::Stderr => libc::STDERR_FILENO,
+        };
+
+        unsafe { libc::isatty(fd) != 0 }
+    }
+
+    #[cfg(unix)]
+    pub fn width(output: StdStream) -> Option<usize> {
+        use libc;
+
+        let fd = match output {
+            StdStream::Stdin => libc::STDIN_FILENO,
+            StdStream::Stdout => libc::STDOUT_FILENO,
+            StdStream::Stderr => libc::STDERR_FILENO,
+        };
+
+        unsafe {
+            let mut size: libc::winsize = std::mem::zeroed();
+            if libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_col > 0 {
+                Some(size.ws_col as usize)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// A terminal in raw mode, reading a key at a time without echoing it, until the guard is
+    /// dropped and the terminal's previous mode is restored.
+    #[cfg(unix)]
+    pub struct RawMode {
+        fd:       libc::c_int,
+        original: libc::termios,
+    }
+
+    #[cfg(unix)]
+    impl RawMode {
+        pub fn stdin() -> io::Result<Self> { Self::enable(libc::STDIN_FILENO) }
+
+        pub fn enable(fd: libc::c_int) -> io::Result<Self> {
+            unsafe {
+                let mut original: libc::termios = std::mem::zeroed();
+                if libc::tcgetattr(fd, &mut original) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let mut raw = original;
+                raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+                raw.c_cc[libc::VMIN] = 1;
+                raw.c_cc[libc::VTIME] = 0;
+                if libc::tcsetattr(fd, libc::TCSANOW, &raw) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(RawMode { fd, original })
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe {
+                libc::tcsetattr(self.fd, libc::TCSANOW, &self.original);
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    pub fn width(_output: StdStream) -> Option<usize> { None }
+
+    /// A console in raw mode, reading a key at a time without echoing it, until the guard is
+    /// dropped and the console's previous mode is restored.
+    #[cfg(windows)]
+    pub struct RawMode {
+        handle:   winapi::um::winnt::HANDLE,
+        original: winapi::shared::minwindef::DWORD,
+    }
+
+    #[cfg(windows)]
+    impl RawMode {
+        pub fn stdin() -> io::Result<Self> {
+            use winapi::um::{consoleapi,
+                             processenv,
+                             winbase,
+                             wincon};
+
+            unsafe {
+                let handle = processenv::GetStdHandle(winbase::STD_INPUT_HANDLE);
+                let mut original = 0;
+                if consoleapi::GetConsoleMode(handle, &mut original) == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let raw = original & !(wincon::ENABLE_LINE_INPUT | wincon::ENABLE_ECHO_INPUT);
+                if consoleapi::SetConsoleMode(handle, raw) == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(RawMode { handle, original })
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe {
+                winapi::um::consoleapi::SetConsoleMode(self.handle, self.original);
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    pub fn isatty(output: StdStream) -> bool {
+        use winapi::um::{consoleapi,
+                         processenv,
+                         winbase};
+
+        let handle = match output {
+            StdStream::Stdin => winbase::STD_INPUT_HANDLE,
+            StdStream::Stdout => winbase::STD_OUTPUT_HANDLE,
+            StdStream::Stderr => winbase::STD_ERROR_HANDLE,
+        };
+
+        unsafe {
+            let handle = processenv::GetStdHandle(handle);
+            let mut out = 0;
+            consoleapi::GetConsoleMode(handle, &mut out) != 0
+        }
+    }
+}
+
+/// An iterator adapter which tracks how many of a known number of items have been yielded,
+/// redrawing a `label NN%` line as it goes and finishing the line once the iterator is exhausted.
+pub struct ProgressIter<I> {
+    inner:    I,
+    label:    String,
+    total:    u64,
+    current:  u64,
+    render:   bool,
+    finished: bool,
+}
+
+impl<I> ProgressIter<I> where I: ExactSizeIterator
+{
+    pub fn new(label: &str, inner: I, render: bool) -> Self {
+        let total = inner.len() as u64;
+        ProgressIter { inner,
+                       label: label.to_string(),
+                       total,
+                       current: 0,
+                       render,
+                       finished: false }
+    }
+
+    /// Percentage of the items that have been yielded so far.
+    pub fn percent(&self) -> u8 {
+        if self.finished {
+            100
+        } else {
+            percent(self.current, self.total)
+        }
+    }
+
+    /// Redraws the display. An iterator has no way to return the error of a failed write, so
+    /// the display is given up on instead.
+    fn draw(&mut self) {
+        if self.render {
+            let line = format!("{} {:>3}%", self.label, self.percent());
+            let mut stdout = io::stdout();
+            let drawn = rewrite_line(&mut stdout, &line, terminal_width());
+            if let Err(e) = drawn.and_then(|_| stdout.flush()) {
+                debug!("Unable to draw progress, no longer drawing it: {}", e);
+                self.render = false;
+            }
+        }
+    }
+}
+
+impl<I> Iterator for ProgressIter<I> where I: ExactSizeIterator
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner.next() {
+            Some(item) => {
+                self.current += 1;
+                self.draw();
+                Some(item)
+            }
+            None => {
+                if !self.finished {
+                    self.finished = true;
+                    self.draw();
+                    if self.render {
+                        if let Err(e) = writeln!(io::stdout()) {
+                            debug!("Unable to finish drawing progress: {}", e);
+                        }
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+}
+
+impl<I> ExactSizeIterator for ProgressIter<I> where I: ExactSizeIterator {}
+
+/// Counts through a known number of named steps, writing `[N/M] label` for each. Steps past the
+/// last are shown as the last, so an underestimated total never reads as `[6/5]`.
+pub struct StepProgress {
+    total:    usize,
+    current:  usize,
+    rewrite:  bool,
+    last_len: usize,
+}
+
+impl StepProgress {
+    pub fn new(total: usize, rewrite: bool) -> Self {
+        StepProgress { total,
+                       current: 0,
+                       rewrite,
+                       last_len: 0 }
+    }
+
+    /// Moves on to the next step and writes it to `out`, which should be the UI's output stream
+    /// (`UIWriter::out`), so that the step goes wherever the rest of the output does.
+    pub fn step<W, T>(&mut self, out: &mut W, label: T) -> io::Result<()>
+        where W: Write + ?Sized,
+              T: fmt::Display
+    {
+        self.current = (self.current + 1).min(self.total);
+        let line = format!("[{}/{}] {}", self.current, self.total, label);
+        if self.rewrite {
+            match terminal_width() {
+                Some(width) => rewrite_line(out, &line, Some(width))?,
+                None => {
+                    // Pad over whatever is left of a longer previous step.
+                    let pad = self.last_len.saturating_sub(line.width());
+                    write!(out, "\r{}{}", line, " ".repeat(pad))?;
+                }
+            }
+            self.last_len = line.width();
+            if self.is_complete() {
+                writeln!(out)?;
+            }
+            out.flush()
+        } else {
+            writeln!(out, "{}", line)
+        }
+    }
+
+    /// The number of the step most recently written, or 0 before the first.
+    pub fn current(&self) -> usize { self.current }
+
+    pub fn total(&self) -> usize { self.total }
+
+    /// True once the last step has been written.
+    pub fn is_complete(&self) -> bool { self.current == self.total }
+}
+
+/// A reader which advances a progress display by every byte read through it and finishes the
+/// display at end of input, so it can be handed straight to `io::copy`. Without a display (e.g.
+/// when `UIWriter::progress` returned `None`) it simply reads from `inner`.
+pub struct ProgressReader<R, P> {
+    inner:    R,
+    progress: Option<P>,
+    finished: bool,
+}
+
+impl<R, P> ProgressReader<R, P>
+    where R: Read,
+          P: DisplayProgress
+{
+    pub fn new(inner: R, progress: Option<P>) -> Self {
+        ProgressReader { inner,
+                         progress,
+                         finished: false }
+    }
+
+    pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R, P> Read for ProgressReader<R, P>
+    where R: Read,
+          P: DisplayProgress
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(ref mut progress) = self.progress {
+            if n > 0 {
+                progress.write_all(&buf[..n])?;
+            } else if !buf.is_empty() && !self.finished {
+                self.finished = true;
+                progress.finish();
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Returns how far `current` is towards `total` as a whole percentage, clamped to 100. A `total`
+/// of zero is considered complete.
+pub fn percent(current: u64, total: u64) -> u8 {
+    if total == 0 {
+        return 100;
+    }
+    (u128::from(current.min(total)) * 100 / u128::from(total)) as u8
+}
+
+/// Which multiples `format_bytes` counts in: powers of 1000 (`kB`, `MB`, ...) or powers of 1024
+/// (`KiB`, `MiB`, ...).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ByteUnits {
+    Si,
+    Iec,
+}
+
+/// Formats a number of bytes for people, e.g. `42.3 MiB`. Amounts below the first multiple are
+/// written exactly, e.g. `999 B`.
+pub fn format_bytes(n: u64, units: ByteUnits) -> String {
+    let (base, names) = match units {
+        ByteUnits::Si => (1000.0, ["B", "kB", "MB", "GB", "TB", "PB", "EB"]),
+        ByteUnits::Iec => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"]),
+    };
+    if (n as f64) < base {
+        return format!("{} B", n);
+    }
+    let mut value = n as f64;
+    let mut unit = 0;
+    // Compare against the rounded value, so that e.g. 1023.96 KiB is written as 1.0 MiB rather
+    // than 1024.0 KiB.
+    while (value * 10.0).round() / 10.0 >= base && unit < names.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, names[unit])
+}
+
+/// Formats a transfer rate for people, e.g. `1.5 MiB/s`.
+pub fn format_rate(bytes_per_sec: u64, units: ByteUnits) -> String {
+    format!("{}/s", format_bytes(bytes_per_sec, units))
+}
+
+/// A writer which removes ANSI escape sequences from the bytes passing through it, so that a
+/// copy of terminal output (such as a log file) is plain text. Both CSI sequences (such as
+/// colors) and OSC sequences (such as window titles) are removed.
+///
+/// Parsing state is kept between calls to `write`, so a sequence which begins in one write and
+/// ends in another is still removed entirely.
+pub struct StripAnsiWriter<W> {
+    inner: W,
+    state: AnsiState,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AnsiState {
+    /// Plain text, which is passed through
+    Ground,
+    /// After an `ESC`
+    Escape,
+    /// After the intermediate bytes (`0x20..=0x2f`) of an escape sequence such as `ESC ( B`,
+    /// which ends with a byte in `0x30..=0x7e`
+    EscapeIntermediate,
+    /// Inside a `ESC [` control sequence, which ends with a byte in `0x40..=0x7e`
+    Csi,
+    /// Inside a `ESC ]` operating system command, which ends with `BEL` or `ESC \`
+    Osc,
+    /// After an `ESC` inside an operating system command
+    OscEscape,
+}
+
+impl<W> StripAnsiWriter<W> where W: Write
+{
+    pub fn new(inner: W) -> Self {
+        StripAnsiWriter { inner,
+                          state: AnsiState::Ground }
+    }
+
+    pub fn get_ref(&self) -> &W { &self.inner }
+
+    pub fn into_inner(self) -> W { self.inner }
+}
+
+impl<W> Write for StripAnsiWriter<W> where W: Write
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut plain = Vec::with_capacity(buf.len());
+        for &byte in buf {
+            self.state = match (self.state, byte) {
+                (AnsiState::Ground, 0x1b) => AnsiState::Escape,
+                (AnsiState::Ground, _) => {
+                    plain.push(byte);
+                    AnsiState::Ground
+                }
+                (AnsiState::Escape, b'[') => AnsiState::Csi,
+                (AnsiState::Escape, b']') => AnsiState::Osc,
+                (AnsiState::Escape, 0x1b) => AnsiState::Escape,
+                (AnsiState::Escape, 0x20..=0x2f) => AnsiState::EscapeIntermediate,
+                (AnsiState::Escape, _) => AnsiState::Ground,
+                (AnsiState::EscapeIntermediate, 0x20..=0x2f) => AnsiState::EscapeIntermediate,
+                (AnsiState::EscapeIntermediate, _) => AnsiState::Ground,
+                (AnsiState::Csi, 0x40..=0x7e) => AnsiState::Ground,
+                (AnsiState::Csi, _) => AnsiState::Csi,
+                (AnsiState::Osc, 0x07) => AnsiState::Ground,
+                (AnsiState::Osc, 0x1b) => AnsiState::OscEscape,
+                (AnsiState::Osc, _) => AnsiState::Osc,
+                (AnsiState::OscEscape, b'\\') => AnsiState::Ground,
+                (AnsiState::OscEscape, _) => AnsiState::Osc,
+            };
+        }
+        self.inner.write_all(&plain)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
+}
+
+/// A moving progress bar to track progress of a sized event, similar to wget, curl, npm, etc.
+///
+/// This is designed to satisfy a generic behavior which sets the size of the task (usually a
+/// number of bytes representing the total download/upload/transfer size) and will be a generic
+/// writer (i.e. implementing the `Write` trait) as a means to increase progress towards
+/// completion.
+///
+/// Every update is also written to the `HAB_PROGRESS_PIPE` pipe, when one is set.
+pub struct ConsoleProgressBar {
+    bar:     pbr::ProgressBar<Stdout>,
+    total:   u64,
+    current: u64,
+    pipe:    Option<ProgressPipe>,
+    label:   Option<String>,
+    style:   BarStyle,
+}
+
+impl ConsoleProgressBar {
+    /// Creates a bar for an operation of `total` bytes, ready for writes without calling `size`.
+    pub fn new_sized(total: u64) -> Self {
+        let mut pipe = ProgressPipe::from_env();
+        if let Some(ref mut pipe) = pipe {
+            pipe.size(total);
+        }
+        let style = bar_style_from_env();
+        ConsoleProgressBar { bar: bytes_bar(total, None, style),
+                             total,
+                             current: 0,
+                             pipe,
+                             label: None,
+                             style }
+    }
+
+    /// Draws the bar in `style`, rather than the one `HAB_PROGRESS_STYLE` names.
+    pub fn with_style(mut self, style: BarStyle) -> Self {
+        self.bar.format(style.format());
+        self.style = style;
+        self
+    }
+
+    pub fn style(&self) -> BarStyle { self.style }
+
+    /// Shows `label`, such as the package identifier being downloaded, ahead of the bar.
+    pub fn set_label(&mut self, label: &str) {
+        self.bar.message(&bar_message(Some(label)));
+        self.label = Some(label.to_string());
+    }
+
+    pub fn label(&self) -> Option<&str> { self.label.as_ref().map(String::as_str) }
+}
+
+impl Default for ConsoleProgressBar {
+    fn default() -> Self {
+        let style = bar_style_from_env();
+        ConsoleProgressBar { bar: bytes_bar(0, None, style),
+                             total: 0,
+                             current: 0,
+                             pipe: ProgressPipe::from_env(),
+                             label: None,
+                             style }
+    }
+}
+
+impl DisplayProgress for ConsoleProgressBar {
+    fn size(&mut self, size: u64) {
+        self.bar = bytes_bar(size, self.label.as_ref().map(String::as_str), self.style);
+        self.total = size;
+        if let Some(ref mut pipe) = self.pipe {
+            pipe.size(size);
+        }
+    }
+
+    fn finish(&mut self) {
+        println!();
+        io::stdout().flush().expect("flush() fail");
+        if let Some(ref mut pipe) = self.pipe {
+            pipe.finish();
+        }
+    }
+}
+
+impl Write for ConsoleProgressBar {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.bar.write(buf) {
+            Ok(n) => {
+                self.current += n as u64;
+                if let Some(ref mut pipe) = self.pipe {
+                    pipe.advance(n as u64);
+                }
+                if self.current == self.total {
+                    self.finish();
+                }
+                Ok(n)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> { self.bar.flush() }
+}
+
+/// The characters a `ConsoleSpinner` turns through, one per write.
+pub const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+
+/// A turning spinner and a running byte count to track progress of an event whose size isn't
+/// known, the counterpart of `ConsoleProgressBar`. Like the bar, it also writes every update to
+/// the `HAB_PROGRESS_PIPE` pipe, when one is set.
+pub struct ConsoleSpinner {
+    current: u64,
+    frame:   usize,
+    pipe:    Option<ProgressPipe>,
+    out:     Box<dyn WriteColor + Send>,
+}
+
+impl ConsoleSpinner {
+    /// A spinner which is drawn on `out`.
+    pub fn new(out: Box<dyn WriteColor + Send>) -> Self {
+        ConsoleSpinner { current: 0,
+                         frame: 0,
+                         pipe: ProgressPipe::from_env(),
+                         out }
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn current(&self) -> u64 { self.current }
+}
+
+impl DisplayProgress for ConsoleSpinner {
+    /// A spinner has no total to set, so a size learned after the transfer started is ignored.
+    fn size(&mut self, _size: u64) {}
+
+    /// Ends the spinner's line. `finish` has no way to return the error of a failed write, so it
+    /// is only logged.
+    fn finish(&mut self) {
+        if let Err(e) = writeln!(self.out).and_then(|_| self.out.flush()) {
+            debug!("Unable to finish the spinner: {}", e);
+        }
+        if let Some(ref mut pipe) = self.pipe {
+            pipe.finish();
+        }
+    }
+}
+
+impl Write for ConsoleSpinner {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.current += buf.len() as u64;
+        if let Some(ref mut pipe) = self.pipe {
+            pipe.advance(buf.len() as u64);
+        }
+        self.frame = (self.frame + 1) % SPINNER_FRAMES.len();
+        let line = format!("{} {}",
+                           SPINNER_FRAMES[self.frame],
+                           format_bytes(self.current, ByteUnits::Iec));
+        rewrite_line(&mut self.out, &line, terminal_width())?;
+        self.out.flush()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> { self.out.flush() }
+}
+
+fn bytes_bar(size: u64, label: Option<&str>, style: BarStyle) -> pbr::ProgressBar<Stdout> {
+    let mut bar = pbr::ProgressBar::new(size);
+    bar.format(style.format());
+    bar.set_units(pbr::Units::Bytes);
+    bar.show_tick = true;
+    bar.message(&bar_message(label));
+    bar
+}
+
+/// The message a bytes bar starts with: its label, if it has one, set off from the bar.
+fn bar_message(label: Option<&str>) -> String {
+    match label {
+        Some(label) => format!("{}  ", label),
+        None => "    ".to_string(),
+    }
+}
+
+#[cfg(unix)]
+fn make_fifo(path: &Path) -> io::Result<()> {
+    use std::{ffi::CString,
+              os::unix::ffi::OsStrExt};
+
+    let c_path =
+        CString::new(path.as_os_str().as_bytes()).map_err(|e| {
+                                                     io::Error::new(io::ErrorKind::InvalidInput, e)
+                                                 })?;
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+lazy_static! {
+    /// The FIFOs progress pipes have created, which `remove_created_fifos` removes again.
+    static ref CREATED_FIFOS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+}
+
+/// Removes the FIFOs progress pipes have created, so that none is left behind once the process
+/// exits. `UI::finalize` calls this.
+pub fn remove_created_fifos() {
+    let created = mem::replace(&mut *CREATED_FIFOS.lock().expect("FIFO list lock is poisoned"),
+                               Vec::new());
+    for path in created {
+        if let Err(e) = fs::remove_file(&path) {
+            debug!("Unable to remove progress pipe {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// A progress update, as written to a progress pipe, one per line.
+#[derive(Serialize)]
+struct PipeEvent<'a> {
+    event:   &'a str,
+    current: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total:   Option<u64>,
+}
+
+/// A pipe progress updates are written to as NDJSON, so that another process can follow a
+/// transfer without scraping the terminal. Writing is best effort: an update the pipe has no
+/// room for is dropped rather than waited on, and the pipe is given up on once its reader goes.
+pub struct ProgressPipe {
+    file:    Option<File>,
+    current: u64,
+    total:   Option<u64>,
+}
+
+impl ProgressPipe {
+    /// Opens the pipe named by `HAB_PROGRESS_PIPE`, if it is set and can be opened.
+    pub fn from_env() -> Option<Self> {
+        let path = env::var(PROGRESS_PIPE_ENVVAR).ok()?;
+        match ProgressPipe::open(Path::new(&path)) {
+            Ok(pipe) => Some(pipe),
+            Err(e) => {
+                debug!("Not writing progress to {}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Opens the FIFO at `path` for writing without blocking, first creating it if there is
+    /// nothing there. Fails when the FIFO has no reader, rather than waiting for one. A FIFO
+    /// created here is kept, for a reader to open, until `remove_created_fifos`.
+    #[cfg(unix)]
+    pub fn open(path: &Path) -> io::Result<Self> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        if !path.exists() {
+            make_fifo(path)?;
+            CREATED_FIFOS.lock()
+                         .expect("FIFO list lock is poisoned")
+                         .push(path.to_path_buf());
+        }
+        let file = fs::OpenOptions::new().write(true)
+                                         .custom_flags(libc::O_NONBLOCK)
+                                         .open(path)?;
+        Ok(ProgressPipe::with_file(file))
+    }
+
+    /// Opens the named pipe at `path` (e.g. `\\.\pipe\hab-progress`) for writing. The pipe
+    /// must already have been created by its reader.
+    #[cfg(windows)]
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = fs::OpenOptions::new().write(true).open(path)?;
+        Ok(ProgressPipe::with_file(file))
+    }
+
+    fn with_file(file: File) -> Self {
+        ProgressPipe { file:    Some(file),
+                       current: 0,
+                       total:   None, }
+    }
+
+    pub fn size(&mut self, total: u64) {
+        self.total = Some(total);
+        self.send("size");
+    }
+
+    pub fn advance(&mut self, bytes: u64) {
+        self.current += bytes;
+        self.send("progress");
+    }
+
+    pub fn finish(&mut self) { self.send("finish"); }
+
+    fn send(&mut self, event: &str) {
+        let line = match serde_json::to_string(&PipeEvent { event,
+                                                            current: self.current,
+                                                            total: self.total })
+        {
+            Ok(json) => format!("{}\n", json),
+            Err(_) => return,
+        };
+        let failed = match self.file {
+            Some(ref mut file) => {
+                // A line this short is written to a pipe whole or not at all, so a reader which
+                // has fallen behind misses the event rather than getting part of it.
+                match file.write_all(line.as_bytes()) {
+                    Ok(()) => false,
+                    Err(ref e) => e.kind() != io::ErrorKind::WouldBlock,
+                }
+            }
+            None => false,
+        };
+        if failed {
+            debug!("Progress pipe closed, no longer writing progress to it");
+            self.file = None;
+        }
+    }
+}
+
+fn write_warn<U, T>(ui: &mut U, message: T) -> io::Result<()>
+    where U: UIWriter + ?Sized,
+          T: fmt::Display
+{
+    write_warn_in(ui, message, Color::Warn)
+}
+
+/// Writes a warning in `color`, for one which needs more of the reader's attention than most.
+fn write_warn_in<U, T>(ui: &mut U, message: T, color: Color) -> io::Result<()>
+    where U: UIWriter + ?Sized,
+          T: fmt::Display
+{
+    let prefix = if ui.accessible() {
+        format!("{} {}",
+                severity_tag(Color::Warn),
+                Glyph::SlashedZero.to_style_str(GlyphStyle::Ascii)
+                                  .trim_start())
+    } else {
+        Glyph::SlashedZero.to_str().to_string()
+    };
+    println(ui.err(),
+            format!("{} {}", prefix, message).as_bytes(),
+            ColorSpec::new().set_fg(Some(color.into())).set_bold(true))
+}
+
+fn write_fatal<U, T>(ui: &mut U, message: T) -> io::Result<()>
+    where U: UIWriter + ?Sized,
+          T: fmt::Display
+{
+    let style = if ui.accessible() {
+        GlyphStyle::Ascii
+    } else {
+        glyph_style_from_env()
+    };
+    let symbol = Glyph::ErrorX.to_style_str(style);
+    let (banner, symbol) = match ui.error_banner_style() {
+        ErrorBannerStyle::Classic => (symbol.to_string(), symbol),
+        ErrorBannerStyle::Rule => {
+            let width = capped_width(terminal_width().unwrap_or(PARA_WIDTH), ui.max_width());
+            let single = symbol.chars().next().map_or(0, char::len_utf8);
+            (labelled_rule("", width, style), &symbol[..single])
+        }
+    };
+    let prefix = if ui.accessible() {
+        format!("{} {}", severity_tag(Color::Critical), symbol)
+    } else {
+        symbol.to_string()
+    };
+    let framed = !ui.compact_errors();
+    if framed {
+        println(ui.err(),
+                banner.as_bytes(),
+                ColorSpec::new().set_fg(Some(Color::Critical.into()))
+                                .set_bold(true))?;
+    }
+    for line in message.to_string().lines() {
+        println(ui.err(),
+                format!("{} {}", prefix, line).as_bytes(),
+                ColorSpec::new().set_fg(Some(Color::Critical.into()))
+                                .set_bold(true))?;
+    }
+    if framed {
+        println(ui.err(),
+                banner.as_bytes(),
+                ColorSpec::new().set_fg(Some(Color::Critical.into()))
+                                .set_bold(true))?;
+    }
+    Ok(())
+}
+
+/// The stream a `status` message is written to.
+#[derive(Clone, Copy)]
+enum StatusStream {
+    Out,
+    Err,
+}
+
+fn write_status<U, T>(ui: &mut U,
+                      stream: StatusStream,
+                      status: Status,
+                      severity: Color,
+                      message: T)
+                      -> io::Result<()>
+    where U: UIWriter + ?Sized,
+          T: fmt::Display
+{
+    if ui.output_mode() == OutputMode::Json || ui.event_stream().is_some() {
+        let (_, status_str, _) = status.parts();
+        return write_json_event(ui, "status", Some(&status_str), &message.to_string());
+    }
+    let color = severity;
+    let prefix = status_prefix(ui, &status, color);
+    let stream = match stream {
+        StatusStream::Out => ui.out(),
+        StatusStream::Err => ui.err(),
+    };
+    print(stream,
+          prefix.as_bytes(),
+          ColorSpec::new().set_fg(Some(color.into())).set_bold(true))?;
+    stream.write_all(format!(" {}\n", message).as_bytes())?;
+    stream.flush()
+}
+
+/// What a status line starts with, before its message: the nesting indent, the symbol, and the
+/// status text, with the severity tag also in accessible mode.
+fn status_prefix<U>(ui: &U, status: &Status, severity: Color) -> String
+    where U: UIWriter + ?Sized
+{
+    let (symbol, status_str, _) = status.parts();
+    let status_str = match ui.status_column_width() {
+        Some(width) => status_column(&status_str, width),
+        None => status_str,
+    };
+    let prefix = if ui.accessible() {
+        format!("{} {} {}",
+                severity_tag(severity),
+                symbol.to_padded_style_str(GlyphStyle::Ascii),
+                status_str)
+    } else {
+        format!("{} {}", symbol.to_padded_str(), status_str)
+    };
+    format!("{}{}", nesting_indent(ui.nesting_depth()), prefix)
+}
+
+/// A `begin`, `end`, or `status` message as written, one per line, in JSON output mode.
+#[derive(Serialize)]
+struct JsonEvent<'a> {
+    seq:     u64,
+    event:   &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status:  Option<&'a str>,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    phase:   Option<&'a str>,
+}
+
+/// A group of related warnings, as written in JSON output mode.
+#[derive(Serialize)]
+struct JsonWarnGroup<'a> {
+    seq:   u64,
+    level: &'a str,
+    title: &'a str,
+    items: &'a [String],
+}
+
+fn write_json_event<U>(ui: &mut U,
+                       event: &str,
+                       status: Option<&str>,
+                       message: &str)
+                       -> io::Result<()>
+    where U: UIWriter + ?Sized
+{
+    let seq = ui.next_event_seq();
+    let phase = ui.phase().map(str::to_string);
+    let json = serde_json::to_string(&JsonEvent { seq,
+                                                  event,
+                                                  status,
+                                                  message,
+                                                  phase: phase.as_ref().map(String::as_str) })
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    write_json_line(ui, &json)
+}
+
+/// Writes one line of JSON output to the event stream when there is one, and otherwise to
+/// standard output.
+fn write_json_line<U>(ui: &mut U, json: &str) -> io::Result<()>
+    where U: UIWriter + ?Sized
+{
+    match ui.event_stream() {
+        Some(stream) => {
+            stream.write_all(format!("{}\n", json).as_bytes())?;
+            stream.flush()
+        }
+        None => {
+            ui.out().write_all(format!("{}\n", json).as_bytes())?;
+            ui.out().flush()
+        }
+    }
+}
+
+/// The indentation for text messages written `depth` levels of nesting deep.
+fn nesting_indent(depth: usize) -> String { " ".repeat(depth * NESTING_INDENT) }
+
+/// Textual severity marker used in accessible mode so meaning survives without color.
+fn severity_tag(color: Color) -> &'static str {
+    match color {
+        Color::Critical => "[ERR]",
+        Color::Warn => "[WARN]",
+        _ => "[OK]",
+    }
+}
+
+/// Right-aligns a status word in a column `width` characters wide, truncating it if too long.
+fn status_column(status: &str, width: usize) -> String {
+    let truncated: String = status.chars().take(width).collect();
+    format!("{:>width$}", truncated, width = width)
+}
+
+/// Cuts `text` down to at most `max_cols` columns of display width, ending it with an ellipsis
+/// in the `HAB_GLYPH_STYLE` style when anything was cut. Text which already fits is returned as it
+/// is.
+pub fn truncate_display(text: &str, max_cols: usize) -> String {
+    truncate_display_style(text, max_cols, glyph_style_from_env())
+}
+
+/// Like `truncate_display`, but with the ellipsis (`…`, or `...` in the `Ascii` style) of the
+/// given style. Text is only ever cut between characters, so a wide character which would
+/// straddle the limit is dropped whole; when even the ellipsis doesn't fit in `max_cols`, the
+/// text is cut without one.
+pub fn truncate_display_style(text: &str, max_cols: usize, style: GlyphStyle) -> String {
+    if text.width() <= max_cols {
+        return text.to_string();
+    }
+    let ellipsis = Glyph::Elipses.to_style_str(style);
+    let (budget, ellipsis) = if ellipsis.width() <= max_cols {
+        (max_cols - ellipsis.width(), ellipsis)
+    } else {
+        (max_cols, "")
+    };
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let width = c.width().unwrap_or(0);
+        if used + width > budget {
+            break;
+        }
+        used += width;
+        truncated.push(c);
+    }
+    truncated.push_str(ellipsis);
+    truncated
+}
+
+/// Redraws the current line as `line`, for rendering in place. `width` should be asked of the
+/// terminal before every redraw, since the terminal may have been resized since the last one:
+/// the line is cut to fit `width`, and padded out to it so that nothing is left of a longer
+/// previous line. Widths are counted in columns, so a wide character which would straddle the
+/// edge is cut whole. Without a width, `line` is written as it is.
+pub fn rewrite_line<W>(out: &mut W, line: &str, width: Option<usize>) -> io::Result<()>
+    where W: Write + ?Sized
+{
+    match width {
+        Some(width) => {
+            let mut cut = String::new();
+            let mut used = 0;
+            for c in line.chars() {
+                let c_width = c.width().unwrap_or(0);
+                if used + c_width > width {
+                    break;
+                }
+                used += c_width;
+                cut.push(c);
+            }
+            write!(out, "\r{}{}", cut, " ".repeat(width - used))
+        }
+        None => write!(out, "\r{}", line),
+    }
+}
+
+/// A line `width` columns wide with ` label ` set into the middle of it, the label truncated so
+/// that at least one column of the line is left on each side of it.
+fn labelled_rule(label: &str, width: usize, style: GlyphStyle) -> String {
+    let fill = match style {
+        GlyphStyle::Ascii => "-",
+        GlyphStyle::Full | GlyphStyle::Limited => "─",
+    };
+    let label = truncate_display_style(label, width.saturating_sub(4), style);
+    if label.is_empty() {
+        return fill.repeat(width);
+    }
+    let rest = width - label.width() - 2;
+    let left = rest / 2;
+    format!("{} {} {}",
+            fill.repeat(left),
+            label,
+            fill.repeat(rest - left))
+}
+
+/// Limits `width` to `max`, when one is set.
+fn capped_width(width: usize, max: Option<usize>) -> usize {
+    match max {
+        Some(max) => width.min(max),
+        None => width,
+    }
+}
+
+/// Returns the width of the terminal in columns.
+///
+/// The `COLUMNS` environment variable takes precedence over asking the terminal attached to
+/// standard output. Returns `None` if neither is available.
+pub fn terminal_width() -> Option<usize> {
+    match env::var(COLUMNS_ENVVAR).ok()
+                                  .and_then(|val| val.trim().parse::<usize>().ok())
+    {
+        Some(width) if width > 0 => Some(width),
+        _ => tty::width(StdStream::Stdout),
+    }
+}
+
+/// Breaks `text` into lines of at most `wrap_width` columns, breaking only between words, so a
+/// word longer than `wrap_width` gets a line of its own. A `wrap_width` of 0 is taken as 1.
+pub fn wrap_text(text: &str, wrap_width: usize) -> Vec<String> {
+    let wrap_width = wrap_width.max(1);
+    let mut lines = Vec::new();
+    let mut buffer = String::new();
+    let mut width = 0;
+    for word in text.split_whitespace() {
+        let wl = word.width();
+        if (width + wl + 1) > wrap_width && !buffer.is_empty() {
+            lines.push(buffer.trim_end().to_string());
+            buffer.clear();
+            width = 0;
+        }
+        width = width + wl + 1;
+        buffer.push_str(word);
+        buffer.push(' ');
+    }
+    if !buffer.is_empty() {
+        lines.push(buffer.trim_end().to_string());
+    }
+    lines
+}
+
+/// Writes each paragraph of `text` wrapped to `wrap_width` columns, indented by `left_indent`.
+/// On a terminal too narrow for the indent, the text is still wrapped at least a character wide
+/// rather than the width underflowing.
+pub fn print_wrapped<U>(stream: &mut dyn WriteColor,
+                        text: U,
+                        wrap_width: usize,
+                        left_indent: usize)
+                        -> io::Result<()>
+    where U: AsRef<str>
+{
+    // As `para` has always written them, each line is indented by at least a column, and ends
+    // with a space.
+    let indent = " ".repeat(left_indent.max(1));
+    print_wrapped_paragraphs(stream,
+                             text.as_ref(),
+                             wrap_width.saturating_sub(left_indent),
+                             &indent,
+                             &indent,
+                             " ")
+}
+
+/// Like `print_wrapped`, but the first line starts with `first_line_prefix`, and every line after
+/// it is indented by the prefix's width as well, so that the text lines up under the first line's.
+pub fn print_wrapped_with_prefix<U>(stream: &mut dyn WriteColor,
+                                    text: U,
+                                    wrap_width: usize,
+                                    left_indent: usize,
+                                    first_line_prefix: &str)
+                                    -> io::Result<()>
+    where U: AsRef<str>
+{
+    let (first, rest) = prefixed_indents(left_indent, first_line_prefix);
+    print_wrapped_paragraphs(stream,
+                             text.as_ref(),
+                             wrap_width.saturating_sub(rest.width()),
+                             &first,
+                             &rest,
+                             "")
+}
+
+/// The indent of the first line of text set off by `first_line_prefix`, and of the lines after
+/// it, which line up under the first line's text.
+fn prefixed_indents(left_indent: usize, first_line_prefix: &str) -> (String, String) {
+    let indent = " ".repeat(left_indent);
+    (format!("{}{}", indent, first_line_prefix),
+     format!("{}{}", indent, " ".repeat(first_line_prefix.width())))
+}
+
+/// Writes each paragraph of `text` as `print_wrapped_lines` does, with a blank line after each.
+fn print_wrapped_paragraphs(stream: &mut dyn WriteColor,
+                            text: &str,
+                            text_width: usize,
+                            first_line_prefix: &str,
+                            prefix: &str,
+                            line_end: &str)
+                            -> io::Result<()> {
+    let mut line_prefix = first_line_prefix;
+    for paragraph in text.split("\n\n") {
+        print_wrapped_lines(stream, paragraph, text_width, line_prefix, prefix, line_end)?;
+        stream.write_all(b"\n")?;
+        line_prefix = prefix;
+    }
+    stream.flush()
+}
+
+/// Writes `text` wrapped to `text_width` columns (at least one), its first line after
+/// `first_line_prefix` and every other after `prefix`, with each line ending in `line_end`.
+fn print_wrapped_lines(stream: &mut dyn WriteColor,
+                       text: &str,
+                       text_width: usize,
+                       first_line_prefix: &str,
+                       prefix: &str,
+                       line_end: &str)
+                       -> io::Result<()> {
+    let mut line_prefix = first_line_prefix;
+    for line in wrap_text(text, text_width.max(1)) {
+        stream.write_all(format!("{}{}{}\n", line_prefix, line, line_end).as_bytes())?;
+        line_prefix = prefix;
+    }
+    Ok(())
+}
+
+/// Writes `buf` in the color of `color_spec`, without flushing, so that the fragments of a line
+/// are written together. `println`, or the caller, flushes once the line is done.
+pub fn print(writer: &mut WriteColor, buf: &[u8], color_spec: &ColorSpec) -> io::Result<()> {
+    writer.reset()?;
+    writer.set_color(color_spec)?;
+    writer.write_all(buf)?;
+    writer.reset()
+}
+
+pub fn println(writer: &mut WriteColor, buf: &[u8], color_spec: &ColorSpec) -> io::Result<()> {
+    print(writer, buf, color_spec)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Cursor,
+              panic,
+              sync::{Arc,
+                     RwLock}};
+    use termcolor::NoColor;
+
+    #[derive(Clone)]
+    pub struct OutputBuffer {
+        pub cursor: Arc<RwLock<Cursor<Vec<u8>>>>,
+    }
+
+    impl OutputBuffer {
+        fn new() -> Self {
+            OutputBuffer { cursor: Arc::new(RwLock::new(Cursor::new(Vec::new()))), }
+        }
+
+        fn contents(&self) -> String {
+            String::from_utf8(self.cursor
+                                  .read()
+                                  .expect("Cursor lock is poisoned")
+                                  .get_ref()
+                                  .clone()).expect("Output is not UTF-8")
+        }
+    }
+
+    impl Write for OutputBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.cursor
+                .write()
+                .expect("Cursor lock is poisoned")
+                .write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.cursor
+                .write()
+                .expect("Cursor lock is poisoned")
+                .flush()
+        }
+    }
+
+    fn ui_with_input(input: &str) -> (UI, OutputBuffer, OutputBuffer) {
+        let stdout_buf = OutputBuffer::new();
+        let stderr_buf = OutputBuffer::new();
+        let (stdout, stderr) = (stdout_buf.clone(), stderr_buf.clone());
+
+        let ui = UI::with_streams(Box::new(Cursor::new(input.as_bytes().to_vec())),
+                                  move || Box::new(stdout.clone()),
+                                  move || Box::new(stderr.clone()),
+                                  ColorChoice::Never,
+                                  false);
+
+        (ui, stdout_buf, stderr_buf)
+    }
+
+    #[test]
+    fn prompt_yes_no_reads_input_by_default() {
+        let (mut ui, ..) = ui_with_input("n\n");
+        assert!(!ui.prompt_yes_no("Continue?", Some(true)).unwrap());
+    }
+
+    #[test]
+    fn prompt_yes_no_cancellable_returns_each_answer() {
+        for &(input, default, expected) in &[("y\n", None, YesNoCancel::Yes),
+                                             ("no\n", None, YesNoCancel::No),
+                                             ("\n", Some(false), YesNoCancel::No),
+                                             ("\n", Some(true), YesNoCancel::Yes)]
+        {
+            let (mut ui, ..) = ui_with_input(input);
+            assert_eq!(ui.prompt_yes_no_cancellable("Continue?", default).unwrap(),
+                       expected,
+                       "answering {:?}",
+                       input);
+        }
+    }
+
+    #[test]
+    fn prompt_yes_no_fails_rather_than_looping_when_input_has_ended() {
+        let mut ui = UI::with_streams(Box::new(io::empty()),
+                                      || Box::new(io::sink()),
+                                      || Box::new(io::sink()),
+                                      ColorChoice::Never,
+                                      false);
+
+        match ui.prompt_yes_no("Continue?", None) {
+            Err(Error::UnexpectedEof) => (),
+            other => panic!("Expected UnexpectedEof, got {:?}", other),
+        }
+        assert!(ui.prompt_yes_no("Continue?", Some(true)).unwrap());
+        assert!(!ui.prompt_yes_no("Continue?", Some(false)).unwrap());
+    }
+
+    #[test]
+    fn prompt_yes_no_cancellable_returns_quit_rather_than_exiting() {
+        let (mut ui, ..) = ui_with_input("q\n");
+        assert_eq!(ui.prompt_yes_no_cancellable("Continue?", Some(true))
+                     .unwrap(),
+                   YesNoCancel::Cancel);
+
+        let (mut ui, ..) = ui_with_input("quit\n");
+        assert_eq!(ui.prompt_yes_no_cancellable("Continue?", None).unwrap(),
+                   YesNoCancel::Cancel);
+    }
+
+    #[test]
+    fn prompt_yes_no_all_remembers_all_for_the_tag() {
+        let (mut ui, stdout, _) = ui_with_input("all\n");
+
+        assert!(ui.prompt_yes_no_all("overwrite", "Overwrite a.toml?", Some(false))
+                  .unwrap());
+        assert!(ui.prompt_yes_no_all("overwrite", "Overwrite b.toml?", Some(false))
+                  .unwrap());
+        assert!(ui.prompt_yes_no_all("overwrite", "Overwrite c.toml?", Some(false))
+                  .unwrap());
+
+        assert_eq!(stdout.contents(),
+                   "Overwrite a.toml? [yes/No/all/none/quit] ");
+    }
+
+    #[test]
+    fn prompt_yes_no_all_remembers_none_only_for_its_tag() {
+        let (mut ui, stdout, _) = ui_with_input("none\n");
+
+        assert!(!ui.prompt_yes_no_all("overwrite", "Overwrite a.toml?", Some(true))
+                   .unwrap());
+        assert!(!ui.prompt_yes_no_all("overwrite", "Overwrite b.toml?", Some(true))
+                   .unwrap());
+        // Another tag is asked, and with the input used up takes its default.
+        assert!(ui.prompt_yes_no_all("delete", "Delete a.toml?", Some(true))
+                  .unwrap());
+
+        assert_eq!(stdout.contents().matches("Overwrite").count(), 1);
+        assert!(stdout.contents().contains("Delete a.toml?"));
+    }
+
+    #[test]
+    fn prompt_yes_no_all_plain_answers_are_not_remembered() {
+        let (mut ui, stdout, _) = ui_with_input("y\n");
+
+        assert!(ui.prompt_yes_no_all("overwrite", "Overwrite a.toml?", None)
+                  .unwrap());
+        assert!(!ui.prompt_yes_no_all("overwrite", "Overwrite b.toml?", Some(false))
+                   .unwrap());
+
+        assert_eq!(stdout.contents().matches("Overwrite").count(), 2);
+    }
+
+    #[test]
+    fn prompt_yes_no_all_fails_when_answered_quit() {
+        let (mut ui, ..) = ui_with_input("quit\n");
+        match ui.prompt_yes_no_all("overwrite", "Overwrite a.toml?", Some(true)) {
+            Err(Error::PromptCancelled) => (),
+            other => panic!("Expected PromptCancelled, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn prompt_ask_secret_default_masks_the_shown_default() {
+        let (mut ui, stdout, _) = ui_with_input("\n");
+
+        let answer = ui.prompt_ask_secret_default("Token", Some("_Qk9-c0ffee1234"))
+                       .unwrap();
+
+        assert_eq!(answer, "_Qk9-c0ffee1234");
+        assert_eq!(stdout.contents(), "Token: [default: ****1234] ");
+    }
+
+    #[test]
+    fn prompt_ask_secret_default_returns_a_typed_answer() {
+        let (mut ui, ..) = ui_with_input("new-token\n");
+        assert_eq!(ui.prompt_ask_secret_default("Token", Some("old-token"))
+                     .unwrap(),
+                   "new-token");
+    }
+
+    #[test]
+    fn mask_secret_hides_short_secrets_entirely() {
+        assert_eq!(mask_secret("abcdefgh"), "****efgh");
+        assert_eq!(mask_secret("abcd"), "****");
+        assert_eq!(mask_secret(""), "****");
+        assert_eq!(mask_secret("äöüßéèê"), "****ßéèê");
+    }
+
+    #[test]
+    fn assume_yes_short_circuits_prompt_yes_no() {
+        let (mut ui, stdout, _) = ui_with_input("n\n");
+        ui.set_assume_yes(true);
+        assert!(ui.prompt_yes_no("Continue?", Some(false)).unwrap());
+        assert!(ui.prompt_yes_no("Continue?", None).unwrap());
+        assert_eq!(stdout.contents(), "");
+    }
+
+    #[test]
+    fn prompt_confirm_phrase_requires_exact_match() {
+        let (mut ui, ..) = ui_with_input("delete\n");
+        assert!(!ui.prompt_confirm_phrase("Really?", "DELETE").unwrap());
+        let (mut ui, ..) = ui_with_input("DELETE\n");
+        assert!(ui.prompt_confirm_phrase("Really?", "DELETE").unwrap());
+    }
+
+    #[test]
+    fn assume_yes_short_circuits_prompt_confirm_phrase() {
+        let (mut ui, stdout, _) = ui_with_input("nope\n");
+        ui.set_assume_yes(true);
+        assert!(ui.prompt_confirm_phrase("Really?", "DELETE").unwrap());
+        assert_eq!(stdout.contents(), "");
+    }
+
+    #[test]
+    fn assume_yes_confirms_destructive_actions_after_warning() {
+        let (mut ui, stdout, stderr) = ui_with_input("");
+        ui.set_assume_yes(true);
+        assert!(ui.confirm_destructive("This deletes the stable channel", "stable")
+                  .unwrap());
+        assert!(stderr.contents()
+                      .contains("This deletes the stable channel"));
+        assert_eq!(stdout.contents(), "");
+    }
+
+    #[test]
+    fn confirm_destructive_warns_in_red() {
+        let stderr_buf = OutputBuffer::new();
+        let shell = Shell::from_parts(Box::new(io::empty()),
+                                      Box::new(io::sink()),
+                                      ColorChoice::Always,
+                                      true,
+                                      Box::new(stderr_buf.clone()),
+                                      ColorChoice::Always,
+                                      true);
+        let mut ui = UI::new(shell);
+        ui.set_assume_yes(true);
+
+        assert!(ui.confirm_destructive("This deletes the stable channel", "stable")
+                  .unwrap());
+
+        let err = stderr_buf.contents();
+        assert!(err.contains("\x1b[31m"), "{:?}", err);
+        assert!(err.contains("This deletes the stable channel"), "{:?}", err);
+    }
+
+    #[test]
+    fn confirm_destructive_refuses_when_there_is_no_one_to_prompt() {
+        let (mut ui, _, stderr) = ui_with_input("stable\n");
+        match ui.confirm_destructive("This deletes the stable channel", "stable") {
+            Err(Error::ConfirmationRequired(ref impact)) => {
+                assert_eq!(impact, "This deletes the stable channel")
+            }
+            other => panic!("Expected ConfirmationRequired, got {:?}", other),
+        }
+        assert!(stderr.contents()
+                      .contains("This deletes the stable channel"));
+    }
+
+    locked_env_var!(LANG, lock_lang_var);
+    locked_env_var!(LC_ALL, lock_lc_all_var);
+    locked_env_var!(LC_MESSAGES, lock_lc_messages_var);
+
+    #[test]
+    fn prompt_yes_no_accepts_answers_in_the_locale_language() {
+        let lang = lock_lang_var();
+        let lc_all = lock_lc_all_var();
+        let lc_messages = lock_lc_messages_var();
+        lc_all.unset();
+        lc_messages.unset();
+        lang.set("fr_FR.UTF-8");
+
+        let (mut ui, stdout, _) = ui_with_input("oui\n");
+        assert!(ui.prompt_yes_no("Continuer ?", Some(false)).unwrap());
+        assert!(stdout.contents().contains("[oui/Non/quitter]"),
+                "{}",
+                stdout.contents());
+        let (mut ui, ..) = ui_with_input("non\n");
+        assert!(!ui.prompt_yes_no("Continuer ?", Some(true)).unwrap());
+        let (mut ui, ..) = ui_with_input("O\n");
+        assert!(ui.prompt_yes_no("Continuer ?", None).unwrap());
+        let (mut ui, ..) = ui_with_input("yes\n");
+        assert!(ui.prompt_yes_no("Continuer ?", Some(false)).unwrap());
+    }
+
+    #[test]
+    fn prompt_yes_no_falls_back_to_english() {
+        let lang = lock_lang_var();
+        let lc_all = lock_lc_all_var();
+        let lc_messages = lock_lc_messages_var();
+        lc_all.unset();
+        lc_messages.unset();
+        lang.set("C");
+
+        let (mut ui, stdout, _) = ui_with_input("oui\n");
+        assert!(!ui.prompt_yes_no("Continue?", Some(false)).unwrap());
+        // "oui" isn't an answer in English, so the question is asked again, and then answered
+        // with the default.
+        assert_eq!(stdout.contents().matches("[yes/No/quit]").count(), 2);
+    }
+
+    #[test]
+    fn yes_no_words_are_chosen_by_language() {
+        assert_eq!(yes_no_words_for("de_DE.UTF-8").language, "de");
+        assert_eq!(yes_no_words_for("es").language, "es");
+        assert_eq!(yes_no_words_for("fr_CA@euro").language, "fr");
+        assert_eq!(yes_no_words_for("en_US.UTF-8").language, "en");
+        assert_eq!(yes_no_words_for("POSIX").language, "en");
+    }
+
+    locked_env_var!(COLUMNS, lock_columns_var);
+    locked_env_var!(EDITOR, lock_editor_var);
+    locked_env_var!(HAB_NONINTERACTIVE, lock_noninteractive_var);
+    locked_env_var!(HAB_ASSUME_YES, lock_assume_yes_var);
+    locked_env_var!(HAB_MAX_WIDTH, lock_max_width_var);
+
+    #[test]
+    fn edit_with_a_missing_editor_names_it() {
+        let lock = lock_editor_var();
+        lock.set("hab-no-such-editor");
+        let (mut ui, ..) = ui_with_input("");
+
+        match ui.edit(&["contents"]) {
+            Err(Error::EditorNotFound(ref editor)) => assert_eq!(editor, "hab-no-such-editor"),
+            other => panic!("Expected EditorNotFound, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn edit_keeps_text_which_is_not_utf8() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let editor = dir.path().join("latin1-editor");
+        fs::write(&editor, "#!/bin/sh\nprintf 'caf\\351\\n' > \"$1\"\n").unwrap();
+        fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+        let lock = lock_editor_var();
+        lock.set(editor.to_str().unwrap());
+        let (mut ui, ..) = ui_with_input("");
+
+        match ui.edit(&["contents"]) {
+            Err(Error::EditInvalidUtf8(ref path)) => {
+                assert_eq!(fs::read(path).unwrap(), b"caf\xe9\n");
+                fs::remove_file(path).unwrap();
+            }
+            other => panic!("Expected EditInvalidUtf8, got {:?}", other),
+        }
+    }
+
+    /// A `UIWriter` over ANSI buffers, for asserting on the escape sequences the default trait
+    /// methods produce.
+    struct AnsiWriter {
+        out:        termcolor::Buffer,
+        err:        termcolor::Buffer,
+        accessible: bool,
+    }
+
+    impl AnsiWriter {
+        fn new() -> Self {
+            AnsiWriter { out:        termcolor::Buffer::ansi(),
+                         err:        termcolor::Buffer::ansi(),
+                         accessible: false, }
+        }
+
+        fn out_contents(&self) -> String { String::from_utf8_lossy(self.out.as_slice()).into() }
+
+        fn err_contents(&self) -> String { String::from_utf8_lossy(self.err.as_slice()).into() }
+    }
+
+    impl UIWriter for AnsiWriter {
+        type ProgressBar = ConsoleProgressBar;
+
+        fn out(&mut self) -> &mut dyn WriteColor { &mut self.out }
+
+        fn err(&mut self) -> &mut dyn WriteColor { &mut self.err }
+
+        fn is_out_a_terminal(&self) -> bool { true }
+
+        fn is_err_a_terminal(&self) -> bool { true }
+
+        fn progress(&self) -> Option<Self::ProgressBar> { None }
+
+        fn accessible(&self) -> bool { self.accessible }
+    }
+
+    #[test]
+    fn accessible_status_includes_tag_and_color() {
+        let mut ui = AnsiWriter::new();
+        ui.accessible = true;
+        ui.status(Status::Promoted, "core/redis").unwrap();
+        ui.status(Status::Missing, "core/nginx").unwrap();
+        let out = ui.out_contents();
+        assert!(out.contains("[OK] [x] Promoted"));
+        assert!(out.contains("[ERR] ??? Missing"));
+        assert!(out.contains("\x1b[32m"), "green is still set: {:?}", out);
+        assert!(out.contains("\x1b[31m"), "red is still set: {:?}", out);
+    }
+
+    #[test]
+    fn accessible_warn_and_fatal_include_tags_and_color() {
+        let mut ui = AnsiWriter::new();
+        ui.accessible = true;
+        ui.warn("careful").unwrap();
+        ui.fatal("broken").unwrap();
+        let err = ui.err_contents();
+        assert!(err.contains("[WARN] 0 careful"));
+        assert!(err.contains("[ERR] XXX broken"));
+        assert!(err.contains("\x1b[33m"), "yellow is still set: {:?}", err);
+        assert!(err.contains("\x1b[31m"), "red is still set: {:?}", err);
+    }
+
+    #[test]
+    fn tags_are_omitted_unless_accessible() {
+        let mut ui = AnsiWriter::new();
+        ui.status(Status::Promoted, "core/redis").unwrap();
+        ui.warn("careful").unwrap();
+        assert!(!ui.out_contents().contains("[OK]"));
+        assert!(!ui.err_contents().contains("[WARN]"));
+    }
+
+    #[test]
+    fn status_with_overrides_color() {
+        let mut ui = AnsiWriter::new();
+        ui.status_with(Status::Skipping, Color::Critical, "core/redis")
+          .unwrap();
+        let out = ui.out_contents();
+        let (symbol, ..) = Status::Skipping.parts();
+        assert!(out.contains(&format!("{} Skipping", symbol.to_str())));
+        assert!(out.contains("\x1b[31m"), "red is used: {:?}", out);
+
+        let mut ui = AnsiWriter::new();
+        ui.status(Status::Skipping, "core/redis").unwrap();
+        assert!(!ui.out_contents().contains("\x1b[31m"));
+    }
+
+    #[test]
+    fn status_done_is_a_green_check_mark_on_the_output_stream() {
+        let mut ui = AnsiWriter::new();
+        ui.status_done("core/redis saved").unwrap();
+
+        let out = ui.out_contents();
+        assert!(out.contains(&format!("{} Done", Glyph::CheckMark.to_padded_str())),
+                "{:?}",
+                out);
+        assert!(out.contains(" core/redis saved\n"), "{:?}", out);
+        assert!(out.contains("\x1b[32m"), "green is used: {:?}", out);
+        assert_eq!(ui.err_contents(), "");
+    }
+
+    #[test]
+    fn status_fail_is_a_red_x_on_the_error_stream() {
+        let mut ui = AnsiWriter::new();
+        ui.status_fail("core/redis not saved").unwrap();
+
+        let err = ui.err_contents();
+        assert!(err.contains(&format!("{} Failed", Glyph::ErrorX.to_padded_str())),
+                "{:?}",
+                err);
+        assert!(err.contains(" core/redis not saved\n"), "{:?}", err);
+        assert!(err.contains("\x1b[31m"), "red is used: {:?}", err);
+        assert_eq!(ui.out_contents(), "");
+    }
+
+    #[test]
+    fn status_done_and_fail_are_recorded() {
+        let mut ui = RecordingUI::new(UI::with_sinks());
+        ui.status_done("saved").unwrap();
+        ui.status_fail("not saved").unwrap();
+
+        assert_eq!(ui.events(),
+                   &[UiEvent::Status("Done".to_string(), "saved".to_string()),
+                     UiEvent::Status("Failed".to_string(), "not saved".to_string())]);
+    }
+
+    #[test]
+    fn wrap_text_breaks_on_word_boundaries() {
+        assert_eq!(wrap_text("the quick brown fox jumps", 10),
+                   vec!["the quick", "brown fox", "jumps"]);
+        assert_eq!(wrap_text("supercalifragilistic word", 5),
+                   vec!["supercalifragilistic", "word"]);
+        assert!(wrap_text("", 10).is_empty());
+    }
+
+    #[test]
+    fn wrap_text_measures_words_in_columns() {
+        assert_eq!(wrap_text("日本語 日本語", 10), vec!["日本語", "日本語"]);
+        assert_eq!(wrap_text("café crème", 11), vec!["café crème"]);
+    }
+
+    #[test]
+    fn wrap_text_treats_zero_width_as_one() {
+        assert_eq!(wrap_text("a bc d", 0), vec!["a", "bc", "d"]);
+    }
+
+    #[test]
+    fn print_wrapped_narrower_than_its_indent_does_not_underflow() {
+        let mut buffer = termcolor::Buffer::no_color();
+        print_wrapped(&mut buffer, "the quick fox", 2, 4).unwrap();
+
+        let out = String::from_utf8(buffer.into_inner()).unwrap();
+        let lines: Vec<&str> = out.lines().filter(|l| !l.is_empty()).collect();
+        assert_eq!(lines, vec!["    the ", "    quick ", "    fox "]);
+        assert!(lines.iter().all(|l| !l.trim().is_empty()));
+    }
+
+    #[test]
+    fn apply_env_applies_settings_to_a_ui_with_chosen_coloring() {
+        let assume_yes = lock_assume_yes_var();
+        assume_yes.set("true");
+        let max_width = lock_max_width_var();
+        max_width.set("40");
+        let (mut ui, ..) = ui_with_input("");
+        ui.apply_env();
+        assert!(ui.assume_yes());
+        assert_eq!(ui.max_width(), Some(40));
+    }
+
+    #[test]
+    fn info_is_not_wrapped_by_default() {
+        let lock = lock_columns_var();
+        lock.set("10");
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.info("the quick brown fox jumps").unwrap();
+        assert_eq!(stdout.contents(), "the quick brown fox jumps\n");
+    }
+
+    #[test]
+    fn info_wraps_only_when_enabled_and_width_exceeded() {
+        let lock = lock_columns_var();
+        lock.set("10");
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.set_wrap_info(true);
+        ui.info("short").unwrap();
+        ui.info("the quick brown fox jumps").unwrap();
+        assert_eq!(stdout.contents(), "short\nthe quick\nbrown fox\njumps\n");
+    }
+
+    #[test]
+    fn shell_from_parts_colors_streams_independently() {
+        let stdout_buf = OutputBuffer::new();
+        let stderr_buf = OutputBuffer::new();
+        let mut shell = Shell::from_parts(Box::new(io::empty()),
+                                          Box::new(stdout_buf.clone()),
+                                          ColorChoice::Never,
+                                          false,
+                                          Box::new(stderr_buf.clone()),
+                                          ColorChoice::Always,
+                                          true);
+
+        assert!(!shell.out().supports_color());
+        assert!(!shell.out().is_a_terminal());
+        assert!(shell.err().supports_color());
+        assert!(shell.err().is_a_terminal());
+
+        let mut ui = UI::new(shell);
+        ui.status(Status::Downloading, "core/redis").unwrap();
+        ui.warn("careful").unwrap();
+        assert!(!stdout_buf.contents().contains("\x1b["));
+        assert!(stderr_buf.contents().contains("\x1b[33m"));
+    }
+
+    #[test]
+    fn status_progress_reports_in_progress_then_done() {
+        let (ui, stdout, _) = ui_with_input("");
+        let mut ui = RecordingUI::new(ui);
+
+        let result = ui.status_progress(Status::Promoting,
+                                        Status::Promoted,
+                                        "core/redis/4.0.14/20190319155852",
+                                        || Ok::<_, ()>(42))
+                       .unwrap();
+
+        assert_eq!(result, Ok(42));
+        let ident = "core/redis/4.0.14/20190319155852".to_string();
+        assert_eq!(ui.events(),
+                   &[UiEvent::Status("Promoting".to_string(), ident.clone()),
+                     UiEvent::Status("Promoted".to_string(), ident)]);
+        let out = stdout.contents();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("Promoting core/redis/4.0.14/20190319155852"));
+        assert!(lines[1].ends_with("Promoted core/redis/4.0.14/20190319155852"));
+    }
+
+    #[test]
+    fn status_progress_draws_the_outcome_over_the_in_progress_line_on_a_terminal() {
+        let columns = lock_columns_var();
+        columns.set("40");
+        let (mut ui, stdout) = tty_ui_with_input("");
+
+        let result = ui.status_progress(Status::Promoting, Status::Promoted, "core/redis", || {
+                           Ok::<_, ()>(())
+                       })
+                       .unwrap();
+
+        assert_eq!(result, Ok(()));
+        let out = stdout.contents();
+        assert_eq!(out.lines().count(), 1);
+        let drawn: Vec<&str> = out.split('\r').collect();
+        assert_eq!(drawn.len(), 4);
+        assert!(drawn[1].trim_end().ends_with("Promoting core/redis"));
+        assert_eq!(drawn[2], " ".repeat(40));
+        assert!(drawn[3].ends_with("Promoted core/redis\n"));
+    }
+
+    #[test]
+    fn status_progress_reports_a_failure() {
+        let (ui, stdout, stderr) = ui_with_input("");
+        let mut ui = RecordingUI::new(ui);
+
+        let result = ui.status_progress(Status::Uploading, Status::Uploaded, "core/redis", || {
+                           Err::<(), _>("refused")
+                       })
+                       .unwrap();
+
+        assert_eq!(result, Err("refused"));
+        assert!(stdout.contents().contains("Uploading core/redis"));
+        assert!(!stdout.contents().contains("Uploaded"));
+        assert!(stderr.contents().contains("Failed core/redis"));
+    }
+
+    #[test]
+    fn with_context_prefixes_the_error_with_what_was_being_done() {
+        let (ui, stdout, stderr) = ui_with_input("");
+        let mut ui = RecordingUI::new(ui);
+
+        let result = ui.with_context("promoting acme/redis to stable", Err::<(), _>("refused"));
+
+        assert_eq!(result, Err("refused"));
+        assert!(stderr.contents()
+                      .contains("while promoting acme/redis to stable: refused"));
+        assert_eq!(stdout.contents(), "");
+        assert_eq!(ui.events(),
+                   &[UiEvent::Fatal("while promoting acme/redis to stable: \
+                                     refused"
+                                             .to_string())]);
+    }
+
+    #[test]
+    fn capture_returns_the_output_instead_of_writing_it() {
+        let (mut ui, stdout, stderr) = ui_with_input("");
+
+        let (result, captured) = ui.capture(|ui| {
+                                       ui.info("core/redis").unwrap();
+                                       ui.warn("no license").unwrap();
+                                       7
+                                   });
+        ui.info("after").unwrap();
+
+        assert_eq!(result, 7);
+        assert!(captured.starts_with("core/redis\n"), "{:?}", captured);
+        assert!(captured.contains("no license"), "{:?}", captured);
+        assert_eq!(stdout.contents(), "after\n");
+        assert_eq!(stderr.contents(), "");
+    }
+
+    #[test]
+    fn capture_puts_the_streams_back_after_a_panic() {
+        let (mut ui, stdout, _) = ui_with_input("");
+
+        let panicked = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                                               ui.capture(|ui| {
+                                                     ui.info("core/redis").unwrap();
+                                                     panic!("interrupted");
+                                                 })
+                                           }));
+        ui.info("after").unwrap();
+
+        assert!(panicked.is_err());
+        assert_eq!(stdout.contents(), "after\n");
+    }
+
+    #[test]
+    fn with_context_writes_nothing_on_success() {
+        let (mut ui, stdout, stderr) = ui_with_input("");
+
+        assert_eq!(ui.with_context("promoting acme/redis to stable", Ok::<_, String>(7)),
+                   Ok(7));
+        assert_eq!(stdout.contents(), "");
+        assert_eq!(stderr.contents(), "");
+    }
+
+    #[test]
+    fn tip_is_written_in_the_important_color_to_standard_output() {
+        let stdout_buf = OutputBuffer::new();
+        let stderr_buf = OutputBuffer::new();
+        let shell = Shell::from_parts(Box::new(io::empty()),
+                                      Box::new(stdout_buf.clone()),
+                                      ColorChoice::Always,
+                                      true,
+                                      Box::new(stderr_buf.clone()),
+                                      ColorChoice::Always,
+                                      true);
+        let mut ui = RecordingUI::new(UI::new(shell));
+
+        ui.tip("Tip: set HAB_AUTH_TOKEN to avoid passing --auth")
+          .unwrap();
+
+        let out = stdout_buf.contents();
+        assert!(out.contains("\x1b[36m"), "{:?}", out);
+        assert!(out.contains(&format!("{} Tip: set HAB_AUTH_TOKEN to avoid passing --auth",
+                                      Glyph::FingerPoint.to_str())),
+                "{:?}",
+                out);
+        assert_eq!(stderr_buf.contents(), "");
+        assert_eq!(ui.events(),
+                   &[UiEvent::Tip("Tip: set HAB_AUTH_TOKEN to avoid passing \
+                                   --auth"
+                                          .to_string())]);
+    }
+
+    #[test]
+    fn tip_is_left_out_when_quiet() {
+        let (mut ui, stdout, stderr) = ui_with_input("");
+        ui.set_quiet(true);
+
+        ui.tip("Tip: set HAB_AUTH_TOKEN to avoid passing --auth")
+          .unwrap();
+        ui.info("core/redis").unwrap();
+
+        assert_eq!(stdout.contents(), "core/redis\n");
+        assert_eq!(stderr.contents(), "");
+    }
+
+    #[test]
+    fn raw_writes_bytes_unmodified_after_resetting_color() {
+        let stdout_buf = OutputBuffer::new();
+        let shell = Shell::from_parts(Box::new(io::empty()),
+                                      Box::new(stdout_buf.clone()),
+                                      ColorChoice::Always,
+                                      true,
+                                      Box::new(io::sink()),
+                                      ColorChoice::Never,
+                                      false);
+        let mut ui = UI::new(shell);
+        ui.set_output_mode(OutputMode::Json);
+        ui.out()
+          .set_color(ColorSpec::new().set_fg(Some(Color::Warn.into())))
+          .unwrap();
+
+        let bytes = b"c2VjcmV0\x00\xff\n\x1b";
+        ui.raw(bytes).unwrap();
+
+        let out = stdout_buf.cursor.read().unwrap().get_ref().clone();
+        assert!(out.ends_with(&[&b"\x1b[0m"[..], &bytes[..]].concat()),
+                "{:?}",
+                out);
+    }
+
+    #[test]
+    fn status_symbols_are_padded_to_the_widest_glyph() {
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.status(Status::Installed, "core/redis").unwrap();
+        ui.status(Status::Custom(Glyph::ErrorX, "Failed".to_string()),
+                  "core/nginx")
+          .unwrap();
+        ui.status(Status::Skipping, "core/zlib").unwrap();
+
+        let out = stdout.contents();
+        // The number of columns before each line's status text.
+        let columns: Vec<_> = out.lines()
+                                 .zip(&["Installed", "Failed", "Skipping"])
+                                 .map(|(line, text)| line.split(text).next().unwrap().width())
+                                 .collect();
+        assert_eq!(columns.len(), 3);
+        assert!(columns.iter().all(|&c| c == columns[0]), "{}", out);
+    }
+
+    #[test]
+    fn glyph_widths_are_counted_in_columns() {
+        assert_eq!(Glyph::CheckMark.width(GlyphStyle::Full), 1);
+        assert_eq!(Glyph::ErrorX.width(GlyphStyle::Full), 3);
+        assert_eq!(Glyph::CheckMark.width(GlyphStyle::Ascii), 3);
+        assert_eq!(Glyph::CheckMark.to_padded_style_str(GlyphStyle::Limited),
+                   "  √");
+        assert_eq!(Glyph::DownArrow.to_padded_style_str(GlyphStyle::Ascii),
+                   "  >");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn colors_round_trip_through_their_lowercase_names() {
+        assert_eq!(serde_json::to_string(&Color::Important).unwrap(),
+                   "\"important\"");
+        for color in &[Color::Plain,
+                       Color::Info,
+                       Color::Important,
+                       Color::Warn,
+                       Color::Critical,
+                       Color::End]
+        {
+            let json = serde_json::to_string(color).unwrap();
+            assert_eq!(serde_json::from_str::<Color>(&json).unwrap(), *color);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn glyphs_round_trip_through_their_lowercase_names() {
+        assert_eq!(serde_json::to_string(&Glyph::CheckMark).unwrap(),
+                   "\"check_mark\"");
+        for glyph in Glyph::ALL.iter() {
+            let json = serde_json::to_string(glyph).unwrap();
+            assert_eq!(serde_json::from_str::<Glyph>(&json).unwrap(), *glyph);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn glyph_styles_round_trip_through_their_lowercase_names() {
+        for style in &[GlyphStyle::Full, GlyphStyle::Limited, GlyphStyle::Ascii] {
+            let json = serde_json::to_string(style).unwrap();
+            assert_eq!(json, format!("\"{}\"", style));
+            assert_eq!(serde_json::from_str::<GlyphStyle>(&json).unwrap(), *style);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn unknown_color_glyph_or_style_names_do_not_deserialize() {
+        assert!(serde_json::from_str::<Color>("\"mauve\"").is_err());
+        assert!(serde_json::from_str::<Color>("\"Warn\"").is_err());
+        assert!(serde_json::from_str::<Glyph>("\"snowman\"").is_err());
+        assert!(serde_json::from_str::<GlyphStyle>("\"fancy\"").is_err());
+    }
+
+    #[test]
+    fn truncate_display_cuts_ascii_text_with_an_ellipsis() {
+        assert_eq!(truncate_display_style("core/redis", 7, GlyphStyle::Full),
+                   "core/r…");
+        assert_eq!(truncate_display_style("core/redis", 7, GlyphStyle::Ascii),
+                   "core...");
+        assert_eq!(truncate_display_style("core/redis", 2, GlyphStyle::Ascii),
+                   "co");
+    }
+
+    #[test]
+    fn truncate_display_never_splits_a_wide_character() {
+        // Each of these characters is two columns wide.
+        let text = "日本語のテキスト";
+        let truncated = truncate_display_style(text, 6, GlyphStyle::Full);
+        assert_eq!(truncated, "日本…");
+        assert!(truncated.width() <= 6);
+
+        let truncated = truncate_display_style(text, 7, GlyphStyle::Full);
+        assert_eq!(truncated, "日本語…");
+        assert_eq!(truncated.width(), 7);
+    }
+
+    #[test]
+    fn truncate_display_leaves_text_which_fits() {
+        assert_eq!(truncate_display_style("core/redis", 10, GlyphStyle::Ascii),
+                   "core/redis");
+        assert_eq!(truncate_display_style("日本語", 6, GlyphStyle::Full),
+                   "日本語");
+        assert_eq!(truncate_display_style("", 0, GlyphStyle::Full), "");
+    }
+
+    #[test]
+    fn status_column_width_aligns_messages() {
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.set_status_column_width(Some(12));
+
+        ui.status(Status::Downloading, "core/redis").unwrap();
+        ui.status(Status::Using, "core/redis").unwrap();
+        ui.status(Status::Custom(Glyph::CheckMark, "Extraordinarily".to_string()),
+                  "core/redis")
+          .unwrap();
+
+        let out = stdout.contents();
+        let columns: Vec<usize> = out.lines()
+                                     .map(|line| {
+                                         let idx = line.find(" core/redis").unwrap();
+                                         line[..idx].chars().count()
+                                     })
+                                     .collect();
+        assert_eq!(columns.len(), 3);
+        assert!(columns.iter().all(|c| *c == columns[0]));
+        assert!(out.contains(&format!("{:>12} core/redis", "Using")));
+        assert!(out.contains(" Extraordinar core/redis"));
+    }
+
+    #[test]
+    fn recording_ui_records_events_and_output() {
+        let (ui, stdout, _) = ui_with_input("");
+        let mut ui = RecordingUI::new(ui);
+
+        ui.begin("Promoting core/redis").unwrap();
+        ui.status(Status::Promoted, "core/redis").unwrap();
+        ui.end("Done").unwrap();
+
+        assert_eq!(ui.events(),
+                   &[UiEvent::Begin("Promoting core/redis".to_string()),
+                     UiEvent::Status("Promoted".to_string(), "core/redis".to_string()),
+                     UiEvent::End("Done".to_string())]);
+        assert!(stdout.contents().contains("Promoted core/redis"));
+    }
+
+    #[test]
+    fn finalize_writes_the_audit_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("audit.json");
+        let (mut ui, _) = UI::from_env_for_tests();
+        ui.set_audit_file(Some(path.clone()));
+
+        ui.record_change(&json!({ "action": "created", "origin": "core", "channel": "qa" }));
+        ui.record_change(&json!({ "action": "promoted_to", "ident": "core/redis/4.0.14/1" }));
+        ui.finalize().unwrap();
+
+        let audit: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(audit["mutations"][0]["action"], "created");
+        assert_eq!(audit["mutations"][0]["origin"], "core");
+        assert_eq!(audit["mutations"][1]["action"], "promoted_to");
+        assert_eq!(audit["mutations"][1]["ident"], "core/redis/4.0.14/1");
+        assert_eq!(ui.changes().len(), 2);
+    }
+
+    #[test]
+    fn finalize_flushes_buffered_streams() {
+        let stdout_buf = OutputBuffer::new();
+        let stderr_buf = OutputBuffer::new();
+        let (stdout, stderr) = (stdout_buf.clone(), stderr_buf.clone());
+        let mut ui = UI::with_streams(Box::new(io::empty()),
+                                      move || Box::new(io::BufWriter::new(stdout.clone())),
+                                      move || Box::new(io::BufWriter::new(stderr.clone())),
+                                      ColorChoice::Never,
+                                      false);
+
+        ui.out().write_all(b"to stdout").unwrap();
+        ui.err().write_all(b"to stderr").unwrap();
+        assert_eq!(stdout_buf.contents(), "");
+        assert_eq!(stderr_buf.contents(), "");
+
+        ui.finalize().unwrap();
+        assert_eq!(stdout_buf.contents(), "to stdout");
+        assert_eq!(stderr_buf.contents(), "to stderr");
+    }
+
+    #[test]
+    fn color_depth_is_none_without_color_support() {
+        assert_eq!(ColorDepth::detect(false, Some("truecolor"), Some("xterm-256color")),
+                   ColorDepth::None);
+        assert_eq!(ColorDepth::detect(true, None, Some("dumb")),
+                   ColorDepth::None);
+    }
+
+    #[test]
+    fn color_depth_from_colorterm_and_term() {
+        let cases = [(Some("truecolor"), Some("xterm-256color"), ColorDepth::TrueColor),
+                     (Some("24bit"), Some("xterm"), ColorDepth::TrueColor),
+                     (None, Some("xterm-direct"), ColorDepth::TrueColor),
+                     (None, Some("xterm-256color"), ColorDepth::Ansi256),
+                     (None, Some("screen-256color"), ColorDepth::Ansi256),
+                     (Some("yes"), Some("tmux-256color"), ColorDepth::Ansi256),
+                     (None, Some("xterm"), ColorDepth::Ansi16),
+                     (None, Some("vt100"), ColorDepth::Ansi16),
+                     (None, None, ColorDepth::Ansi16)];
+        for &(colorterm, term, depth) in cases.iter() {
+            assert_eq!(ColorDepth::detect(true, colorterm, term),
+                       depth,
+                       "COLORTERM={:?} TERM={:?}",
+                       colorterm,
+                       term);
+        }
+    }
+
+    #[test]
+    fn warn_coalesce_summarizes_repeated_warnings() {
+        let stderr_buf = OutputBuffer::new();
+        let stderr = stderr_buf.clone();
+        let mut ui = UI::with_streams(Box::new(io::empty()),
+                                      || Box::new(io::sink()),
+                                      move || Box::new(stderr.clone()),
+                                      ColorChoice::Never,
+                                      false);
+        ui.set_warn_coalesce(true);
+
+        for _ in 0..5 {
+            ui.warn("disk is full").unwrap();
+        }
+        assert_eq!(stderr_buf.contents().lines().count(), 1);
+
+        ui.warn("disk is fine").unwrap();
+        ui.finalize().unwrap();
+        let glyph = Glyph::SlashedZero.to_str();
+        let expected = vec![format!("{} disk is full", glyph),
+                            format!("{} disk is full (repeated 4 times)", glyph),
+                            format!("{} disk is fine", glyph)];
+        assert_eq!(stderr_buf.contents().lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn set_clock_fixes_the_current_time() {
+        let fixed = SystemTime::UNIX_EPOCH + Duration::from_secs(1_553_011_132);
+        let (mut ui, ..) = ui_with_input("");
+        ui.set_clock(Box::new(move || fixed));
+        assert_eq!(ui.now(), fixed);
+        assert_eq!(RecordingUI::new(ui).now(), fixed);
+    }
+
+    #[test]
+    fn from_env_for_tests_captures_plain_output() {
+        let (mut ui, output) = UI::from_env_for_tests();
+        ui.status(Status::Installed, "core/redis").unwrap();
+        ui.warn("careful").unwrap();
+
+        assert!(!ui.is_a_tty());
+        assert_eq!(output.stdout(),
+                   format!("{} Installed core/redis\n",
+                           Glyph::CheckMark.to_padded_str()));
+        assert_eq!(output.stderr(),
+                   format!("{} careful\n", Glyph::SlashedZero.to_str()));
+    }
+
+    #[test]
+    fn status_min_severity_drops_less_severe_statuses() {
+        let stdout_buf = OutputBuffer::new();
+        let stderr_buf = OutputBuffer::new();
+        let (stdout, stderr) = (stdout_buf.clone(), stderr_buf.clone());
+        let mut ui = UI::with_streams(Box::new(io::empty()),
+                                      move || Box::new(stdout.clone()),
+                                      move || Box::new(stderr.clone()),
+                                      ColorChoice::Never,
+                                      false);
+        ui.set_status_min_severity(Some(Color::Warn));
+
+        ui.status(Status::Installed, "core/redis").unwrap();
+        ui.status(Status::Missing, "core/nginx").unwrap();
+        ui.warn("still written").unwrap();
+
+        let out = stdout_buf.contents();
+        assert!(!out.contains("core/redis"), "{}", out);
+        assert!(out.contains("Missing core/nginx"), "{}", out);
+        assert!(stderr_buf.contents().contains("still written"));
+    }
+
+    #[test]
+    fn nested_begin_and_end_indent_the_messages_between_them() {
+        let (mut ui, output) = UI::from_env_for_tests();
+        ui.set_nesting(true);
+
+        ui.begin("Promoting core/redis").unwrap();
+        ui.info("resolving dependencies").unwrap();
+        ui.begin("Promoting core/glibc").unwrap();
+        ui.status(Status::Promoted, "core/glibc").unwrap();
+        ui.end("Promoted core/glibc").unwrap();
+        ui.end("Promoted core/redis").unwrap();
+        ui.info("done").unwrap();
+
+        let lines: Vec<String> = output.stdout().lines().map(str::to_string).collect();
+        assert_eq!(lines,
+                   vec![format!("{} Promoting core/redis", Glyph::RightShift.to_str()),
+                        "  resolving dependencies".to_string(),
+                        format!("  {} Promoting core/glibc", Glyph::RightShift.to_str()),
+                        format!("    {} Promoted core/glibc",
+                                Glyph::CheckMark.to_padded_str()),
+                        format!("  {} Promoted core/glibc", Glyph::Star.to_str()),
+                        format!("{} Promoted core/redis", Glyph::Star.to_str()),
+                        "done".to_string()]);
+    }
+
+    #[test]
+    fn unmatched_end_does_not_underflow_nesting() {
+        let (mut ui, output) = UI::from_env_for_tests();
+        ui.set_nesting(true);
+
+        ui.end("finished").unwrap();
+        ui.end("finished again").unwrap();
+        ui.begin("started").unwrap();
+        ui.info("inside").unwrap();
+
+        assert_eq!(ui.nesting_depth(), 1);
+        assert!(output.stdout().contains("\n  inside\n"),
+                "{}",
+                output.stdout());
+    }
+
+    #[test]
+    fn nesting_is_off_by_default() {
+        let (mut ui, output) = UI::from_env_for_tests();
+
+        ui.begin("started").unwrap();
+        ui.info("inside").unwrap();
+
+        assert_eq!(ui.nesting_depth(), 0);
+        assert!(output.stdout().ends_with("\ninside\n"),
+                "{}",
+                output.stdout());
+    }
+
+    #[test]
+    fn begin_and_end_symbols_can_be_replaced() {
+        let stdout_buf = OutputBuffer::new();
+        let stdout = stdout_buf.clone();
+        let mut ui = UI::with_streams(Box::new(io::empty()),
+                                      move || Box::new(stdout.clone()),
+                                      || Box::new(io::sink()),
+                                      ColorChoice::Never,
+                                      false);
+        ui.begin("default").unwrap();
+        ui.set_begin_symbol(Glyph::FingerPoint);
+        ui.set_end_symbol(Glyph::CheckMark);
+        ui.begin("started").unwrap();
+        ui.end("finished").unwrap();
+
+        let lines: Vec<String> = stdout_buf.contents().lines().map(str::to_string).collect();
+        assert_eq!(lines,
+                   vec![format!("{} default", Glyph::RightShift.to_str()),
+                        format!("{} started", Glyph::FingerPoint.to_str()),
+                        format!("{} finished", Glyph::CheckMark.to_str())]);
+    }
+
+    fn fatal_lines(isatty: bool, compact: Option<bool>) -> Vec<String> {
+        let stderr_buf = OutputBuffer::new();
+        let stderr = stderr_buf.clone();
+        let mut ui = UI::with_streams(Box::new(io::empty()),
+                                      || Box::new(io::sink()),
+                                      move || Box::new(stderr.clone()),
+                                      ColorChoice::Never,
+                                      isatty);
+        if let Some(compact) = compact {
+            ui.set_compact_errors(compact);
+        }
+        ui.fatal("it broke\nbadly").unwrap();
+        stderr_buf.contents().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn fatal_is_framed_on_a_terminal() {
+        let x = Glyph::ErrorX.to_str();
+        assert_eq!(fatal_lines(true, None),
+                   vec![x.to_string(),
+                        format!("{} it broke", x),
+                        format!("{} badly", x),
+                        x.to_string()]);
+    }
+
+    #[test]
+    fn fatal_rule_banner_spans_the_line() {
+        let columns = lock_columns_var();
+        columns.set("200");
+
+        let stderr_buf = OutputBuffer::new();
+        let stderr = stderr_buf.clone();
+        let mut ui = UI::with_streams(Box::new(io::empty()),
+                                      || Box::new(io::sink()),
+                                      move || Box::new(stderr.clone()),
+                                      ColorChoice::Never,
+                                      true);
+        ui.set_error_banner_style(ErrorBannerStyle::Rule);
+        ui.set_max_width(Some(40));
+        ui.fatal("it broke\nbadly").unwrap();
+        let x = Glyph::ErrorX.to_str().chars().next().unwrap();
+        let contents = stderr_buf.contents();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].chars().count(), 40);
+        assert!(lines[0].chars().all(|c| c == '-' || c == '─'),
+                "{:?}",
+                lines[0]);
+        assert_eq!(lines[1], format!("{} it broke", x));
+        assert_eq!(lines[2], format!("{} badly", x));
+        assert_eq!(lines[3], lines[0]);
+    }
+
+    #[test]
+    fn fatal_is_compact_when_not_a_terminal() {
+        let x = Glyph::ErrorX.to_str();
+        let compact = vec![format!("{} it broke", x), format!("{} badly", x)];
+        assert_eq!(fatal_lines(false, None), compact);
+        assert_eq!(fatal_lines(true, Some(true)), compact);
+        assert_eq!(fatal_lines(false, Some(false)).len(), 4);
+    }
+
+    #[test]
+    fn warn_coalesce_is_off_by_default() {
+        let stderr_buf = OutputBuffer::new();
+        let stderr = stderr_buf.clone();
+        let mut ui = UI::with_streams(Box::new(io::empty()),
+                                      || Box::new(io::sink()),
+                                      move || Box::new(stderr.clone()),
+                                      ColorChoice::Never,
+                                      false);
+
+        for _ in 0..3 {
+            ui.warn("disk is full").unwrap();
+        }
+        ui.finalize().unwrap();
+        assert_eq!(stderr_buf.contents().lines().count(), 3);
+    }
+
+    fn strip_ansi(writes: &[&[u8]]) -> String {
+        let mut writer = StripAnsiWriter::new(Vec::new());
+        for bytes in writes {
+            writer.write_all(bytes).unwrap();
+        }
+        String::from_utf8(writer.into_inner()).unwrap()
+    }
+
+    /// A writer which counts the writes and flushes made to it.
+    #[derive(Clone, Default)]
+    struct CountingWriter {
+        bytes:   Arc<RwLock<Vec<u8>>>,
+        writes:  Arc<RwLock<usize>>,
+        flushes: Arc<RwLock<usize>>,
+    }
+
+    impl CountingWriter {
+        fn escapes(&self) -> usize {
+            String::from_utf8_lossy(&self.bytes.read().unwrap()).matches('\x1b')
+                                                                .count()
+        }
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            *self.writes.write().unwrap() += 1;
+            self.bytes.write().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            *self.flushes.write().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    fn print_same_color_thrice<W>(writer: &mut W)
+        where W: WriteColor
+    {
+        let green = ColorSpec::new().set_fg(Some(termcolor::Color::Green))
+                                    .clone();
+        print(writer, b"core/", &green).unwrap();
+        print(writer, b"redis/", &green).unwrap();
+        println(writer, b"4.0.14", &green).unwrap();
+    }
+
+    #[test]
+    fn output_stream_sets_a_repeated_color_once() {
+        let direct = CountingWriter::default();
+        let mut ansi = Ansi::new(direct.clone());
+        print_same_color_thrice(&mut ansi);
+
+        let cached = CountingWriter::default();
+        let mut stream = OutputStream::new(WriteStream::Ansi(Ansi::new(Box::new(cached.clone()))),
+                                           ColorChoice::Always,
+                                           true);
+        print_same_color_thrice(&mut stream);
+
+        assert_eq!(strip_ansi(&[&cached.bytes.read().unwrap()]),
+                   "core/redis/4.0.14\n");
+        assert!(cached.escapes() < direct.escapes(),
+                "{} escapes cached, {} direct",
+                cached.escapes(),
+                direct.escapes());
+        assert!(*cached.writes.read().unwrap() < *direct.writes.read().unwrap());
+        assert_eq!(*cached.flushes.read().unwrap(), 1);
+    }
+
+    #[test]
+    fn output_stream_resets_before_plain_text_and_on_flush() {
+        let cached = CountingWriter::default();
+        let mut stream = OutputStream::new(WriteStream::Ansi(Ansi::new(Box::new(cached.clone()))),
+                                           ColorChoice::Always,
+                                           true);
+        let green = ColorSpec::new().set_fg(Some(termcolor::Color::Green))
+                                    .clone();
+
+        print(&mut stream, b"Promoted", &green).unwrap();
+        stream.write_all(b" core/redis").unwrap();
+        print(&mut stream, b"!", &green).unwrap();
+        stream.flush().unwrap();
+
+        let bytes = String::from_utf8(cached.bytes.read().unwrap().clone()).unwrap();
+        assert!(bytes.contains("Promoted\x1b[0m core/redis"), "{:?}", bytes);
+        assert!(bytes.ends_with("!\x1b[0m"), "{:?}", bytes);
+    }
+
+    /// A reader which has gone away, as `head` does once it has read enough.
+    struct BrokenPipeWriter;
+
+    impl Write for BrokenPipeWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "Broken pipe"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "Broken pipe"))
+        }
+    }
+
+    #[test]
+    fn a_broken_pipe_closes_stdout_rather_than_failing() {
+        let mut ui = UI::with_streams(Box::new(io::empty()),
+                                      || Box::new(BrokenPipeWriter),
+                                      || Box::new(BrokenPipeWriter),
+                                      ColorChoice::Never,
+                                      false);
+        assert!(!ui.is_output_closed());
+
+        ui.info("core/redis/4.0.14").unwrap();
+        assert!(ui.is_output_closed());
+        ui.status(Status::Downloading, "core/redis").unwrap();
+        ui.out().flush().unwrap();
+
+        let err = ui.err().write_all(b"oops").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn strip_ansi_writer_removes_colors() {
+        let mut buffer = termcolor::Buffer::ansi();
+        print(&mut buffer,
+              b"Promoted",
+              ColorSpec::new().set_fg(Some(termcolor::Color::Green))
+                              .set_bold(true)).unwrap();
+        buffer.write_all(b" core/redis\n").unwrap();
+
+        assert_eq!(strip_ansi(&[buffer.as_slice()]), "Promoted core/redis\n");
+    }
+
+    #[test]
+    fn strip_ansi_writer_removes_osc_sequences() {
+        assert_eq!(strip_ansi(&[b"\x1b]0;hab\x07before \x1b]2;a \x1b[1m title\x1b\\after"]),
+                   "before after");
+    }
+
+    #[test]
+    fn strip_ansi_writer_removes_sequences_split_across_writes() {
+        assert_eq!(strip_ansi(&[b"plain \x1b", b"[1;3", b"2mgreen\x1b[0", b"m done"]),
+                   "plain green done");
+        assert_eq!(strip_ansi(&[b"a\x1b]0;ti", b"tle\x1b", b"\\b"]), "ab");
+    }
+
+    #[test]
+    fn strip_ansi_writer_leaks_no_bytes_from_split_sequences() {
+        let out = strip_ansi(&[b"\x1b[3", b"1mred\x1b[0m"]);
+        assert_eq!(out, "red");
+
+        let out = strip_ansi(&[b"\x1b(", b"Bcharset\x1b", b"\x1b[0m"]);
+        assert_eq!(out, "charset");
+
+        let out = strip_ansi(&[b"\x1b", b"[", b"1", b";", b"31", b"m", b"bold red"]);
+        assert_eq!(out, "bold red");
+        assert!(!out.bytes().any(|b| b == 0x1b));
+    }
+
+    fn ui_with_ttys(input: bool, out: bool, err: bool) -> UI {
+        UI::new(Shell::new(InputStream::new(Box::new(io::empty()), input),
+                           OutputStream::new(WriteStream::Write(Box::new(io::sink())),
+                                             ColorChoice::Never,
+                                             out),
+                           OutputStream::new(WriteStream::Write(Box::new(io::sink())),
+                                             ColorChoice::Never,
+                                             err)))
+    }
+
+    #[test]
+    fn is_interactive_requires_every_stream_to_be_a_terminal() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+
+        assert!(ui_with_ttys(true, true, true).is_interactive());
+        assert!(!ui_with_ttys(false, true, true).is_interactive());
+        assert!(!ui_with_ttys(true, false, true).is_interactive());
+        assert!(!ui_with_ttys(true, true, false).is_interactive());
+    }
+
+    #[test]
+    fn is_interactive_is_false_when_noninteractive_is_set() {
+        let lock = lock_noninteractive_var();
+        lock.set("true");
+        assert!(!ui_with_ttys(true, true, true).is_interactive());
+        lock.set("1");
+        assert!(!ui_with_ttys(true, true, true).is_interactive());
+        lock.set("0");
+        assert!(ui_with_ttys(true, true, true).is_interactive());
+    }
+
+    #[test]
+    fn is_interactive_is_false_when_assuming_yes() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+
+        let mut ui = ui_with_ttys(true, true, true);
+        ui.set_assume_yes(true);
+        assert!(!ui.is_interactive());
+    }
+
+    fn tty_ui_with_input(input: &str) -> (UI, OutputBuffer) {
+        let stdout_buf = OutputBuffer::new();
+        let stdout = stdout_buf.clone();
+        let ui = UI::with_streams(Box::new(Cursor::new(input.as_bytes().to_vec())),
+                                  move || Box::new(stdout.clone()),
+                                  || Box::new(io::sink()),
+                                  ColorChoice::Never,
+                                  true);
+        (ui, stdout_buf)
+    }
+
+    #[test]
+    fn confirm_destructive_asks_for_the_phrase_on_a_terminal() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+
+        let (mut ui, stdout) = tty_ui_with_input("stable\n");
+        assert!(ui.confirm_destructive("This deletes the stable channel", "stable")
+                  .unwrap());
+        assert!(stdout.contents()
+                      .contains("Are you sure? Type stable to continue: "));
+
+        let (mut ui, _) = tty_ui_with_input("yes\n");
+        assert!(!ui.confirm_destructive("This deletes the stable channel", "stable")
+                   .unwrap());
+    }
+
+    #[test]
+    fn prompt_ask_multiline_stops_at_terminator() {
+        let (mut ui, stdout) = tty_ui_with_input("first line\nsecond line\n.\nignored\n");
+        let text = ui.prompt_ask_multiline("Description", "").unwrap();
+        assert_eq!(text, "first line\nsecond line");
+        assert!(stdout.contents()
+                      .contains("Description (end with a line containing only '.')"));
+
+        let (mut ui, _) = tty_ui_with_input("one\n.\nEND\n");
+        let text = ui.prompt_ask_multiline("Description", "END").unwrap();
+        assert_eq!(text, "one\n.");
+    }
+
+    #[test]
+    fn prompt_ask_path_accepts_an_existing_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let default = dir.path().to_string_lossy().into_owned();
+        let (mut ui, ..) = ui_with_input("");
+
+        let path = ui.prompt_ask_path("Key cache", Some(&default), true, PathKind::Dir)
+                     .unwrap();
+
+        assert_eq!(path, dir.path());
+        match ui.prompt_ask_path("Key file", Some(&default), true, PathKind::File) {
+            Err(Error::InvalidPath(_, ref reason)) => assert_eq!(reason, "it is not a file"),
+            other => panic!("Expected InvalidPath, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn prompt_ask_path_refuses_a_missing_file_which_must_exist() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let missing = dir.path().join("missing.toml");
+        let (mut ui, ..) = ui_with_input("");
+
+        match ui.prompt_ask_path("Config", missing.to_str(), true, PathKind::File) {
+            Err(Error::InvalidPath(ref path, ref reason)) => {
+                assert_eq!(path, &missing);
+                assert_eq!(reason, "it does not exist");
+            }
+            other => panic!("Expected InvalidPath, got {:?}", other),
+        }
+        assert_eq!(ui.prompt_ask_path("Config", missing.to_str(), false, PathKind::File)
+                     .unwrap(),
+                   missing);
+        match ui.prompt_ask_path("Config", None, true, PathKind::File) {
+            Err(Error::PathRequired(ref question)) => assert_eq!(question, "Config"),
+            other => panic!("Expected PathRequired, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn prompt_ask_path_asks_again_until_the_path_exists() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let dir = tempfile::TempDir::new().unwrap();
+        let existing = dir.path().join("cli.toml");
+        fs::write(&existing, "").unwrap();
+        // The second answer is empty, which takes the default.
+        let input = format!("{}\n", dir.path().join("missing.toml").display());
+        let (mut ui, stdout) = tty_ui_with_input(&input);
+
+        let path = ui.prompt_ask_path("Config", existing.to_str(), true, PathKind::File)
+                     .unwrap();
+
+        assert_eq!(path, existing);
+        assert_eq!(stdout.contents().matches("Config").count(), 2);
+    }
+
+    #[test]
+    fn prompt_int_accepts_a_number_in_range() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let (mut ui, _) = tty_ui_with_input(" 8080 \n");
+
+        assert_eq!(ui.prompt_int("Port", None, Some(1..=65535)).unwrap(), 8080);
+    }
+
+    #[test]
+    fn prompt_int_asks_again_when_out_of_range() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let (mut ui, stdout) = tty_ui_with_input("0\n70000\n443\n");
+
+        assert_eq!(ui.prompt_int("Port", None, Some(1..=65535)).unwrap(), 443);
+        assert_eq!(stdout.contents().matches("Port").count(), 3);
+    }
+
+    #[test]
+    fn prompt_int_asks_again_when_not_a_number() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let (mut ui, stdout) = tty_ui_with_input("three\n2.5\n-3\n");
+
+        assert_eq!(ui.prompt_int("Retries", None, None).unwrap(), -3);
+        assert_eq!(stdout.contents().matches("Retries").count(), 3);
+    }
+
+    #[test]
+    fn prompt_int_takes_the_default_on_an_empty_answer() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let (mut ui, stdout) = tty_ui_with_input("\n");
+
+        assert_eq!(ui.prompt_int("Retries", Some(5), Some(0..=10)).unwrap(), 5);
+        assert!(stdout.contents().contains("5"));
+    }
+
+    #[test]
+    fn prompt_int_checks_the_default_when_there_is_no_one_to_ask() {
+        let (mut ui, ..) = ui_with_input("");
+
+        assert_eq!(ui.prompt_int("Retries", Some(5), Some(0..=10)).unwrap(), 5);
+        match ui.prompt_int("Retries", Some(50), Some(0..=10)) {
+            Err(Error::InvalidNumber(ref value, ref reason)) => {
+                assert_eq!(value, "50");
+                assert_eq!(reason, "it is not between 0 and 10");
+            }
+            other => panic!("Expected InvalidNumber, got {:?}", other),
+        }
+        match ui.prompt_int("Retries", None, None) {
+            Err(Error::NumberRequired(ref question)) => assert_eq!(question, "Retries"),
+            other => panic!("Expected NumberRequired, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn expand_tilde_uses_the_home_dir() {
+        let home = Path::new("/home/hab");
+
+        assert_eq!(expand_tilde("~", Some(home)), PathBuf::from("/home/hab"));
+        assert_eq!(expand_tilde("~/.hab/cache", Some(home)),
+                   PathBuf::from("/home/hab/.hab/cache"));
+        assert_eq!(expand_tilde("~bob/keys", Some(home)),
+                   PathBuf::from("~bob/keys"));
+        assert_eq!(expand_tilde("/hab/cache", Some(home)),
+                   PathBuf::from("/hab/cache"));
+        assert_eq!(expand_tilde("~/keys", None), PathBuf::from("~/keys"));
+    }
+
+    #[test]
+    fn read_key_reads_one_character_when_not_interactive() {
+        let (mut ui, ..) = ui_with_input("yes");
+        assert_eq!(ui.read_key().unwrap(), 'y');
+        assert_eq!(ui.read_key().unwrap(), 'e');
+
+        let (mut ui, ..) = ui_with_input("é\r\u{1b}");
+        assert_eq!(ui.read_key().unwrap(), 'é');
+        assert_eq!(ui.read_key().unwrap(), KEY_ENTER);
+        assert_eq!(ui.read_key().unwrap(), KEY_ESC);
+    }
+
+    #[test]
+    fn read_key_fails_at_the_end_of_input() {
+        let (mut ui, ..) = ui_with_input("");
+        assert!(ui.read_key().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn raw_mode_reads_a_key_without_enter_and_restores_the_terminal() {
+        use std::{ffi::CStr,
+                  fs::OpenOptions,
+                  os::unix::io::{AsRawFd,
+                                 FromRawFd}};
+
+        let (mut master, slave) = unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(fd >= 0, "{}", io::Error::last_os_error());
+            assert_eq!(libc::grantpt(fd), 0);
+            assert_eq!(libc::unlockpt(fd), 0);
+            let name = CStr::from_ptr(libc::ptsname(fd)).to_str()
+                                                        .unwrap()
+                                                        .to_string();
+            (File::from_raw_fd(fd),
+             OpenOptions::new().read(true)
+                               .write(true)
+                               .open(name)
+                               .unwrap())
+        };
+        let lflag = |file: &File| {
+            let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+            assert_eq!(unsafe { libc::tcgetattr(file.as_raw_fd(), &mut termios) },
+                       0);
+            termios.c_lflag
+        };
+        let original = lflag(&slave);
+        assert_ne!(original & libc::ICANON, 0);
+
+        let key = {
+            let _raw = tty::RawMode::enable(slave.as_raw_fd()).unwrap();
+            assert_eq!(lflag(&slave) & (libc::ICANON | libc::ECHO), 0);
+            // Without raw mode, this read would wait for a newline that never comes.
+            master.write_all(b"k").unwrap();
+            read_key_from(&mut &slave).unwrap()
+        };
+
+        assert_eq!(key, 'k');
+        assert_eq!(lflag(&slave), original);
+    }
+
+    #[test]
+    fn prompt_ask_multiline_reads_to_eof_when_not_interactive() {
+        let (mut ui, ..) = ui_with_input("first line\n.\nlast line");
+        let text = ui.prompt_ask_multiline("Description", ".").unwrap();
+        assert_eq!(text, "first line\n.\nlast line");
+    }
+
+    #[test]
+    fn max_width_caps_para() {
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.set_max_width(Some(30));
+        ui.para("The quick brown fox jumps over the lazy dog and keeps running far beyond the \
+                 edge of a very wide terminal")
+          .unwrap();
+        let out = stdout.contents();
+        assert!(out.lines().count() > 2);
+        assert!(out.lines().all(|l| l.trim_end().chars().count() <= 30),
+                "{:?}",
+                out);
+    }
+
+    #[test]
+    fn para_with_prefixes_only_the_first_line() {
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.set_max_width(Some(30));
+        ui.para_with("The quick brown fox jumps over the lazy dog and keeps on running",
+                     2,
+                     "1. ")
+          .unwrap();
+        assert_eq!(stdout.contents(),
+                   "  1. The quick brown fox\n     jumps over the lazy dog\n     and keeps on \
+                    running\n\n");
+    }
+
+    #[test]
+    fn ordered_list_aligns_numbers_past_nine() {
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.set_max_width(Some(30));
+        let mut steps: Vec<String> = (1..=12).map(|n| format!("Step {}", n)).collect();
+        steps[9] = "Restart the supervisor once every package has been promoted".to_string();
+        ui.ordered_list(&steps).unwrap();
+        let out = stdout.contents();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 26);
+        assert_eq!(lines[0], "   1. Step 1");
+        assert_eq!(lines[1], "");
+        assert_eq!(lines[16], "   9. Step 9");
+        assert_eq!(lines[18], "  10. Restart the supervisor");
+        assert_eq!(lines[19], "      once every package has");
+        assert_eq!(lines[20], "      been promoted");
+        assert_eq!(lines[21], "");
+        assert_eq!(lines[22], "  11. Step 11");
+        assert_eq!(lines[24], "  12. Step 12");
+        assert_eq!(lines[25], "");
+    }
+
+    #[test]
+    fn max_width_caps_rule_and_info() {
+        let columns = lock_columns_var();
+        columns.set("200");
+
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.set_wrap_info(true);
+        ui.set_max_width(Some(40));
+        ui.rule().unwrap();
+        ui.info("word ".repeat(20)).unwrap();
+        let out = stdout.contents();
+        let mut lines = out.lines();
+        assert_eq!(lines.next().unwrap(), "-".repeat(40));
+        assert!(lines.all(|l| l.chars().count() <= 40), "{:?}", out);
+
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.rule().unwrap();
+        assert_eq!(stdout.contents(), format!("{}\n", "-".repeat(200)));
+    }
+
+    #[test]
+    fn max_width_alone_leaves_info_unwrapped() {
+        let columns = lock_columns_var();
+        columns.set("200");
+
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.set_max_width(Some(40));
+        assert_eq!(ui.info_wrap_width(), None);
+        ui.info("word ".repeat(20).trim_end()).unwrap();
+        assert_eq!(stdout.contents().lines().count(), 1);
+    }
+
+    #[test]
+    fn divider_with_label_spans_the_width_with_the_label_in_the_middle() {
+        let columns = lock_columns_var();
+        columns.set("200");
+
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.set_max_width(Some(30));
+        let mut ui = RecordingUI::new(ui);
+        ui.divider_with_label("Dependencies").unwrap();
+
+        let out = stdout.contents();
+        let line = out.trim_end_matches('\n');
+        assert_eq!(line.width(), 30, "{:?}", out);
+        let (left, right) = line.split_at(line.find(" Dependencies ").unwrap());
+        assert_eq!(left.width(), 8, "{:?}", out);
+        assert_eq!(right.width(), 22, "{:?}", out);
+        assert_eq!(ui.events(), &[UiEvent::Divider("Dependencies".to_string())]);
+    }
+
+    #[test]
+    fn labelled_rule_truncates_a_label_too_long_for_the_line() {
+        assert_eq!(labelled_rule("Dependencies", 20, GlyphStyle::Ascii),
+                   "--- Dependencies ---");
+        assert_eq!(labelled_rule("a very long label", 12, GlyphStyle::Ascii),
+                   "- a ver... -");
+        assert_eq!(labelled_rule("Dependencies", 3, GlyphStyle::Full), "───");
+    }
+
+    #[test]
+    fn events_go_to_the_event_stream_when_one_is_set() {
+        let (mut ui, stdout, stderr) = ui_with_input("");
+        let events = OutputBuffer::new();
+        ui.set_event_stream(Some(Box::new(events.clone())));
+
+        ui.begin("Installing core/redis").unwrap();
+        ui.status(Status::Downloading, "core/redis").unwrap();
+        ui.end("Installed core/redis").unwrap();
+
+        let events: Vec<serde_json::Value> = events.contents()
+                                                   .lines()
+                                                   .map(|line| serde_json::from_str(line).unwrap())
+                                                   .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0]["event"], "begin");
+        assert_eq!(events[1]["status"], "Downloading");
+        assert_eq!(events[2]["message"], "Installed core/redis");
+        assert_eq!(stdout.contents(), "");
+        assert_eq!(stderr.contents(), "");
+    }
+
+    #[test]
+    fn events_return_to_standard_output_when_the_event_stream_is_unset() {
+        let (mut ui, stdout, _) = ui_with_input("");
+        let events = OutputBuffer::new();
+        ui.set_event_stream(Some(Box::new(events.clone())));
+        ui.set_event_stream(None);
+
+        ui.begin("Installing core/redis").unwrap();
+
+        assert_eq!(events.contents(), "");
+        assert!(stdout.contents().contains("Installing core/redis"));
+    }
+
+    #[test]
+    fn json_events_carry_seq_and_phase() {
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.set_output_mode(OutputMode::Json);
+
+        ui.begin("Installing core/redis").unwrap();
+        ui.set_phase("Downloading");
+        ui.status(Status::Downloading, "core/redis").unwrap();
+        ui.set_phase("Verifying");
+        ui.status(Status::Verified, "core/redis").unwrap();
+        ui.end("Installed core/redis").unwrap();
+
+        let events: Vec<serde_json::Value> = stdout.contents()
+                                                   .lines()
+                                                   .map(|line| serde_json::from_str(line).unwrap())
+                                                   .collect();
+        assert_eq!(events.len(), 4);
+        for (idx, event) in events.iter().enumerate() {
+            assert_eq!(event["seq"], idx as u64 + 1);
+        }
+        assert_eq!(events[0]["event"], "begin");
+        assert!(events[0].get("phase").is_none());
+        assert_eq!(events[1]["status"], "Downloading");
+        assert_eq!(events[1]["phase"], "Downloading");
+        assert_eq!(events[2]["phase"], "Verifying");
+        assert_eq!(events[3]["event"], "end");
+        assert_eq!(events[3]["message"], "Installed core/redis");
+        assert_eq!(events[3]["phase"], "Verifying");
+    }
+
+    fn dependency_warnings() -> Vec<String> {
+        vec!["core/glibc".to_string(),
+             "core/openssl".to_string(),
+             "core/zlib".to_string()]
+    }
+
+    #[test]
+    fn warn_group_in_human_mode_bullets_each_warning() {
+        let (mut ui, stdout, stderr) = ui_with_input("");
+
+        ui.warn_group("3 dependencies not in channel 'stable'",
+                      &dependency_warnings())
+          .unwrap();
+
+        assert_eq!(stdout.contents(), "");
+        let lines: Vec<String> = stderr.contents().lines().map(str::to_string).collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].ends_with("3 dependencies not in channel 'stable'"),
+                "{}",
+                lines[0]);
+        assert_eq!(&lines[1..],
+                   &["  - core/glibc".to_string(),
+                     "  - core/openssl".to_string(),
+                     "  - core/zlib".to_string()]);
+    }
+
+    #[test]
+    fn warn_group_in_json_mode_is_one_object() {
+        let (mut ui, stdout, stderr) = ui_with_input("");
+        ui.set_output_mode(OutputMode::Json);
+
+        ui.warn_group("3 dependencies not in channel 'stable'",
+                      &dependency_warnings())
+          .unwrap();
+
+        assert_eq!(stderr.contents(), "");
+        let contents = stdout.contents();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let group: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(group["level"], "warn_group");
+        assert_eq!(group["title"], "3 dependencies not in channel 'stable'");
+        assert_eq!(group["items"],
+                   serde_json::json!(["core/glibc", "core/openssl", "core/zlib"]));
+    }
+
+    #[test]
+    fn json_value_in_json_mode_is_one_line_of_json() {
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.set_output_mode(OutputMode::Json);
+        let value = serde_json::json!({"ident": "core/redis/4.0.14/20190319155852",
+                                       "channels": ["stable", "unstable"]});
+
+        ui.json_value(&value).unwrap();
+        ui.json_value_or(&value, "core/redis in 2 channels")
+          .unwrap();
+
+        let contents = stdout.contents();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            assert_eq!(serde_json::from_str::<serde_json::Value>(line).unwrap(),
+                       value);
+        }
+    }
+
+    #[test]
+    fn json_value_in_human_mode_is_pretty_or_rendered() {
+        let (mut ui, stdout, _) = ui_with_input("");
+        let value = serde_json::json!({"ident": "core/redis/4.0.14/20190319155852",
+                                       "channels": ["stable", "unstable"]});
+
+        ui.json_value(&value).unwrap();
+        let pretty = stdout.contents();
+        assert!(pretty.lines().count() > 1, "{}", pretty);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&pretty).unwrap(),
+                   value);
+
+        let (mut ui, stdout, _) = ui_with_input("");
+        ui.json_value_or(&value, "core/redis in 2 channels")
+          .unwrap();
+        assert_eq!(stdout.contents(), "core/redis in 2 channels\n");
+    }
+
+    #[cfg(unix)]
+    fn fifo_reader(path: &Path) -> File {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        fs::OpenOptions::new().read(true)
+                              .custom_flags(libc::O_NONBLOCK)
+                              .open(path)
+                              .unwrap()
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn progress_pipe_writes_ndjson_to_a_fifo() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("progress");
+        make_fifo(&path).unwrap();
+        let mut reader = fifo_reader(&path);
+
+        let mut pipe = ProgressPipe::open(&path).unwrap();
+        pipe.size(10);
+        pipe.advance(4);
+        pipe.advance(6);
+        pipe.finish();
+        drop(pipe);
+
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        let events: Vec<serde_json::Value> =
+            contents.lines()
+                    .map(|line| serde_json::from_str(line).unwrap())
+                    .collect();
+        assert_eq!(events,
+                   vec![json!({"event": "size", "current": 0, "total": 10}),
+                        json!({"event": "progress", "current": 4, "total": 10}),
+                        json!({"event": "progress", "current": 10, "total": 10}),
+                        json!({"event": "finish", "current": 10, "total": 10})]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn progress_pipe_without_a_reader_fails_instead_of_blocking() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("progress");
+
+        assert!(ProgressPipe::open(&path).is_err());
+        remove_created_fifos();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn output_mode_from_str() {
+        assert_eq!(OutputMode::from_str("json").unwrap(), OutputMode::Json);
+        assert_eq!(OutputMode::from_str("JSON").unwrap(), OutputMode::Json);
+        assert_eq!(OutputMode::from_str("human").unwrap(), OutputMode::Human);
+        assert_eq!(OutputMode::from_str("yaml").unwrap(), OutputMode::Yaml);
+        assert!(OutputMode::from_str("xml").is_err());
+    }
+
+    #[test]
+    fn console_progress_bar_new_sized_accepts_writes() {
+        let mut bar = ConsoleProgressBar::new_sized(10);
+        assert_eq!(bar.write(b"abcd").unwrap(), 4);
+        assert_eq!(bar.total, 10);
+        assert_eq!(bar.current, 4);
+    }
+
+    #[test]
+    fn bar_styles_map_to_their_format_characters() {
+        assert_eq!(BarStyle::default(), BarStyle::Classic);
+        assert_eq!(BarStyle::Classic.format(), "[=>-]");
+        assert_eq!(BarStyle::Blocks.format(), "[██░]");
+        assert_eq!(BarStyle::Ascii.format(), "[##-]");
+        // pbr takes the format one character at a time.
+        for style in &[BarStyle::Classic, BarStyle::Blocks, BarStyle::Ascii] {
+            assert_eq!(style.format().chars().count(), 5);
+        }
+    }
+
+    #[test]
+    fn bar_styles_are_parsed_by_name() {
+        assert_eq!(BarStyle::from_str("Blocks").unwrap(), BarStyle::Blocks);
+        assert_eq!(BarStyle::from_str("ascii").unwrap(), BarStyle::Ascii);
+        assert_eq!(BarStyle::from_str(&BarStyle::Classic.to_string()).unwrap(),
+                   BarStyle::Classic);
+        match BarStyle::from_str("fancy") {
+            Err(Error::BadProgressStyle(ref style)) => assert_eq!(style, "fancy"),
+            other => panic!("Expected BadProgressStyle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn console_progress_bar_keeps_its_style_when_sized() {
+        let mut bar = ConsoleProgressBar::new_sized(10).with_style(BarStyle::Ascii);
+        bar.size(20);
+        assert_eq!(bar.style(), BarStyle::Ascii);
+        assert_eq!(bar.total, 20);
+    }
+
+    #[test]
+    fn progress_for_labels_the_bar() {
+        let ui = UI::with_streams(Box::new(io::empty()),
+                                  || Box::new(io::sink()),
+                                  || Box::new(io::sink()),
+                                  ColorChoice::Never,
+                                  true);
+
+        let mut bar = ui.progress_for("acme/redis").unwrap();
+        assert_eq!(bar.label(), Some("acme/redis"));
+        assert_eq!(bar_message(bar.label()), "acme/redis  ");
+
+        bar.size(10);
+        assert_eq!(bar.label(), Some("acme/redis"));
+        assert_eq!(ui.progress().unwrap().label(), None);
+    }
+
+    #[test]
+    fn console_spinner_counts_writes() {
+        let out = OutputBuffer::new();
+        let mut spinner = ConsoleSpinner::new(Box::new(NoColor::new(out.clone())));
+        assert_eq!(spinner.write(b"abcd").unwrap(), 4);
+        spinner.size(100);
+        assert_eq!(spinner.write(b"ef").unwrap(), 2);
+        assert_eq!(spinner.current(), 6);
+    }
+
+    #[test]
+    fn console_spinner_stops_drawing_on_a_broken_pipe_without_failing() {
+        let closed = WriteStream::from_write(|| Box::new(BrokenPipeWriter));
+        let out = OutputStream::new(closed, ColorChoice::Never, true).ending_on_broken_pipe();
+        let mut spinner = ConsoleSpinner::new(Box::new(out));
+        assert_eq!(spinner.write(b"abcd").unwrap(), 4);
+        spinner.finish();
+    }
+
+    #[test]
+    fn console_spinner_draws_on_the_stream_it_is_given() {
+        let out = OutputBuffer::new();
+        let mut spinner = ConsoleSpinner::new(Box::new(NoColor::new(out.clone())));
+        spinner.write_all(b"abcd").unwrap();
+        spinner.finish();
+
+        let drawn = out.contents();
+        assert!(drawn.starts_with(&format!("\r{} 4 B", SPINNER_FRAMES[1])));
+        assert!(drawn.ends_with('\n'));
+    }
+
+    fn terminal_ui() -> UI {
+        UI::from_parts(Box::new(io::empty()),
+                       Box::new(io::sink()),
+                       ColorChoice::Never,
+                       true,
+                       Box::new(io::sink()),
+                       ColorChoice::Never,
+                       true)
+    }
+
+    #[test]
+    fn progress_with_total_gives_a_bar_for_a_known_size() {
+        let mut progress = terminal_ui().progress_with_total(Some(10))
+                                        .expect("a progress display on a terminal");
+        assert_eq!(progress.write(b"abcd").unwrap(), 4);
+        assert_eq!(progress.write(b"efghij").unwrap(), 6);
+    }
+
+    #[test]
+    fn progress_with_total_gives_a_spinner_for_an_unknown_size() {
+        // Drawing the spinner is tested through `ConsoleSpinner` itself, as this one draws on
+        // standard output.
+        let mut progress = terminal_ui().progress_with_total(None)
+                                        .expect("a progress display on a terminal");
+        progress.size(3);
+    }
+
+    #[test]
+    fn progress_with_total_gives_nothing_off_a_terminal() {
+        let ui = UI::with_streams(Box::new(io::empty()),
+                                  || Box::new(io::sink()),
+                                  || Box::new(io::sink()),
+                                  ColorChoice::Never,
+                                  false);
+        assert!(ui.progress_with_total(Some(10)).is_none());
+        assert!(ui.progress_with_total(None).is_none());
+    }
+
+    #[derive(Default)]
+    struct CountingProgress {
+        total:    u64,
+        finishes: usize,
+    }
+
+    impl DisplayProgress for CountingProgress {
+        fn size(&mut self, _size: u64) {}
+
+        fn finish(&mut self) { self.finishes += 1; }
+    }
+
+    impl Write for CountingProgress {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.total += buf.len() as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn progress_reader_counts_bytes_and_finishes_once() {
+        let data = vec![7u8; 10_000];
+        let mut reader = ProgressReader::new(&data[..], Some(CountingProgress::default()));
+        let mut copied = Vec::new();
+        assert_eq!(io::copy(&mut reader, &mut copied).unwrap(), 10_000);
+        assert_eq!(reader.read(&mut [0u8; 8]).unwrap(), 0);
+
+        assert_eq!(copied, data);
+        let progress = reader.progress.unwrap();
+        assert_eq!(progress.total, 10_000);
+        assert_eq!(progress.finishes, 1);
+    }
+
+    #[test]
+    fn progress_reader_without_progress_only_reads() {
+        let mut reader = ProgressReader::<_, CountingProgress>::new(&b"hello"[..], None);
+        let mut copied = Vec::new();
+        io::copy(&mut reader, &mut copied).unwrap();
+        assert_eq!(copied, b"hello");
+    }
+
+    #[test]
+    fn format_bytes_boundaries() {
+        assert_eq!(format_bytes(0, ByteUnits::Iec), "0 B");
+        assert_eq!(format_bytes(999, ByteUnits::Iec), "999 B");
+        assert_eq!(format_bytes(1023, ByteUnits::Iec), "1023 B");
+        assert_eq!(format_bytes(1024, ByteUnits::Iec), "1.0 KiB");
+        assert_eq!(format_bytes(1_048_575, ByteUnits::Iec), "1.0 MiB");
+        assert_eq!(format_bytes(1_610_612_736, ByteUnits::Iec), "1.5 GiB");
+        assert_eq!(format_bytes(u64::max_value(), ByteUnits::Iec), "16.0 EiB");
+
+        assert_eq!(format_bytes(0, ByteUnits::Si), "0 B");
+        assert_eq!(format_bytes(999, ByteUnits::Si), "999 B");
+        assert_eq!(format_bytes(1000, ByteUnits::Si), "1.0 kB");
+        assert_eq!(format_bytes(1024, ByteUnits::Si), "1.0 kB");
+        assert_eq!(format_bytes(u64::max_value(), ByteUnits::Si), "18.4 EB");
+    }
+
+    #[test]
+    fn format_rate_appends_per_second() {
+        assert_eq!(format_rate(1536, ByteUnits::Iec), "1.5 KiB/s");
+        assert_eq!(format_rate(12, ByteUnits::Si), "12 B/s");
+    }
+
+    #[test]
+    fn percent_is_clamped_and_handles_zero_total() {
+        assert_eq!(percent(0, 4), 0);
+        assert_eq!(percent(1, 3), 33);
+        assert_eq!(percent(4, 4), 100);
+        assert_eq!(percent(9, 4), 100);
+        assert_eq!(percent(0, 0), 100);
+        assert_eq!(percent(u64::max_value(), u64::max_value()), 100);
+    }
+
+    #[test]
+    fn rewrite_line_clears_to_the_new_width_after_a_shrink() {
+        let mut wide = Vec::new();
+        rewrite_line(&mut wide, &"x".repeat(30), Some(40)).unwrap();
+        assert_eq!(String::from_utf8(wide).unwrap(),
+                   format!("\r{}{}", "x".repeat(30), " ".repeat(10)));
+
+        let mut narrow = Vec::new();
+        rewrite_line(&mut narrow, "abc", Some(20)).unwrap();
+        assert_eq!(String::from_utf8(narrow).unwrap(),
+                   format!("\rabc{}", " ".repeat(17)));
+    }
+
+    #[test]
+    fn rewrite_line_cuts_a_line_wider_than_the_terminal() {
+        let mut out = Vec::new();
+        rewrite_line(&mut out, &"x".repeat(30), Some(20)).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   format!("\r{}", "x".repeat(20)));
+
+        let mut out = Vec::new();
+        rewrite_line(&mut out, "abc", None).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\rabc");
+    }
+
+    #[test]
+    fn rewrite_line_counts_wide_characters_as_the_columns_they_fill() {
+        let mut out = Vec::new();
+        rewrite_line(&mut out, "日本語", Some(5)).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\r日本 ");
+
+        let mut out = Vec::new();
+        rewrite_line(&mut out, "日本", Some(6)).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\r日本  ");
+    }
+
+    #[test]
+    fn progress_iter_yields_all_items_and_reaches_100() {
+        let (mut ui, ..) = ui_with_input("");
+        let mut iter = ui.progress_iter("Promoting", 0..5);
+        assert_eq!(iter.percent(), 0);
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.percent(), 20);
+        let rest: Vec<_> = iter.by_ref().collect();
+        assert_eq!(rest, vec![1, 2, 3, 4]);
+        assert_eq!(iter.percent(), 100);
+    }
+
+    #[test]
+    fn progress_iter_over_nothing_is_complete() {
+        let (mut ui, ..) = ui_with_input("");
+        let mut iter = ui.progress_iter("Promoting", Vec::<u8>::new().into_iter());
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.percent(), 100);
+    }
+
+    #[test]
+    fn progress_steps_counts_steps() {
+        let (mut ui, stdout, _) = ui_with_input("");
+        let mut steps = ui.progress_steps(3);
+        assert_eq!(steps.current(), 0);
+        assert!(!steps.is_complete());
+        steps.step(ui.out(), "core/redis").unwrap();
+        steps.step(ui.out(), "core/nginx").unwrap();
+        assert_eq!(steps.current(), 2);
+        assert!(!steps.is_complete());
+        steps.step(ui.out(), "core/busybox").unwrap();
+        assert_eq!(steps.current(), 3);
+        assert!(steps.is_complete());
+        assert_eq!(stdout.contents(),
+                   "[1/3] core/redis\n[2/3] core/nginx\n[3/3] core/busybox\n");
+    }
+
+    #[test]
+    fn progress_steps_clamps_extra_steps_to_the_total() {
+        let (mut ui, stdout, _) = ui_with_input("");
+        let mut steps = ui.progress_steps(2);
+        for label in &["a", "b", "c"] {
+            steps.step(ui.out(), label).unwrap();
+        }
+        assert_eq!(stdout.contents(), "[1/2] a\n[2/2] b\n[2/2] c\n");
+        assert_eq!(steps.current(), 2);
+        assert_eq!(steps.total(), 2);
+        assert!(steps.is_complete());
+    }
+
+    #[test]
+    fn assume_yes_uses_prompt_ask_default() {
+        let (mut ui, ..) = ui_with_input("typed\nanswer\n");
+        ui.set_assume_yes(true);
+        assert_eq!(ui.prompt_ask("Name", Some("default")).unwrap(), "default");
+        assert_eq!(ui.prompt_ask("Name", None).unwrap(), "typed");
+    }
 }
@@ -0,0 +1,61 @@
+//! The record of the changes made to Builder channels during one run of hab, in the order they
+//! were made, for an audit log of what, say, a CI job changed. The UI keeps the record, and
+//! writes it to `HAB_AUDIT_FILE` when it is finalized.
+
+use serde_json;
+
+use crate::{common::ui::{UIWriter,
+                         UI},
+            error::Result};
+
+/// A change made to a Builder channel, as recorded in a `SessionAudit`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ChannelMutation {
+    Created { origin: String, channel: String },
+    PromotedTo { ident: String, channel: String },
+    DemotedFrom { ident: String, channel: String },
+}
+
+/// Every change made to Builder channels during the session.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SessionAudit {
+    pub mutations: Vec<ChannelMutation>,
+}
+
+impl SessionAudit {
+    /// The changes to Builder channels `ui` has recorded so far. Fails, rather than leave it out
+    /// of the record, on any change which isn't one.
+    pub fn of(ui: &UI) -> Result<Self> {
+        let mutations = ui.changes()
+                          .iter()
+                          .map(|change| serde_json::from_value(change.clone()))
+                          .collect::<serde_json::Result<_>>()?;
+        Ok(SessionAudit { mutations })
+    }
+}
+
+/// Records changes to Builder channels with any UI.
+pub trait RecordMutation {
+    fn record_mutation(&mut self, mutation: ChannelMutation);
+}
+
+impl<U: UIWriter> RecordMutation for U {
+    fn record_mutation(&mut self, mutation: ChannelMutation) { self.record_change(&mutation) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn session_audit_fails_on_a_change_which_is_not_a_channel_mutation() {
+        let (mut ui, _) = UI::from_env_for_tests();
+        ui.record_mutation(ChannelMutation::Created { origin:  "core".to_string(),
+                                                      channel: "bar".to_string(), });
+        assert_eq!(SessionAudit::of(&ui).unwrap().mutations.len(), 1);
+
+        ui.record_change(&"not a mutation");
+        assert!(SessionAudit::of(&ui).is_err());
+    }
+}
@@ -94,6 +94,16 @@ pub fn get() -> App<'static, 'static> {
             (about: "Commands relating to Habitat Builder")
             (aliases: &["b", "bl", "bld"])
             (@setting ArgRequiredElseHelp)
+            (@subcommand auth =>
+                (about: "Commands relating to Habitat Builder auth tokens")
+                (@setting ArgRequiredElseHelp)
+                (@subcommand store =>
+                    (about: "Saves a Builder auth token in the OS keyring, where it is used when \
+                        no token is given by argument, environment or cli.toml")
+                    (@arg AUTH_TOKEN: -z --auth +takes_value
+                        "Authentication token for Builder. Default is from 'HAB_AUTH_TOKEN'")
+                )
+            )
             (@subcommand job =>
                 (about: "Commands relating to Habitat Builder jobs")
                 (aliases: &["j", "jo"])
@@ -195,7 +205,7 @@ pub fn get() -> App<'static, 'static> {
                         "Specify an alternate Builder endpoint. If not specified, the value will \
                          be taken from the HAB_BLDR_URL environment variable if defined. (default: \
                          https://bldr.habitat.sh)")
-                    (@arg CHANNEL: +required + takes_value "The channel name")
+                    (@arg CHANNEL: +required +takes_value "The channel name")
                     (@arg ORIGIN: -o --origin +takes_value {valid_origin}
                         "Sets the origin to which the channel will belong. Default is from \
                         'HAB_ORIGIN' or cli.toml")
@@ -207,10 +217,12 @@ pub fn get() -> App<'static, 'static> {
                         "Specify an alternate Builder endpoint. If not specified, the value will \
                          be taken from the HAB_BLDR_URL environment variable if defined. (default: \
                          https://bldr.habitat.sh)")
-                    (@arg CHANNEL: +required + takes_value "The channel name")
+                    (@arg CHANNEL: +required +takes_value "The channel name")
                     (@arg ORIGIN: -o --origin +takes_value {valid_origin}
                         "Sets the origin to which the channel belongs. Default is from 'HAB_ORIGIN'\
                         or cli.toml")
+                    (@arg YES: -y --yes
+                        "Destroy the channel without typing its name to confirm")
                 )
                 (@subcommand list =>
                     (about: "Lists origin channels")
@@ -222,7 +234,9 @@ pub fn get() -> App<'static, 'static> {
                     (@arg ORIGIN: +takes_value {valid_origin}
                         "The origin for which channels will be listed. Default is from 'HAB_ORIGIN'\
                         or cli.toml")
+                    (@arg AUTH_TOKEN: -z --auth +takes_value "Authentication token for Builder")
                 )
+                (subcommand: sub_bldr_channel_promote_history())
             )
         )
         (@subcommand origin =>
@@ -489,10 +503,55 @@ pub fn get() -> App<'static, 'static> {
                 (@arg BLDR_URL: -u --url +takes_value {valid_url} "Specify an alternate Builder \
                     endpoint. If not specified, the value will be taken from the HAB_BLDR_URL \
                     environment variable if defined. (default: https://bldr.habitat.sh)")
-                (@arg PKG_IDENT: +required +takes_value {valid_fully_qualified_ident} "A fully qualified package identifier \
-                    (ex: core/busybox-static/1.42.2/20170513215502)")
+                (@arg PKG_IDENT: +required +takes_value +multiple {valid_ident_or_env}
+                    "One or more fully qualified package identifiers \
+                    (ex: core/busybox-static/1.42.2/20170513215502), or a single package \
+                    identifier which is resolved to its latest release in the --channel-from \
+                    channel (ex: core/busybox-static). @env stands for the identifier in the \
+                    --ident-from-env variable")
                 (@arg CHANNEL: +required +takes_value "Promote to the specified release channel")
+                (@arg CHANNEL_FROM: --("channel-from") +takes_value conflicts_with[FROM_CHANNEL]
+                    "The channel a package identifier which isn't fully qualified is resolved to \
+                    its latest release in (default: unstable)")
+                (@arg FROM_CHANNEL: --("from-channel") +takes_value
+                    "Promote the latest release of the package in this channel, even when the \
+                    package identifier is fully qualified")
+                (@arg IDENT_FROM_ENV: --("ident-from-env") +takes_value
+                    "The environment variable a package identifier of @env is read from \
+                    (default: HAB_PKG_IDENT)")
                 (@arg AUTH_TOKEN: -z --auth +takes_value "Authentication token for Builder")
+                (@arg OUTPUT_FILE: --("output-file") +takes_value
+                    "Also write the result to this file, as YAML if HAB_OUTPUT_FORMAT is 'yaml' \
+                    and as JSON otherwise")
+                (@arg RETRY_CHANNEL_CREATE: --("retry-channel-create")
+                    "Retry creating the channel when Builder fails transiently, as when many jobs \
+                    promote to a new channel at once")
+                (@arg REQUIRE_NONEMPTY: --("require-nonempty")
+                    "Fail, rather than warn, when there are no packages to promote")
+                (@arg YES: -y --yes
+                    "Promote without asking for confirmation, even when there are more packages \
+                    than HAB_BULK_CONFIRM_THRESHOLD (default: 25)")
+                (@arg CONCURRENCY: --concurrency +takes_value {valid_numeric::<usize>}
+                    "Promote up to this many of the packages at once (default: 1)")
+                (@arg APPEND: --append conflicts_with[REPLACE]
+                    "Add the package to the channel, leaving it in every channel it is already in \
+                    (default)")
+                (@arg REPLACE: --replace requires[REPLACE_FROM]
+                    "Move the package: promote it to the channel, then demote it from the \
+                    --replace-from channel, rolling the promotion back if the demotion fails")
+                (@arg REPLACE_FROM: --("replace-from") +takes_value requires[REPLACE]
+                    conflicts_with[FROM_CHANNEL]
+                    "The channel --replace demotes the package from")
+                (@arg DEMOTE_AFTER: --("demote-after") +takes_value
+                    conflicts_with[REPLACE_FROM FROM_CHANNEL]
+                    "Demote the package from this channel once it has been promoted, keeping the \
+                    promotion if the demotion fails")
+                (@arg ATOMIC: --atomic requires[DEMOTE_AFTER]
+                    "Roll the promotion back if --demote-after fails to demote the package")
+                (@arg WAIT: --wait +takes_value min_values(0) require_equals(true)
+                    {valid_numeric::<u64>}
+                    "Once promoted, wait until Builder shows each fully qualified package in the \
+                    channel, for up to the given number of seconds (default: 60)")
             )
             (@subcommand demote =>
                 (about: "Demote a package from a specified channel")
@@ -504,6 +563,11 @@ pub fn get() -> App<'static, 'static> {
                     (ex: core/busybox-static/1.42.2/20170513215502)")
                 (@arg CHANNEL: +required +takes_value "Demote from the specified release channel")
                 (@arg AUTH_TOKEN: -z --auth +takes_value "Authentication token for Builder")
+                (@arg DRYRUN: -d --("dry-run")
+                    "Just show what would be demoted, don't actually do it")
+                (@arg OUTPUT_FILE: --("output-file") +takes_value
+                    "Also write the result to this file, as YAML if HAB_OUTPUT_FORMAT is 'yaml' \
+                    and as JSON otherwise")
             )
             (@subcommand channels =>
                 (about: "Find out what channels a package belongs to")
@@ -695,6 +759,21 @@ fn alias_term() -> App<'static, 'static> {
     )
 }
 
+fn sub_bldr_channel_promote_history() -> App<'static, 'static> {
+    clap_app!(("promote-history") =>
+        (about: "Lists the packages promoted into and demoted out of a channel, newest first")
+        (@arg BLDR_URL: -u --url +takes_value {valid_url}
+            "Specify an alternate Builder endpoint. If not specified, the value will \
+             be taken from the HAB_BLDR_URL environment variable if defined. (default: \
+             https://bldr.habitat.sh)")
+        (@arg CHANNEL: +required +takes_value "The channel name")
+        (@arg ORIGIN: -o --origin +takes_value {valid_origin}
+            "Sets the origin to which the channel belongs. Default is from 'HAB_ORIGIN' \
+            or cli.toml")
+        (@arg AUTH_TOKEN: -z --auth +takes_value "Authentication token for Builder")
+    )
+}
+
 fn sub_cli_setup() -> App<'static, 'static> {
     clap_app!(@subcommand setup =>
         (about: "Sets up the CLI with reasonable defaults.")
@@ -1191,6 +1270,14 @@ fn valid_ident(val: String) -> result::Result<(), String> {
     }
 }
 
+#[allow(clippy::needless_pass_by_value)] // Signature required by CLAP
+fn valid_ident_or_env(val: String) -> result::Result<(), String> {
+    if val == crate::command::pkg::promote::IDENT_FROM_ENV {
+        return Ok(());
+    }
+    valid_ident(val)
+}
+
 #[allow(clippy::needless_pass_by_value)] // Signature required by CLAP
 fn valid_target(val: String) -> result::Result<(), String> {
     match PackageTarget::from_str(&val) {
@@ -1,4 +1,7 @@
-use crate::{api_client::Client,
+use crate::{api_client::{ChannelCreated,
+                         Client},
+            audit::{ChannelMutation,
+                    RecordMutation},
             common::ui::{Status,
                          UIWriter,
                          UI},
@@ -19,10 +22,20 @@ pub fn start(ui: &mut UI,
 
     ui.status(Status::Creating, format!("channel {}.", channel))?;
 
-    api_client.create_channel(origin, channel, token)
-              .map_err(Error::APIClient)?;
-
-    ui.status(Status::Created, format!("channel {}.", channel))?;
+    let created = api_client.create_channel(origin, channel, token)
+                            .map_err(Error::APIClient)?;
+    match created {
+        ChannelCreated::Created => {
+            ui.record_mutation(ChannelMutation::Created { origin:  origin.to_string(),
+                                                          channel: channel.to_string(), });
+            ui.status(Status::Created, format!("channel {}.", channel))?;
+        }
+        // Only promotion takes an existing channel to be good enough; asked to create one,
+        // it existing already is still a conflict.
+        ChannelCreated::AlreadyExisted => {
+            return Err(Error::ChannelAlreadyExists(channel.to_string()));
+        }
+    }
 
     Ok(())
 }
@@ -1,5 +1,6 @@
 use crate::{api_client::Client,
             common::ui::{Status,
+                         UIReader,
                          UIWriter,
                          UI},
             hcore::ChannelIdent};
@@ -17,6 +18,12 @@ pub fn start(ui: &mut UI,
              -> Result<()> {
     let bldr_client = Client::new(bldr_url, PRODUCT, VERSION, None).map_err(Error::APIClient)?;
 
+    let impact = format!("This destroys the '{}' channel of the '{}' origin",
+                         channel, origin);
+    if !ui.confirm_destructive(&impact, &channel.to_string())? {
+        return Err(Error::ConfirmationDeclined(impact));
+    }
+
     ui.status(Status::Deleting, format!("channel {}.", channel))?;
 
     bldr_client.delete_channel(origin, channel, token)
@@ -0,0 +1,89 @@
+use std::io::{self,
+              Write};
+
+use tabwriter::TabWriter;
+
+use crate::{api_client::{ChannelEvent,
+                         Client},
+            common::ui::{Status,
+                         UIWriter,
+                         UI},
+            hcore::ChannelIdent};
+
+use crate::{error::{Error,
+                    Result},
+            PRODUCT,
+            VERSION};
+
+pub fn start(ui: &mut UI,
+             bldr_url: &str,
+             origin: &str,
+             channel: &ChannelIdent,
+             token: Option<&str>)
+             -> Result<()> {
+    let api_client = Client::new(bldr_url, PRODUCT, VERSION, None).map_err(Error::APIClient)?;
+
+    ui.status(Status::Determining,
+              format!("promotion history of channel {}.", channel))?;
+
+    let events = api_client.channel_history(origin, channel, token)
+                           .map_err(Error::APIClient)?;
+    ui.raw(history_table(events)?.as_bytes())?;
+    Ok(())
+}
+
+/// Renders channel events as a table, newest first. Events without a readable timestamp are
+/// listed last.
+fn history_table(mut events: Vec<ChannelEvent>) -> Result<String> {
+    events.sort_by(|a, b| b.timestamp().cmp(&a.timestamp()));
+
+    let mut tw = TabWriter::new(vec![]);
+    writeln!(&mut tw, "TIME\tOPERATION\tPACKAGE\tREQUESTER")?;
+    for event in events {
+        writeln!(&mut tw,
+                 "{}\t{}\t{}\t{}",
+                 event.created_at,
+                 event.operation,
+                 event.ident,
+                 event.requester.as_ref().map(String::as_str).unwrap_or("-"))?;
+    }
+    tw.flush()?;
+    let written = tw.into_inner().map_err(io::Error::from)?;
+    Ok(String::from_utf8_lossy(&written).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api_client::ChannelOperation;
+
+    fn event(operation: ChannelOperation, ident: &str, created_at: &str) -> ChannelEvent {
+        ChannelEvent { operation,
+                       ident: ident.to_string(),
+                       created_at: created_at.to_string(),
+                       requester: None }
+    }
+
+    #[test]
+    fn history_table_lists_newest_first() {
+        let events = vec![event(ChannelOperation::Promote,
+                                "core/redis/4.0.10/20180608091936",
+                                "2018-06-09T10:00:00Z"),
+                          event(ChannelOperation::Demote,
+                                "core/redis/4.0.10/20180608091936",
+                                "garbage"),
+                          event(ChannelOperation::Promote,
+                                "core/redis/4.0.14/20190319155852",
+                                "2019-03-20T10:00:00Z")];
+        let table = history_table(events).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("TIME"));
+        assert!(lines[1].starts_with("2019-03-20T10:00:00Z"));
+        assert!(lines[1].contains("core/redis/4.0.14/20190319155852"));
+        assert!(lines[2].starts_with("2018-06-09T10:00:00Z"));
+        assert!(lines[3].starts_with("garbage"));
+        assert!(lines[3].contains("demote"));
+    }
+}
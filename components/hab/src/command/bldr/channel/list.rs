@@ -1,23 +1,66 @@
+use std::io::{self,
+              Write};
+
+use tabwriter::TabWriter;
+
 use crate::{api_client::Client,
             common::ui::{Status,
                          UIWriter,
-                         UI}};
+                         UI},
+            hcore::ChannelIdent};
 
 use crate::{error::{Error,
                     Result},
             PRODUCT,
             VERSION};
 
-pub fn start(ui: &mut UI, bldr_url: &str, origin: &str) -> Result<()> {
+pub fn start(ui: &mut UI, bldr_url: &str, origin: &str, token: Option<&str>) -> Result<()> {
     let api_client = Client::new(bldr_url, PRODUCT, VERSION, None).map_err(Error::APIClient)?;
 
     ui.status(Status::Determining, format!("channels for {}.", origin))?;
 
-    match api_client.list_channels(origin, false) {
+    match api_client.list_channels(origin, token) {
         Ok(channels) => {
-            println!("{}", channels.join("\n"));
+            let mut tw = TabWriter::new(vec![]);
+            writeln!(&mut tw, "CHANNEL")?;
+            for channel in pinned_first(channels) {
+                writeln!(&mut tw, "{}", channel)?;
+            }
+            tw.flush()?;
+            let written = tw.into_inner().map_err(io::Error::from)?;
+            ui.raw(&written)?;
             Ok(())
         }
         Err(e) => Err(Error::APIClient(e)),
     }
 }
+
+/// Orders channels with `stable` and `unstable` first, followed by the rest alphabetically.
+fn pinned_first(mut channels: Vec<ChannelIdent>) -> Vec<ChannelIdent> {
+    let pinned = [ChannelIdent::stable(), ChannelIdent::unstable()];
+    channels.sort_by_key(|c| {
+                match pinned.iter().position(|p| p == c) {
+                    Some(idx) => (idx, String::new()),
+                    None => (pinned.len(), c.to_string()),
+                }
+            });
+    channels
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stable_and_unstable_are_pinned_first() {
+        let channels = vec![ChannelIdent::from("zeta"),
+                            ChannelIdent::unstable(),
+                            ChannelIdent::from("alpha"),
+                            ChannelIdent::stable()];
+        assert_eq!(pinned_first(channels),
+                   vec![ChannelIdent::stable(),
+                        ChannelIdent::unstable(),
+                        ChannelIdent::from("alpha"),
+                        ChannelIdent::from("zeta")]);
+    }
+}
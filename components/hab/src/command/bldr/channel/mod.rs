@@ -1,3 +1,4 @@
 pub mod create;
 pub mod destroy;
+pub mod history;
 pub mod list;
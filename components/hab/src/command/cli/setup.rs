@@ -302,8 +302,8 @@ fn prompt_auth_token(ui: &mut UI) -> Result<String> {
         }
         None => henv::var(AUTH_TOKEN_ENVVAR).ok(),
     };
-    Ok(ui.prompt_ask("Habitat personal access token",
-                     default.as_ref().map(|x| &**x))?)
+    Ok(ui.prompt_ask_secret_default("Habitat personal access token",
+                                    default.as_ref().map(|x| &**x))?)
 }
 
 fn prompt_ctl_secret(ui: &mut UI) -> Result<String> {
@@ -319,8 +319,8 @@ fn prompt_ctl_secret(ui: &mut UI) -> Result<String> {
         }
         None => henv::var(CTL_SECRET_ENVVAR).ok(),
     };
-    Ok(ui.prompt_ask("Habitat Supervisor CtlGateway secret",
-                     default.as_ref().map(|x| &**x))?)
+    Ok(ui.prompt_ask_secret_default("Habitat Supervisor CtlGateway secret",
+                                    default.as_ref().map(|x| &**x))?)
 }
 
 fn ask_enable_analytics(ui: &mut UI, analytics_path: &Path) -> Result<bool> {
@@ -32,9 +32,7 @@ pub fn start(ui: &mut UI, bldr_url: &str, ident: &PackageIdent, token: Option<&s
 
     ui.begin(format!("Retrieving channels for {}", ident))?;
     let channels = api_client.package_channels(ident, token)?;
-    for channel in &channels {
-        println!("{}", channel);
-    }
+    ui.json_value_or(&channels, channels.join("\n"))?;
 
     Ok(())
 }
@@ -7,16 +7,41 @@
 //! ```
 //! This will demote the acme package specified from the stable channel, removing it.
 //!
+//! ```bash
+//! $ hab pkg demote --dry-run acme/redis/2.0.7/2112010203120101 stable
+//! ```
+//! This will report whether the package would be removed from the stable channel, without
+//! modifying Builder.
+//!
+//! `start_stale` cleans up a channel by demoting every package which was promoted into it longer
+//! ago than a given age, as recorded by the channel's history, once that has been confirmed.
+//!
 //! Notes:
 //!    The package should already have been uploaded to Builder.
 //!    If the specified channel does not exist, this will fail.
-//!
+//!    Packages cannot be demoted from unstable, and demoting from stable is warned about.
+
+use std::{collections::HashMap,
+          str::FromStr,
+          time::Duration};
+
+use chrono::{DateTime,
+             Utc};
 
-use crate::{api_client::Client,
+use super::{BulkOutcome,
+            ExecutionStrategy};
+use crate::{api_client::{self,
+                         ChannelEvent,
+                         ChannelOperation,
+                         Client},
+            audit::{ChannelMutation,
+                    RecordMutation},
             common::ui::{Status,
+                         UIReader,
                          UIWriter,
                          UI},
-            hcore::{package::PackageIdent,
+            hcore::{package::{PackageIdent,
+                              PackageTarget},
                     ChannelIdent}};
 
 use crate::{error::{Error,
@@ -26,34 +51,537 @@ use crate::{error::{Error,
 
 /// Demote a package from the specified channel.
 ///
+/// With `ExecutionStrategy::DryRun`, only the package's channel membership is read and the
+/// demotion that would happen is reported.
+///
 /// # Failures
 ///
 /// * Fails if it cannot find the specified package in Builder.
 /// * Fails if the channel specified does not exist.
+/// * Fails if the package is not in the channel specified.
 /// * Fails if "unstable" is the channel specified.
 pub fn start(ui: &mut UI,
              bldr_url: &str,
              ident: &PackageIdent,
              channel: &ChannelIdent,
-             token: &str)
+             token: &str,
+             execution_strategy: ExecutionStrategy)
              -> Result<()> {
     let api_client = Client::new(bldr_url, PRODUCT, VERSION, None)?;
+    demote(ui, &api_client, ident, channel, token, execution_strategy)
+}
+
+/// Demote every package in the specified channel which was promoted into it more than
+/// `older_than` ago, continuing past failures. With `ExecutionStrategy::DryRun`, the stale
+/// packages are only reported.
+///
+/// # Failures
+///
+/// * Fails if Builder cannot be reached or rejects the auth token
+/// * Fails if the channel's history cannot be read from Builder
+/// * Fails if "stable" or "unstable" is the channel specified
+/// * Fails if the demotions are not confirmed, or there is no one to confirm them
+pub fn start_stale(ui: &mut UI,
+                   bldr_url: &str,
+                   origin: &str,
+                   channel: &ChannelIdent,
+                   older_than: Duration,
+                   token: &str,
+                   execution_strategy: ExecutionStrategy)
+                   -> Result<BulkOutcome> {
+    let api_client = Client::new(bldr_url, PRODUCT, VERSION, None)?;
+    api_client.ping(Some(token))?;
+    // An age too great for chrono is one no package can have reached.
+    let older_than =
+        chrono::Duration::from_std(older_than).unwrap_or_else(|_| chrono::Duration::max_value());
+    demote_stale(ui,
+                 &api_client,
+                 origin,
+                 channel,
+                 Utc::now(),
+                 older_than,
+                 token,
+                 execution_strategy)
+}
+
+/// The Builder calls a demotion makes.
+trait DemoteApi {
+    fn package_channels(&self,
+                        ident: &PackageIdent,
+                        token: Option<&str>)
+                        -> api_client::Result<Vec<String>>;
+
+    fn demote_package(&self,
+                      ident: &PackageIdent,
+                      channel: &ChannelIdent,
+                      target: PackageTarget,
+                      token: &str)
+                      -> api_client::Result<()>;
+
+    fn channel_history(&self,
+                       origin: &str,
+                       channel: &ChannelIdent,
+                       token: Option<&str>)
+                       -> api_client::Result<Vec<ChannelEvent>>;
+}
+
+impl DemoteApi for Client {
+    fn package_channels(&self,
+                        ident: &PackageIdent,
+                        token: Option<&str>)
+                        -> api_client::Result<Vec<String>> {
+        Client::package_channels(self, ident, token)
+    }
 
+    fn demote_package(&self,
+                      ident: &PackageIdent,
+                      channel: &ChannelIdent,
+                      target: PackageTarget,
+                      token: &str)
+                      -> api_client::Result<()> {
+        Client::demote_package(self, ident, channel, target, token)
+    }
+
+    fn channel_history(&self,
+                       origin: &str,
+                       channel: &ChannelIdent,
+                       token: Option<&str>)
+                       -> api_client::Result<Vec<ChannelEvent>> {
+        Client::channel_history(self, origin, channel, token)
+    }
+}
+
+fn demote<U, A>(ui: &mut U,
+                api_client: &A,
+                ident: &PackageIdent,
+                channel: &ChannelIdent,
+                token: &str,
+                execution_strategy: ExecutionStrategy)
+                -> Result<()>
+    where U: UIWriter,
+          A: DemoteApi
+{
     ui.begin(format!("Demoting {} from {}", ident, channel))?;
 
     if channel == &ChannelIdent::unstable() {
         return Err(Error::CannotRemoveFromChannel((ident.to_string(), channel.to_string())));
     }
+    if channel == &ChannelIdent::stable() {
+        ui.warn(format!("Demoting {} from stable; anything installing it from stable will no \
+                         longer find this release",
+                        ident))?;
+    }
 
-    match api_client.demote_package(ident, channel, token) {
-        Ok(_) => (),
-        Err(e) => {
-            println!("Failed to demote '{}': {:?}", ident, e);
-            return Err(Error::from(e));
+    if let ExecutionStrategy::DryRun = execution_strategy {
+        let channels = api_client.package_channels(ident, Some(token))?;
+        if channels.iter().any(|c| *c == channel.to_string()) {
+            ui.status(Status::DryRunDeleting,
+                      format!("{} from channel '{}'", ident, channel))?;
+        } else {
+            ui.status(Status::Skipping,
+                      format!("{}, not in channel '{}'", ident, channel))?;
         }
+        return Ok(());
     }
 
+    let demoted = api_client.demote_package(ident, channel, PackageTarget::active_target(), token)
+                            .map_err(Error::from);
+    ui.with_context(&format!("demoting {} from '{}'", ident, channel), demoted)?;
+
+    ui.record_mutation(ChannelMutation::DemotedFrom { ident:   ident.to_string(),
+                                                      channel: channel.to_string(), });
     ui.status(Status::Demoted, ident)?;
 
     Ok(())
 }
+
+#[allow(clippy::too_many_arguments)]
+fn demote_stale<U, A>(ui: &mut U,
+                      api_client: &A,
+                      origin: &str,
+                      channel: &ChannelIdent,
+                      now: DateTime<Utc>,
+                      older_than: chrono::Duration,
+                      token: &str,
+                      execution_strategy: ExecutionStrategy)
+                      -> Result<BulkOutcome>
+    where U: UIReader + UIWriter,
+          A: DemoteApi
+{
+    if channel == &ChannelIdent::stable() || channel == &ChannelIdent::unstable() {
+        return Err(Error::CannotDemoteStaleFrom(channel.to_string()));
+    }
+
+    let events = api_client.channel_history(origin, channel, Some(token))?;
+    let stale = stale_packages(&events, now, older_than);
+    if let ExecutionStrategy::Run = execution_strategy {
+        if !stale.is_empty() {
+            let impact = format!("This demotes {} packages from channel '{}'",
+                                 stale.len(),
+                                 channel);
+            if !ui.confirm_destructive(&impact, &channel.to_string())? {
+                return Err(Error::ConfirmationDeclined(impact));
+            }
+        }
+    }
+    let mut outcome = BulkOutcome::default();
+    for ident in stale {
+        let result = demote(ui, api_client, &ident, channel, token, execution_strategy);
+        outcome.record(&ident, &result);
+    }
+    if let ExecutionStrategy::Run = execution_strategy {
+        outcome.print_summary(ui, Status::Demoted, "not in channel")?;
+    }
+    Ok(outcome)
+}
+
+/// The packages which a channel's history shows are still in the channel, having last been
+/// promoted into it more than `older_than` before `now`, oldest first. Events without a readable
+/// timestamp can't be placed in the history and are ignored.
+fn stale_packages(events: &[ChannelEvent],
+                  now: DateTime<Utc>,
+                  older_than: chrono::Duration)
+                  -> Vec<PackageIdent> {
+    let mut timed: Vec<_> = events.iter()
+                                  .filter_map(|e| e.timestamp().map(|t| (t, e)))
+                                  .collect();
+    timed.sort_by_key(|&(time, _)| time);
+
+    let mut latest = HashMap::new();
+    for (time, event) in timed {
+        latest.insert(event.ident.as_str(), (time, event.operation));
+    }
+
+    let mut stale: Vec<_> =
+        latest.into_iter()
+              .filter(|&(_, (time, operation))| {
+                  operation == ChannelOperation::Promote
+                  && now.signed_duration_since(time) > older_than
+              })
+              .filter_map(|(ident, (time, _))| {
+                  PackageIdent::from_str(ident).ok().map(|i| (time, i))
+              })
+              .collect();
+    stale.sort_by(|a, b| {
+             a.0
+              .cmp(&b.0)
+              .then_with(|| a.1.to_string().cmp(&b.1.to_string()))
+         });
+    stale.into_iter().map(|(_, ident)| ident).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{audit::SessionAudit,
+                command::pkg::fake_api::FakeApi,
+                common::ui::{RecordingUI,
+                             UiEvent}};
+
+    impl DemoteApi for FakeApi {
+        fn package_channels(&self,
+                            ident: &PackageIdent,
+                            _token: Option<&str>)
+                            -> api_client::Result<Vec<String>> {
+            self.answer_package_channels(ident)
+        }
+
+        fn demote_package(&self,
+                          ident: &PackageIdent,
+                          channel: &ChannelIdent,
+                          _target: PackageTarget,
+                          _token: &str)
+                          -> api_client::Result<()> {
+            if let Some(answer) = self.answer_demote(ident, channel) {
+                return answer;
+            }
+            if !self.channels.contains(&channel.to_string()) {
+                return Err(api_client::Error::PackageNotInChannel(ident.to_string(),
+                                                                  channel.to_string()));
+            }
+            Ok(())
+        }
+
+        fn channel_history(&self,
+                           _origin: &str,
+                           _channel: &ChannelIdent,
+                           _token: Option<&str>)
+                           -> api_client::Result<Vec<ChannelEvent>> {
+            Ok(self.history.clone())
+        }
+    }
+
+    fn ident() -> PackageIdent {
+        PackageIdent::from_str("core/redis/4.0.14/20190319155852").unwrap()
+    }
+
+    fn event(operation: ChannelOperation, ident: &str, created_at: &str) -> ChannelEvent {
+        ChannelEvent { operation,
+                       ident: ident.to_string(),
+                       created_at: created_at.to_string(),
+                       requester: None }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2019-04-01T00:00:00Z").unwrap()
+                                                            .with_timezone(&Utc)
+    }
+
+    fn mixed_history() -> Vec<ChannelEvent> {
+        vec![event(ChannelOperation::Promote,
+                   "core/redis/4.0.14/20190319155852",
+                   "2019-01-10T12:00:00Z"),
+             event(ChannelOperation::Promote,
+                   "core/nginx/1.15.6/20190115235725",
+                   "2019-03-30T12:00:00Z"),
+             event(ChannelOperation::Promote,
+                   "core/busybox/1.30.1/20190101000000",
+                   "2019-01-02T12:00:00Z"),
+             event(ChannelOperation::Demote,
+                   "core/busybox/1.30.1/20190101000000",
+                   "2019-01-03T12:00:00Z"),
+             event(ChannelOperation::Promote,
+                   "core/glibc/2.27/20190115002733",
+                   "2019-01-05T12:00:00Z"),
+             event(ChannelOperation::Promote,
+                   "core/zlib/1.2.11/20190115003728",
+                   "yesterday")]
+    }
+
+    #[test]
+    fn stale_packages_are_those_promoted_before_the_threshold() {
+        let stale = stale_packages(&mixed_history(), now(), chrono::Duration::days(30));
+
+        assert_eq!(stale,
+                   vec![PackageIdent::from_str("core/glibc/2.27/20190115002733").unwrap(),
+                        ident()]);
+    }
+
+    #[test]
+    fn a_package_promoted_again_is_as_old_as_its_last_promotion() {
+        let history = vec![event(ChannelOperation::Promote,
+                                 "core/redis/4.0.14/20190319155852",
+                                 "2019-01-10T12:00:00Z"),
+                           event(ChannelOperation::Demote,
+                                 "core/redis/4.0.14/20190319155852",
+                                 "2019-01-11T12:00:00Z"),
+                           event(ChannelOperation::Promote,
+                                 "core/redis/4.0.14/20190319155852",
+                                 "2019-03-31T12:00:00Z")];
+
+        assert!(stale_packages(&history, now(), chrono::Duration::days(30)).is_empty());
+    }
+
+    #[test]
+    fn demote_stale_demotes_only_stale_packages() {
+        let (mut ui, _) = UI::from_env_for_tests();
+        ui.set_assume_yes(true);
+        let api = FakeApi::with_history(mixed_history());
+
+        let outcome = demote_stale(&mut ui,
+                                   &api,
+                                   "core",
+                                   &ChannelIdent::from("bar"),
+                                   now(),
+                                   chrono::Duration::days(30),
+                                   "token",
+                                   ExecutionStrategy::Run).unwrap();
+
+        assert_eq!(api.demoted(),
+                   vec!["core/glibc/2.27/20190115002733".to_string(),
+                        "core/redis/4.0.14/20190319155852".to_string()]);
+        assert!(outcome.is_success());
+        assert_eq!(outcome.succeeded.len(), 2);
+    }
+
+    #[test]
+    fn demote_stale_needs_confirmation() {
+        let (mut ui, _) = UI::from_env_for_tests();
+        let api = FakeApi::with_history(mixed_history());
+
+        match demote_stale(&mut ui,
+                           &api,
+                           "core",
+                           &ChannelIdent::from("bar"),
+                           now(),
+                           chrono::Duration::days(30),
+                           "token",
+                           ExecutionStrategy::Run)
+        {
+            Err(Error::HabitatCommon(crate::common::Error::ConfirmationRequired(ref impact))) => {
+                assert_eq!(impact, "This demotes 2 packages from channel 'bar'")
+            }
+            other => panic!("Expected ConfirmationRequired, got {:?}", other),
+        }
+        assert!(api.demoted().is_empty());
+    }
+
+    #[test]
+    fn demote_stale_dry_run_demotes_nothing() {
+        let (mut ui, output) = UI::from_env_for_tests();
+        let api = FakeApi::with_history(mixed_history());
+
+        demote_stale(&mut ui,
+                     &api,
+                     "core",
+                     &ChannelIdent::from("bar"),
+                     now(),
+                     chrono::Duration::days(30),
+                     "token",
+                     ExecutionStrategy::DryRun).unwrap();
+
+        assert!(api.demoted().is_empty());
+        assert!(output.stdout()
+                      .contains("core/glibc/2.27/20190115002733 from channel 'bar'"));
+    }
+
+    #[test]
+    fn demote_stale_refuses_stable() {
+        let (mut ui, _) = UI::from_env_for_tests();
+        let api = FakeApi::with_history(mixed_history());
+
+        match demote_stale(&mut ui,
+                           &api,
+                           "core",
+                           &ChannelIdent::stable(),
+                           now(),
+                           chrono::Duration::days(30),
+                           "token",
+                           ExecutionStrategy::Run)
+        {
+            Err(Error::CannotDemoteStaleFrom(_)) => (),
+            other => panic!("Expected CannotDemoteStaleFrom, got {:?}", other),
+        }
+        assert!(api.demoted().is_empty());
+    }
+
+    #[test]
+    fn dry_run_previews_removal_without_demoting() {
+        let (mut ui, output) = UI::from_env_for_tests();
+        let api = FakeApi::with_channels(&["unstable", "stable"]);
+
+        demote(&mut ui,
+               &api,
+               &ident(),
+               &ChannelIdent::stable(),
+               "token",
+               ExecutionStrategy::DryRun).unwrap();
+
+        assert!(api.demoted().is_empty());
+        let out = output.stdout();
+        assert!(out.contains("Would be deleted (Dry run) core/redis/4.0.14/20190319155852 from \
+                              channel 'stable'"),
+                "{}",
+                out);
+        assert!(SessionAudit::of(&ui).unwrap().mutations.is_empty());
+    }
+
+    #[test]
+    fn demote_audits_the_demotion() {
+        let (mut ui, _) = UI::from_env_for_tests();
+        let api = FakeApi::with_channels(&["unstable", "bar"]);
+
+        demote(&mut ui,
+               &api,
+               &ident(),
+               &ChannelIdent::from("bar"),
+               "token",
+               ExecutionStrategy::Run).unwrap();
+
+        assert_eq!(SessionAudit::of(&ui).unwrap().mutations,
+                   vec![ChannelMutation::DemotedFrom { ident:   "core/redis/4.0.14/20190319155852"
+                                                                    .to_string(),
+                                                       channel: "bar".to_string(), }]);
+    }
+
+    #[test]
+    fn dry_run_reports_packages_not_in_channel() {
+        let (mut ui, output) = UI::from_env_for_tests();
+        let api = FakeApi::with_channels(&["unstable"]);
+
+        demote(&mut ui,
+               &api,
+               &ident(),
+               &ChannelIdent::stable(),
+               "token",
+               ExecutionStrategy::DryRun).unwrap();
+
+        assert!(api.demoted().is_empty());
+        let out = output.stdout();
+        assert!(out.contains("Skipping core/redis/4.0.14/20190319155852, not in channel 'stable'"),
+                "{}",
+                out);
+        assert!(!out.contains("Would be deleted"));
+    }
+
+    #[test]
+    fn run_demotes_package() {
+        let (mut ui, output) = UI::from_env_for_tests();
+        let api = FakeApi::with_channels(&["unstable", "stable"]);
+
+        demote(&mut ui,
+               &api,
+               &ident(),
+               &ChannelIdent::stable(),
+               "token",
+               ExecutionStrategy::Run).unwrap();
+
+        assert_eq!(api.demoted(),
+                   vec!["core/redis/4.0.14/20190319155852".to_string()]);
+        assert!(output.stdout().contains("Demoted"));
+    }
+
+    #[test]
+    fn demote_from_unstable_is_refused() {
+        let (mut ui, _) = UI::from_env_for_tests();
+        let api = FakeApi::with_channels(&["unstable"]);
+
+        match demote(&mut ui,
+                     &api,
+                     &ident(),
+                     &ChannelIdent::unstable(),
+                     "token",
+                     ExecutionStrategy::Run)
+        {
+            Err(Error::CannotRemoveFromChannel(_)) => (),
+            other => panic!("Expected CannotRemoveFromChannel, got {:?}", other),
+        }
+        assert!(api.demoted().is_empty());
+    }
+
+    #[test]
+    fn demote_from_stable_warns_first() {
+        let (ui, _) = UI::from_env_for_tests();
+        let mut ui = RecordingUI::new(ui);
+        let api = FakeApi::with_channels(&["unstable", "stable"]);
+
+        demote(&mut ui,
+               &api,
+               &ident(),
+               &ChannelIdent::stable(),
+               "token",
+               ExecutionStrategy::Run).unwrap();
+
+        match ui.events()[1] {
+            UiEvent::Warn(ref message) => assert!(message.contains("from stable"), "{}", message),
+            ref other => panic!("Expected a warning before demoting, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn demote_of_package_not_in_channel_fails() {
+        let (mut ui, _) = UI::from_env_for_tests();
+        let api = FakeApi::with_channels(&["unstable"]);
+
+        match demote(&mut ui,
+                     &api,
+                     &ident(),
+                     &ChannelIdent::from("bar"),
+                     "token",
+                     ExecutionStrategy::Run)
+        {
+            Err(Error::APIClient(api_client::Error::PackageNotInChannel(..))) => (),
+            other => panic!("Expected PackageNotInChannel, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! A stand-in for Builder which the promote and demote tests share. Each command's tests
+//! implement its own Builder trait for `FakeApi` from the answers kept here.
+
+use std::{cell::RefCell,
+          str::FromStr};
+
+use crate::{api_client::{self,
+                         ChannelCreated,
+                         ChannelEvent,
+                         OriginSettings,
+                         PromoteResult},
+            hcore::{package::PackageIdent,
+                    ChannelIdent}};
+
+pub struct FakeApi {
+    pub result:           PromoteResult,
+    pub latest:           Option<PackageIdent>,
+    pub channels:         Vec<String>,
+    /// Answers to `package_channels` calls, given before falling back to `channels`.
+    pub channel_answers:  RefCell<Vec<api_client::Result<Vec<String>>>>,
+    pub settings:         Option<OriginSettings>,
+    /// Answers to `origin_settings` calls, given before falling back to `settings`.
+    pub settings_answers: RefCell<Vec<api_client::Result<OriginSettings>>>,
+    pub history:          Vec<ChannelEvent>,
+    pub creates:          RefCell<Vec<api_client::Result<ChannelCreated>>>,
+    pub promotes:         RefCell<Vec<api_client::Result<PromoteResult>>>,
+    pub demotes:          RefCell<Vec<api_client::Result<()>>>,
+    pub calls:            RefCell<Vec<String>>,
+    pub keys:             RefCell<Vec<String>>,
+}
+
+impl FakeApi {
+    pub fn new(result: PromoteResult) -> Self {
+        FakeApi { result,
+                  latest: None,
+                  channels: Vec::new(),
+                  channel_answers: RefCell::new(Vec::new()),
+                  settings: None,
+                  settings_answers: RefCell::new(Vec::new()),
+                  history: Vec::new(),
+                  creates: RefCell::new(Vec::new()),
+                  promotes: RefCell::new(Vec::new()),
+                  demotes: RefCell::new(Vec::new()),
+                  calls: RefCell::new(Vec::new()),
+                  keys: RefCell::new(Vec::new()) }
+    }
+
+    /// Answers successive `promote_package` calls with `promotes`, then with success.
+    pub fn with_promotes(promotes: Vec<api_client::Result<PromoteResult>>) -> Self {
+        FakeApi { promotes: RefCell::new(promotes),
+                  ..FakeApi::new(PromoteResult::Promoted) }
+    }
+
+    /// Answers successive `demote_package` calls with `demotes`, then with success.
+    pub fn with_demotes(demotes: Vec<api_client::Result<()>>) -> Self {
+        FakeApi { demotes: RefCell::new(demotes),
+                  ..FakeApi::new(PromoteResult::Promoted) }
+    }
+
+    pub fn with_channels(channels: &[&str]) -> Self {
+        FakeApi { channels: channels.iter().map(|c| c.to_string()).collect(),
+                  ..FakeApi::new(PromoteResult::Promoted) }
+    }
+
+    /// Answers successive `create_channel` calls with `creates`, then with `Created`.
+    pub fn with_creates(creates: Vec<api_client::Result<ChannelCreated>>) -> Self {
+        FakeApi { creates: RefCell::new(creates),
+                  ..FakeApi::new(PromoteResult::Promoted) }
+    }
+
+    pub fn with_latest(latest: &str) -> Self {
+        FakeApi { latest: Some(PackageIdent::from_str(latest).unwrap()),
+                  ..FakeApi::new(PromoteResult::Promoted) }
+    }
+
+    /// Answers `origin_settings` with a required channel order, rather than as a Builder without
+    /// origin settings.
+    pub fn with_channel_order(order: &[&str]) -> Self {
+        let settings = OriginSettings { required_channel_order: order.iter()
+                                                                     .map(|c| c.to_string())
+                                                                     .collect(), };
+        FakeApi { settings: Some(settings),
+                  ..FakeApi::new(PromoteResult::Promoted) }
+    }
+
+    /// A channel's history of `history`, for a package in the `unstable` and `bar` channels.
+    pub fn with_history(history: Vec<ChannelEvent>) -> Self {
+        FakeApi { history,
+                  ..FakeApi::with_channels(&["unstable", "bar"]) }
+    }
+
+    /// The packages Builder has been asked to demote so far, in order.
+    pub fn demoted(&self) -> Vec<String> {
+        self.calls
+            .borrow()
+            .iter()
+            .filter(|call| call.starts_with("demote "))
+            .filter_map(|call| call.split(' ').nth(1))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Answers a `package_channels` call for `ident`.
+    pub fn answer_package_channels(&self, ident: &PackageIdent) -> api_client::Result<Vec<String>> {
+        self.calls.borrow_mut().push(format!("channels {}", ident));
+        let mut answers = self.channel_answers.borrow_mut();
+        if answers.is_empty() {
+            Ok(self.channels.clone())
+        } else {
+            answers.remove(0)
+        }
+    }
+
+    /// Notes a `demote_package` call, and pops the answer `with_demotes` gave for it, if any.
+    pub fn answer_demote(&self,
+                         ident: &PackageIdent,
+                         channel: &ChannelIdent)
+                         -> Option<api_client::Result<()>> {
+        self.calls
+            .borrow_mut()
+            .push(format!("demote {} {}", ident, channel));
+        let mut demotes = self.demotes.borrow_mut();
+        if demotes.is_empty() {
+            None
+        } else {
+            Some(demotes.remove(0))
+        }
+    }
+}
@@ -6,6 +6,8 @@ pub mod dependencies;
 pub mod env;
 pub mod exec;
 pub mod export;
+#[cfg(test)]
+mod fake_api;
 pub mod hash;
 pub mod header;
 pub mod info;
@@ -19,6 +21,25 @@ pub mod uninstall;
 pub mod upload;
 pub mod verify;
 
+use std::{fs::File,
+          io::{self,
+               Write},
+          path::Path};
+
+use serde::Serialize;
+
+use crate::{common::ui::{Color,
+                         OutputMode,
+                         Status,
+                         UIWriter},
+            error::{Error,
+                    Result},
+            hcore::{package::PackageIdent,
+                    ChannelIdent}};
+
+/// The names Builder accepts for a channel, for telling users what a valid one looks like.
+pub const CHANNEL_NAME_PATTERN: &str = "[A-Za-z0-9_.-]+";
+
 /// Used in commands like uninstall which provide a --dry-run option
 #[derive(Clone, Copy)]
 pub enum ExecutionStrategy {
@@ -45,3 +66,308 @@ pub enum DependencyRelation {
     Requires,
     Supports,
 }
+
+/// The result of applying a Builder operation to several packages. Every package is attempted,
+/// even when an earlier one fails.
+#[derive(Debug, Default, Serialize)]
+pub struct BulkOutcome {
+    pub succeeded: Vec<String>,
+    /// Packages the operation left alone because they were already as it would have made them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped:   Vec<String>,
+    pub failed:    Vec<BulkFailure>,
+}
+
+/// A package the bulk operation could not be applied to, and why.
+#[derive(Debug, Serialize)]
+pub struct BulkFailure {
+    pub ident: String,
+    pub error: String,
+}
+
+#[derive(Serialize)]
+struct BulkSummary<'a> {
+    summary: &'a BulkOutcome,
+}
+
+impl BulkOutcome {
+    /// The outcome of a bulk operation which was given no packages at all.
+    pub fn empty() -> Self { BulkOutcome::default() }
+
+    pub fn record<T, E>(&mut self, ident: &PackageIdent, result: &std::result::Result<T, E>)
+        where E: std::fmt::Display
+    {
+        match *result {
+            Ok(_) => self.succeeded.push(ident.to_string()),
+            Err(ref e) => {
+                self.failed.push(BulkFailure { ident: ident.to_string(),
+                                               error: e.to_string(), })
+            }
+        }
+    }
+
+    /// Records a package the operation had no need to change.
+    pub fn record_skipped(&mut self, ident: &PackageIdent) { self.skipped.push(ident.to_string()) }
+
+    /// True when every package was processed successfully.
+    pub fn is_success(&self) -> bool { self.failed.is_empty() }
+
+    /// True when no package was processed, successfully or not.
+    pub fn is_empty(&self) -> bool {
+        self.succeeded.is_empty() && self.skipped.is_empty() && self.failed.is_empty()
+    }
+
+    /// A one line count of what happened, such as
+    /// `Promoted 18, skipped 2 (already present), failed 1`. Skips and failures are only
+    /// mentioned when there were some.
+    pub fn summary_line(&self, verb: &str, skip_reason: &str) -> String {
+        format!("{} {}", verb, self.summary_counts(skip_reason))
+    }
+
+    fn summary_counts(&self, skip_reason: &str) -> String {
+        let mut counts = self.succeeded.len().to_string();
+        if !self.skipped.is_empty() {
+            counts.push_str(&format!(", skipped {} ({})", self.skipped.len(), skip_reason));
+        }
+        if !self.failed.is_empty() {
+            counts.push_str(&format!(", failed {}", self.failed.len()));
+        }
+        counts
+    }
+
+    /// How the summary should be colored: `Critical` when anything failed, `Warn` when anything
+    /// was skipped, and `Info` otherwise.
+    pub fn summary_severity(&self) -> Color {
+        if !self.failed.is_empty() {
+            Color::Critical
+        } else if !self.skipped.is_empty() {
+            Color::Warn
+        } else {
+            Color::Info
+        }
+    }
+
+    /// Writes the summary line as a `status` when everything succeeded, and otherwise as a
+    /// warning or a fatal message according to `summary_severity`. `status` supplies the verb.
+    pub fn print_summary<U>(&self, ui: &mut U, status: Status, skip_reason: &str) -> io::Result<()>
+        where U: UIWriter
+    {
+        let (_, verb, _) = status.parts();
+        match self.summary_severity() {
+            Color::Critical => ui.fatal(self.summary_line(&verb, skip_reason)),
+            Color::Warn => ui.warn(self.summary_line(&verb, skip_reason)),
+            _ => ui.status(status, self.summary_counts(skip_reason)),
+        }
+    }
+
+    /// The machine-readable summary, wrapped in a top-level `summary` object.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&BulkSummary { summary: self })
+    }
+}
+
+/// Parses a channel name given on the command line, so that one Builder would reject fails here
+/// with a message naming the value, rather than part way through a promotion or demotion.
+pub fn parse_channel(name: &str) -> Result<ChannelIdent> {
+    let valid = !name.is_empty()
+                && name.chars()
+                       .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if valid {
+        Ok(ChannelIdent::from(name))
+    } else {
+        Err(Error::InvalidChannelName(name.to_string()))
+    }
+}
+
+/// Writes a command's structured result to `path`, regardless of what the UI prints. The file is
+/// YAML when `format` is `Yaml` and JSON otherwise, since a human rendering has no use on disk.
+pub fn write_result_file<T>(path: &Path, format: OutputMode, value: &T) -> Result<()>
+    where T: Serialize
+{
+    let contents = match format {
+        OutputMode::Yaml => serde_yaml::to_string(value)?,
+        OutputMode::Human | OutputMode::Json => serde_json::to_string_pretty(value)?,
+    };
+    let mut file = File::create(path)?;
+    writeln!(file, "{}", contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::ui::{UIWriter,
+                            UI};
+    use std::{fs,
+              str::FromStr};
+    use tempfile::TempDir;
+
+    #[test]
+    fn parse_channel_accepts_builder_channel_names() {
+        for name in &["stable", "unstable", "release-1.2_rc", "bldr-1234"] {
+            assert_eq!(parse_channel(name).unwrap(), ChannelIdent::from(*name));
+        }
+    }
+
+    #[test]
+    fn parse_channel_rejects_invalid_names() {
+        for name in &["", "my channel", "qa/staging", "stäble"] {
+            match parse_channel(name) {
+                Err(Error::InvalidChannelName(ref bad)) => assert_eq!(bad, name),
+                other => {
+                    panic!("Expected InvalidChannelName for {:?}, got {:?}",
+                           name, other)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_channel_fatal_message_names_the_value_and_pattern() {
+        let (mut ui, output) = UI::from_env_for_tests();
+
+        ui.fatal(parse_channel("qa/staging").unwrap_err()).unwrap();
+
+        let stderr = output.stderr();
+        assert!(stderr.contains("Invalid channel name 'qa/staging'"),
+                "{}",
+                stderr);
+        assert!(stderr.contains(CHANNEL_NAME_PATTERN), "{}", stderr);
+    }
+
+    #[test]
+    fn bulk_outcome_classifies_results() {
+        let mut outcome = BulkOutcome::default();
+        let good = PackageIdent::from_str("core/redis/4.0.14/20190319155852").unwrap();
+        let bad = PackageIdent::from_str("core/nginx/1.15.6/20190115235725").unwrap();
+
+        outcome.record::<(), String>(&good, &Ok(()));
+        assert!(outcome.is_success());
+
+        outcome.record::<(), String>(&bad, &Err("[403 Forbidden]".to_string()));
+        assert!(!outcome.is_success());
+        assert_eq!(outcome.succeeded, vec![good.to_string()]);
+        assert_eq!(outcome.failed.len(), 1);
+        assert_eq!(outcome.failed[0].ident, bad.to_string());
+    }
+
+    #[test]
+    fn bulk_outcome_json_summary() {
+        let mut outcome = BulkOutcome::default();
+        let good = PackageIdent::from_str("core/redis/4.0.14/20190319155852").unwrap();
+        let bad = PackageIdent::from_str("core/nginx/1.15.6/20190115235725").unwrap();
+        outcome.record::<(), String>(&good, &Ok(()));
+        outcome.record::<(), String>(&bad, &Err("not found".to_string()));
+
+        let json: serde_json::Value = serde_json::from_str(&outcome.to_json().unwrap()).unwrap();
+        assert_eq!(json["summary"]["succeeded"][0],
+                   "core/redis/4.0.14/20190319155852");
+        assert_eq!(json["summary"]["failed"][0]["ident"],
+                   "core/nginx/1.15.6/20190115235725");
+        assert_eq!(json["summary"]["failed"][0]["error"], "not found");
+    }
+
+    #[test]
+    fn empty_outcome_is_empty() {
+        assert!(BulkOutcome::empty().is_empty());
+        assert!(!sample_outcome().is_empty());
+    }
+
+    fn outcome_of(succeeded: usize, skipped: usize, failed: usize) -> BulkOutcome {
+        let ident = PackageIdent::from_str("core/redis/4.0.14/20190319155852").unwrap();
+        let mut outcome = BulkOutcome::default();
+        for _ in 0..succeeded {
+            outcome.record::<(), String>(&ident, &Ok(()));
+        }
+        for _ in 0..skipped {
+            outcome.record_skipped(&ident);
+        }
+        for _ in 0..failed {
+            outcome.record::<(), String>(&ident, &Err("not found".to_string()));
+        }
+        outcome
+    }
+
+    #[test]
+    fn summary_of_all_ok_is_info() {
+        let outcome = outcome_of(18, 0, 0);
+
+        assert_eq!(outcome.summary_line("Promoted", "already present"),
+                   "Promoted 18");
+        assert_eq!(outcome.summary_severity(), Color::Info);
+    }
+
+    #[test]
+    fn summary_with_skips_is_a_warning() {
+        let outcome = outcome_of(18, 2, 0);
+
+        assert_eq!(outcome.summary_line("Promoted", "already present"),
+                   "Promoted 18, skipped 2 (already present)");
+        assert_eq!(outcome.summary_severity(), Color::Warn);
+    }
+
+    #[test]
+    fn summary_with_failures_is_critical() {
+        let outcome = outcome_of(18, 2, 1);
+
+        assert_eq!(outcome.summary_line("Promoted", "already present"),
+                   "Promoted 18, skipped 2 (already present), failed 1");
+        assert_eq!(outcome.summary_severity(), Color::Critical);
+        assert!(!outcome_of(0, 0, 1).is_success());
+    }
+
+    #[test]
+    fn print_summary_writes_by_severity() {
+        let (mut ui, output) = UI::from_env_for_tests();
+
+        outcome_of(3, 0, 0).print_summary(&mut ui, Status::Promoted, "already present")
+                           .unwrap();
+        outcome_of(3, 0, 1).print_summary(&mut ui, Status::Promoted, "already present")
+                           .unwrap();
+
+        assert!(output.stdout().contains("Promoted 3"),
+                "{}",
+                output.stdout());
+        assert!(output.stderr().contains("Promoted 3, failed 1"),
+                "{}",
+                output.stderr());
+    }
+
+    fn sample_outcome() -> BulkOutcome {
+        let mut outcome = BulkOutcome::default();
+        let good = PackageIdent::from_str("core/redis/4.0.14/20190319155852").unwrap();
+        let bad = PackageIdent::from_str("core/nginx/1.15.6/20190115235725").unwrap();
+        outcome.record::<(), String>(&good, &Ok(()));
+        outcome.record::<(), String>(&bad, &Err("not found".to_string()));
+        outcome
+    }
+
+    #[test]
+    fn write_result_file_as_json() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("result.json");
+        write_result_file(&path, OutputMode::Json, &sample_outcome()).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["succeeded"][0], "core/redis/4.0.14/20190319155852");
+        assert_eq!(json["failed"][0]["ident"],
+                   "core/nginx/1.15.6/20190115235725");
+        assert_eq!(json["failed"][0]["error"], "not found");
+    }
+
+    #[test]
+    fn write_result_file_as_yaml() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("result.yaml");
+        write_result_file(&path, OutputMode::Yaml, &sample_outcome()).unwrap();
+
+        let yaml: serde_yaml::Value =
+            serde_yaml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(yaml["succeeded"][0].as_str(),
+                   Some("core/redis/4.0.14/20190319155852"));
+        assert_eq!(yaml["failed"][0]["ident"].as_str(),
+                   Some("core/nginx/1.15.6/20190115235725"));
+        assert_eq!(yaml["failed"][0]["error"].as_str(), Some("not found"));
+    }
+}
@@ -7,60 +7,2535 @@
 //! ```
 //! //! This will promote the acme package specified to the stable channel.
 //!
+//! ```bash
+//! $ HAB_OUTPUT_FORMAT=json hab pkg promote acme/redis/2.0.7/2112010203120101 \
+//!     acme/nginx/1.15.6/20190115235725 stable
+//! ```
+//! This will attempt to promote every listed package and finish with a JSON summary of which
+//! packages succeeded and which failed.
+//!
+//! ```bash
+//! $ hab pkg promote --from-channel unstable acme/redis staging
+//! ```
+//! This will promote the latest acme/redis release in the unstable channel to the staging
+//! channel.
+//!
+//! Release pipelines can promote what a build step just built with `start_from_results`, which
+//! reads the package identifier from the build's results JSON file.
+//!
+//! ```bash
+//! $ HAB_PKG_IDENT=acme/redis/2.0.7/2112010203120101 hab pkg promote @env stable
+//! ```
+//! This will promote the package named by `HAB_PKG_IDENT`, or by the variable given with
+//! `--ident-from-env`, so that CI pipelines needn't interpolate it into the command line.
+//!
 //! Notes:
 //!    The package should already have been uploaded to Builder.
-//!    If the specified channel does not exist, it will be created.
-//!
+//!    If the specified channel does not exist, it will be created. With
+//!    `--retry-channel-create`, creating it is retried when Builder fails transiently, and a
+//!    channel which another promotion created first is used as it is.
+//!    On a terminal, a promotion which fails transiently can be retried on the spot.
+//!    When the origin has a required channel order on Builder, a package is only promoted to
+//!    stable once it is in every channel in it. Otherwise, when `HAB_PROMOTE_REQUIRE_CHANNELS` is
+//!    set to a comma separated list of channels (e.g. `qa,staging`), a package is only promoted
+//!    to stable once it is in all of them.
+//!    A bulk promotion of more packages than `HAB_BULK_CONFIRM_THRESHOLD` (25 by default) must be
+//!    confirmed by typing the channel's name at a prompt, or with `--yes` when no one can be asked.
+//!    Promoting only ever adds a package to a channel (`--append`, the default), leaving it in
+//!    every channel it was already in. To move it instead, `--replace --replace-from <CHANNEL>`
+//!    promotes it and then demotes it from that channel, rolling the promotion back when the
+//!    demotion fails. `--demote-after <CHANNEL>` does the same but keeps the promotion when the
+//!    demotion fails, unless `--atomic` is also given.
+//!    Builder may take a moment to show a package in the channel it was just promoted to. With
+//!    `--wait[=<SECONDS>]`, promote doesn't finish until it does, or the timeout elapses.
+//!    With `--concurrency <N>`, a bulk promotion promotes up to N packages at once. Promotions
+//!    which fail transiently then fail rather than being asked about, and once Builder rate
+//!    limits one promotion, every other one waits as long as it asked.
 
 use crate::{api_client::{self,
-                         Client},
-            common::ui::{Status,
-                         UIWriter,
-                         UI},
-            hcore::{package::PackageIdent,
+                         ChannelCreated,
+                         Client,
+                         ClientBuilder,
+                         OriginSettings,
+                         PackageMetadata,
+                         PromoteResult,
+                         Sleeper,
+                         ThreadSleeper},
+            audit::{ChannelMutation,
+                    RecordMutation},
+            command::pkg::BulkOutcome,
+            common::{command::package::install::{RETRIES,
+                                                 RETRY_WAIT},
+                     ui::{rewrite_line,
+                          terminal_width,
+                          Glyph,
+                          OutputMode,
+                          Status,
+                          StepProgress,
+                          UIReader,
+                          UIWriter,
+                          SPINNER_FRAMES,
+                          UI}},
+            hcore::{env as henv,
+                    package::{PackageIdent,
+                              PackageTarget},
                     ChannelIdent}};
 use hyper::status::StatusCode;
+use retry::retry;
+use std::{collections::HashMap,
+          fs,
+          io::Write,
+          path::Path,
+          str::FromStr,
+          sync::{atomic::{AtomicUsize,
+                          Ordering},
+                 mpsc,
+                 Arc,
+                 Mutex},
+          thread,
+          time::{Duration,
+                 Instant}};
 
 use crate::{error::{Error,
                     Result},
             PRODUCT,
             VERSION};
 
+/// The channels, separated by commas, which a package must already be in before it is promoted
+/// to stable. Unset or empty, there is no such requirement.
+pub const REQUIRE_CHANNELS_ENVVAR: &str = "HAB_PROMOTE_REQUIRE_CHANNELS";
+
+/// The package identifier which stands for one read from an environment variable.
+pub const IDENT_FROM_ENV: &str = "@env";
+
+/// The environment variable a package identifier of `@env` is read from by default.
+pub const PKG_IDENT_ENVVAR: &str = "HAB_PKG_IDENT";
+
+/// The most packages a bulk promotion may promote without being confirmed.
+pub const BULK_CONFIRM_THRESHOLD_ENVVAR: &str = "HAB_BULK_CONFIRM_THRESHOLD";
+
+/// The confirmation threshold used when `HAB_BULK_CONFIRM_THRESHOLD` is unset or not a number.
+pub const DEFAULT_BULK_CONFIRM_THRESHOLD: usize = 25;
+
+/// How long `--wait` waits for a promoted package to be found in its channel, when it isn't told.
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to wait between each look for a promoted package in its channel.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The longest that concurrent promotions pause for when Builder rate limits one of them.
+const MAX_RATE_LIMIT_PAUSE: Duration = Duration::from_secs(60);
+
 /// Promote a package to the specified channel.
 ///
 /// # Failures
 ///
 /// * Fails if it cannot find the specified package in Builder
+/// * Fails if the auth token does not have access to the package's origin
 pub fn start(ui: &mut UI,
              bldr_url: &str,
              ident: &PackageIdent,
              channel: &ChannelIdent,
-             token: &str)
+             token: &str,
+             retry_create: bool)
              -> Result<()> {
     let api_client = Client::new(bldr_url, PRODUCT, VERSION, None)?;
+    promote(ui,
+            &api_client,
+            ident,
+            channel,
+            token,
+            &PromoteOptions::new(retry_create, required_channels())).map(|_| ())
+}
 
-    ui.begin(format!("Promoting {} to channel '{}'", ident, channel))?;
+/// Move a package from `replace_from` to the specified channel: promote it, then demote it from
+/// `replace_from`. When the demotion fails, the promotion is rolled back (unless the package was
+/// already in the channel), so that the package is left in the channels it started in.
+///
+/// # Failures
+///
+/// * Fails as `start` does
+/// * Fails if the package cannot be demoted from `replace_from`
+pub fn start_replace(ui: &mut UI,
+                     bldr_url: &str,
+                     ident: &PackageIdent,
+                     channel: &ChannelIdent,
+                     replace_from: &ChannelIdent,
+                     token: &str,
+                     retry_create: bool)
+                     -> Result<()> {
+    let api_client = Client::new(bldr_url, PRODUCT, VERSION, None)?;
+    promote_then_demote(ui,
+                        &api_client,
+                        ident,
+                        channel,
+                        replace_from,
+                        token,
+                        &PromoteOptions::new(retry_create, required_channels()),
+                        true)
+}
+
+/// Promote a package to the specified channel, then demote it from `demote_after`. A failed
+/// demotion is reported but leaves the promotion in place; `start_replace` is the same, but
+/// rolls the promotion back.
+///
+/// # Failures
+///
+/// * Fails as `start` does
+/// * Fails if the package cannot be demoted from `demote_after`
+pub fn start_demote_after(ui: &mut UI,
+                          bldr_url: &str,
+                          ident: &PackageIdent,
+                          channel: &ChannelIdent,
+                          demote_after: &ChannelIdent,
+                          token: &str,
+                          retry_create: bool)
+                          -> Result<()> {
+    let api_client = Client::new(bldr_url, PRODUCT, VERSION, None)?;
+    promote_then_demote(ui,
+                        &api_client,
+                        ident,
+                        channel,
+                        demote_after,
+                        token,
+                        &PromoteOptions::new(retry_create, required_channels()),
+                        false)
+}
+
+/// Promote the latest release of a package in `from_channel` to `to_channel`, returning the
+/// release which was promoted.
+///
+/// # Failures
+///
+/// * Fails if no release of the package is in `from_channel`
+/// * Fails if the auth token does not have access to the package's origin
+pub fn start_latest(ui: &mut UI,
+                    bldr_url: &str,
+                    ident: &PackageIdent,
+                    from_channel: &ChannelIdent,
+                    to_channel: &ChannelIdent,
+                    token: &str,
+                    retry_create: bool)
+                    -> Result<PackageIdent> {
+    let api_client = Client::new(bldr_url, PRODUCT, VERSION, None)?;
+    promote_latest(ui,
+                   &api_client,
+                   ident,
+                   from_channel,
+                   to_channel,
+                   token,
+                   &PromoteOptions::new(retry_create, required_channels()))
+}
+
+/// Promote the package named by the `ident` field of a build results JSON file, such as
+/// `{ "ident": "acme/redis/2.0.7/20190319155852" }`, to the specified channel.
+///
+/// # Failures
+///
+/// * Fails if the results file cannot be read or is not JSON
+/// * Fails if the results file has no fully qualified `ident`
+/// * Fails as `start` does
+pub fn start_from_results(ui: &mut UI,
+                          bldr_url: &str,
+                          results_path: &Path,
+                          channel: &ChannelIdent,
+                          token: &str,
+                          retry_create: bool)
+                          -> Result<()> {
+    let ident = ident_from_results(results_path)?;
+    start(ui, bldr_url, &ident, channel, token, retry_create)
+}
+
+/// Returns whether promoting the package to the specified channel would change the channels it is
+/// in, that is, whether it is not already in `channel`. Nothing is changed on Builder, so that
+/// pipelines can skip a promotion which a rerun would only repeat.
+///
+/// # Failures
+///
+/// * Fails if the channels the package is in cannot be retrieved from Builder
+pub fn would_change(bldr_url: &str,
+                    ident: &PackageIdent,
+                    channel: &ChannelIdent,
+                    token: &str)
+                    -> Result<bool> {
+    let api_client = Client::new(bldr_url, PRODUCT, VERSION, None)?;
+    promotion_would_change(&api_client, ident, channel, token)
+}
+
+/// Waits until Builder shows the package in the specified channel, which it may not do right away
+/// after the package is promoted there, so that a download which follows the promotion finds it.
+/// On a terminal, a spinner is shown while waiting.
+///
+/// # Failures
+///
+/// * Fails if the channels the package is in cannot be retrieved from Builder
+/// * Fails if the package is not in the channel once `timeout` has elapsed
+pub fn wait_until_in_channel(ui: &mut UI,
+                             bldr_url: &str,
+                             ident: &PackageIdent,
+                             channel: &ChannelIdent,
+                             token: &str,
+                             timeout: Duration)
+                             -> Result<()> {
+    let api_client = Client::new(bldr_url, PRODUCT, VERSION, None)?;
+    wait_for_channel(ui,
+                     &api_client,
+                     &ThreadSleeper,
+                     ident,
+                     channel,
+                     token,
+                     timeout)
+}
+
+/// Reads a package identifier from the environment variable `var`, as CI systems often export
+/// the identifier of what they built.
+///
+/// # Failures
+///
+/// * Fails if `var` is not set
+/// * Fails if the value of `var` is not a valid package identifier
+pub fn ident_from_env(var: &str) -> Result<PackageIdent> {
+    let value = henv::var(var).map_err(|_| Error::IdentEnvVarUnset(var.to_string()))?;
+    PackageIdent::from_str(value.trim()).map_err(|_| {
+                                            Error::InvalidIdentInEnvVar(var.to_string(), value)
+                                        })
+}
+
+fn ident_from_results(results_path: &Path) -> Result<PackageIdent> {
+    let results: serde_json::Value = serde_json::from_str(&fs::read_to_string(results_path)?)?;
+    let invalid = || Error::InvalidBuildResults(results_path.display().to_string());
+    let ident = PackageIdent::from_str(results["ident"].as_str().ok_or_else(invalid)?)?;
+    if ident.fully_qualified() {
+        Ok(ident)
+    } else {
+        Err(invalid())
+    }
+}
+
+/// How `start_bulk` promotes its packages.
+pub struct BulkOptions {
+    /// Whether creating the channel is retried when Builder fails transiently.
+    pub retry_create:     bool,
+    /// Fail, rather than warn, when there are no packages to promote.
+    pub require_nonempty: bool,
+    /// The most packages promoted at once.
+    pub concurrency:      usize,
+}
+
+/// Promote each of the packages to the specified channel, continuing past failures. One client,
+/// and so one pool of connections, is shared by every promotion.
+///
+/// Once every package has been attempted, a one line summary of the packages that were promoted,
+/// skipped and failed is written, or, when the output format is JSON, a summary listing them.
+///
+/// Given no packages, this warns and returns `BulkOutcome::empty()`, or fails when
+/// `require_nonempty` is set, so that an empty list is not mistaken for a successful promotion.
+/// Before anything is asked or promoted, Builder is pinged, so that an unreachable Builder or an
+/// expired token fails the whole promotion up front rather than part way through it.
+///
+/// With a `concurrency` above 1, up to that many packages are promoted at once, each reported as
+/// it finishes, while the outcome still lists them in the order they were given.
+pub fn start_bulk(ui: &mut UI,
+                  bldr_url: &str,
+                  idents: &[PackageIdent],
+                  channel: &ChannelIdent,
+                  token: &str,
+                  options: &BulkOptions)
+                  -> Result<BulkOutcome> {
+    if idents.is_empty() {
+        if options.require_nonempty {
+            return Err(Error::NoPackagesToPromote);
+        }
+        ui.warn("No packages to promote")?;
+        return Ok(BulkOutcome::empty());
+    }
+    let api_client = ClientBuilder::new(bldr_url, PRODUCT, VERSION)?.build()?;
+    api_client.ping(Some(token))?;
+    confirm_bulk(ui, idents.len(), channel, bulk_confirm_threshold())?;
+
+    let promote_options = PromoteOptions::new(options.retry_create, required_channels());
+    let results = if options.concurrency > 1 {
+        promote_concurrently(ui,
+                             PausedApi::new(Arc::new(api_client), ThreadSleeper),
+                             idents,
+                             channel,
+                             token,
+                             options.concurrency,
+                             promote_options)?
+    } else {
+        promote_each(ui, &api_client, idents, channel, token, &promote_options)?
+    };
+
+    let mut outcome = BulkOutcome::default();
+    for (ident, result) in idents.iter().zip(results) {
+        match result {
+            Ok(PromoteResult::AlreadyInChannel) => outcome.record_skipped(ident),
+            // Other failures were written, with what was being done, as they happened.
+            Err(ref e @ Error::InsufficientOriginAccess(..)) => {
+                ui.fatal(e)?;
+                outcome.record(ident, &result)
+            }
+            _ => outcome.record(ident, &result),
+        }
+    }
+
+    if ui.output_mode() == OutputMode::Json {
+        ui.out()
+          .write_all(format!("{}\n", outcome.to_json()?).as_bytes())?;
+        ui.out().flush()?;
+    } else {
+        outcome.print_summary(ui, Status::Promoted, "already present")?;
+    }
+
+    Ok(outcome)
+}
+
+fn bulk_confirm_threshold() -> usize {
+    henv::var(BULK_CONFIRM_THRESHOLD_ENVVAR).ok()
+                                            .and_then(|val| val.trim().parse().ok())
+                                            .unwrap_or(DEFAULT_BULK_CONFIRM_THRESHOLD)
+}
+
+/// Fails unless a bulk promotion of `count` packages may go ahead. One of more than `threshold`
+/// packages is a destructive action, which the UI must be assuming yes for, or have the channel's
+/// name typed at its prompt, and is an error when it is declined or there is no one to prompt,
+/// rather than something done silently.
+fn confirm_bulk<U>(ui: &mut U, count: usize, channel: &ChannelIdent, threshold: usize) -> Result<()>
+    where U: UIReader + UIWriter
+{
+    if count <= threshold {
+        return Ok(());
+    }
+    let impact = format!("This promotes {} packages to channel '{}'", count, channel);
+    if ui.confirm_destructive(&impact, &channel.to_string())? {
+        Ok(())
+    } else {
+        Err(Error::ConfirmationDeclined(impact))
+    }
+}
+
+/// The Builder calls a promotion makes.
+trait PromoteApi {
+    fn create_channel(&self,
+                      origin: &str,
+                      channel: &ChannelIdent,
+                      token: &str)
+                      -> api_client::Result<ChannelCreated>;
+
+    fn promote_package(&self,
+                       ident: &PackageIdent,
+                       channel: &ChannelIdent,
+                       token: &str,
+                       idempotency_key: &str)
+                       -> api_client::Result<PromoteResult>;
+
+    fn demote_package(&self,
+                      ident: &PackageIdent,
+                      channel: &ChannelIdent,
+                      token: &str)
+                      -> api_client::Result<()>;
+
+    fn show_package(&self,
+                    ident: &PackageIdent,
+                    target: PackageTarget,
+                    channel: &ChannelIdent,
+                    token: Option<&str>)
+                    -> api_client::Result<PackageMetadata>;
+
+    fn package_channels(&self,
+                        ident: &PackageIdent,
+                        token: Option<&str>)
+                        -> api_client::Result<Vec<String>>;
+
+    fn origin_settings(&self,
+                       origin: &str,
+                       token: Option<&str>)
+                       -> api_client::Result<OriginSettings>;
+}
+
+impl PromoteApi for Client {
+    fn create_channel(&self,
+                      origin: &str,
+                      channel: &ChannelIdent,
+                      token: &str)
+                      -> api_client::Result<ChannelCreated> {
+        Client::create_channel(self, origin, channel, token)
+    }
+
+    fn promote_package(&self,
+                       ident: &PackageIdent,
+                       channel: &ChannelIdent,
+                       token: &str,
+                       idempotency_key: &str)
+                       -> api_client::Result<PromoteResult> {
+        Client::promote_package_with_key(self, ident, channel, token, idempotency_key)
+    }
+
+    fn demote_package(&self,
+                      ident: &PackageIdent,
+                      channel: &ChannelIdent,
+                      token: &str)
+                      -> api_client::Result<()> {
+        Client::demote_package(self, ident, channel, PackageTarget::active_target(), token)
+    }
+
+    fn show_package(&self,
+                    ident: &PackageIdent,
+                    target: PackageTarget,
+                    channel: &ChannelIdent,
+                    token: Option<&str>)
+                    -> api_client::Result<PackageMetadata> {
+        Client::show_package(self, ident, target, channel, token)
+    }
+
+    fn package_channels(&self,
+                        ident: &PackageIdent,
+                        token: Option<&str>)
+                        -> api_client::Result<Vec<String>> {
+        Client::package_channels(self, ident, token)
+    }
+
+    fn origin_settings(&self,
+                       origin: &str,
+                       token: Option<&str>)
+                       -> api_client::Result<OriginSettings> {
+        Client::origin_settings(self, origin, token)
+    }
+}
+
+/// How `promote` promotes a package.
+struct PromoteOptions {
+    /// Whether creating the channel is retried when Builder fails transiently.
+    retry_create: bool,
+    /// The channels `REQUIRE_CHANNELS_ENVVAR` requires, for origins without a required order.
+    env_required: Vec<ChannelIdent>,
+    /// The channels each origin requires a package to be in before it is promoted to stable, as
+    /// they were first looked up.
+    required:     Mutex<HashMap<String, Vec<ChannelIdent>>>,
+}
 
-    if channel != &ChannelIdent::stable() && channel != &ChannelIdent::unstable() {
-        match api_client.create_channel(&ident.origin, channel, token) {
-            Ok(_) => (),
-            Err(api_client::Error::APIError(StatusCode::Conflict, _)) => (),
+impl PromoteOptions {
+    fn new(retry_create: bool, env_required: Vec<ChannelIdent>) -> Self {
+        PromoteOptions { retry_create,
+                         env_required,
+                         required: Mutex::new(HashMap::new()) }
+    }
+
+    /// The channels a package of `origin` must be in before it is promoted to stable: those in
+    /// the origin's required channel order on Builder, or, when it has none, `env_required`.
+    /// Builder is only asked once for each origin. When it
+    /// can't say, the environment's channels are required, with a warning unless Builder simply
+    /// doesn't keep origin settings.
+    fn required_channels<U, A>(&self,
+                               ui: &mut U,
+                               api_client: &A,
+                               origin: &str,
+                               token: &str)
+                               -> Result<Vec<ChannelIdent>>
+        where U: UIWriter,
+              A: PromoteApi
+    {
+        if let Some(required) = self.required
+                                    .lock()
+                                    .expect("Required channels lock is poisoned")
+                                    .get(origin)
+        {
+            return Ok(required.clone());
+        }
+        let required = match api_client.origin_settings(origin, Some(token)) {
+            Ok(ref settings) if !settings.required_channel_order.is_empty() => {
+                settings.required_channel_order
+                        .iter()
+                        .map(|c| ChannelIdent::from(c.as_str()))
+                        .collect()
+            }
+            Ok(_) | Err(api_client::Error::NotSupported(_)) => self.env_required.clone(),
             Err(e) => {
-                println!("Failed to create '{}' channel: {:?}", channel, e);
-                return Err(Error::from(e));
+                ui.warn(format!("Unable to retrieve the required channel order of origin '{}' \
+                                 ({}); falling back to {}",
+                                origin, e, REQUIRE_CHANNELS_ENVVAR))?;
+                self.env_required.clone()
             }
         };
+        self.required
+            .lock()
+            .expect("Required channels lock is poisoned")
+            .insert(origin.to_string(), required.clone());
+        Ok(required)
+    }
+}
+
+fn promote<U, A>(ui: &mut U,
+                 api_client: &A,
+                 ident: &PackageIdent,
+                 channel: &ChannelIdent,
+                 token: &str,
+                 options: &PromoteOptions)
+                 -> Result<PromoteResult>
+    where U: UIReader + UIWriter,
+          A: PromoteApi
+{
+    ui.begin(format!("Promoting {} to channel '{}'", ident, channel))?;
+
+    if channel == &ChannelIdent::stable() {
+        let required = options.required_channels(ui, api_client, &ident.origin, token)?;
+        check_promotion_policy(api_client, ident, channel, &required, token)?;
+    }
+
+    if needs_creating(channel) {
+        match create_with_retry(options.retry_create, || {
+                  api_client.create_channel(&ident.origin, channel, token)
+              }) {
+            Ok(created) => note_channel_created(ui, ident, channel, created),
+            Err(e) => return channel_not_created(ui, e, ident, channel),
+        };
+    }
+
+    let result = promote_or_ask_to_retry(ui, api_client, ident, channel, token)?;
+    report_promotion(ui, ident, channel, result)
+}
+
+/// Promotes each of `idents` in turn, writing a numbered step naming each one before its
+/// promotion's own output.
+fn promote_each<U, A>(ui: &mut U,
+                      api_client: &A,
+                      idents: &[PackageIdent],
+                      channel: &ChannelIdent,
+                      token: &str,
+                      options: &PromoteOptions)
+                      -> Result<Vec<std::result::Result<PromoteResult, String>>>
+    where U: UIReader + UIWriter,
+          A: PromoteApi
+{
+    let mut steps = bulk_steps(idents.len());
+    let mut results = Vec::new();
+    for ident in idents {
+        step(ui, &mut steps, ident)?;
+        let result = promote(ui, api_client, ident, channel, token, options);
+        results.push(result.map_err(|e| e.to_string()));
+    }
+    Ok(results)
+}
+
+/// The steps of a bulk promotion. Each promotion writes lines of its own, so a step is never
+/// rewritten in place, but gets a line of its own too.
+fn bulk_steps(total: usize) -> StepProgress { StepProgress::new(total, false) }
+
+/// Writes the next of a bulk promotion's steps, unless the output is JSON, which has no place for
+/// it.
+fn step<U>(ui: &mut U, steps: &mut StepProgress, ident: &PackageIdent) -> Result<()>
+    where U: UIWriter
+{
+    if ui.output_mode() == OutputMode::Human {
+        steps.step(ui.out(), ident)?;
     }
+    Ok(())
+}
+
+/// Creates a channel with `create`, trying again, up to `RETRIES` times `RETRY_WAIT` milliseconds
+/// apart, while Builder fails transiently, when `retry_create` is set. Any other result is
+/// returned as it is, as is the last transient failure.
+fn create_with_retry<F>(retry_create: bool, mut create: F) -> api_client::Result<ChannelCreated>
+    where F: FnMut() -> api_client::Result<ChannelCreated>
+{
+    let tries = if retry_create { RETRIES } else { 1 };
+    let mut transient = None;
+    let result = retry(tries,
+                       RETRY_WAIT,
+                       || {
+                           match create() {
+                               Err(e) => {
+                                   if api_client::retry::is_transient(&e) {
+                                       transient = Some(e);
+                                       None
+                                   } else {
+                                       Some(Err(e))
+                                   }
+                               }
+                               created => Some(created),
+                           }
+                       },
+                       Option::is_some);
+    match result {
+        Ok(Some(result)) => result,
+        _ => Err(transient.expect("Only a transient failure is retried")),
+    }
+}
+
+/// Whether promoting to `channel` needs it created first, as every channel but stable and
+/// unstable does.
+fn needs_creating(channel: &ChannelIdent) -> bool {
+    channel != &ChannelIdent::stable() && channel != &ChannelIdent::unstable()
+}
+
+fn note_channel_created<U>(ui: &mut U,
+                           ident: &PackageIdent,
+                           channel: &ChannelIdent,
+                           created: ChannelCreated)
+    where U: UIWriter
+{
+    if created == ChannelCreated::Created {
+        ui.record_mutation(ChannelMutation::Created { origin:  ident.origin.clone(),
+                                                      channel: channel.to_string(), })
+    }
+}
+
+/// The error for `channel` not being created for the promotion of `ident`. One for a lack of
+/// origin access already names the origin and channel, and is returned as it is.
+fn channel_not_created<U, T>(ui: &mut U,
+                             e: api_client::Error,
+                             ident: &PackageIdent,
+                             channel: &ChannelIdent)
+                             -> Result<T>
+    where U: UIWriter
+{
+    let err = access_error(e, &ident.origin, channel);
+    if let Error::InsufficientOriginAccess(..) = err {
+        return Err(err);
+    }
+    ui.with_context(&format!("creating channel '{}'", channel), Err(err))
+}
 
-    match api_client.promote_package(ident, channel, token) {
-        Ok(_) => (),
+/// Reports the result of promoting `ident` to `channel`.
+fn report_promotion<U>(ui: &mut U,
+                       ident: &PackageIdent,
+                       channel: &ChannelIdent,
+                       result: api_client::Result<PromoteResult>)
+                       -> Result<PromoteResult>
+    where U: UIWriter
+{
+    match result {
+        Ok(PromoteResult::Promoted) => {
+            ui.record_mutation(ChannelMutation::PromotedTo { ident:   ident.to_string(),
+                                                             channel: channel.to_string(), });
+            ui.status(Status::Promoted, ident)?;
+            Ok(PromoteResult::Promoted)
+        }
+        Ok(PromoteResult::AlreadyInChannel) => {
+            ui.status(Status::Skipping,
+                      format!("{}, already in channel '{}'", ident, channel))?;
+            Ok(PromoteResult::AlreadyInChannel)
+        }
         Err(e) => {
-            println!("Failed to promote '{}': {:?}", ident, e);
-            return Err(Error::from(e));
+            let err = access_error(e, &ident.origin, channel);
+            if let Error::InsufficientOriginAccess(..) = err {
+                return Err(err);
+            }
+            ui.with_context(&format!("promoting {} to '{}'", ident, channel), Err(err))
         }
     }
+}
 
-    ui.status(Status::Promoted, ident)?;
+/// Waits out Builder's rate limiting for every worker of a concurrent promotion: once Builder
+/// rate limits one of them, the rest wait as long as it asked (for at most
+/// `MAX_RATE_LIMIT_PAUSE`), rather than carrying on into the limit.
+struct RateLimitPause<S> {
+    until:   Mutex<Option<Instant>>,
+    sleeper: S,
+}
 
-    Ok(())
+impl<S> RateLimitPause<S> where S: Sleeper
+{
+    fn new(sleeper: S) -> Self {
+        RateLimitPause { until: Mutex::new(None),
+                         sleeper }
+    }
+
+    /// Waits out any pause, then makes the call, pausing every worker for as long as Builder
+    /// asks when the call is rate limited.
+    fn call<T, F>(&self, f: F) -> api_client::Result<T>
+        where F: FnOnce() -> api_client::Result<T>
+    {
+        let until = *self.until
+                         .lock()
+                         .expect("Rate limit pause lock is poisoned");
+        if let Some(until) = until {
+            let now = Instant::now();
+            if until > now {
+                self.sleeper.sleep(until - now);
+            }
+        }
+        let result = f();
+        if let Err(api_client::Error::RateLimited(Some(wait), _)) = result {
+            let resume = Instant::now() + wait.min(MAX_RATE_LIMIT_PAUSE);
+            let mut until = self.until
+                                .lock()
+                                .expect("Rate limit pause lock is poisoned");
+            if until.map_or(true, |until| until < resume) {
+                *until = Some(resume);
+            }
+        }
+        result
+    }
+}
+
+/// A `PromoteApi` whose every call keeps to a `RateLimitPause` shared with other workers.
+struct PausedApi<A, S> {
+    api_client: Arc<A>,
+    pause:      Arc<RateLimitPause<S>>,
+}
+
+impl<A, S> PausedApi<A, S> where S: Sleeper
+{
+    fn new(api_client: Arc<A>, sleeper: S) -> Self {
+        PausedApi { api_client,
+                    pause: Arc::new(RateLimitPause::new(sleeper)) }
+    }
+}
+
+impl<A, S> Clone for PausedApi<A, S> {
+    fn clone(&self) -> Self {
+        PausedApi { api_client: self.api_client.clone(),
+                    pause:      self.pause.clone(), }
+    }
+}
+
+impl<A, S> PromoteApi for PausedApi<A, S>
+    where A: PromoteApi,
+          S: Sleeper
+{
+    fn create_channel(&self,
+                      origin: &str,
+                      channel: &ChannelIdent,
+                      token: &str)
+                      -> api_client::Result<ChannelCreated> {
+        self.pause
+            .call(|| self.api_client.create_channel(origin, channel, token))
+    }
+
+    fn promote_package(&self,
+                       ident: &PackageIdent,
+                       channel: &ChannelIdent,
+                       token: &str,
+                       idempotency_key: &str)
+                       -> api_client::Result<PromoteResult> {
+        self.pause.call(|| {
+                      self.api_client
+                          .promote_package(ident, channel, token, idempotency_key)
+                  })
+    }
+
+    fn demote_package(&self,
+                      ident: &PackageIdent,
+                      channel: &ChannelIdent,
+                      token: &str)
+                      -> api_client::Result<()> {
+        self.pause
+            .call(|| self.api_client.demote_package(ident, channel, token))
+    }
+
+    fn show_package(&self,
+                    ident: &PackageIdent,
+                    target: PackageTarget,
+                    channel: &ChannelIdent,
+                    token: Option<&str>)
+                    -> api_client::Result<PackageMetadata> {
+        self.pause
+            .call(|| self.api_client.show_package(ident, target, channel, token))
+    }
+
+    fn package_channels(&self,
+                        ident: &PackageIdent,
+                        token: Option<&str>)
+                        -> api_client::Result<Vec<String>> {
+        self.pause
+            .call(|| self.api_client.package_channels(ident, token))
+    }
+
+    fn origin_settings(&self,
+                       origin: &str,
+                       token: Option<&str>)
+                       -> api_client::Result<OriginSettings> {
+        self.pause
+            .call(|| self.api_client.origin_settings(origin, token))
+    }
+}
+
+/// What a worker's promotion of one package came to: the result, the output `promote` wrote
+/// for it, and the changes it recorded, for the thread with the UI to report.
+struct WorkerPromotion {
+    result:  std::result::Result<PromoteResult, String>,
+    output:  String,
+    changes: Vec<serde_json::Value>,
+}
+
+/// Promotes each of `idents` on up to `concurrency` worker threads, which share `api_client`
+/// and `options`. Each worker promotes with `promote`, through a UI of its own which no one can
+/// answer, and sends what it wrote back to this thread, which writes it out, after a numbered
+/// step naming the package, as each promotion finishes. The results are returned in the order
+/// of `idents`; a package whose worker stopped before promoting it has failed.
+fn promote_concurrently<U, A, S>(ui: &mut U,
+                                 api_client: PausedApi<A, S>,
+                                 idents: &[PackageIdent],
+                                 channel: &ChannelIdent,
+                                 token: &str,
+                                 concurrency: usize,
+                                 options: PromoteOptions)
+                                 -> Result<Vec<std::result::Result<PromoteResult, String>>>
+    where U: UIWriter,
+          A: PromoteApi + Send + Sync + 'static,
+          S: Sleeper + Send + Sync + 'static
+{
+    let shared = Arc::new(idents.to_vec());
+    let next = Arc::new(AtomicUsize::new(0));
+    let options = Arc::new(options);
+    let (tx, rx) = mpsc::channel();
+
+    let mut workers = Vec::new();
+    let output_mode = ui.output_mode();
+    for _ in 0..concurrency.min(idents.len()) {
+        let (api, options) = (api_client.clone(), options.clone());
+        let (idents, next, tx) = (shared.clone(), next.clone(), tx.clone());
+        let (channel, token) = (channel.clone(), token.to_string());
+        let worker = thread::spawn(move || {
+            let mut index = next.fetch_add(1, Ordering::SeqCst);
+            while index < idents.len() {
+                let mut worker_ui = UI::with_sinks();
+                worker_ui.set_output_mode(output_mode);
+                let (result, output) =
+                    worker_ui.capture(|ui| {
+                                 promote(ui, &api, &idents[index], &channel, &token, &options)
+                             });
+                let promotion = WorkerPromotion { result: result.map_err(|e| e.to_string()),
+                                                  output,
+                                                  changes: worker_ui.changes().to_vec() };
+                if tx.send((index, promotion)).is_err() {
+                    break;
+                }
+                index = next.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        workers.push(worker);
+    }
+    drop(tx);
+
+    let mut steps = bulk_steps(idents.len());
+    let mut results: Vec<Option<_>> = idents.iter().map(|_| None).collect();
+    for (index, promotion) in rx {
+        step(ui, &mut steps, &idents[index])?;
+        ui.raw(promotion.output.as_bytes())?;
+        for change in &promotion.changes {
+            ui.record_change(change);
+        }
+        results[index] = Some(promotion.result);
+    }
+    for worker in workers {
+        if worker.join().is_err() {
+            debug!("A promotion worker panicked");
+        }
+    }
+    Ok(results.into_iter()
+              .map(|result| {
+                  result.unwrap_or_else(|| Err("The promotion was never finished".to_string()))
+              })
+              .collect())
+}
+
+/// Promotes the package to `channel` and then demotes it from `demote_from`. When `rollback` is
+/// set, the package is demoted from `channel` again if the second step fails and the first one
+/// added it there.
+#[allow(clippy::too_many_arguments)]
+fn promote_then_demote<U, A>(ui: &mut U,
+                             api_client: &A,
+                             ident: &PackageIdent,
+                             channel: &ChannelIdent,
+                             demote_from: &ChannelIdent,
+                             token: &str,
+                             options: &PromoteOptions,
+                             rollback: bool)
+                             -> Result<()>
+    where U: UIReader + UIWriter,
+          A: PromoteApi
+{
+    let promoted = promote(ui, api_client, ident, channel, token, options)?;
+
+    let err = match api_client.demote_package(ident, demote_from, token) {
+        Ok(()) => {
+            ui.record_mutation(ChannelMutation::DemotedFrom { ident:   ident.to_string(),
+                                                              channel: demote_from.to_string(), });
+            ui.status(Status::Demoted,
+                      format!("{} from channel '{}'", ident, demote_from))?;
+            return Ok(());
+        }
+        Err(e) => access_error(e, &ident.origin, demote_from),
+    };
+
+    if promoted != PromoteResult::Promoted {
+        // The package was already in the channel, so there is no promotion to keep or roll back
+    } else if !rollback {
+        ui.warn(format!("Keeping the promotion of '{}' to channel '{}'; it is now in both '{}' \
+                         and '{}'",
+                        ident, channel, channel, demote_from))?;
+    } else {
+        match api_client.demote_package(ident, channel, token) {
+            Ok(()) => {
+                ui.record_mutation(ChannelMutation::DemotedFrom { ident:   ident.to_string(),
+                                                                  channel: channel.to_string(), });
+                ui.status(Status::Demoted,
+                          format!("{} from channel '{}', rolling back the promotion",
+                                  ident, channel))?;
+            }
+            Err(e) => {
+                ui.warn(format!("Failed to roll back the promotion of '{}' to channel '{}' \
+                                 ({}); it is now in both '{}' and '{}'",
+                                ident, channel, e, channel, demote_from))?;
+            }
+        }
+    }
+    Err(err)
+}
+
+/// Promotes the package, asking whether to try again when it fails transiently and someone is
+/// there to answer. Every attempt sends the same idempotency key, so that Builder won't apply a
+/// promotion twice if an attempt which seemed to fail actually reached it.
+fn promote_or_ask_to_retry<U, A>(ui: &mut U,
+                                 api_client: &A,
+                                 ident: &PackageIdent,
+                                 channel: &ChannelIdent,
+                                 token: &str)
+                                 -> Result<api_client::Result<PromoteResult>>
+    where U: UIReader + UIWriter,
+          A: PromoteApi
+{
+    let key = api_client::promote_idempotency_key(ident, channel);
+    loop {
+        let result = api_client.promote_package(ident, channel, token, &key);
+        if let Err(ref e) = result {
+            if api_client::retry::is_transient(e) && ui.is_interactive() {
+                ui.warn(format!("Failed to promote '{}': {}", ident, e))?;
+                if ui.prompt_yes_no("Retry?", Some(true))? {
+                    continue;
+                }
+            }
+        }
+        return Ok(result);
+    }
+}
+
+/// Resolves the latest release of `ident` in `from_channel` and promotes it to `to_channel`,
+/// returning the release which was promoted.
+fn promote_latest<U, A>(ui: &mut U,
+                        api_client: &A,
+                        ident: &PackageIdent,
+                        from_channel: &ChannelIdent,
+                        to_channel: &ChannelIdent,
+                        token: &str,
+                        options: &PromoteOptions)
+                        -> Result<PackageIdent>
+    where U: UIReader + UIWriter,
+          A: PromoteApi
+{
+    ui.status(Status::Determining,
+              format!("latest {} in channel '{}'", ident, from_channel))?;
+    let latest = match api_client.show_package(ident,
+                                               PackageTarget::active_target(),
+                                               from_channel,
+                                               Some(token))
+    {
+        Ok(metadata) => metadata.ident,
+        Err(api_client::Error::PackageNotFound(_)) => {
+            return Err(Error::PackageNotFoundInChannel(ident.to_string(),
+                                                       from_channel.to_string()));
+        }
+        Err(e) => return Err(Error::from(e)),
+    };
+    ui.status(Status::Found,
+              format!("{}, promoting from '{}' to '{}'",
+                      latest, from_channel, to_channel))?;
+    promote(ui, api_client, &latest, to_channel, token, options)?;
+    Ok(latest)
+}
+
+/// The channels `REQUIRE_CHANNELS_ENVVAR` requires a package to be in before it is promoted to
+/// stable.
+fn required_channels() -> Vec<ChannelIdent> {
+    henv::var(REQUIRE_CHANNELS_ENVVAR).map(|val| parse_required_channels(&val))
+                                      .unwrap_or_default()
+}
+
+fn parse_required_channels(val: &str) -> Vec<ChannelIdent> {
+    val.split(',')
+       .map(str::trim)
+       .filter(|c| !c.is_empty())
+       .map(ChannelIdent::from)
+       .collect()
+}
+
+/// Looks for the package in `channel` every `WAIT_POLL_INTERVAL` until it is found, for as many
+/// intervals as fit in `timeout`. A package Builder doesn't know of yet is simply not found.
+fn wait_for_channel<U, A, S>(ui: &mut U,
+                             api_client: &A,
+                             sleeper: &S,
+                             ident: &PackageIdent,
+                             channel: &ChannelIdent,
+                             token: &str,
+                             timeout: Duration)
+                             -> Result<()>
+    where U: UIWriter,
+          A: PromoteApi,
+          S: Sleeper
+{
+    let interval = WAIT_POLL_INTERVAL.as_secs();
+    let polls = (timeout.as_secs() + interval - 1) / interval;
+    let spin = ui.is_out_a_terminal() && ui.output_mode() == OutputMode::Human;
+    let waiting = format!("for {} to be found in channel '{}'", ident, channel);
+    if !spin {
+        ui.status(Status::Custom(Glyph::Elipses, "Waiting".to_string()),
+                  &waiting)?;
+    }
+    let spinner_line = |poll: u64| {
+        format!("{} Waiting {}",
+                SPINNER_FRAMES[poll as usize % SPINNER_FRAMES.len()],
+                waiting)
+    };
+
+    let mut poll = 0;
+    loop {
+        let found = match api_client.package_channels(ident, Some(token)) {
+            Ok(channels) => channels.contains(&channel.to_string()),
+            Err(api_client::Error::APIError(StatusCode::NotFound, _)) => false,
+            Err(e) => return Err(access_error(e, &ident.origin, channel)),
+        };
+        if found || poll == polls {
+            if spin {
+                // Blank the spinner out, and leave the cursor at the start of the line.
+                let blank = " ".repeat(spinner_line(poll).len());
+                rewrite_line(ui.out(), &blank, terminal_width())?;
+                ui.out().write_all(b"\r")?;
+            }
+            if found {
+                ui.status(Status::Found, format!("{} in channel '{}'", ident, channel))?;
+                return Ok(());
+            }
+            return Err(Error::PromotionWaitTimedOut(ident.to_string(),
+                                                    channel.to_string(),
+                                                    timeout.as_secs()));
+        }
+        if spin {
+            rewrite_line(ui.out(), &spinner_line(poll), terminal_width())?;
+            ui.out().flush()?;
+        }
+        sleeper.sleep(WAIT_POLL_INTERVAL);
+        poll += 1;
+    }
+}
+
+fn promotion_would_change<A>(api_client: &A,
+                             ident: &PackageIdent,
+                             channel: &ChannelIdent,
+                             token: &str)
+                             -> Result<bool>
+    where A: PromoteApi
+{
+    let channels = api_client.package_channels(ident, Some(token))
+                             .map_err(|e| access_error(e, &ident.origin, channel))?;
+    Ok(!channels.contains(&channel.to_string()))
+}
+
+/// Fails a promotion to stable of a package which is not yet in every one of
+/// `required_channels`.
+fn check_promotion_policy<A>(api_client: &A,
+                             ident: &PackageIdent,
+                             channel: &ChannelIdent,
+                             required_channels: &[ChannelIdent],
+                             token: &str)
+                             -> Result<()>
+    where A: PromoteApi
+{
+    if channel != &ChannelIdent::stable() {
+        return Ok(());
+    }
+    let missing = missing_required_channels(api_client, ident, required_channels, token)?;
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::PromotionPolicyViolation(ident.to_string(), missing))
+    }
+}
+
+/// The channels of `required_channels` which the package is not yet in.
+fn missing_required_channels<A>(api_client: &A,
+                                ident: &PackageIdent,
+                                required_channels: &[ChannelIdent],
+                                token: &str)
+                                -> api_client::Result<Vec<String>>
+    where A: PromoteApi
+{
+    if required_channels.is_empty() {
+        return Ok(Vec::new());
+    }
+    let channels = api_client.package_channels(ident, Some(token))?;
+    Ok(required_channels.iter()
+                        .map(ChannelIdent::to_string)
+                        .filter(|c| !channels.contains(c))
+                        .collect())
+}
+
+/// Maps a `403 Forbidden` from Builder to an error naming the origin and channel involved, so the
+/// user learns their token lacks the needed origin role rather than seeing a bare status code.
+fn access_error(err: api_client::Error, origin: &str, channel: &ChannelIdent) -> Error {
+    match err {
+        api_client::Error::APIError(StatusCode::Forbidden, _) => {
+            Error::InsufficientOriginAccess(origin.to_string(), channel.to_string())
+        }
+        e => Error::from(e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use habitat_common::locked_env_var;
+    use std::{io::{self,
+                   Cursor},
+              path::PathBuf,
+              sync::Condvar,
+              time::Duration};
+    use tempfile::TempDir;
+    use termcolor::ColorChoice;
+
+    use super::*;
+    use crate::{audit::SessionAudit,
+                command::pkg::fake_api::FakeApi,
+                common::ui::{Glyph,
+                             RecordingUI,
+                             UiEvent}};
+
+    impl PromoteApi for FakeApi {
+        fn create_channel(&self,
+                          origin: &str,
+                          channel: &ChannelIdent,
+                          _token: &str)
+                          -> api_client::Result<ChannelCreated> {
+            self.calls
+                .borrow_mut()
+                .push(format!("create {}/{}", origin, channel));
+            let mut creates = self.creates.borrow_mut();
+            if creates.is_empty() {
+                Ok(ChannelCreated::Created)
+            } else {
+                creates.remove(0)
+            }
+        }
+
+        fn promote_package(&self,
+                           ident: &PackageIdent,
+                           channel: &ChannelIdent,
+                           _token: &str,
+                           idempotency_key: &str)
+                           -> api_client::Result<PromoteResult> {
+            self.calls
+                .borrow_mut()
+                .push(format!("promote {} {}", ident, channel));
+            self.keys.borrow_mut().push(idempotency_key.to_string());
+            let mut promotes = self.promotes.borrow_mut();
+            if promotes.is_empty() {
+                Ok(self.result)
+            } else {
+                promotes.remove(0)
+            }
+        }
+
+        fn demote_package(&self,
+                          ident: &PackageIdent,
+                          channel: &ChannelIdent,
+                          _token: &str)
+                          -> api_client::Result<()> {
+            self.answer_demote(ident, channel).unwrap_or(Ok(()))
+        }
+
+        fn show_package(&self,
+                        ident: &PackageIdent,
+                        target: PackageTarget,
+                        channel: &ChannelIdent,
+                        _token: Option<&str>)
+                        -> api_client::Result<PackageMetadata> {
+            self.calls
+                .borrow_mut()
+                .push(format!("show {} {}", ident, channel));
+            match self.latest {
+                Some(ref latest) => {
+                    Ok(PackageMetadata { ident: latest.clone(),
+                                         channels: vec![channel.clone()],
+                                         target })
+                }
+                None => Err(api_client::Error::PackageNotFound(ident.to_string())),
+            }
+        }
+
+        fn package_channels(&self,
+                            ident: &PackageIdent,
+                            _token: Option<&str>)
+                            -> api_client::Result<Vec<String>> {
+            self.answer_package_channels(ident)
+        }
+
+        fn origin_settings(&self,
+                           _origin: &str,
+                           _token: Option<&str>)
+                           -> api_client::Result<OriginSettings> {
+            let mut answers = self.settings_answers.borrow_mut();
+            if answers.is_empty() {
+                self.settings
+                    .clone()
+                    .ok_or_else(|| api_client::Error::NotSupported("origin settings".to_string()))
+            } else {
+                answers.remove(0)
+            }
+        }
+    }
+
+    fn recording_ui() -> RecordingUI {
+        RecordingUI::new(UI::with_streams(Box::new(io::empty()),
+                                          || Box::new(io::sink()),
+                                          || Box::new(io::sink()),
+                                          ColorChoice::Never,
+                                          false))
+    }
+
+    fn ident() -> PackageIdent {
+        PackageIdent::from_str("core/redis/4.0.14/20190319155852").unwrap()
+    }
+
+    fn no_retry() -> PromoteOptions { PromoteOptions::new(false, Vec::new()) }
+
+    /// The options `start` builds, requiring the channels of `REQUIRE_CHANNELS_ENVVAR`. Only the
+    /// tests holding `lock_require_channels_var` may call this.
+    fn env_options() -> PromoteOptions { PromoteOptions::new(false, required_channels()) }
+
+    #[test]
+    fn promote_emits_begin_then_status() {
+        let mut ui = recording_ui();
+        let api = FakeApi::new(PromoteResult::Promoted);
+
+        promote(&mut ui,
+                &api,
+                &ident(),
+                &ChannelIdent::from("bar"),
+                "token",
+                &no_retry()).unwrap();
+
+        let begin = "Promoting core/redis/4.0.14/20190319155852 to channel 'bar'";
+        assert_eq!(ui.events(),
+                   &[UiEvent::Begin(begin.to_string()),
+                     UiEvent::Status("Promoted".to_string(),
+                                     "core/redis/4.0.14/20190319155852".to_string())]);
+        assert_eq!(*api.calls.borrow(),
+                   vec!["create core/bar".to_string(),
+                        "promote core/redis/4.0.14/20190319155852 bar".to_string()]);
+    }
+
+    fn results_file(dir: &TempDir, contents: &str) -> PathBuf {
+        let path = dir.path().join("results.json");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn ident_from_results_reads_the_ident_field() {
+        let dir = TempDir::new().unwrap();
+        let path = results_file(&dir,
+                                r#"{
+                                    "origin": "core",
+                                    "name": "redis",
+                                    "version": "4.0.14",
+                                    "release": "20190319155852",
+                                    "ident": "core/redis/4.0.14/20190319155852",
+                                    "target": "x86_64-linux"
+                                }"#);
+
+        assert_eq!(ident_from_results(&path).unwrap(), ident());
+    }
+
+    #[test]
+    fn ident_from_results_requires_a_fully_qualified_ident() {
+        let dir = TempDir::new().unwrap();
+        for contents in &[r#"{ "name": "redis" }"#,
+                          r#"{ "ident": 42 }"#,
+                          r#"{ "ident": "core/redis" }"#]
+        {
+            let path = results_file(&dir, contents);
+            match ident_from_results(&path) {
+                Err(Error::InvalidBuildResults(_)) => (),
+                other => {
+                    panic!("Expected InvalidBuildResults for {}, got {:?}",
+                           contents, other)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ident_from_results_rejects_invalid_json() {
+        let dir = TempDir::new().unwrap();
+        let path = results_file(&dir, "ident = core/redis");
+        match ident_from_results(&path) {
+            Err(Error::JsonErr(_)) => (),
+            other => panic!("Expected JsonErr, got {:?}", other),
+        }
+    }
+
+    locked_env_var!(HAB_NONINTERACTIVE, lock_noninteractive_var);
+
+    fn interactive_ui(input: &str) -> RecordingUI {
+        RecordingUI::new(UI::with_streams(Box::new(Cursor::new(input.as_bytes().to_vec())),
+                                          || Box::new(io::sink()),
+                                          || Box::new(io::sink()),
+                                          ColorChoice::Never,
+                                          true))
+    }
+
+    locked_env_var!(HAB_BULK_CONFIRM_THRESHOLD, lock_bulk_confirm_threshold_var);
+    locked_env_var!(HAB_PKG_IDENT, lock_pkg_ident_var);
+    locked_env_var!(HAB_PROMOTE_REQUIRE_CHANNELS, lock_require_channels_var);
+
+    #[test]
+    fn ident_from_env_parses_a_set_variable() {
+        let lock = lock_pkg_ident_var();
+        lock.set("core/redis/4.0.14/20190319155852");
+
+        assert_eq!(ident_from_env(PKG_IDENT_ENVVAR).unwrap(), ident());
+    }
+
+    #[test]
+    fn ident_from_env_rejects_an_invalid_ident() {
+        let lock = lock_pkg_ident_var();
+        lock.set("not an ident");
+
+        match ident_from_env(PKG_IDENT_ENVVAR) {
+            Err(Error::InvalidIdentInEnvVar(ref var, ref value)) => {
+                assert_eq!(var, "HAB_PKG_IDENT");
+                assert_eq!(value, "not an ident");
+            }
+            other => panic!("Expected InvalidIdentInEnvVar, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ident_from_env_fails_when_the_variable_is_unset() {
+        let lock = lock_pkg_ident_var();
+        lock.unset();
+
+        match ident_from_env(PKG_IDENT_ENVVAR) {
+            Err(Error::IdentEnvVarUnset(ref var)) => assert_eq!(var, "HAB_PKG_IDENT"),
+            other => panic!("Expected IdentEnvVarUnset, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bulk_confirm_threshold_defaults_when_unset_or_invalid() {
+        let lock = lock_bulk_confirm_threshold_var();
+        lock.unset();
+        assert_eq!(bulk_confirm_threshold(), DEFAULT_BULK_CONFIRM_THRESHOLD);
+
+        lock.set("many");
+        assert_eq!(bulk_confirm_threshold(), DEFAULT_BULK_CONFIRM_THRESHOLD);
+
+        lock.set("100");
+        assert_eq!(bulk_confirm_threshold(), 100);
+    }
+
+    #[test]
+    fn bulk_under_the_threshold_is_not_confirmed() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let mut ui = interactive_ui("");
+
+        confirm_bulk(&mut ui, 25, &ChannelIdent::from("bar"), 25).unwrap();
+        assert!(ui.events().is_empty());
+    }
+
+    #[test]
+    fn bulk_over_the_threshold_asks_for_confirmation() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let mut ui = interactive_ui("bar\n");
+
+        confirm_bulk(&mut ui, 26, &ChannelIdent::from("bar"), 25).unwrap();
+        assert_eq!(ui.events(),
+                   &[UiEvent::ConfirmDestructive("This promotes 26 packages to \
+                                                  channel 'bar'"
+                                                                .to_string(),
+                                                 "bar".to_string())]);
+    }
+
+    #[test]
+    fn bulk_over_the_threshold_is_declined_without_the_channel_name() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let mut ui = interactive_ui("yes\n");
+
+        match confirm_bulk(&mut ui, 26, &ChannelIdent::from("bar"), 25) {
+            Err(Error::ConfirmationDeclined(ref impact)) => {
+                assert_eq!(impact, "This promotes 26 packages to channel 'bar'")
+            }
+            other => panic!("Expected ConfirmationDeclined, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bulk_over_the_threshold_while_assuming_yes_is_not_asked() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let mut inner = UI::with_streams(Box::new(io::empty()),
+                                         || Box::new(io::sink()),
+                                         || Box::new(io::sink()),
+                                         ColorChoice::Never,
+                                         true);
+        inner.set_assume_yes(true);
+        let mut ui = RecordingUI::new(inner);
+
+        // Asking would read the end of the input, which doesn't confirm anything.
+        confirm_bulk(&mut ui, 26, &ChannelIdent::from("bar"), 25).unwrap();
+    }
+
+    #[test]
+    fn bulk_over_the_threshold_without_anyone_to_ask_is_an_error() {
+        let lock = lock_noninteractive_var();
+        lock.set("1");
+        let mut ui = interactive_ui("");
+
+        match confirm_bulk(&mut ui, 26, &ChannelIdent::from("bar"), 25) {
+            Err(Error::HabitatCommon(crate::common::Error::ConfirmationRequired(ref impact))) => {
+                assert_eq!(impact, "This promotes 26 packages to channel 'bar'")
+            }
+            other => panic!("Expected ConfirmationRequired, got {:?}", other),
+        }
+    }
+
+    fn unavailable() -> api_client::Error {
+        api_client::Error::APIError(StatusCode::ServiceUnavailable, String::new())
+    }
+
+    #[test]
+    fn transient_promote_failure_is_retried_when_the_user_says_yes() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let mut ui = interactive_ui("yes\n");
+        let api = FakeApi::with_promotes(vec![Err(unavailable())]);
+
+        promote(&mut ui,
+                &api,
+                &ident(),
+                &ChannelIdent::from("bar"),
+                "token",
+                &no_retry()).unwrap();
+
+        assert_eq!(api.calls
+                      .borrow()
+                      .iter()
+                      .filter(|c| c.starts_with("promote"))
+                      .count(),
+                   2);
+        assert!(ui.events()
+                  .contains(&UiEvent::PromptYesNo("Retry?".to_string(), Some(true))));
+        assert_eq!(ui.events().last(),
+                   Some(&UiEvent::Status("Promoted".to_string(),
+                                         "core/redis/4.0.14/20190319155852".to_string())));
+    }
+
+    #[test]
+    fn retried_promote_sends_the_same_idempotency_key() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let mut ui = interactive_ui("yes\nyes\n");
+        let api = FakeApi::with_promotes(vec![Err(unavailable()), Err(unavailable())]);
+
+        promote(&mut ui,
+                &api,
+                &ident(),
+                &ChannelIdent::from("bar"),
+                "token",
+                &no_retry()).unwrap();
+
+        let keys = api.keys.borrow();
+        assert_eq!(keys.len(), 3);
+        assert!(keys.iter().all(|key| *key == keys[0]), "{:?}", keys);
+        assert_eq!(keys[0],
+                   api_client::promote_idempotency_key(&ident(), &ChannelIdent::from("bar")));
+    }
+
+    #[test]
+    fn transient_promote_failure_fails_when_the_user_says_no() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let mut ui = interactive_ui("no\n");
+        let api = FakeApi::with_promotes(vec![Err(unavailable())]);
+
+        assert!(promote(&mut ui,
+                        &api,
+                        &ident(),
+                        &ChannelIdent::from("bar"),
+                        "token",
+                        &no_retry()).is_err());
+        assert_eq!(api.calls
+                      .borrow()
+                      .iter()
+                      .filter(|c| c.starts_with("promote"))
+                      .count(),
+                   1);
+    }
+
+    #[test]
+    fn each_transient_promote_failure_is_asked_about_before_another_attempt() {
+        let lock = lock_noninteractive_var();
+        lock.unset();
+        let mut ui = interactive_ui("yes\nno\n");
+        let api = FakeApi::with_promotes(vec![Err(unavailable()), Err(unavailable())]);
+
+        assert!(promote(&mut ui,
+                        &api,
+                        &ident(),
+                        &ChannelIdent::from("bar"),
+                        "token",
+                        &no_retry()).is_err());
+        assert_eq!(api.calls
+                      .borrow()
+                      .iter()
+                      .filter(|c| c.starts_with("promote"))
+                      .count(),
+                   2);
+        assert_eq!(ui.events()
+                     .iter()
+                     .filter(|e| {
+                         if let UiEvent::PromptYesNo(..) = **e {
+                             true
+                         } else {
+                             false
+                         }
+                     })
+                     .count(),
+                   2);
+    }
+
+    #[test]
+    fn transient_promote_failure_is_not_retried_without_a_terminal() {
+        let mut ui = recording_ui();
+        let api = FakeApi::with_promotes(vec![Err(unavailable())]);
+
+        assert!(promote(&mut ui,
+                        &api,
+                        &ident(),
+                        &ChannelIdent::from("bar"),
+                        "token",
+                        &no_retry()).is_err());
+        assert!(!ui.events().iter().any(|e| {
+                                       if let UiEvent::PromptYesNo(..) = *e {
+                                           true
+                                       } else {
+                                           false
+                                       }
+                                   }));
+    }
+
+    #[test]
+    fn required_channels_are_comma_separated() {
+        assert_eq!(parse_required_channels("qa, staging,,"),
+                   vec![ChannelIdent::from("qa"), ChannelIdent::from("staging")]);
+        assert!(parse_required_channels("").is_empty());
+    }
+
+    #[test]
+    fn promotion_policy_allows_packages_in_every_required_channel() {
+        let api = FakeApi::with_channels(&["unstable", "qa", "staging"]);
+        let required = parse_required_channels("qa,staging");
+        let stable = ChannelIdent::stable();
+
+        check_promotion_policy(&api, &ident(), &stable, &required, "token").unwrap();
+    }
+
+    #[test]
+    fn promotion_policy_rejects_skipped_channels() {
+        let api = FakeApi::with_channels(&["unstable", "qa"]);
+        let required = parse_required_channels("qa,staging");
+
+        match check_promotion_policy(&api, &ident(), &ChannelIdent::stable(), &required, "token") {
+            Err(Error::PromotionPolicyViolation(ident, missing)) => {
+                assert_eq!(ident, "core/redis/4.0.14/20190319155852");
+                assert_eq!(missing, vec!["staging".to_string()]);
+            }
+            other => panic!("Expected PromotionPolicyViolation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn builder_channel_order_is_preferred_to_the_environment() {
+        let lock = lock_require_channels_var();
+        lock.set("qa");
+        let api = FakeApi::with_channel_order(&["qa", "staging"]);
+
+        assert_eq!(env_options().required_channels(&mut recording_ui(), &api, "core", "token")
+                                .unwrap(),
+                   parse_required_channels("qa,staging"));
+    }
+
+    #[test]
+    fn required_channels_fall_back_to_the_environment_without_origin_settings() {
+        let lock = lock_require_channels_var();
+        lock.set("qa");
+
+        let required = |api: &FakeApi| {
+            env_options().required_channels(&mut recording_ui(), api, "core", "token")
+                         .unwrap()
+        };
+
+        assert_eq!(required(&FakeApi::new(PromoteResult::Promoted)),
+                   parse_required_channels("qa"));
+        assert_eq!(required(&FakeApi::with_channel_order(&[])),
+                   parse_required_channels("qa"));
+
+        lock.unset();
+        assert!(required(&FakeApi::new(PromoteResult::Promoted)).is_empty());
+    }
+
+    #[test]
+    fn required_channels_fall_back_to_the_environment_with_a_warning_when_builder_fails() {
+        let lock = lock_require_channels_var();
+        lock.set("qa");
+        let mut ui = recording_ui();
+        let api = FakeApi::new(PromoteResult::Promoted);
+        *api.settings_answers.borrow_mut() =
+            vec![Err(api_client::Error::APIError(StatusCode::InternalServerError, String::new()))];
+
+        assert_eq!(env_options().required_channels(&mut ui, &api, "core", "token")
+                                .unwrap(),
+                   parse_required_channels("qa"));
+        match ui.events().last() {
+            Some(UiEvent::Warn(message)) => {
+                assert!(message.starts_with("Unable to retrieve the required channel order"))
+            }
+            other => panic!("Expected a warning, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn required_channels_are_read_from_the_environment_once() {
+        let lock = lock_require_channels_var();
+        lock.set("qa");
+        let options = env_options();
+        lock.set("staging");
+
+        assert_eq!(options.required_channels(&mut recording_ui(),
+                                             &FakeApi::new(PromoteResult::Promoted),
+                                             "core",
+                                             "token")
+                          .unwrap(),
+                   parse_required_channels("qa"));
+    }
+
+    #[test]
+    fn required_channels_are_only_looked_up_once_for_each_origin() {
+        let lock = lock_require_channels_var();
+        lock.set("qa");
+        let mut ui = recording_ui();
+        let api = FakeApi::new(PromoteResult::Promoted);
+        *api.settings_answers.borrow_mut() =
+            vec![Ok(OriginSettings { required_channel_order: vec!["staging".to_string()], })];
+        let options = env_options();
+
+        for _ in 0..2 {
+            assert_eq!(options.required_channels(&mut ui, &api, "core", "token")
+                              .unwrap(),
+                       parse_required_channels("staging"));
+        }
+    }
+
+    #[test]
+    fn promotion_policy_only_applies_to_stable() {
+        let api = FakeApi::with_channels(&["unstable"]);
+        let required = parse_required_channels("qa,staging");
+
+        let (qa, stable) = (ChannelIdent::from("qa"), ChannelIdent::stable());
+
+        check_promotion_policy(&api, &ident(), &qa, &required, "token").unwrap();
+        check_promotion_policy(&api, &ident(), &stable, &[], "token").unwrap();
+        assert!(api.calls.borrow().is_empty());
+    }
+
+    fn bulk_options(require_nonempty: bool) -> BulkOptions {
+        BulkOptions { retry_create: false,
+                      require_nonempty,
+                      concurrency: 1 }
+    }
+
+    #[test]
+    fn bulk_promote_of_no_packages_warns() {
+        let (mut ui, output) = UI::from_env_for_tests();
+
+        let outcome = start_bulk(&mut ui,
+                                 "https://bldr.example.com",
+                                 &[],
+                                 &ChannelIdent::from("bar"),
+                                 "token",
+                                 &bulk_options(false)).unwrap();
+
+        assert!(outcome.is_empty());
+        assert!(output.stderr().contains("No packages to promote"),
+                "{}",
+                output.stderr());
+    }
+
+    #[test]
+    fn bulk_promote_of_no_packages_fails_when_required() {
+        let (mut ui, _) = UI::from_env_for_tests();
+
+        match start_bulk(&mut ui,
+                         "https://bldr.example.com",
+                         &[],
+                         &ChannelIdent::from("bar"),
+                         "token",
+                         &bulk_options(true))
+        {
+            Err(Error::NoPackagesToPromote) => (),
+            other => panic!("Expected NoPackagesToPromote, got {:?}", other),
+        }
+    }
+
+    /// A `FakeApi` which workers on several threads can share, and which holds back promoting
+    /// `slow` until every other package has been promoted, so that it finishes last.
+    struct ConcurrentFakeApi {
+        fake:     Mutex<FakeApi>,
+        promoted: Condvar,
+        slow:     Option<(PackageIdent, usize)>,
+    }
+
+    impl ConcurrentFakeApi {
+        /// `slow` is the package to promote last, and how many others there are to wait for.
+        fn new(fake: FakeApi, slow: Option<(PackageIdent, usize)>) -> Arc<Self> {
+            Arc::new(ConcurrentFakeApi { fake: Mutex::new(fake),
+                                         promoted: Condvar::new(),
+                                         slow })
+        }
+
+        fn promoted(&self) -> Vec<String> {
+            let mut calls = promotions(&self.fake.lock().unwrap());
+            calls.sort();
+            calls
+        }
+    }
+
+    fn promotions(fake: &FakeApi) -> Vec<String> {
+        let mut calls = fake.calls.borrow().clone();
+        calls.retain(|call| call.starts_with("promote"));
+        calls
+    }
+
+    impl PromoteApi for ConcurrentFakeApi {
+        fn create_channel(&self,
+                          origin: &str,
+                          channel: &ChannelIdent,
+                          token: &str)
+                          -> api_client::Result<ChannelCreated> {
+            self.fake
+                .lock()
+                .unwrap()
+                .create_channel(origin, channel, token)
+        }
+
+        fn promote_package(&self,
+                           ident: &PackageIdent,
+                           channel: &ChannelIdent,
+                           token: &str,
+                           idempotency_key: &str)
+                           -> api_client::Result<PromoteResult> {
+            let mut fake = self.fake.lock().unwrap();
+            if let Some((ref slow, others)) = self.slow {
+                while slow == ident && promotions(&fake).len() < others {
+                    fake = self.promoted.wait(fake).unwrap();
+                }
+            }
+            let result = fake.promote_package(ident, channel, token, idempotency_key);
+            self.promoted.notify_all();
+            result
+        }
+
+        fn demote_package(&self,
+                          ident: &PackageIdent,
+                          channel: &ChannelIdent,
+                          token: &str)
+                          -> api_client::Result<()> {
+            self.fake
+                .lock()
+                .unwrap()
+                .demote_package(ident, channel, token)
+        }
+
+        fn show_package(&self,
+                        ident: &PackageIdent,
+                        target: PackageTarget,
+                        channel: &ChannelIdent,
+                        token: Option<&str>)
+                        -> api_client::Result<PackageMetadata> {
+            self.fake
+                .lock()
+                .unwrap()
+                .show_package(ident, target, channel, token)
+        }
+
+        fn package_channels(&self,
+                            ident: &PackageIdent,
+                            token: Option<&str>)
+                            -> api_client::Result<Vec<String>> {
+            self.fake.lock().unwrap().package_channels(ident, token)
+        }
+
+        fn origin_settings(&self,
+                           origin: &str,
+                           token: Option<&str>)
+                           -> api_client::Result<OriginSettings> {
+            self.fake.lock().unwrap().origin_settings(origin, token)
+        }
+    }
+
+    fn idents(count: usize) -> Vec<PackageIdent> {
+        (0..count).map(|i| format!("core/redis/4.0.14/2019031915585{}", i))
+                  .map(|ident| PackageIdent::from_str(&ident).unwrap())
+                  .collect()
+    }
+
+    #[test]
+    fn concurrent_bulk_promote_keeps_the_order_packages_were_given_in() {
+        let mut ui = recording_ui();
+        let idents = idents(4);
+        let api = ConcurrentFakeApi::new(FakeApi::new(PromoteResult::Promoted),
+                                         Some((idents[0].clone(), 3)));
+        let channel = ChannelIdent::unstable();
+
+        let results = promote_concurrently(&mut ui,
+                                           PausedApi::new(api.clone(), TestSleeper::default()),
+                                           &idents,
+                                           &channel,
+                                           "token",
+                                           3,
+                                           no_retry()).unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert!(results.iter()
+                       .all(|result| result.as_ref().ok() == Some(&PromoteResult::Promoted)));
+        let expected: Vec<String> = idents.iter()
+                                          .map(|ident| format!("promote {} unstable", ident))
+                                          .collect();
+        assert_eq!(api.promoted(), expected);
+        // The slow first package is reported last, once it has finished.
+        let last = match ui.events().last() {
+            Some(UiEvent::Raw(bytes)) => String::from_utf8(bytes.clone()).unwrap(),
+            other => panic!("Expected the last package's output, got {:?}", other),
+        };
+        assert!(last.contains(&format!("Promoted {}", idents[0])));
+    }
+
+    #[test]
+    fn concurrent_bulk_promote_reports_each_failure_in_its_place() {
+        let mut ui = recording_ui();
+        let idents = idents(3);
+        let conflict = api_client::Error::APIError(StatusCode::Conflict, String::new());
+        let fake = FakeApi::with_promotes(vec![Ok(PromoteResult::Promoted),
+                                               Err(conflict),
+                                               Ok(PromoteResult::AlreadyInChannel)]);
+        // A single worker takes the answers in the order the packages were given.
+        let api = ConcurrentFakeApi::new(fake, None);
+
+        let results = promote_concurrently(&mut ui,
+                                           PausedApi::new(api.clone(), TestSleeper::default()),
+                                           &idents,
+                                           &ChannelIdent::unstable(),
+                                           "token",
+                                           1,
+                                           no_retry()).unwrap();
+
+        assert_eq!(results[0].as_ref().ok(), Some(&PromoteResult::Promoted));
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().ok(),
+                   Some(&PromoteResult::AlreadyInChannel));
+        assert_eq!(api.promoted().len(), 3);
+    }
+
+    #[test]
+    fn bulk_promote_numbers_each_package_before_promoting_it() {
+        let (mut ui, output) = UI::from_env_for_tests();
+        let idents = idents(2);
+        let api = FakeApi::new(PromoteResult::Promoted);
+
+        promote_each(&mut ui,
+                     &api,
+                     &idents,
+                     &ChannelIdent::unstable(),
+                     "token",
+                     &no_retry()).unwrap();
+
+        let steps: Vec<String> = output.stdout()
+                                       .lines()
+                                       .filter(|line| line.starts_with('['))
+                                       .map(str::to_string)
+                                       .collect();
+        assert_eq!(steps,
+                   vec![format!("[1/2] {}", idents[0]),
+                        format!("[2/2] {}", idents[1])]);
+    }
+
+    #[test]
+    fn concurrent_bulk_promote_numbers_each_package_as_it_finishes() {
+        let (mut ui, output) = UI::from_env_for_tests();
+        let idents = idents(2);
+        let api = ConcurrentFakeApi::new(FakeApi::new(PromoteResult::Promoted), None);
+
+        promote_concurrently(&mut ui,
+                             PausedApi::new(api, TestSleeper::default()),
+                             &idents,
+                             &ChannelIdent::unstable(),
+                             "token",
+                             2,
+                             no_retry()).unwrap();
+
+        let steps: Vec<String> = output.stdout()
+                                       .lines()
+                                       .filter(|line| line.starts_with('['))
+                                       .map(|line| line[..6].to_string())
+                                       .collect();
+        assert_eq!(steps, vec!["[1/2] ", "[2/2] "]);
+    }
+
+    #[test]
+    fn concurrent_bulk_promote_records_what_each_worker_changed() {
+        let mut ui = recording_ui();
+        let idents = idents(2);
+        let api = ConcurrentFakeApi::new(FakeApi::new(PromoteResult::Promoted), None);
+
+        promote_concurrently(&mut ui,
+                             PausedApi::new(api, TestSleeper::default()),
+                             &idents,
+                             &ChannelIdent::unstable(),
+                             "token",
+                             2,
+                             no_retry()).unwrap();
+
+        let mut promoted: Vec<ChannelMutation> =
+            SessionAudit::of(&ui.into_inner()).unwrap()
+                                              .mutations
+                                              .into_iter()
+                                              .filter(|mutation| {
+                                                  match mutation {
+                                                      ChannelMutation::PromotedTo { .. } => true,
+                                                      _ => false,
+                                                  }
+                                              })
+                                              .collect();
+        promoted.sort_by_key(|mutation| format!("{:?}", mutation));
+        assert_eq!(promoted,
+                   idents.iter()
+                         .map(|ident| {
+                             ChannelMutation::PromotedTo { ident:   ident.to_string(),
+                                                           channel: "unstable".to_string(), }
+                         })
+                         .collect::<Vec<_>>());
+    }
+
+    /// A `Sleeper` which workers on several threads can share, and which doesn't wait but
+    /// remembers each wait it was asked for.
+    #[derive(Default)]
+    struct TestSleeper {
+        slept: Mutex<Vec<Duration>>,
+    }
+
+    impl TestSleeper {
+        fn slept(&self) -> Vec<Duration> { self.slept.lock().unwrap().clone() }
+    }
+
+    impl Sleeper for TestSleeper {
+        fn sleep(&self, duration: Duration) { self.slept.lock().unwrap().push(duration) }
+    }
+
+    impl<'a> Sleeper for &'a TestSleeper {
+        fn sleep(&self, duration: Duration) { (*self).sleep(duration) }
+    }
+
+    #[test]
+    fn a_rate_limited_call_pauses_the_calls_after_it_for_at_most_the_cap() {
+        let sleeper = TestSleeper::default();
+        let pause = RateLimitPause::new(&sleeper);
+
+        let limited: api_client::Result<()> =
+            pause.call(|| {
+                     Err(api_client::Error::RateLimited(Some(Duration::from_secs(3600)),
+                                                        String::new()))
+                 });
+        assert!(limited.is_err());
+        assert!(sleeper.slept().is_empty());
+
+        pause.call(|| Ok(())).unwrap();
+        let slept = sleeper.slept();
+        assert_eq!(slept.len(), 1);
+        assert!(slept[0] <= MAX_RATE_LIMIT_PAUSE);
+        assert!(slept[0] > MAX_RATE_LIMIT_PAUSE - Duration::from_secs(10));
+    }
+
+    #[test]
+    fn promote_writes_begin_and_promoted_lines() {
+        let (mut ui, output) = UI::from_env_for_tests();
+        let api = FakeApi::new(PromoteResult::Promoted);
+
+        promote(&mut ui,
+                &api,
+                &ident(),
+                &ChannelIdent::from("bar"),
+                "token",
+                &no_retry()).unwrap();
+
+        assert_eq!(output.stdout(),
+                   format!("{} Promoting core/redis/4.0.14/20190319155852 to channel 'bar'\n{} \
+                            Promoted core/redis/4.0.14/20190319155852\n",
+                           Glyph::RightShift.to_str(),
+                           Glyph::CheckMark.to_padded_str()));
+    }
+
+    #[test]
+    fn promote_audits_the_channel_it_creates_and_the_promotion() {
+        let (mut ui, _) = UI::from_env_for_tests();
+        let api = FakeApi::new(PromoteResult::Promoted);
+
+        promote(&mut ui,
+                &api,
+                &ident(),
+                &ChannelIdent::from("bar"),
+                "token",
+                &no_retry()).unwrap();
+
+        assert_eq!(SessionAudit::of(&ui).unwrap().mutations,
+                   vec![ChannelMutation::Created { origin:  "core".to_string(),
+                                                   channel: "bar".to_string(), },
+                        ChannelMutation::PromotedTo { ident:   "core/redis/4.0.14/20190319155852"
+                                                                   .to_string(),
+                                                      channel: "bar".to_string(), },]);
+    }
+
+    #[test]
+    fn promote_does_not_audit_a_channel_which_already_existed_or_a_skip() {
+        let (mut ui, _) = UI::from_env_for_tests();
+        let mut api = FakeApi::with_creates(vec![Ok(ChannelCreated::AlreadyExisted)]);
+        api.result = PromoteResult::AlreadyInChannel;
+
+        promote(&mut ui,
+                &api,
+                &ident(),
+                &ChannelIdent::from("bar"),
+                "token",
+                &no_retry()).unwrap();
+
+        assert!(SessionAudit::of(&ui).unwrap().mutations.is_empty());
+    }
+
+    #[test]
+    fn promote_to_stable_skips_channel_creation_and_reports_membership() {
+        let mut ui = recording_ui();
+        let api = FakeApi::new(PromoteResult::AlreadyInChannel);
+
+        promote(&mut ui,
+                &api,
+                &ident(),
+                &ChannelIdent::stable(),
+                "token",
+                &no_retry()).unwrap();
+
+        let message = "core/redis/4.0.14/20190319155852, already in channel 'stable'";
+        assert_eq!(ui.events()[1],
+                   UiEvent::Status("Skipping".to_string(), message.to_string()));
+        assert_eq!(api.calls.borrow().len(), 1);
+    }
+
+    #[test]
+    fn channel_creation_is_retried_until_another_creator_wins() {
+        let mut ui = recording_ui();
+        let api = FakeApi::with_creates(vec![
+            Err(api_client::Error::APIError(
+                StatusCode::ServiceUnavailable,
+                String::new(),
+            )),
+            Ok(ChannelCreated::AlreadyExisted),
+        ]);
+
+        promote(&mut ui,
+                &api,
+                &ident(),
+                &ChannelIdent::from("bar"),
+                "token",
+                &PromoteOptions::new(true, Vec::new())).unwrap();
+
+        assert_eq!(*api.calls.borrow(),
+                   vec!["create core/bar".to_string(),
+                        "create core/bar".to_string(),
+                        "promote core/redis/4.0.14/20190319155852 bar".to_string()]);
+    }
+
+    #[test]
+    fn channel_creation_is_not_retried_by_default() {
+        let mut ui = recording_ui();
+        let api = FakeApi::with_creates(vec![Err(api_client::Error::APIError(
+            StatusCode::ServiceUnavailable,
+            String::new(),
+        ))]);
+
+        let result = promote(&mut ui,
+                             &api,
+                             &ident(),
+                             &ChannelIdent::from("bar"),
+                             "token",
+                             &no_retry());
+
+        assert!(result.is_err());
+        assert_eq!(*api.calls.borrow(), vec!["create core/bar".to_string()]);
+    }
+
+    #[test]
+    fn promote_appends_without_demoting_from_any_channel() {
+        let mut ui = recording_ui();
+        let api = FakeApi::new(PromoteResult::Promoted);
+
+        promote(&mut ui,
+                &api,
+                &ident(),
+                &ChannelIdent::from("bar"),
+                "token",
+                &no_retry()).unwrap();
+
+        assert!(api.calls.borrow().iter().all(|c| !c.starts_with("demote")));
+    }
+
+    #[test]
+    fn replace_promotes_then_demotes_from_the_old_channel() {
+        let (mut ui, _) = UI::from_env_for_tests();
+        let api = FakeApi::new(PromoteResult::Promoted);
+
+        promote_then_demote(&mut ui,
+                            &api,
+                            &ident(),
+                            &ChannelIdent::from("staging"),
+                            &ChannelIdent::from("qa"),
+                            "token",
+                            &no_retry(),
+                            true).unwrap();
+
+        assert_eq!(*api.calls.borrow(),
+                   vec!["create core/staging".to_string(),
+                        "promote core/redis/4.0.14/20190319155852 staging".to_string(),
+                        "demote core/redis/4.0.14/20190319155852 qa".to_string()]);
+        assert_eq!(SessionAudit::of(&ui).unwrap().mutations[2],
+                   ChannelMutation::DemotedFrom { ident:
+                                                      "core/redis/4.0.14/20190319155852".to_string(),
+                                                  channel: "qa".to_string(), });
+    }
+
+    #[test]
+    fn replace_rolls_back_the_promotion_when_the_demotion_fails() {
+        let mut ui = recording_ui();
+        let api = FakeApi::with_demotes(vec![Err(api_client::Error::APIError(
+            StatusCode::InternalServerError,
+            String::new(),
+        ))]);
+
+        let result = promote_then_demote(&mut ui,
+                                         &api,
+                                         &ident(),
+                                         &ChannelIdent::from("staging"),
+                                         &ChannelIdent::from("qa"),
+                                         "token",
+                                         &no_retry(),
+                                         true);
+
+        assert!(result.is_err());
+        assert_eq!(&api.calls.borrow()[2..],
+                   &["demote core/redis/4.0.14/20190319155852 qa".to_string(),
+                     "demote core/redis/4.0.14/20190319155852 staging".to_string()]);
+        let warned = ui.events().iter().any(|e| {
+                                           match *e {
+                                               UiEvent::Warn(ref w) => w.starts_with("Failed to"),
+                                               _ => false,
+                                           }
+                                       });
+        assert!(!warned,
+                "the demotion failure is returned, rather than also warned about");
+    }
+
+    #[test]
+    fn replace_keeps_a_package_which_was_already_in_the_channel() {
+        let mut ui = recording_ui();
+        let mut api = FakeApi::with_demotes(vec![Err(api_client::Error::APIError(
+            StatusCode::InternalServerError,
+            String::new(),
+        ))]);
+        api.result = PromoteResult::AlreadyInChannel;
+
+        let result = promote_then_demote(&mut ui,
+                                         &api,
+                                         &ident(),
+                                         &ChannelIdent::from("staging"),
+                                         &ChannelIdent::from("qa"),
+                                         "token",
+                                         &no_retry(),
+                                         true);
+
+        assert!(result.is_err());
+        assert_eq!(api.calls.borrow().last().unwrap(),
+                   "demote core/redis/4.0.14/20190319155852 qa");
+    }
+
+    #[test]
+    fn demote_after_promotes_then_demotes() {
+        let mut ui = recording_ui();
+        let api = FakeApi::new(PromoteResult::Promoted);
+
+        promote_then_demote(&mut ui,
+                            &api,
+                            &ident(),
+                            &ChannelIdent::from("stable"),
+                            &ChannelIdent::from("staging"),
+                            "token",
+                            &no_retry(),
+                            false).unwrap();
+
+        assert_eq!(&api.calls.borrow()[1..],
+                   &["promote core/redis/4.0.14/20190319155852 stable".to_string(),
+                     "demote core/redis/4.0.14/20190319155852 staging".to_string()]);
+    }
+
+    #[test]
+    fn demote_after_keeps_the_promotion_when_the_demotion_fails() {
+        let (mut ui, _) = UI::from_env_for_tests();
+        let api = FakeApi::with_demotes(vec![Err(api_client::Error::APIError(
+            StatusCode::InternalServerError,
+            String::new(),
+        ))]);
+
+        let result = promote_then_demote(&mut ui,
+                                         &api,
+                                         &ident(),
+                                         &ChannelIdent::from("stable"),
+                                         &ChannelIdent::from("staging"),
+                                         "token",
+                                         &no_retry(),
+                                         false);
+
+        assert!(result.is_err());
+        assert_eq!(api.calls.borrow().last().unwrap(),
+                   "demote core/redis/4.0.14/20190319155852 staging");
+        assert_eq!(SessionAudit::of(&ui).unwrap().mutations.last().unwrap(),
+                   &ChannelMutation::PromotedTo { ident:
+                                                      "core/redis/4.0.14/20190319155852".to_string(),
+                                                  channel: "stable".to_string(), });
+    }
+
+    #[test]
+    fn demote_after_does_not_keep_a_promotion_it_did_not_make() {
+        let mut ui = recording_ui();
+        let mut api = FakeApi::with_demotes(vec![Err(api_client::Error::APIError(
+            StatusCode::InternalServerError,
+            String::new(),
+        ))]);
+        api.result = PromoteResult::AlreadyInChannel;
+
+        let result = promote_then_demote(&mut ui,
+                                         &api,
+                                         &ident(),
+                                         &ChannelIdent::from("stable"),
+                                         &ChannelIdent::from("staging"),
+                                         "token",
+                                         &no_retry(),
+                                         false);
+
+        assert!(result.is_err());
+        assert!(!ui.events().iter().any(|e| {
+                                       match *e {
+                                           UiEvent::Warn(_) => true,
+                                           _ => false,
+                                       }
+                                   }));
+    }
+
+    #[test]
+    fn wait_polls_until_the_package_is_in_the_channel() {
+        let mut ui = recording_ui();
+        let api = FakeApi::with_channels(&["unstable", "stable"]);
+        *api.channel_answers.borrow_mut() =
+            vec![Err(api_client::Error::APIError(StatusCode::NotFound, String::new())),
+                 Ok(vec!["unstable".to_string()]),];
+        let sleeper = TestSleeper::default();
+
+        wait_for_channel(&mut ui,
+                         &api,
+                         &sleeper,
+                         &ident(),
+                         &ChannelIdent::stable(),
+                         "token",
+                         DEFAULT_WAIT_TIMEOUT).unwrap();
+
+        assert_eq!(api.calls.borrow().len(), 3);
+        assert_eq!(sleeper.slept(),
+                   vec![WAIT_POLL_INTERVAL, WAIT_POLL_INTERVAL]);
+        assert_eq!(ui.events().last(),
+                   Some(&UiEvent::Status("Found".to_string(),
+                                         "core/redis/4.0.14/20190319155852 in channel 'stable'"
+                                             .to_string())));
+    }
+
+    #[test]
+    fn wait_times_out_when_the_package_never_appears() {
+        let mut ui = recording_ui();
+        let api = FakeApi::with_channels(&["unstable"]);
+        let sleeper = TestSleeper::default();
+
+        match wait_for_channel(&mut ui,
+                               &api,
+                               &sleeper,
+                               &ident(),
+                               &ChannelIdent::stable(),
+                               "token",
+                               Duration::from_secs(5))
+        {
+            Err(Error::PromotionWaitTimedOut(_, ref channel, 5)) => assert_eq!(channel, "stable"),
+            other => panic!("Expected PromotionWaitTimedOut, got {:?}", other),
+        }
+        assert_eq!(sleeper.slept().len(), 3);
+        assert_eq!(api.calls.borrow().len(), 4);
+    }
+
+    #[test]
+    fn would_change_when_the_package_is_not_in_the_channel() {
+        let api = FakeApi::with_channels(&["unstable", "qa"]);
+
+        assert!(promotion_would_change(&api,
+                                       &ident(),
+                                       &ChannelIdent::from("stable"),
+                                       "token").unwrap());
+        assert!(api.calls.borrow().iter().all(|c| c.starts_with("channels")));
+    }
+
+    #[test]
+    fn would_not_change_when_the_package_is_already_in_the_channel() {
+        let api = FakeApi::with_channels(&["unstable", "stable"]);
+
+        assert!(!promotion_would_change(&api,
+                                        &ident(),
+                                        &ChannelIdent::from("stable"),
+                                        "token").unwrap());
+    }
+
+    #[test]
+    fn forbidden_maps_to_insufficient_origin_access() {
+        let channel = ChannelIdent::from("private");
+        let err = access_error(api_client::Error::APIError(StatusCode::Forbidden,
+                                                           "[err: 0, msg: forbidden]".to_string()),
+                               "acme",
+                               &channel);
+        match err {
+            Error::InsufficientOriginAccess(ref origin, ref channel) => {
+                assert_eq!(origin, "acme");
+                assert_eq!(channel, "private");
+            }
+            ref e => panic!("Expected InsufficientOriginAccess, got {:?}", e),
+        }
+        let msg = err.to_string();
+        assert!(msg.contains("'private' channel"));
+        assert!(msg.contains("'acme' origin"));
+    }
+
+    #[test]
+    fn forbidden_promotion_is_returned_without_being_written() {
+        let mut ui = recording_ui();
+        let api =
+            FakeApi::with_promotes(vec![Err(api_client::Error::APIError(StatusCode::Forbidden,
+                                                                        String::new()))]);
+
+        match promote(&mut ui,
+                      &api,
+                      &ident(),
+                      &ChannelIdent::from("bar"),
+                      "token",
+                      &no_retry())
+        {
+            Err(Error::InsufficientOriginAccess(ref origin, _)) => assert_eq!(origin, "core"),
+            other => panic!("Expected InsufficientOriginAccess, got {:?}", other),
+        }
+        assert!(!ui.events().iter().any(|e| {
+                                       if let UiEvent::Fatal(_) = *e {
+                                           true
+                                       } else {
+                                           false
+                                       }
+                                   }));
+    }
+
+    #[test]
+    fn other_api_errors_are_passed_through() {
+        let channel = ChannelIdent::from("private");
+        let err = access_error(api_client::Error::APIError(StatusCode::NotFound, String::new()),
+                               "acme",
+                               &channel);
+        match err {
+            Error::APIClient(api_client::Error::APIError(StatusCode::NotFound, _)) => (),
+            e => panic!("Expected the original API error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn promote_latest_resolves_and_promotes_the_latest_release() {
+        let mut ui = recording_ui();
+        let api = FakeApi::with_latest("core/redis/4.0.14/20190319155852");
+        let ident = PackageIdent::from_str("core/redis").unwrap();
+
+        let promoted = promote_latest(&mut ui,
+                                      &api,
+                                      &ident,
+                                      &ChannelIdent::unstable(),
+                                      &ChannelIdent::from("staging"),
+                                      "token",
+                                      &no_retry()).unwrap();
+
+        assert_eq!(promoted,
+                   PackageIdent::from_str("core/redis/4.0.14/20190319155852").unwrap());
+        assert_eq!(*api.calls.borrow(),
+                   vec!["show core/redis unstable".to_string(),
+                        "create core/staging".to_string(),
+                        "promote core/redis/4.0.14/20190319155852 staging".to_string()]);
+        let found = "core/redis/4.0.14/20190319155852, promoting from 'unstable' to 'staging'";
+        assert!(ui.events()
+                  .contains(&UiEvent::Status("Found".to_string(), found.to_string())));
+    }
+
+    #[test]
+    fn promote_latest_resolves_in_the_given_source_channel() {
+        let mut ui = recording_ui();
+        let api = FakeApi::with_latest("core/redis/4.0.14/20190319155852");
+        let ident = PackageIdent::from_str("core/redis").unwrap();
+
+        promote_latest(&mut ui,
+                       &api,
+                       &ident,
+                       &ChannelIdent::from("qa"),
+                       &ChannelIdent::from("staging"),
+                       "token",
+                       &no_retry()).unwrap();
+
+        assert_eq!(api.calls.borrow()[0], "show core/redis qa");
+    }
+
+    #[test]
+    fn promote_latest_fails_when_source_channel_has_no_release() {
+        let mut ui = recording_ui();
+        let api = FakeApi::new(PromoteResult::Promoted);
+        let ident = PackageIdent::from_str("core/redis").unwrap();
+
+        match promote_latest(&mut ui,
+                             &api,
+                             &ident,
+                             &ChannelIdent::unstable(),
+                             &ChannelIdent::from("staging"),
+                             "token",
+                             &no_retry())
+        {
+            Err(Error::PackageNotFoundInChannel(ref ident, ref channel)) => {
+                assert_eq!(ident, "core/redis");
+                assert_eq!(channel, "unstable");
+            }
+            other => panic!("Expected PackageNotFoundInChannel, got {:?}", other),
+        }
+        assert_eq!(api.calls.borrow().len(), 1);
+    }
 }
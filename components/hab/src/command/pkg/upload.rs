@@ -72,12 +72,12 @@ pub fn start(ui: &mut UI,
             ui.status(Status::Using, format!("existing {}", &ident))?;
             Ok(())
         }
-        Err(api_client::Error::APIError(StatusCode::NotFound, _)) | Ok(_) => {
+        Err(api_client::Error::PackageNotFound(_)) | Ok(_) => {
             for dep in tdeps.into_iter() {
                 match api_client.show_package(&dep, target, &ChannelIdent::unstable(), Some(token))
                 {
                     Ok(_) => ui.status(Status::Using, format!("existing {}", &dep))?,
-                    Err(api_client::Error::APIError(StatusCode::NotFound, _)) => {
+                    Err(api_client::Error::PackageNotFound(_)) => {
                         let candidate_path = match archive_path.parent() {
                             Some(p) => PathBuf::from(p),
                             None => unreachable!(),
@@ -144,30 +144,33 @@ fn upload_into_depot(ui: &mut UI,
                      mut archive: &mut PackageArchive)
                      -> Result<()> {
     ui.status(Status::Uploading, archive.path.display())?;
-    let package_uploaded =
-        match api_client.put_package(&mut archive, token, force_upload, ui.progress()) {
-            Ok(_) => true,
-            Err(api_client::Error::APIError(StatusCode::Conflict, _)) => {
-                println!("Package already exists on remote; skipping.");
-                true
-            }
-            Err(api_client::Error::APIError(StatusCode::UnprocessableEntity, _)) => {
-                return Err(Error::PackageArchiveMalformed(format!("{}",
-                                                                  archive.path
-                                                                         .display())));
-            }
-            Err(api_client::Error::APIError(StatusCode::NotImplemented, _)) => {
-                println!("Package platform or architecture not supported by the targeted depot; \
-                          skipping.");
-                false
-            }
-            Err(api_client::Error::APIError(StatusCode::FailedDependency, _)) => {
-                ui.fatal("Package upload introduces a circular dependency - please check \
-                          pkg_deps; skipping.")?;
-                false
-            }
-            Err(e) => return Err(Error::from(e)),
-        };
+    let package_uploaded = match api_client.put_package(&mut archive,
+                                                        token,
+                                                        force_upload,
+                                                        ui.progress_for(&ident.to_string()))
+    {
+        Ok(_) => true,
+        Err(api_client::Error::APIError(StatusCode::Conflict, _)) => {
+            println!("Package already exists on remote; skipping.");
+            true
+        }
+        Err(api_client::Error::APIError(StatusCode::UnprocessableEntity, _)) => {
+            return Err(Error::PackageArchiveMalformed(format!("{}",
+                                                              archive.path
+                                                                     .display())));
+        }
+        Err(api_client::Error::APIError(StatusCode::NotImplemented, _)) => {
+            println!("Package platform or architecture not supported by the targeted depot; \
+                      skipping.");
+            false
+        }
+        Err(api_client::Error::APIError(StatusCode::FailedDependency, _)) => {
+            ui.fatal("Package upload introduces a circular dependency - please check pkg_deps; \
+                      skipping.")?;
+            false
+        }
+        Err(e) => return Err(Error::from(e)),
+    };
     ui.status(Status::Uploaded, ident)?;
 
     // Promote to additional_release_channel if specified
@@ -178,7 +181,6 @@ fn upload_into_depot(ui: &mut UI,
         if channel != ChannelIdent::stable() && channel != ChannelIdent::unstable() {
             match api_client.create_channel(&ident.origin, &channel, token) {
                 Ok(_) => (),
-                Err(api_client::Error::APIError(StatusCode::Conflict, _)) => (),
                 Err(e) => return Err(Error::from(e)),
             };
         }
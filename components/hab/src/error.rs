@@ -14,7 +14,9 @@ use crate::{api_client,
             protocol::net,
             sup_client::SrvClientError};
 use handlebars;
+use serde_yaml;
 use toml;
+use url;
 
 pub type Result<T> = result::Result<T, Error>;
 
@@ -23,13 +25,17 @@ pub type Result<T> = result::Result<T, Error>;
 pub enum Error {
     APIClient(api_client::Error),
     ArgumentError(&'static str),
+    BulkPromotionFailed(usize /* failed */, usize /* attempted */),
     ButterflyError(String),
+    CannotDemoteStaleFrom(String /* channel */),
     CannotParseBinlinkBinaryName(PathBuf),
     CannotParseBinlinkSource(PathBuf),
     CannotRemoveDockerStudio,
     CannotRemoveFromChannel((String, String)),
     CannotRemovePackage(hcore::package::PackageIdent, usize),
+    ChannelAlreadyExists(String),
     CommandNotFoundInPkg((String, String)),
+    ConfirmationDeclined(String /* impact */),
     CryptoCLI(String),
     CtlClient(SrvClientError),
     DockerDaemonDown,
@@ -44,16 +50,30 @@ pub enum Error {
     HabitatCore(hcore::Error),
     // Boxed due to clippy::large_enum_variant
     HandlebarsRenderError(Box<handlebars::TemplateRenderError>),
+    IdentEnvVarUnset(String /* variable */),
+    InsufficientOriginAccess(String /* origin */, String /* channel */),
+    InvalidBldrUrl(String, url::ParseError),
+    InvalidBuildResults(String /* path */),
+    InvalidChannelName(String),
+    InvalidIdentInEnvVar(String /* variable */, String /* value */),
     IO(io::Error),
     JobGroupPromoteOrDemote(api_client::Error, bool /* promote */),
     JobGroupCancel(api_client::Error),
     JobGroupPromoteOrDemoteUnprocessable(bool /* promote */),
     JsonErr(serde_json::Error),
+    Keyring(String),
     NameLookup,
     NetErr(net::NetErr),
+    NoPackagesToPromote,
     PackageArchiveMalformed(String),
+    PackageNotFoundInChannel(String /* ident */, String /* channel */),
     ParseIntError(num::ParseIntError),
     PathPrefixError(path::StripPrefixError),
+    PromotionPolicyViolation(String,      // ident
+                             Vec<String>  /* missing channels */),
+    PromotionWaitTimedOut(String, // ident
+                          String, // channel
+                          u64     /* seconds */),
     ProvidesError(String),
     RemoteSupResolutionError(String, io::Error),
     RootRequired,
@@ -63,6 +83,7 @@ pub enum Error {
     TomlDeserializeError(toml::de::Error),
     TomlSerializeError(toml::ser::Error),
     Utf8Error(String),
+    YamlErr(serde_yaml::Error),
 }
 
 impl fmt::Display for Error {
@@ -70,7 +91,13 @@ impl fmt::Display for Error {
         let msg = match *self {
             Error::APIClient(ref e) => e.to_string(),
             Error::ArgumentError(ref e) => e.to_string(),
+            Error::BulkPromotionFailed(failed, attempted) => {
+                format!("{} of {} packages failed to promote", failed, attempted)
+            }
             Error::ButterflyError(ref e) => e.to_string(),
+            Error::CannotDemoteStaleFrom(ref c) => {
+                format!("Stale packages cannot be demoted from the {} channel.", c)
+            }
             Error::CannotParseBinlinkBinaryName(ref p) => {
                 format!("Cannot parse binlink binary name from {}.", p.display())
             }
@@ -87,10 +114,14 @@ impl fmt::Display for Error {
                 format!("Can't remove package: {}. It is a dependency of {} packages",
                         p, c)
             }
+            Error::ChannelAlreadyExists(ref c) => format!("Channel {} already exists.", c),
             Error::CommandNotFoundInPkg((ref p, ref c)) => {
                 format!("`{}' was not found under any 'PATH' directories in the {} package",
                         c, p)
             }
+            Error::ConfirmationDeclined(ref impact) => {
+                format!("Not going ahead, as it was not confirmed: {}", impact)
+            }
             Error::CryptoCLI(ref e) => e.to_string(),
             Error::CtlClient(ref e) => e.to_string(),
             Error::DockerDaemonDown => {
@@ -131,6 +162,38 @@ impl fmt::Display for Error {
             Error::HabitatCommon(ref e) => e.to_string(),
             Error::HabitatCore(ref e) => e.to_string(),
             Error::HandlebarsRenderError(ref e) => e.to_string(),
+            Error::IdentEnvVarUnset(ref var) => {
+                format!("The package identifier was to be read from {}, which is not set",
+                        var)
+            }
+            Error::InsufficientOriginAccess(ref origin, ref channel) => {
+                format!("Your auth token does not have access to the '{}' channel in the '{}' \
+                         origin. Promoting packages and creating channels requires membership in \
+                         the origin; check that the token belongs to a member of '{}' with \
+                         permission to modify its channels.",
+                        channel, origin, origin)
+            }
+            Error::InvalidBldrUrl(ref url, ref e) => {
+                format!("Invalid Builder URL '{}': {}. Check the --url option or the HAB_BLDR_URL \
+                         environment variable.",
+                        url, e)
+            }
+            Error::InvalidBuildResults(ref path) => {
+                format!("Build results file '{}' has no 'ident' field with a fully qualified \
+                         package identifier",
+                        path)
+            }
+            Error::InvalidChannelName(ref name) => {
+                format!("Invalid channel name '{}'. Channel names may only contain letters, \
+                         numbers, '-', '_' and '.' (matching {}).",
+                        name,
+                        crate::command::pkg::CHANNEL_NAME_PATTERN)
+            }
+            Error::InvalidIdentInEnvVar(ref var, ref value) => {
+                format!("'{}' from {} is not a valid package identifier. Package identifiers have \
+                         the form origin/name[/version[/release]]",
+                        value, var)
+            }
             Error::IO(ref err) => format!("{}", err),
             Error::JobGroupPromoteOrDemoteUnprocessable(true) => {
                 "Failed to promote job group, the build job is still in progress".to_string()
@@ -144,15 +207,33 @@ impl fmt::Display for Error {
                         e)
             }
             Error::JsonErr(ref e) => e.to_string(),
+            Error::Keyring(ref e) => format!("Unable to use the OS keyring: {}", e),
             Error::JobGroupCancel(ref e) => format!("Failed to cancel job group: {:?}", e),
             Error::NameLookup => "Error resolving a name or IP address".to_string(),
             Error::NetErr(ref e) => e.to_string(),
+            Error::NoPackagesToPromote => "No packages to promote".to_string(),
             Error::PackageArchiveMalformed(ref e) => {
                 format!("Package archive was unreadable or contained unexpected contents: {:?}",
                         e)
             }
+            Error::PackageNotFoundInChannel(ref ident, ref channel) => {
+                format!("No release of {} was found in the '{}' channel",
+                        ident, channel)
+            }
             Error::ParseIntError(ref err) => format!("{}", err),
             Error::PathPrefixError(ref err) => format!("{}", err),
+            Error::PromotionPolicyViolation(ref ident, ref missing) => {
+                format!("{} cannot be promoted to stable until it is in the {} channel(s), as \
+                         required by the origin's channel order on Builder or by \
+                         HAB_PROMOTE_REQUIRE_CHANNELS",
+                        ident,
+                        missing.join(", "))
+            }
+            Error::PromotionWaitTimedOut(ref ident, ref channel, secs) => {
+                format!("{} was promoted, but could not be found in the '{}' channel within {} \
+                         seconds",
+                        ident, channel, secs)
+            }
             Error::ProvidesError(ref err) => format!("Can't find {}", err),
             Error::RemoteSupResolutionError(ref sup_addr, ref err) => {
                 format!("Failed to resolve remote supervisor '{}': {}",
@@ -169,6 +250,7 @@ impl fmt::Display for Error {
             Error::TomlDeserializeError(ref e) => format!("Can't deserialize TOML: {}", e),
             Error::TomlSerializeError(ref e) => format!("Can't serialize TOML: {}", e),
             Error::Utf8Error(ref e) => format!("Error processing a string as UTF-8: {}", e),
+            Error::YamlErr(ref e) => format!("Can't serialize YAML: {}", e),
         };
         write!(f, "{}", msg)
     }
@@ -179,7 +261,11 @@ impl error::Error for Error {
         match *self {
             Error::APIClient(ref err) => err.description(),
             Error::ArgumentError(_) => "There was an error parsing an error or with it's value",
+            Error::BulkPromotionFailed(..) => "Some packages of a bulk promotion failed to promote",
             Error::ButterflyError(_) => "Butterfly has had an error",
+            Error::CannotDemoteStaleFrom(_) => {
+                "Stale packages cannot be demoted from the specified channel"
+            }
             Error::CannotParseBinlinkBinaryName(_) => "Cannot parse binlink binary name",
             Error::CannotParseBinlinkSource(_) => "Cannot parse binlink source path",
             Error::CannotRemoveFromChannel(_) => {
@@ -191,9 +277,11 @@ impl error::Error for Error {
             Error::CannotRemovePackage(..) => {
                 "A package can only be removed if it is not a dependency of any other package"
             }
+            Error::ChannelAlreadyExists(_) => "The channel already exists",
             Error::CommandNotFoundInPkg(_) => {
                 "Command was not found under any 'PATH' directories in the package"
             }
+            Error::ConfirmationDeclined(_) => "A destructive action was not confirmed",
             Error::CryptoCLI(_) => "A cryptographic error has occurred",
             Error::CtlClient(ref err) => err.description(),
             Error::DockerDaemonDown => "The Docker daemon could not be found.",
@@ -207,6 +295,18 @@ impl error::Error for Error {
             Error::HabitatCommon(ref err) => err.description(),
             Error::HabitatCore(ref err) => err.description(),
             Error::HandlebarsRenderError(ref err) => err.description(),
+            Error::IdentEnvVarUnset(_) => {
+                "The environment variable holding the package identifier is not set"
+            }
+            Error::InsufficientOriginAccess(..) => {
+                "Auth token lacks the origin access needed to modify the channel"
+            }
+            Error::InvalidBldrUrl(..) => "Invalid Builder URL",
+            Error::InvalidBuildResults(_) => "Build results file has no package identifier",
+            Error::InvalidChannelName(_) => "Invalid channel name",
+            Error::InvalidIdentInEnvVar(..) => {
+                "The environment variable holds an invalid package identifier"
+            }
             Error::IO(ref err) => err.description(),
             Error::JobGroupPromoteOrDemoteUnprocessable(true) => {
                 "Failed to promote job group, the build job is still in progress"
@@ -216,13 +316,22 @@ impl error::Error for Error {
             }
             Error::JobGroupPromoteOrDemote(ref err, _) => err.description(),
             Error::JobGroupCancel(ref err) => err.description(),
+            Error::Keyring(_) => "Unable to use the OS keyring",
             Error::NetErr(ref err) => err.description(),
+            Error::NoPackagesToPromote => "No packages to promote",
             Error::NameLookup => "Error resolving a name or IP address",
             Error::PackageArchiveMalformed(_) => {
                 "Package archive was unreadable or had unexpected contents"
             }
+            Error::PackageNotFoundInChannel(..) => "No matching package found in channel",
             Error::ParseIntError(ref err) => err.description(),
             Error::PathPrefixError(ref err) => err.description(),
+            Error::PromotionPolicyViolation(..) => {
+                "Package is not in the channels required before promoting to stable"
+            }
+            Error::PromotionWaitTimedOut(..) => {
+                "Timed out waiting for a promoted package to be found in its channel"
+            }
             Error::ProvidesError(_) => {
                 "Can't find a package that provides the given search parameter"
             }
@@ -237,6 +346,7 @@ impl error::Error for Error {
             Error::TomlDeserializeError(_) => "Can't deserialize TOML",
             Error::TomlSerializeError(_) => "Can't serialize TOML",
             Error::Utf8Error(_) => "Error processing string as UTF-8",
+            Error::YamlErr(_) => "Can't serialize YAML",
         }
     }
 }
@@ -293,3 +403,7 @@ impl From<net::NetErr> for Error {
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self { Error::JsonErr(err) }
 }
+
+impl From<serde_yaml::Error> for Error {
+    fn from(err: serde_yaml::Error) -> Self { Error::YamlErr(err) }
+}
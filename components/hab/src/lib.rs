@@ -32,12 +32,14 @@ extern crate winapi;
 extern crate winreg;
 
 pub mod analytics;
+pub mod audit;
 pub mod cli;
 pub mod command;
 pub mod config;
 pub mod error;
 mod exec;
 pub mod scaffolding;
+pub mod token_store;
 
 pub const PRODUCT: &str = "hab";
 pub const VERSION: &str = include_str!(concat!(env!("OUT_DIR"), "/VERSION"));
@@ -59,6 +59,8 @@ use hab::{analytics,
                   Result},
           feat,
           scaffolding,
+          token_store::{OsKeyring,
+                        TokenStore},
           AUTH_TOKEN_ENVVAR,
           BLDR_URL_ENVVAR,
           CTL_SECRET_ENVVAR,
@@ -81,11 +83,13 @@ use std::{env,
           process,
           result,
           str::FromStr,
-          thread};
+          thread,
+          time::Duration};
 use tabwriter::TabWriter;
 use termcolor::{self,
                 Color,
                 ColorSpec};
+use url::Url;
 
 /// Makes the --org CLI param optional when this env var is set
 const HABITAT_ORG_ENVVAR: &str = "HAB_ORG";
@@ -111,8 +115,19 @@ fn main() {
     thread::spawn(analytics::instrument_subcommand);
     if let Err(e) = start(&mut ui) {
         ui.fatal(e).unwrap();
+        finalize(&mut ui);
         std::process::exit(1)
     }
+    finalize(&mut ui);
+}
+
+/// Finishes the UI's output. Failing to do so is not worth failing an otherwise finished command
+/// over, so it is only warned about.
+fn finalize(ui: &mut UI) {
+    if let Err(e) = ui.finalize() {
+        ui.warn(format!("Unable to finish writing output: {}", e))
+          .ok();
+    }
 }
 
 fn start(ui: &mut UI) -> Result<()> {
@@ -187,6 +202,12 @@ fn start(ui: &mut UI) -> Result<()> {
         }
         ("bldr", Some(matches)) => {
             match matches.subcommand() {
+                ("auth", Some(m)) => {
+                    match m.subcommand() {
+                        ("store", Some(m)) => sub_bldr_auth_store(ui, m)?,
+                        _ => unreachable!(),
+                    }
+                }
                 ("job", Some(m)) => {
                     match m.subcommand() {
                         ("start", Some(m)) => sub_bldr_job_start(ui, m)?,
@@ -202,6 +223,7 @@ fn start(ui: &mut UI) -> Result<()> {
                         ("create", Some(m)) => sub_bldr_channel_create(ui, m)?,
                         ("destroy", Some(m)) => sub_bldr_channel_destroy(ui, m)?,
                         ("list", Some(m)) => sub_bldr_channel_list(ui, m)?,
+                        ("promote-history", Some(m)) => sub_bldr_channel_promote_history(ui, m)?,
                         _ => unreachable!(),
                     }
                 }
@@ -562,6 +584,17 @@ fn sub_pkg_uninstall(ui: &mut UI, m: &ArgMatches<'_>) -> Result<()> {
                                    &excludes,
                                    &services)
 }
+
+fn sub_bldr_auth_store(ui: &mut UI, m: &ArgMatches<'_>) -> Result<()> {
+    let token = match explicit_auth_token(m.value_of("AUTH_TOKEN")) {
+        Some(token) => token,
+        None => return Err(Error::ArgumentError("No auth token specified")),
+    };
+    OsKeyring.store(&token)?;
+    ui.end("Saved the Builder auth token in the OS keyring.")?;
+    Ok(())
+}
+
 fn sub_bldr_channel_create(ui: &mut UI, m: &ArgMatches<'_>) -> Result<()> {
     let url = bldr_url_from_matches(&m)?;
     let origin = origin_param_or_env(&m)?;
@@ -575,13 +608,29 @@ fn sub_bldr_channel_destroy(ui: &mut UI, m: &ArgMatches<'_>) -> Result<()> {
     let origin = origin_param_or_env(&m)?;
     let channel = required_channel_from_matches(&m);
     let token = auth_token_param_or_env(&m)?;
+    if m.is_present("YES") {
+        ui.set_assume_yes(true);
+    }
     command::bldr::channel::destroy::start(ui, &url, &token, &origin, &channel)
 }
 
 fn sub_bldr_channel_list(ui: &mut UI, m: &ArgMatches<'_>) -> Result<()> {
     let url = bldr_url_from_matches(&m)?;
     let origin = origin_param_or_env(&m)?;
-    command::bldr::channel::list::start(ui, &url, &origin)
+    let token = maybe_auth_token(&m);
+    command::bldr::channel::list::start(ui, &url, &origin, token.as_ref().map(String::as_str))
+}
+
+fn sub_bldr_channel_promote_history(ui: &mut UI, m: &ArgMatches<'_>) -> Result<()> {
+    let url = bldr_url_from_matches(&m)?;
+    let origin = origin_param_or_env(&m)?;
+    let channel = required_channel_from_matches(&m);
+    let token = maybe_auth_token(&m);
+    command::bldr::channel::history::start(ui,
+                                           &url,
+                                           &origin,
+                                           &channel,
+                                           token.as_ref().map(String::as_str))
 }
 
 fn sub_bldr_job_start(ui: &mut UI, m: &ArgMatches<'_>) -> Result<()> {
@@ -828,18 +877,192 @@ fn sub_pkg_info(ui: &mut UI, m: &ArgMatches<'_>) -> Result<()> {
 
 fn sub_pkg_promote(ui: &mut UI, m: &ArgMatches<'_>) -> Result<()> {
     let url = bldr_url_from_matches(&m)?;
-    let channel = required_channel_from_matches(&m);
+    let channel = command::pkg::parse_channel(m.value_of("CHANNEL").unwrap())?; // Required via clap
     let token = auth_token_param_or_env(&m)?;
-    let ident = PackageIdent::from_str(m.value_of("PKG_IDENT").unwrap())?; // Required via clap
-    command::pkg::promote::start(ui, &url, &ident, &channel, &token)
+    let retry_create = m.is_present("RETRY_CHANNEL_CREATE");
+    let mut idents = Vec::new();
+    for ident in m.values_of("PKG_IDENT").unwrap() {
+        // Required via clap
+        if ident == command::pkg::promote::IDENT_FROM_ENV {
+            let var = m.value_of("IDENT_FROM_ENV")
+                       .unwrap_or(command::pkg::promote::PKG_IDENT_ENVVAR);
+            idents.push(command::pkg::promote::ident_from_env(var)?);
+        } else {
+            idents.push(PackageIdent::from_str(ident)?);
+        }
+    }
+    // `--demote-after X --atomic` is another way of saying `--replace --replace-from X`
+    let replace_from = match m.value_of("DEMOTE_AFTER") {
+        Some(demote_after) if m.is_present("ATOMIC") => Some(demote_after),
+        _ => m.value_of("REPLACE_FROM"),
+    };
+    if let Some(replace_from) = replace_from {
+        if idents.len() != 1 || !idents[0].fully_qualified() {
+            return Err(Error::ArgumentError("Only a single fully qualified \
+                                             package identifier can be promoted \
+                                             with --replace"));
+        }
+        let replace_from = demote_channel(replace_from, &channel)?;
+        let result = command::pkg::promote::start_replace(ui,
+                                                          &url,
+                                                          &idents[0],
+                                                          &channel,
+                                                          &replace_from,
+                                                          &token,
+                                                          retry_create);
+        let result = wait_if_asked(ui, m, &url, &idents[0], &channel, &token, result);
+        return single_result_file(ui, m, &idents[0], result);
+    }
+    if let Some(demote_after) = m.value_of("DEMOTE_AFTER") {
+        if idents.len() != 1 || !idents[0].fully_qualified() {
+            return Err(Error::ArgumentError("Only a single fully qualified \
+                                             package identifier can be promoted \
+                                             with --demote-after"));
+        }
+        let demote_after = demote_channel(demote_after, &channel)?;
+        let result = command::pkg::promote::start_demote_after(ui,
+                                                               &url,
+                                                               &idents[0],
+                                                               &channel,
+                                                               &demote_after,
+                                                               &token,
+                                                               retry_create);
+        let result = wait_if_asked(ui, m, &url, &idents[0], &channel, &token, result);
+        return single_result_file(ui, m, &idents[0], result);
+    }
+    if let Some(from_channel) = latest_source_channel(&m, &idents)? {
+        if idents.len() != 1 {
+            return Err(Error::ArgumentError("Only a single package identifier \
+                                             can be resolved to its latest \
+                                             release"));
+        }
+        let result = command::pkg::promote::start_latest(ui,
+                                                         &url,
+                                                         &idents[0],
+                                                         &from_channel,
+                                                         &channel,
+                                                         &token,
+                                                         retry_create);
+        let result = match result {
+            Ok(latest) => wait_if_asked(ui, m, &url, &latest, &channel, &token, Ok(())),
+            Err(e) => Err(e),
+        };
+        return single_result_file(ui, m, &idents[0], result);
+    }
+    if idents.len() == 1 {
+        let result =
+            command::pkg::promote::start(ui, &url, &idents[0], &channel, &token, retry_create);
+        let result = wait_if_asked(ui, m, &url, &idents[0], &channel, &token, result);
+        return single_result_file(ui, m, &idents[0], result);
+    }
+    let concurrency = match m.value_of("CONCURRENCY") {
+        Some(n) => n.parse().map_err(Error::ParseIntError)?,
+        None => 1,
+    };
+    if m.is_present("YES") {
+        ui.set_assume_yes(true);
+    }
+    let require_nonempty = m.is_present("REQUIRE_NONEMPTY");
+    let options = command::pkg::promote::BulkOptions { retry_create,
+                                                       require_nonempty,
+                                                       concurrency };
+    let outcome = command::pkg::promote::start_bulk(ui, &url, &idents, &channel, &token, &options)?;
+    if let Some(path) = m.value_of("OUTPUT_FILE") {
+        command::pkg::write_result_file(Path::new(path), ui.output_mode(), &outcome)?;
+    }
+    if !outcome.is_success() {
+        return Err(Error::BulkPromotionFailed(outcome.failed.len(), idents.len()));
+    }
+    for ident in &outcome.succeeded {
+        let ident = PackageIdent::from_str(ident)?;
+        wait_if_asked(ui, m, &url, &ident, &channel, &token, Ok(()))?;
+    }
+    Ok(())
+}
+
+/// The channel given to `hab pkg promote` to demote the package from once it is promoted, which
+/// must not be the channel it is promoted to.
+fn demote_channel(name: &str, channel: &ChannelIdent) -> Result<ChannelIdent> {
+    let demote_from = command::pkg::parse_channel(name)?;
+    if demote_from == *channel {
+        return Err(Error::ArgumentError("A package cannot be demoted from \
+                                         the channel it is promoted to"));
+    }
+    Ok(demote_from)
+}
+
+/// Once a package has been promoted, waits until it is found in the channel, when `hab pkg
+/// promote` was given `--wait`, for the `--wait` timeout or `DEFAULT_WAIT_TIMEOUT`.
+fn wait_if_asked(ui: &mut UI,
+                 m: &ArgMatches<'_>,
+                 url: &str,
+                 ident: &PackageIdent,
+                 channel: &ChannelIdent,
+                 token: &str,
+                 promoted: Result<()>)
+                 -> Result<()> {
+    if promoted.is_err() || !m.is_present("WAIT") {
+        return promoted;
+    }
+    if !ident.fully_qualified() {
+        ui.warn(format!("Not waiting for {} to be found in channel '{}', as only a fully \
+                         qualified package can be looked for",
+                        ident, channel))?;
+        return promoted;
+    }
+    let timeout = match m.value_of("WAIT") {
+        Some(secs) => Duration::from_secs(secs.parse().map_err(Error::ParseIntError)?),
+        None => command::pkg::promote::DEFAULT_WAIT_TIMEOUT,
+    };
+    command::pkg::promote::wait_until_in_channel(ui, url, ident, channel, token, timeout)
+}
+
+/// The channel `hab pkg promote` resolves its package to the latest release in, if it resolves
+/// one at all: the `--from-channel` channel whenever it is given, and otherwise the
+/// `--channel-from` channel, or unstable, when a package identifier isn't fully qualified.
+fn latest_source_channel(m: &ArgMatches<'_>,
+                         idents: &[PackageIdent])
+                         -> Result<Option<ChannelIdent>> {
+    if let Some(from_channel) = m.value_of("FROM_CHANNEL") {
+        return Ok(Some(command::pkg::parse_channel(from_channel)?));
+    }
+    if idents.iter().all(PackageIdent::fully_qualified) {
+        return Ok(None);
+    }
+    match m.value_of("CHANNEL_FROM") {
+        Some(channel_from) => Ok(Some(command::pkg::parse_channel(channel_from)?)),
+        None => Ok(Some(ChannelIdent::unstable())),
+    }
 }
 
 fn sub_pkg_demote(ui: &mut UI, m: &ArgMatches<'_>) -> Result<()> {
     let url = bldr_url_from_matches(&m)?;
-    let channel = required_channel_from_matches(&m);
+    let channel = command::pkg::parse_channel(m.value_of("CHANNEL").unwrap())?; // Required via clap
     let token = auth_token_param_or_env(&m)?;
     let ident = PackageIdent::from_str(m.value_of("PKG_IDENT").unwrap())?; // Required via clap
-    command::pkg::demote::start(ui, &url, &ident, &channel, &token)
+    let execution_strategy = if m.is_present("DRYRUN") {
+        command::pkg::ExecutionStrategy::DryRun
+    } else {
+        command::pkg::ExecutionStrategy::Run
+    };
+    let result =
+        command::pkg::demote::start(ui, &url, &ident, &channel, &token, execution_strategy);
+    single_result_file(ui, m, &ident, result)
+}
+
+/// Writes the outcome of a command applied to a single package to the `--output-file`, when one
+/// is given, and then hands back the command's result.
+fn single_result_file(ui: &mut UI,
+                      m: &ArgMatches<'_>,
+                      ident: &PackageIdent,
+                      result: Result<()>)
+                      -> Result<()> {
+    if let Some(path) = m.value_of("OUTPUT_FILE") {
+        let mut outcome = command::pkg::BulkOutcome::default();
+        outcome.record(ident, &result);
+        command::pkg::write_result_file(Path::new(path), ui.output_mode(), &outcome)?;
+    }
+    result
 }
 
 fn sub_pkg_channels(ui: &mut UI, m: &ArgMatches<'_>) -> Result<()> {
@@ -1311,23 +1534,41 @@ fn raw_parse_args() -> (Vec<OsString>, Vec<OsString>) {
 
 /// Check to see if the user has passed in an AUTH_TOKEN param. If not, check the
 /// HAB_AUTH_TOKEN env var. If not, check the CLI config to see if there is a default auth
-/// token set. If that's empty too, then error.
+/// token set. If not, check the OS keyring. If that's empty too, then error.
 fn auth_token_param_or_env(m: &ArgMatches<'_>) -> Result<String> {
-    match m.value_of("AUTH_TOKEN") {
-        Some(o) => Ok(o.to_string()),
-        None => {
-            match henv::var(AUTH_TOKEN_ENVVAR) {
-                Ok(v) => Ok(v),
-                Err(_) => {
-                    let config = config::load()?;
-                    match config.auth_token {
-                        Some(v) => Ok(v),
-                        None => return Err(Error::ArgumentError("No auth token specified")),
-                    }
-                }
-            }
-        }
+    resolve_auth_token(m.value_of("AUTH_TOKEN"),
+                       || Ok(config::load()?.auth_token),
+                       &OsKeyring)
+}
+
+/// The auth token given as an argument, or else in the HAB_AUTH_TOKEN env var.
+fn explicit_auth_token(param: Option<&str>) -> Option<String> {
+    param.map(str::to_string)
+         .or_else(|| henv::var(AUTH_TOKEN_ENVVAR).ok())
+}
+
+/// The logic behind `auth_token_param_or_env`, with the CLI config and the keyring passed in.
+/// The config is only loaded when neither the argument nor the environment has a token. A
+/// keyring which can't be read is only logged, as having no token there, so that the error
+/// is the one saying no token was given.
+fn resolve_auth_token<F, S>(param: Option<&str>, config_token: F, keyring: &S) -> Result<String>
+    where F: FnOnce() -> Result<Option<String>>,
+          S: TokenStore
+{
+    if let Some(token) = explicit_auth_token(param) {
+        return Ok(token);
+    }
+    if let Some(token) = config_token()? {
+        return Ok(token);
     }
+    let stored = match keyring.get() {
+        Ok(stored) => stored,
+        Err(e) => {
+            debug!("Unable to read the auth token from the OS keyring: {}", e);
+            None
+        }
+    };
+    stored.ok_or(Error::ArgumentError("No auth token specified"))
 }
 
 /// Check if the HAB_CTL_SECRET env var. If not, check the CLI config to see if there is a ctl
@@ -1394,21 +1635,27 @@ fn org_param_or_env(m: &ArgMatches<'_>) -> Result<String> {
 /// var. If not, check the CLI config to see if there is a default url set. If that's empty too,
 /// then we'll use the default (https://bldr.habitat.sh).
 fn bldr_url_from_matches(matches: &ArgMatches<'_>) -> Result<String> {
-    match matches.value_of("BLDR_URL") {
-        Some(url) => Ok(url.to_string()),
-        None => {
-            match henv::var(BLDR_URL_ENVVAR) {
-                Ok(v) => Ok(v),
-                Err(_) => {
-                    let config = config::load()?;
-                    match config.bldr_url {
-                        Some(v) => Ok(v),
-                        None => Ok(default_bldr_url()),
-                    }
-                }
-            }
+    let arg = matches.value_of("BLDR_URL");
+    if arg.is_none() && henv::var(BLDR_URL_ENVVAR).is_err() {
+        if let Some(url) = config::load()?.bldr_url {
+            return resolve_bldr_url(Some(&url));
         }
     }
+    resolve_bldr_url(arg)
+}
+
+/// Resolve the Builder URL to use, taken from the argument if given, otherwise from the
+/// `HAB_BLDR_URL` environment variable, and finally falling back to the public Builder. Fails if
+/// the result isn't a valid URL.
+fn resolve_bldr_url(arg: Option<&str>) -> Result<String> {
+    let url = match arg {
+        Some(url) => url.to_string(),
+        None => henv::var(BLDR_URL_ENVVAR).unwrap_or_else(|_| default_bldr_url()),
+    };
+    match Url::parse(&url) {
+        Ok(_) => Ok(url),
+        Err(e) => Err(Error::InvalidBldrUrl(url, e)),
+    }
 }
 
 /// Resolve a channel. Taken from the environment or from CLI args, if
@@ -1754,7 +2001,9 @@ fn ui() -> UI {
     } else {
         None
     };
-    UI::default_with(output::get_format().color_choice(), isatty)
+    let mut ui = UI::default_with(output::get_format().color_choice(), isatty);
+    ui.apply_env();
+    ui
 }
 
 /// Set all fields for an `SvcLoad` message that we can from the given opts. This function
@@ -1780,6 +2029,314 @@ fn update_svc_load_from_input(m: &ArgMatches<'_>, msg: &mut protocol::ctl::SvcLo
 mod test {
     use super::*;
 
+    mod latest_source_channel {
+        use super::*;
+
+        fn source(args: &[&str]) -> Option<ChannelIdent> {
+            let matches = cli::get().get_matches_from_safe(args).unwrap();
+            let (_, pkg) = matches.subcommand();
+            let (_, promote) = pkg.unwrap().subcommand();
+            let promote = promote.unwrap();
+            let idents: Vec<PackageIdent> = promote.values_of("PKG_IDENT")
+                                                   .unwrap()
+                                                   .map(|i| PackageIdent::from_str(i).unwrap())
+                                                   .collect();
+            latest_source_channel(promote, &idents).unwrap()
+        }
+
+        #[test]
+        fn fully_qualified_idents_are_not_resolved() {
+            assert_eq!(source(&["hab",
+                                "pkg",
+                                "promote",
+                                "core/redis/4.0.14/20190319155852",
+                                "staging",
+                                "--channel-from",
+                                "qa"]),
+                       None);
+        }
+
+        #[test]
+        fn partial_ident_is_resolved_in_unstable_by_default() {
+            assert_eq!(source(&["hab", "pkg", "promote", "core/redis", "staging"]),
+                       Some(ChannelIdent::unstable()));
+        }
+
+        #[test]
+        fn partial_ident_is_resolved_in_the_channel_from_channel() {
+            assert_eq!(source(&["hab",
+                                "pkg",
+                                "promote",
+                                "core/redis",
+                                "staging",
+                                "--channel-from",
+                                "qa"]),
+                       Some(ChannelIdent::from("qa")));
+        }
+
+        #[test]
+        fn from_channel_resolves_even_fully_qualified_idents() {
+            assert_eq!(source(&["hab",
+                                "pkg",
+                                "promote",
+                                "core/redis/4.0.14/20190319155852",
+                                "staging",
+                                "--from-channel",
+                                "qa"]),
+                       Some(ChannelIdent::from("qa")));
+        }
+    }
+
+    mod promote_replace {
+        use super::*;
+
+        fn parse(args: &[&str]) -> bool { cli::get().get_matches_from_safe(args).is_ok() }
+
+        #[test]
+        fn replace_needs_a_channel_to_replace_from() {
+            assert!(!parse(&["hab",
+                             "pkg",
+                             "promote",
+                             "core/redis/4.0.14/20190319155852",
+                             "staging",
+                             "--replace"]));
+            assert!(parse(&["hab",
+                            "pkg",
+                            "promote",
+                            "core/redis/4.0.14/20190319155852",
+                            "staging",
+                            "--replace",
+                            "--replace-from",
+                            "qa"]));
+        }
+
+        #[test]
+        fn append_and_replace_conflict() {
+            assert!(!parse(&["hab",
+                             "pkg",
+                             "promote",
+                             "core/redis/4.0.14/20190319155852",
+                             "staging",
+                             "--append",
+                             "--replace",
+                             "--replace-from",
+                             "qa"]));
+        }
+
+        #[test]
+        fn atomic_needs_a_channel_to_demote_after() {
+            assert!(!parse(&["hab",
+                             "pkg",
+                             "promote",
+                             "core/redis/4.0.14/20190319155852",
+                             "stable",
+                             "--atomic"]));
+            assert!(parse(&["hab",
+                            "pkg",
+                            "promote",
+                            "core/redis/4.0.14/20190319155852",
+                            "stable",
+                            "--demote-after",
+                            "staging",
+                            "--atomic"]));
+        }
+
+        #[test]
+        fn the_target_channel_cannot_also_be_demoted_from() {
+            match demote_channel("stable", &ChannelIdent::stable()) {
+                Err(Error::ArgumentError(_)) => (),
+                other => panic!("Expected an ArgumentError, got {:?}", other),
+            }
+            assert_eq!(demote_channel("staging", &ChannelIdent::stable()).unwrap(),
+                       ChannelIdent::from("staging"));
+        }
+
+        #[test]
+        fn wait_takes_an_optional_timeout() {
+            assert!(parse(&["hab",
+                            "pkg",
+                            "promote",
+                            "core/redis/4.0.14/20190319155852",
+                            "stable",
+                            "--wait"]));
+            assert!(parse(&["hab",
+                            "pkg",
+                            "promote",
+                            "--wait=30",
+                            "core/redis/4.0.14/20190319155852",
+                            "stable"]));
+            assert!(!parse(&["hab",
+                             "pkg",
+                             "promote",
+                             "core/redis/4.0.14/20190319155852",
+                             "stable",
+                             "--wait=soon"]));
+        }
+
+        #[test]
+        fn concurrency_must_be_a_number() {
+            assert!(parse(&["hab",
+                            "pkg",
+                            "promote",
+                            "--concurrency",
+                            "8",
+                            "core/redis/4.0.14/20190319155852",
+                            "core/nginx/1.15.6/20190115235725",
+                            "stable"]));
+            assert!(!parse(&["hab",
+                             "pkg",
+                             "promote",
+                             "--concurrency",
+                             "many",
+                             "core/redis/4.0.14/20190319155852",
+                             "stable"]));
+        }
+    }
+
+    mod resolve_bldr_url {
+        use super::*;
+        use habitat_common::locked_env_var;
+
+        locked_env_var!(HAB_BLDR_URL, lock_bldr_url_var);
+
+        #[test]
+        fn argument_takes_precedence() {
+            let env_var = lock_bldr_url_var();
+            env_var.set("https://env.example.com");
+
+            assert_eq!(resolve_bldr_url(Some("https://arg.example.com")).unwrap(),
+                       "https://arg.example.com");
+        }
+
+        #[test]
+        fn env_var_is_used_without_an_argument() {
+            let env_var = lock_bldr_url_var();
+            env_var.set("https://env.example.com");
+
+            assert_eq!(resolve_bldr_url(None).unwrap(), "https://env.example.com");
+        }
+
+        #[test]
+        fn default_is_used_without_an_argument_or_env_var() {
+            let env_var = lock_bldr_url_var();
+            env_var.unset();
+
+            assert_eq!(resolve_bldr_url(None).unwrap(), default_bldr_url());
+        }
+
+        #[test]
+        fn invalid_url_is_an_error() {
+            match resolve_bldr_url(Some("not a url")) {
+                Err(Error::InvalidBldrUrl(ref url, _)) => assert_eq!(url, "not a url"),
+                other => panic!("Expected InvalidBldrUrl, got {:?}", other),
+            }
+        }
+    }
+
+    mod resolve_auth_token {
+        use super::*;
+        use habitat_common::locked_env_var;
+
+        locked_env_var!(HAB_AUTH_TOKEN, lock_auth_token_var);
+
+        struct FakeKeyring(Option<&'static str>);
+
+        impl TokenStore for FakeKeyring {
+            fn get(&self) -> Result<Option<String>> { Ok(self.0.map(str::to_string)) }
+
+            fn store(&self, _token: &str) -> Result<()> { Ok(()) }
+        }
+
+        struct UnavailableKeyring;
+
+        impl TokenStore for UnavailableKeyring {
+            fn get(&self) -> Result<Option<String>> {
+                Err(Error::Keyring("no keyring daemon".to_string()))
+            }
+
+            fn store(&self, _token: &str) -> Result<()> {
+                Err(Error::Keyring("no keyring daemon".to_string()))
+            }
+        }
+
+        fn config(token: Option<&'static str>) -> impl FnOnce() -> Result<Option<String>> {
+            move || Ok(token.map(str::to_string))
+        }
+
+        #[test]
+        fn argument_takes_precedence() {
+            let env_var = lock_auth_token_var();
+            env_var.set("env-token");
+
+            let token = resolve_auth_token(Some("arg-token"),
+                                           config(Some("config-token")),
+                                           &FakeKeyring(Some("keyring-token")));
+            assert_eq!(token.unwrap(), "arg-token");
+        }
+
+        #[test]
+        fn env_var_takes_precedence_over_config_and_keyring() {
+            let env_var = lock_auth_token_var();
+            env_var.set("env-token");
+
+            let token = resolve_auth_token(None,
+                                           config(Some("config-token")),
+                                           &FakeKeyring(Some("keyring-token")));
+            assert_eq!(token.unwrap(), "env-token");
+        }
+
+        #[test]
+        fn config_takes_precedence_over_keyring() {
+            let env_var = lock_auth_token_var();
+            env_var.unset();
+
+            let token = resolve_auth_token(None,
+                                           config(Some("config-token")),
+                                           &FakeKeyring(Some("keyring-token")));
+            assert_eq!(token.unwrap(), "config-token");
+        }
+
+        #[test]
+        fn keyring_is_used_last() {
+            let env_var = lock_auth_token_var();
+            env_var.unset();
+
+            let token = resolve_auth_token(None, config(None), &FakeKeyring(Some("keyring-token")));
+            assert_eq!(token.unwrap(), "keyring-token");
+        }
+
+        #[test]
+        fn no_token_anywhere_is_an_error() {
+            let env_var = lock_auth_token_var();
+            env_var.unset();
+
+            match resolve_auth_token(None, config(None), &FakeKeyring(None)) {
+                Err(Error::ArgumentError(_)) => (),
+                other => panic!("Expected ArgumentError, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn unavailable_keyring_is_the_same_as_no_token_anywhere() {
+            let env_var = lock_auth_token_var();
+            env_var.unset();
+
+            match resolve_auth_token(None, config(None), &UnavailableKeyring) {
+                Err(Error::ArgumentError(_)) => (),
+                other => panic!("Expected ArgumentError, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn keyring_is_not_consulted_when_a_token_is_found() {
+            let env_var = lock_auth_token_var();
+            env_var.set("env-token");
+
+            let token = resolve_auth_token(None, config(None), &UnavailableKeyring);
+            assert_eq!(token.unwrap(), "env-token");
+        }
+    }
+
     mod resolve_listen_ctl_addr {
         use super::*;
 
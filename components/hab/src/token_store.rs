@@ -0,0 +1,54 @@
+//! Keeping the Builder auth token in the OS keyring (the Secret Service on Linux, the macOS
+//! Keychain, or the Windows Credential Manager) rather than in plain text in `cli.toml`.
+//!
+//! Talking to the keyring needs hab to be built with the `keyring` feature. Without it, no
+//! token is ever found in the keyring and storing one is an error.
+
+use crate::error::{Error,
+                   Result};
+
+/// The keyring service name the Builder auth token is stored under.
+pub const KEYRING_SERVICE: &str = "habitat";
+/// The keyring user name the Builder auth token is stored under.
+pub const KEYRING_USER: &str = "bldr";
+
+/// Somewhere a Builder auth token can be kept between runs of hab.
+pub trait TokenStore {
+    /// Returns the stored token, or `None` if no token has been stored.
+    fn get(&self) -> Result<Option<String>>;
+
+    /// Stores `token`, replacing any previously stored token.
+    fn store(&self, token: &str) -> Result<()>;
+}
+
+/// The operating system's keyring.
+pub struct OsKeyring;
+
+#[cfg(feature = "keyring")]
+impl TokenStore for OsKeyring {
+    fn get(&self) -> Result<Option<String>> {
+        match keyring::Keyring::new(KEYRING_SERVICE, KEYRING_USER).get_password() {
+            Ok(token) => Ok(Some(token)),
+            Err(keyring::KeyringError::NoPasswordFound) => Ok(None),
+            Err(e) => Err(Error::Keyring(e.to_string())),
+        }
+    }
+
+    fn store(&self, token: &str) -> Result<()> {
+        keyring::Keyring::new(KEYRING_SERVICE, KEYRING_USER).set_password(token)
+                                                            .map_err(|e| {
+                                                                Error::Keyring(e.to_string())
+                                                            })
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+impl TokenStore for OsKeyring {
+    fn get(&self) -> Result<Option<String>> { Ok(None) }
+
+    fn store(&self, _token: &str) -> Result<()> {
+        Err(Error::Keyring("this hab was built without the `keyring` \
+                            feature"
+                                    .to_string()))
+    }
+}